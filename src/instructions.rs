@@ -1,7 +1,8 @@
-use crate::sections::Parse;
+use crate::sections::{Error, Parse, ReferenceType, TypeIndex, ValueType};
+use crate::utils::*;
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#control-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ControlInstructions {
     Unreachable = 0x00,  // unreachable
     Nop = 0x01,          // nop
@@ -9,6 +10,7 @@ pub enum ControlInstructions {
     Loop = 0x03,         // loop, ends with 0x0b
     If = 0x04,           // if, ends with 0x0b
     Else = 0x05,         // else
+    End = 0x0b,          // end
     Br = 0x0c,           // br
     BrIf = 0x0d,         // br_if
     BrTable = 0x0e,      // br_table
@@ -19,7 +21,7 @@ pub enum ControlInstructions {
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#reference-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ReferenceInstructions {
     RefNull = 0xd0,   // ref.null
     RefIsNull = 0xd1, // ref.is_null
@@ -27,7 +29,7 @@ pub enum ReferenceInstructions {
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#parametric-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ParametricInstructions {
     Drop = 0x1a,    // drop
     Select = 0x1b,  // select
@@ -35,7 +37,7 @@ pub enum ParametricInstructions {
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#variable-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum VariableInstructions {
     LocalGet = 0x20,  // local.get
     LocalSet = 0x21,  // local.set
@@ -45,21 +47,20 @@ pub enum VariableInstructions {
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#table-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TableInstructions {
-    TableGet = 0x25, // table.get
-    TableSet = 0x26, // table.set
-                     // Todo, figure out how these instructions work
-                     // TableInit = 0xfc, // table.init
-                     // ElemDrop  = 0xfc, // elem.drop
-                     // TableCopy = 0xfc, // table.copy
-                     // TableGrow = 0xfc, // table.grow
-                     // TableSize = 0xfc, // table.size
-                     // TableFill = 0xfc, // table.fill
+    TableGet = 0x25,    // table.get
+    TableSet = 0x26,    // table.set
+    TableInit = 0xfc0c, // table.init
+    ElemDrop = 0xfc0d,  // elem.drop
+    TableCopy = 0xfc0e, // table.copy
+    TableGrow = 0xfc0f, // table.grow
+    TableSize = 0xfc10, // table.size
+    TableFill = 0xfc11, // table.fill
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#memory-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MemoryInstructions {
     // Load Instructions
     I32Load = 0x28,           // i32.load
@@ -87,17 +88,16 @@ pub enum MemoryInstructions {
     I64Store16 = 0x3d, // i64.store16
     I64Store32 = 0x3e, // i64.store32
     // Memory Operations
-    Size = 0x3f, // memory.size
-    Grow = 0x40, // memory.grow
-                 // Todo, figure out how these instructions work
-                 // Init           = 0xfc, // memory.init
-                 // Drop           = 0xfc, // data.drop
-                 // Copy           = 0xfc, // memory.copy
-                 // Fill           = 0xfc, // memory.fill
+    Size = 0x3f,   // memory.size
+    Grow = 0x40,   // memory.grow
+    Init = 0xfc08, // memory.init
+    Drop = 0xfc09, // data.drop
+    Copy = 0xfc0a, // memory.copy
+    Fill = 0xfc0b, // memory.fill
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#numeric-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum NumericInstructions {
     // Constant Instructions
     I32Const = 0x41, // i32.const
@@ -242,35 +242,119 @@ pub enum NumericInstructions {
     I64Extend8Signed = 0xc2,  // i64.extend8_s
     I64Extend16Signed = 0xc3, // i32.extend16_s
     I64Extend32Signed = 0xc4, // i32.extend32_s
+    // the saturating truncations never trap
+    I32TruncSatF32Signed = 0xfc00,   // i32.trunc_sat_f32_s
+    I32TruncSatF32Unsigned = 0xfc01, // i32.trunc_sat_f32_u
+    I32TruncSatF64Signed = 0xfc02,   // i32.trunc_sat_f64_s
+    I32TruncSatF64Unsigned = 0xfc03, // i32.trunc_sat_f64_u
+    I64TruncSatF32Signed = 0xfc04,   // i64.trunc_sat_f32_s
+    I64TruncSatF32Unsigned = 0xfc05, // i64.trunc_sat_f32_u
+    I64TruncSatF64Signed = 0xfc06,   // i64.trunc_sat_f64_s
+    I64TruncSatF64Unsigned = 0xfc07, // i64.trunc_sat_f64_u
 }
 
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#vector-instructions
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum VectorInstructions {
     // Todo, this is for simd, define them in the future.
 }
 
+/// The prefix of the instructions encoded by a u32 opcode after it, the discriminants of them
+/// in the enums above are `0xfc00 | opcode`.
+pub const MISC_PREFIX: u8 = 0xfc;
+
+/// Split the discriminant of an instruction into the prefix and the opcode after it.
+pub(crate) fn split_opcode(discriminant: u32) -> (Option<u8>, u32) {
+    match discriminant >> 8 {
+        0 => (None, discriminant),
+        prefix => (Some(prefix as u8), discriminant & 0xff),
+    }
+}
+
+pub type LabelIndex = u32;
+pub type FuncIndex = u32;
+pub type TableIndex = u32;
+pub type ElemIndex = u32;
+pub type DataIndex = u32;
+
+/// https://webassembly.github.io/spec/core/binary/instructions.html#binary-blocktype
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockType {
+    Empty,
+    Value(ValueType),
+    TypeIndex(TypeIndex),
+}
+
+/// Immediates of control instructions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlOperands {
+    None,
+    Block(BlockType),
+    Label(LabelIndex),
+    // br_table l* l_default
+    BrTable(Vec<LabelIndex>, LabelIndex),
+    Func(FuncIndex),
+    CallIndirect(TypeIndex, TableIndex),
+}
+
+/// Immediates of table instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableOperands {
+    Table(TableIndex),
+    // table.init x y, the table is x and the element segment is y
+    Init(TableIndex, ElemIndex),
+    Elem(ElemIndex),
+    // table.copy x y, from y to x
+    Copy(TableIndex, TableIndex),
+}
+
+/// Immediates of memory instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryOperands {
+    MemArg(MemArg),
+    Data(DataIndex),
+    // memory.size, memory.grow, memory.copy and memory.fill only have reserved zero bytes.
+    None,
+}
+
+/// Immediates of reference instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceOperands {
+    None,
+    RefType(ReferenceType),
+    Func(FuncIndex),
+}
+
+/// https://webassembly.github.io/spec/core/binary/instructions.html#binary-memarg
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemArg {
+    pub align: u32,
+    pub offset: u32,
+}
+
 /// Reference: https://webassembly.github.io/spec/core/binary/instructions.html#expressions
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Expressions {
     pub end: u8, // the end must be 0x0b
     pub instructions: Vec<Instructions>,
 }
 
 /// https://webassembly.github.io/spec/core/binary/instructions.html#expressions
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Instructions {
-    ControlInstructions(ControlInstructions),
-    ReferenceInstructions(ReferenceInstructions),
-    ParametricInstructions(ParametricInstructions),
-    VariableInstructions(VariableInstructions),
-    TableInstructions(TableInstructions),
-    MemoryInstructions(MemoryInstructions),
-    NumericInstructions(NumericInstructions, Primitives),
+    ControlInstructions(ControlInstructions, ControlOperands),
+    ReferenceInstructions(ReferenceInstructions, ReferenceOperands),
+    // select t* carries the value types, the others carry nothing.
+    ParametricInstructions(ParametricInstructions, Vec<ValueType>),
+    VariableInstructions(VariableInstructions, u32),
+    TableInstructions(TableInstructions, TableOperands),
+    MemoryInstructions(MemoryInstructions, MemoryOperands),
+    // only the const instructions carry a primitive.
+    NumericInstructions(NumericInstructions, Option<Primitives>),
     VectorInstructions(VectorInstructions),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Primitives {
     I32(i32),
     I64(i64),
@@ -280,30 +364,935 @@ pub enum Primitives {
 
 impl NumericInstructions {
     pub fn is_i32(&self) -> bool {
+        matches!(self, NumericInstructions::I32Const)
+    }
+
+    pub fn is_f32(&self) -> bool {
+        matches!(self, NumericInstructions::F32Const)
+    }
+
+    pub fn is_i64(&self) -> bool {
+        matches!(self, NumericInstructions::I64Const)
+    }
+
+    pub fn is_f64(&self) -> bool {
+        matches!(self, NumericInstructions::F64Const)
+    }
+}
+
+impl ControlInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x00 => Some(Self::Unreachable),
+            0x01 => Some(Self::Nop),
+            0x02 => Some(Self::Block),
+            0x03 => Some(Self::Loop),
+            0x04 => Some(Self::If),
+            0x05 => Some(Self::Else),
+            0x0b => Some(Self::End),
+            0x0c => Some(Self::Br),
+            0x0d => Some(Self::BrIf),
+            0x0e => Some(Self::BrTable),
+            0x0f => Some(Self::Return),
+            0x10 => Some(Self::Call),
+            0x11 => Some(Self::CallIndirect),
+            _ => None,
+        }
+    }
+}
+
+impl ReferenceInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0xd0 => Some(Self::RefNull),
+            0xd1 => Some(Self::RefIsNull),
+            0xd2 => Some(Self::RefFunc),
+            _ => None,
+        }
+    }
+}
+
+impl ParametricInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x1a => Some(Self::Drop),
+            0x1b => Some(Self::Select),
+            0x1c => Some(Self::SelectV),
+            _ => None,
+        }
+    }
+}
+
+impl VariableInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x20 => Some(Self::LocalGet),
+            0x21 => Some(Self::LocalSet),
+            0x22 => Some(Self::LocalTee),
+            0x23 => Some(Self::GlobalGet),
+            0x24 => Some(Self::GlobalSet),
+            _ => None,
+        }
+    }
+}
+
+impl TableInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x25 => Some(Self::TableGet),
+            0x26 => Some(Self::TableSet),
+            _ => None,
+        }
+    }
+
+    /// The instructions after `MISC_PREFIX`.
+    pub(crate) fn from_misc_opcode(opcode: u32) -> Option<Self> {
+        match opcode {
+            0x0c => Some(Self::TableInit),
+            0x0d => Some(Self::ElemDrop),
+            0x0e => Some(Self::TableCopy),
+            0x0f => Some(Self::TableGrow),
+            0x10 => Some(Self::TableSize),
+            0x11 => Some(Self::TableFill),
+            _ => None,
+        }
+    }
+}
+
+impl MemoryInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x28 => Some(Self::I32Load),
+            0x29 => Some(Self::I64Load),
+            0x2a => Some(Self::F32Load),
+            0x2b => Some(Self::F64Load),
+            0x2c => Some(Self::I32Load8Signed),
+            0x2d => Some(Self::I32Load8Unsigned),
+            0x2e => Some(Self::I32Load16Signed),
+            0x2f => Some(Self::I32Load16Unsigned),
+            0x30 => Some(Self::I64Load8Signed),
+            0x31 => Some(Self::I64Load8Unsigned),
+            0x32 => Some(Self::I64Load16Signed),
+            0x33 => Some(Self::I64Load16Unsigned),
+            0x34 => Some(Self::I64Load32Signed),
+            0x35 => Some(Self::I64Load32Unsigned),
+            0x36 => Some(Self::I32Store),
+            0x37 => Some(Self::I64Store),
+            0x38 => Some(Self::F32Store),
+            0x39 => Some(Self::F64Store),
+            0x3a => Some(Self::I32Store8),
+            0x3b => Some(Self::I32Store16),
+            0x3c => Some(Self::I64Store8),
+            0x3d => Some(Self::I64Store16),
+            0x3e => Some(Self::I64Store32),
+            0x3f => Some(Self::Size),
+            0x40 => Some(Self::Grow),
+            _ => None,
+        }
+    }
+
+    /// The instructions after `MISC_PREFIX`.
+    pub(crate) fn from_misc_opcode(opcode: u32) -> Option<Self> {
+        match opcode {
+            0x08 => Some(Self::Init),
+            0x09 => Some(Self::Drop),
+            0x0a => Some(Self::Copy),
+            0x0b => Some(Self::Fill),
+            _ => None,
+        }
+    }
+}
+
+impl NumericInstructions {
+    pub(crate) fn from_opcode(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x41 => Some(Self::I32Const),
+            0x42 => Some(Self::I64Const),
+            0x43 => Some(Self::F32Const),
+            0x44 => Some(Self::F64Const),
+            0x45 => Some(Self::I32Eqz),
+            0x46 => Some(Self::I32Eq),
+            0x47 => Some(Self::I32Ne),
+            0x48 => Some(Self::I32LtSigned),
+            0x49 => Some(Self::I32LtUnsigned),
+            0x4a => Some(Self::I32GtSigned),
+            0x4b => Some(Self::I32GtUnsigned),
+            0x4c => Some(Self::I32LeSigned),
+            0x4d => Some(Self::I32LeUnsigned),
+            0x4e => Some(Self::I32GeSigned),
+            0x4f => Some(Self::I32GeUnsigned),
+            0x50 => Some(Self::I64Eqz),
+            0x51 => Some(Self::I64Eq),
+            0x52 => Some(Self::I64Ne),
+            0x53 => Some(Self::I64LtSigned),
+            0x54 => Some(Self::I64LtUnsigned),
+            0x55 => Some(Self::I64GtSigned),
+            0x56 => Some(Self::I64GtUnsigned),
+            0x57 => Some(Self::I64LeSigned),
+            0x58 => Some(Self::I64LeUnsigned),
+            0x59 => Some(Self::I64GeSigned),
+            0x5a => Some(Self::I64GeUnsigned),
+            0x5b => Some(Self::F32Eq),
+            0x5c => Some(Self::F32Ne),
+            0x5d => Some(Self::F32Lt),
+            0x5e => Some(Self::F32Gt),
+            0x5f => Some(Self::F32Le),
+            0x60 => Some(Self::F32Ge),
+            0x61 => Some(Self::F64Eq),
+            0x62 => Some(Self::F64Ne),
+            0x63 => Some(Self::F64Lt),
+            0x64 => Some(Self::F64Gt),
+            0x65 => Some(Self::F64Le),
+            0x66 => Some(Self::F64Ge),
+            0x67 => Some(Self::I32Clz),
+            0x68 => Some(Self::I32Ctz),
+            0x69 => Some(Self::I32PopCnt),
+            0x6a => Some(Self::I32Add),
+            0x6b => Some(Self::I32Sub),
+            0x6c => Some(Self::I32Mul),
+            0x6d => Some(Self::I32DivSigned),
+            0x6e => Some(Self::I32DivUnsigned),
+            0x6f => Some(Self::I32RemSigned),
+            0x70 => Some(Self::I32RemUnsigned),
+            0x71 => Some(Self::I32And),
+            0x72 => Some(Self::I32Or),
+            0x73 => Some(Self::I32Xor),
+            0x74 => Some(Self::I32Shl),
+            0x75 => Some(Self::I32ShrSigned),
+            0x76 => Some(Self::I32ShrUnsigned),
+            0x77 => Some(Self::I32Rotl),
+            0x78 => Some(Self::I32Rotr),
+            0x79 => Some(Self::I64Clz),
+            0x7a => Some(Self::I64Ctz),
+            0x7b => Some(Self::I64PopCnt),
+            0x7c => Some(Self::I64Add),
+            0x7d => Some(Self::I64Sub),
+            0x7e => Some(Self::I64Mul),
+            0x7f => Some(Self::I64DivSigned),
+            0x80 => Some(Self::I64DivUnsigned),
+            0x81 => Some(Self::I64RemSigned),
+            0x82 => Some(Self::I64RemUnsigned),
+            0x83 => Some(Self::I64And),
+            0x84 => Some(Self::I64Or),
+            0x85 => Some(Self::I64Xor),
+            0x86 => Some(Self::I64Shl),
+            0x87 => Some(Self::I64ShrSigned),
+            0x88 => Some(Self::I64ShrUnsigned),
+            0x89 => Some(Self::I64Rotl),
+            0x8a => Some(Self::I64Rotr),
+            0x8b => Some(Self::F32Abs),
+            0x8c => Some(Self::F32Neg),
+            0x8d => Some(Self::F32Ceil),
+            0x8e => Some(Self::F32Floor),
+            0x8f => Some(Self::F32Trunc),
+            0x90 => Some(Self::F32Nearest),
+            0x91 => Some(Self::F32Sqrt),
+            0x92 => Some(Self::F32Add),
+            0x93 => Some(Self::F32Sub),
+            0x94 => Some(Self::F32Mul),
+            0x95 => Some(Self::F32Div),
+            0x96 => Some(Self::F32Min),
+            0x97 => Some(Self::F32Max),
+            0x98 => Some(Self::F32CopySign),
+            0x99 => Some(Self::F64Abs),
+            0x9a => Some(Self::F64Neg),
+            0x9b => Some(Self::F64Ceil),
+            0x9c => Some(Self::F64Floor),
+            0x9d => Some(Self::F64Trunc),
+            0x9e => Some(Self::F64Nearest),
+            0x9f => Some(Self::F64Sqrt),
+            0xa0 => Some(Self::F64Add),
+            0xa1 => Some(Self::F64Sub),
+            0xa2 => Some(Self::F64Mul),
+            0xa3 => Some(Self::F64Div),
+            0xa4 => Some(Self::F64Min),
+            0xa5 => Some(Self::F64Mx),
+            0xa6 => Some(Self::F64CopySign),
+            0xa7 => Some(Self::I32WrapI64),
+            0xa8 => Some(Self::I32TruncF32Signed),
+            0xa9 => Some(Self::I32TruncF32Unsigned),
+            0xaa => Some(Self::I32TruncF64Signed),
+            0xab => Some(Self::I32TruncF64Unsigned),
+            0xac => Some(Self::I64ExtendI32Signed),
+            0xad => Some(Self::I64ExtendI32Unsigned),
+            0xae => Some(Self::I64TruncF32Signed),
+            0xaf => Some(Self::I64TruncF32Unsigned),
+            0xb0 => Some(Self::I64TruncF64Signed),
+            0xb1 => Some(Self::I64TruncF64Unsigned),
+            0xb2 => Some(Self::F32ConvertI32Signed),
+            0xb3 => Some(Self::F32ConvertI32Unsigned),
+            0xb4 => Some(Self::F32ConvertI64Signed),
+            0xb5 => Some(Self::F32ConvertI64Unsigned),
+            0xb6 => Some(Self::F32DemoteF64),
+            0xb7 => Some(Self::F64ConvertI32Signed),
+            0xb8 => Some(Self::F64ConvertI32Unsigned),
+            0xb9 => Some(Self::F64ConvertI64Signed),
+            0xba => Some(Self::F64ConvertI64Unsigned),
+            0xbb => Some(Self::F64PromoteF32),
+            0xbc => Some(Self::I32ReinterpretF32),
+            0xbd => Some(Self::I64ReinterpretF64),
+            0xbe => Some(Self::F32ReinterpretI32),
+            0xbf => Some(Self::F64ReinterpretI64),
+            0xc0 => Some(Self::I32Extend8Signed),
+            0xc1 => Some(Self::I32Extend16Signed),
+            0xc2 => Some(Self::I64Extend8Signed),
+            0xc3 => Some(Self::I64Extend16Signed),
+            0xc4 => Some(Self::I64Extend32Signed),
+            _ => None,
+        }
+    }
+
+    /// The instructions after `MISC_PREFIX`.
+    pub(crate) fn from_misc_opcode(opcode: u32) -> Option<Self> {
+        match opcode {
+            0x00 => Some(Self::I32TruncSatF32Signed),
+            0x01 => Some(Self::I32TruncSatF32Unsigned),
+            0x02 => Some(Self::I32TruncSatF64Signed),
+            0x03 => Some(Self::I32TruncSatF64Unsigned),
+            0x04 => Some(Self::I64TruncSatF32Signed),
+            0x05 => Some(Self::I64TruncSatF32Unsigned),
+            0x06 => Some(Self::I64TruncSatF64Signed),
+            0x07 => Some(Self::I64TruncSatF64Unsigned),
+            _ => None,
+        }
+    }
+}
+
+impl ControlInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
         match self {
-            NumericInstructions::I32Const => true,
-            _ => false,
+            Self::Unreachable => "unreachable",
+            Self::Nop => "nop",
+            Self::Block => "block",
+            Self::Loop => "loop",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::End => "end",
+            Self::Br => "br",
+            Self::BrIf => "br_if",
+            Self::BrTable => "br_table",
+            Self::Return => "return",
+            Self::Call => "call",
+            Self::CallIndirect => "call_indirect",
+            Self::BlockType => "blocktype",
         }
     }
 
-    pub fn is_f32(&self) -> bool {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .find(|op| op.name() == name)
+    }
+}
+
+impl ReferenceInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
         match self {
-            NumericInstructions::F32Const => true,
-            _ => false,
+            Self::RefNull => "ref.null",
+            Self::RefIsNull => "ref.is_null",
+            Self::RefFunc => "ref.func",
         }
     }
 
-    pub fn is_i64(&self) -> bool {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .find(|op| op.name() == name)
+    }
+}
+
+impl ParametricInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
         match self {
-            NumericInstructions::I64Const => true,
-            _ => false,
+            Self::Drop => "drop",
+            Self::Select => "select",
+            Self::SelectV => "select",
         }
     }
 
-    pub fn is_f64(&self) -> bool {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .find(|op| op.name() == name)
+    }
+}
+
+impl VariableInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
         match self {
-            NumericInstructions::F64Const => true,
-            _ => false,
+            Self::LocalGet => "local.get",
+            Self::LocalSet => "local.set",
+            Self::LocalTee => "local.tee",
+            Self::GlobalGet => "global.get",
+            Self::GlobalSet => "global.set",
         }
     }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .find(|op| op.name() == name)
+    }
+}
+
+impl TableInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TableGet => "table.get",
+            Self::TableSet => "table.set",
+            Self::TableInit => "table.init",
+            Self::ElemDrop => "elem.drop",
+            Self::TableCopy => "table.copy",
+            Self::TableGrow => "table.grow",
+            Self::TableSize => "table.size",
+            Self::TableFill => "table.fill",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .chain((0..=u8::MAX as u32).filter_map(Self::from_misc_opcode))
+            .find(|op| op.name() == name)
+    }
+}
+
+impl MemoryInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::I32Load => "i32.load",
+            Self::I64Load => "i64.load",
+            Self::F32Load => "f32.load",
+            Self::F64Load => "f64.load",
+            Self::I32Load8Signed => "i32.load8_s",
+            Self::I32Load8Unsigned => "i32.load8_u",
+            Self::I32Load16Signed => "i32.load16_s",
+            Self::I32Load16Unsigned => "i32.load16_u",
+            Self::I64Load8Signed => "i64.load8_s",
+            Self::I64Load8Unsigned => "i64.load8_u",
+            Self::I64Load16Signed => "i64.load16_s",
+            Self::I64Load16Unsigned => "i64.load16_u",
+            Self::I64Load32Signed => "i64.load32_s",
+            Self::I64Load32Unsigned => "i64.load32_u",
+            Self::I32Store => "i32.store",
+            Self::I64Store => "i64.store",
+            Self::F32Store => "f32.store",
+            Self::F64Store => "f64.store",
+            Self::I32Store8 => "i32.store8",
+            Self::I32Store16 => "i32.store16",
+            Self::I64Store8 => "i64.store8",
+            Self::I64Store16 => "i64.store16",
+            Self::I64Store32 => "i64.store32",
+            Self::Size => "memory.size",
+            Self::Grow => "memory.grow",
+            Self::Init => "memory.init",
+            Self::Drop => "data.drop",
+            Self::Copy => "memory.copy",
+            Self::Fill => "memory.fill",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .chain((0..=u8::MAX as u32).filter_map(Self::from_misc_opcode))
+            .find(|op| op.name() == name)
+    }
+}
+
+impl NumericInstructions {
+    /// The name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::I32Const => "i32.const",
+            Self::I64Const => "i64.const",
+            Self::F32Const => "f32.const",
+            Self::F64Const => "f64.const",
+            Self::I32Eqz => "i32.eqz",
+            Self::I32Eq => "i32.eq",
+            Self::I32Ne => "i32.ne",
+            Self::I32LtSigned => "i32.lt_s",
+            Self::I32LtUnsigned => "i32.lt_u",
+            Self::I32GtSigned => "i32.gt_s",
+            Self::I32GtUnsigned => "i32.gt_u",
+            Self::I32LeSigned => "i32.le_s",
+            Self::I32LeUnsigned => "i32.le_u",
+            Self::I32GeSigned => "i32.ge_s",
+            Self::I32GeUnsigned => "i32.ge_u",
+            Self::I64Eqz => "i64.eqz",
+            Self::I64Eq => "i64.eq",
+            Self::I64Ne => "i64.ne",
+            Self::I64LtSigned => "i64.lt_s",
+            Self::I64LtUnsigned => "i64.lt_u",
+            Self::I64GtSigned => "i64.gt_s",
+            Self::I64GtUnsigned => "i64.gt_u",
+            Self::I64LeSigned => "i64.le_s",
+            Self::I64LeUnsigned => "i64.le_u",
+            Self::I64GeSigned => "i64.ge_s",
+            Self::I64GeUnsigned => "i64.ge_u",
+            Self::F32Eq => "f32.eq",
+            Self::F32Ne => "f32.ne",
+            Self::F32Lt => "f32.lt",
+            Self::F32Gt => "f32.gt",
+            Self::F32Le => "f32.le",
+            Self::F32Ge => "f32.ge",
+            Self::F64Eq => "f64.eq",
+            Self::F64Ne => "f64.ne",
+            Self::F64Lt => "f64.lt",
+            Self::F64Gt => "f64.gt",
+            Self::F64Le => "f64.le",
+            Self::F64Ge => "f64.ge",
+            Self::I32Clz => "i32.clz",
+            Self::I32Ctz => "i32.ctz",
+            Self::I32PopCnt => "i32.popcnt",
+            Self::I32Add => "i32.add",
+            Self::I32Sub => "i32.sub",
+            Self::I32Mul => "i32.mul",
+            Self::I32DivSigned => "i32.div_s",
+            Self::I32DivUnsigned => "i32.div_u",
+            Self::I32RemSigned => "i32.rem_s",
+            Self::I32RemUnsigned => "i32.rem_u",
+            Self::I32And => "i32.and",
+            Self::I32Or => "i32.or",
+            Self::I32Xor => "i32.xor",
+            Self::I32Shl => "i32.shl",
+            Self::I32ShrSigned => "i32.shr_s",
+            Self::I32ShrUnsigned => "i32.shr_u",
+            Self::I32Rotl => "i32.rotl",
+            Self::I32Rotr => "i32.rotr",
+            Self::I64Clz => "i64.clz",
+            Self::I64Ctz => "i64.ctz",
+            Self::I64PopCnt => "i64.popcnt",
+            Self::I64Add => "i64.add",
+            Self::I64Sub => "i64.sub",
+            Self::I64Mul => "i64.mul",
+            Self::I64DivSigned => "i64.div_s",
+            Self::I64DivUnsigned => "i64.div_u",
+            Self::I64RemSigned => "i64.rem_s",
+            Self::I64RemUnsigned => "i64.rem_u",
+            Self::I64And => "i64.and",
+            Self::I64Or => "i64.or",
+            Self::I64Xor => "i64.xor",
+            Self::I64Shl => "i64.shl",
+            Self::I64ShrSigned => "i64.shr_s",
+            Self::I64ShrUnsigned => "i64.shr_u",
+            Self::I64Rotl => "i64.rotl",
+            Self::I64Rotr => "i64.rotr",
+            Self::F32Abs => "f32.abs",
+            Self::F32Neg => "f32.neg",
+            Self::F32Ceil => "f32.ceil",
+            Self::F32Floor => "f32.floor",
+            Self::F32Trunc => "f32.trunc",
+            Self::F32Nearest => "f32.nearest",
+            Self::F32Sqrt => "f32.sqrt",
+            Self::F32Add => "f32.add",
+            Self::F32Sub => "f32.sub",
+            Self::F32Mul => "f32.mul",
+            Self::F32Div => "f32.div",
+            Self::F32Min => "f32.min",
+            Self::F32Max => "f32.max",
+            Self::F32CopySign => "f32.copysign",
+            Self::F64Abs => "f64.abs",
+            Self::F64Neg => "f64.neg",
+            Self::F64Ceil => "f64.ceil",
+            Self::F64Floor => "f64.floor",
+            Self::F64Trunc => "f64.trunc",
+            Self::F64Nearest => "f64.nearest",
+            Self::F64Sqrt => "f64.sqrt",
+            Self::F64Add => "f64.add",
+            Self::F64Sub => "f64.sub",
+            Self::F64Mul => "f64.mul",
+            Self::F64Div => "f64.div",
+            Self::F64Min => "f64.min",
+            Self::F64Mx => "f64.max",
+            Self::F64CopySign => "f64.copysign",
+            Self::I32WrapI64 => "i32.wrap_i64",
+            Self::I32TruncF32Signed => "i32.trunc_f32_s",
+            Self::I32TruncF32Unsigned => "i32.trunc_f32_u",
+            Self::I32TruncF64Signed => "i32.trunc_f64_s",
+            Self::I32TruncF64Unsigned => "i32.trunc_f64_u",
+            Self::I64ExtendI32Signed => "i64.extend_i32_s",
+            Self::I64ExtendI32Unsigned => "i64.extend_i32_u",
+            Self::I64TruncF32Signed => "i64.trunc_f32_s",
+            Self::I64TruncF32Unsigned => "i64.trunc_f32_u",
+            Self::I64TruncF64Signed => "i64.trunc_f64_s",
+            Self::I64TruncF64Unsigned => "i64.trunc_f64_u",
+            Self::F32ConvertI32Signed => "f32.convert_i32_s",
+            Self::F32ConvertI32Unsigned => "f32.convert_i32_u",
+            Self::F32ConvertI64Signed => "f32.convert_i64_s",
+            Self::F32ConvertI64Unsigned => "f32.convert_i64_u",
+            Self::F32DemoteF64 => "f32.demote_f64",
+            Self::F64ConvertI32Signed => "f64.convert_i32_s",
+            Self::F64ConvertI32Unsigned => "f64.convert_i32_u",
+            Self::F64ConvertI64Signed => "f64.convert_i64_s",
+            Self::F64ConvertI64Unsigned => "f64.convert_i64_u",
+            Self::F64PromoteF32 => "f64.promote_f32",
+            Self::I32ReinterpretF32 => "i32.reinterpret_f32",
+            Self::I64ReinterpretF64 => "i64.reinterpret_f64",
+            Self::F32ReinterpretI32 => "f32.reinterpret_i32",
+            Self::F64ReinterpretI64 => "f64.reinterpret_i64",
+            Self::I32Extend8Signed => "i32.extend8_s",
+            Self::I32Extend16Signed => "i32.extend16_s",
+            Self::I64Extend8Signed => "i64.extend8_s",
+            Self::I64Extend16Signed => "i64.extend16_s",
+            Self::I64Extend32Signed => "i64.extend32_s",
+            Self::I32TruncSatF32Signed => "i32.trunc_sat_f32_s",
+            Self::I32TruncSatF32Unsigned => "i32.trunc_sat_f32_u",
+            Self::I32TruncSatF64Signed => "i32.trunc_sat_f64_s",
+            Self::I32TruncSatF64Unsigned => "i32.trunc_sat_f64_u",
+            Self::I64TruncSatF32Signed => "i64.trunc_sat_f32_s",
+            Self::I64TruncSatF32Unsigned => "i64.trunc_sat_f32_u",
+            Self::I64TruncSatF64Signed => "i64.trunc_sat_f64_s",
+            Self::I64TruncSatF64Unsigned => "i64.trunc_sat_f64_u",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_opcode)
+            .chain((0..=u8::MAX as u32).filter_map(Self::from_misc_opcode))
+            .find(|op| op.name() == name)
+    }
+}
+impl MemoryInstructions {
+    /// The alignment exponent of the accessed width, used when `align=` is omitted in the text format.
+    pub fn natural_align(&self) -> u32 {
+        match self {
+            Self::I32Load8Signed
+            | Self::I32Load8Unsigned
+            | Self::I64Load8Signed
+            | Self::I64Load8Unsigned
+            | Self::I32Store8
+            | Self::I64Store8
+            | Self::Size
+            | Self::Grow
+            | Self::Init
+            | Self::Drop
+            | Self::Copy
+            | Self::Fill => 0,
+            Self::I32Load16Signed
+            | Self::I32Load16Unsigned
+            | Self::I64Load16Signed
+            | Self::I64Load16Unsigned
+            | Self::I32Store16
+            | Self::I64Store16 => 1,
+            Self::I32Load
+            | Self::F32Load
+            | Self::I64Load32Signed
+            | Self::I64Load32Unsigned
+            | Self::I32Store
+            | Self::F32Store
+            | Self::I64Store32 => 2,
+            Self::I64Load | Self::F64Load | Self::I64Store | Self::F64Store => 3,
+        }
+    }
+}
+
+impl<'a> Parse<'a> for BlockType {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (byte, next) = read_u8(bytes, offset)?;
+        match byte {
+            0x40 => Ok((BlockType::Empty, next)),
+            0x7f | 0x7e | 0x7d | 0x7c | 0x7b | 0x70 | 0x6f => {
+                let (ty, next) = ValueType::from_bytes(bytes, offset)?;
+                Ok((BlockType::Value(ty), next))
+            }
+            // the type index is encoded as a positive signed 33 bits integer.
+            _ => {
+                let (type_idx, count) = signed_leb128_decode(bytes, offset, 33)?;
+                if type_idx < 0 {
+                    return Err(Error::InvalidTag { tag: byte, offset });
+                }
+                Ok((BlockType::TypeIndex(type_idx as TypeIndex), offset + count))
+            }
+        }
+    }
+}
+
+impl<'a> Parse<'a> for MemArg {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (align, offset) = calc_len_and_offset(bytes, offset)?;
+        let (mem_offset, offset) = calc_len_and_offset(bytes, offset)?;
+        Ok((
+            Self {
+                align: align as u32,
+                offset: mem_offset as u32,
+            },
+            offset,
+        ))
+    }
+}
+
+/// Read the zero byte which stands for the memory index, only one memory is supported.
+fn read_reserved(bytes: &[u8], offset: usize) -> Result<usize, Error> {
+    let (reserved, next) = read_u8(bytes, offset)?;
+    if reserved != 0 {
+        return Err(Error::InvalidTag {
+            tag: reserved,
+            offset,
+        });
+    }
+    Ok(next)
+}
+
+/// Read the immediate of `*.const` instructions.
+fn read_primitive(
+    op: NumericInstructions,
+    bytes: &[u8],
+    offset: usize,
+) -> Result<(Option<Primitives>, usize), Error> {
+    let (primitive, offset) = match op {
+        NumericInstructions::I32Const => {
+            let (result, offset) = read_i32(bytes, offset)?;
+            (Primitives::I32(result), offset)
+        }
+        NumericInstructions::I64Const => {
+            let (result, offset) = read_i64(bytes, offset)?;
+            (Primitives::I64(result), offset)
+        }
+        NumericInstructions::F32Const => {
+            let (result, offset) = read_f32(bytes, offset)?;
+            (Primitives::F32(result), offset)
+        }
+        NumericInstructions::F64Const => {
+            let (result, offset) = read_f64(bytes, offset)?;
+            (Primitives::F64(result), offset)
+        }
+        _ => return Ok((None, offset)),
+    };
+    Ok((Some(primitive), offset))
+}
+
+impl<'a> Parse<'a> for Instructions {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (opcode, next) = read_u8(bytes, offset)?;
+
+        if opcode == MISC_PREFIX {
+            let (misc_opcode, next) = calc_len_and_offset(bytes, next)?;
+            let misc_opcode = u32::try_from(misc_opcode).unwrap_or(u32::MAX);
+            if let Some(op) = NumericInstructions::from_misc_opcode(misc_opcode) {
+                return Ok((Instructions::NumericInstructions(op, None), next));
+            }
+            if let Some(op) = TableInstructions::from_misc_opcode(misc_opcode) {
+                let (index, next) = calc_len_and_offset(bytes, next)?;
+                let index = index as u32;
+                let (operands, next) = match op {
+                    // the element segment goes first in the binary format.
+                    TableInstructions::TableInit => {
+                        let (table_idx, next) = calc_len_and_offset(bytes, next)?;
+                        (TableOperands::Init(table_idx as TableIndex, index), next)
+                    }
+                    TableInstructions::ElemDrop => (TableOperands::Elem(index), next),
+                    TableInstructions::TableCopy => {
+                        let (src, next) = calc_len_and_offset(bytes, next)?;
+                        (TableOperands::Copy(index, src as TableIndex), next)
+                    }
+                    _ => (TableOperands::Table(index), next),
+                };
+                return Ok((Instructions::TableInstructions(op, operands), next));
+            }
+            if let Some(op) = MemoryInstructions::from_misc_opcode(misc_opcode) {
+                let (operands, next) = match op {
+                    MemoryInstructions::Init => {
+                        let (data_idx, next) = calc_len_and_offset(bytes, next)?;
+                        let operands = MemoryOperands::Data(data_idx as DataIndex);
+                        (operands, read_reserved(bytes, next)?)
+                    }
+                    MemoryInstructions::Drop => {
+                        let (data_idx, next) = calc_len_and_offset(bytes, next)?;
+                        (MemoryOperands::Data(data_idx as DataIndex), next)
+                    }
+                    // the destination and the source memory.
+                    MemoryInstructions::Copy => {
+                        let next = read_reserved(bytes, next)?;
+                        (MemoryOperands::None, read_reserved(bytes, next)?)
+                    }
+                    _ => (MemoryOperands::None, read_reserved(bytes, next)?),
+                };
+                return Ok((Instructions::MemoryInstructions(op, operands), next));
+            }
+            return Err(Error::UnsupportedInstruction { opcode, offset });
+        }
+
+        if let Some(op) = ControlInstructions::from_opcode(opcode) {
+            let (operands, next) = match op {
+                ControlInstructions::Block
+                | ControlInstructions::Loop
+                | ControlInstructions::If => {
+                    let (block_type, next) = BlockType::from_bytes(bytes, next)?;
+                    (ControlOperands::Block(block_type), next)
+                }
+                ControlInstructions::Br | ControlInstructions::BrIf => {
+                    let (label, next) = calc_len_and_offset(bytes, next)?;
+                    (ControlOperands::Label(label as LabelIndex), next)
+                }
+                ControlInstructions::BrTable => {
+                    let (labels, next) = read_vec(bytes, next, calc_len_and_offset)?;
+                    let (default, next) = calc_len_and_offset(bytes, next)?;
+                    let labels = labels.into_iter().map(|l| l as LabelIndex).collect();
+                    (
+                        ControlOperands::BrTable(labels, default as LabelIndex),
+                        next,
+                    )
+                }
+                ControlInstructions::Call => {
+                    let (func_idx, next) = calc_len_and_offset(bytes, next)?;
+                    (ControlOperands::Func(func_idx as FuncIndex), next)
+                }
+                ControlInstructions::CallIndirect => {
+                    let (type_idx, next) = calc_len_and_offset(bytes, next)?;
+                    let (table_idx, next) = calc_len_and_offset(bytes, next)?;
+                    (
+                        ControlOperands::CallIndirect(
+                            type_idx as TypeIndex,
+                            table_idx as TableIndex,
+                        ),
+                        next,
+                    )
+                }
+                _ => (ControlOperands::None, next),
+            };
+            return Ok((Instructions::ControlInstructions(op, operands), next));
+        }
+
+        if let Some(op) = ReferenceInstructions::from_opcode(opcode) {
+            let (operands, next) = match op {
+                ReferenceInstructions::RefNull => {
+                    let (ref_type, next) = ReferenceType::from_bytes(bytes, next)?;
+                    (ReferenceOperands::RefType(ref_type), next)
+                }
+                ReferenceInstructions::RefFunc => {
+                    let (func_idx, next) = calc_len_and_offset(bytes, next)?;
+                    (ReferenceOperands::Func(func_idx as FuncIndex), next)
+                }
+                ReferenceInstructions::RefIsNull => (ReferenceOperands::None, next),
+            };
+            return Ok((Instructions::ReferenceInstructions(op, operands), next));
+        }
+
+        if let Some(op) = ParametricInstructions::from_opcode(opcode) {
+            let (types, next) = match op {
+                ParametricInstructions::SelectV => read_vec(bytes, next, ValueType::from_bytes)?,
+                _ => (vec![], next),
+            };
+            return Ok((Instructions::ParametricInstructions(op, types), next));
+        }
+
+        if let Some(op) = VariableInstructions::from_opcode(opcode) {
+            let (index, next) = calc_len_and_offset(bytes, next)?;
+            return Ok((Instructions::VariableInstructions(op, index as u32), next));
+        }
+
+        if let Some(op) = TableInstructions::from_opcode(opcode) {
+            let (table_idx, next) = calc_len_and_offset(bytes, next)?;
+            let operands = TableOperands::Table(table_idx as TableIndex);
+            return Ok((Instructions::TableInstructions(op, operands), next));
+        }
+
+        if let Some(op) = MemoryInstructions::from_opcode(opcode) {
+            let (operands, next) = match op {
+                // memory.size and memory.grow are followed by a zero byte.
+                MemoryInstructions::Size | MemoryInstructions::Grow => {
+                    (MemoryOperands::None, read_reserved(bytes, next)?)
+                }
+                _ => {
+                    let (mem_arg, next) = MemArg::from_bytes(bytes, next)?;
+                    (MemoryOperands::MemArg(mem_arg), next)
+                }
+            };
+            return Ok((Instructions::MemoryInstructions(op, operands), next));
+        }
+
+        if let Some(op) = NumericInstructions::from_opcode(opcode) {
+            let (primitive, next) = read_primitive(op, bytes, next)?;
+            return Ok((Instructions::NumericInstructions(op, primitive), next));
+        }
+
+        Err(Error::UnsupportedInstruction { opcode, offset })
+    }
+}
+
+impl Expressions {
+    pub const END: u8 = 0x0b;
+
+    /// Parse the instructions until the `end` which closes the whole expression,
+    /// the `end` of nested blocks are kept in the instructions.
+    /// Return the expression with the offset after the last `end`.
+    pub fn from_bytes(bytes: &[u8], offset: usize) -> Result<(Self, usize), Error> {
+        let mut instructions = vec![];
+        let mut depth = 0usize;
+        let mut offset = offset;
+        loop {
+            let (instruction, next) = Instructions::from_bytes(bytes, offset)?;
+            offset = next;
+            match instruction {
+                Instructions::ControlInstructions(
+                    ControlInstructions::Block
+                    | ControlInstructions::Loop
+                    | ControlInstructions::If,
+                    _,
+                ) => depth += 1,
+                Instructions::ControlInstructions(ControlInstructions::End, _) => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+                _ => (),
+            }
+            instructions.push(instruction);
+        }
+
+        let expressions = Expressions {
+            end: Self::END,
+            instructions,
+        };
+        Ok((expressions, offset))
+    }
+
+    /// Parse a constant expression, like the initializer of global, the offset of element or data,
+    /// or the items of element. Return the expression with the offset after `end`.
+    pub(crate) fn from_const_bytes(bytes: &[u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (opcode, _) = read_u8(bytes, offset)?;
+        let (instruction, next) = Instructions::from_bytes(bytes, offset)?;
+        match instruction {
+            Instructions::NumericInstructions(
+                NumericInstructions::I32Const
+                | NumericInstructions::I64Const
+                | NumericInstructions::F32Const
+                | NumericInstructions::F64Const,
+                _,
+            )
+            | Instructions::ReferenceInstructions(
+                ReferenceInstructions::RefNull | ReferenceInstructions::RefFunc,
+                _,
+            )
+            | Instructions::VariableInstructions(VariableInstructions::GlobalGet, _) => (),
+            _ => return Err(Error::UnsupportedInstruction { opcode, offset }),
+        }
+
+        // the end must follow the instruction.
+        let (end, offset) = read_u8(bytes, next)?;
+        if end != Self::END {
+            return Err(Error::ExpectedEnd { offset: next });
+        }
+
+        let expressions = Expressions {
+            end: Self::END,
+            instructions: vec![instruction],
+        };
+        Ok((expressions, offset))
+    }
 }
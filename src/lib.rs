@@ -0,0 +1,12 @@
+extern crate alloc;
+
+pub mod deserialize;
+pub mod instructions;
+pub mod sections;
+pub mod serialize;
+pub mod text;
+pub mod utils;
+pub mod validation;
+pub mod vm;
+pub mod wasi;
+pub mod wast;
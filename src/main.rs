@@ -1,12 +1,324 @@
-extern crate alloc;
+use std::fs;
 
-pub mod deserialize;
-pub mod instructions;
-pub mod sections;
-pub mod serialize;
-pub mod utils;
-pub mod vm;
+use luban::sections::{self, NumberType, ValueType, WasmModule};
+use luban::text::{self, ParseError};
+use luban::vm::{InstantiationError, Linker, Trap, Value};
+use luban::wasi::{Wasi, WasiCtx};
+use thiserror::Error;
+
+const USAGE: &str = "usage: luban run [--dir <host>[::<guest>]]... [--env <key>=<value>]... \
+<module.wasm> [--invoke <name>] [args...]";
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("{USAGE}")]
+    Usage,
+    #[error("failed to read {path}: {error}")]
+    Io { path: String, error: std::io::Error },
+    #[error("malformed module: {0}")]
+    Malformed(#[from] sections::Error),
+    #[error("malformed text module: {0}")]
+    Parse(#[from] ParseError),
+    #[error("{0}")]
+    Instantiation(#[from] InstantiationError),
+    #[error("unknown exported function {0}")]
+    UnknownExport(String),
+    #[error("expected {expected} arguments, got {actual}")]
+    ArgumentCount { expected: usize, actual: usize },
+    #[error("invalid argument {value} for {ty:?}")]
+    InvalidArgument { value: String, ty: ValueType },
+    #[error("trap: {0}")]
+    Trap(#[from] Trap),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// `luban run [options] <path> [--invoke <name>] [args...]`
+/// The args are passed to the invoked function, or to the WASI program without `--invoke`.
+#[derive(Debug, Default, PartialEq)]
+struct Command {
+    path: String,
+    invoke: Option<String>,
+    args: Vec<String>,
+    /// The host directories and their guest paths.
+    dirs: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+}
+
+fn parse_command(args: &[String]) -> Result<Command, CliError> {
+    let mut args = match args {
+        [run, args @ ..] if run == "run" => args,
+        _ => return Err(CliError::Usage),
+    };
+    let mut command = Command::default();
+    loop {
+        match args {
+            [option, dir, rest @ ..] if option == "--dir" => {
+                let (host, guest) = dir.split_once("::").unwrap_or((dir, dir));
+                command.dirs.push((host.to_string(), guest.to_string()));
+                args = rest;
+            }
+            [option, env, rest @ ..] if option == "--env" => {
+                let (key, value) = env.split_once('=').ok_or(CliError::Usage)?;
+                command.envs.push((key.to_string(), value.to_string()));
+                args = rest;
+            }
+            [path, rest @ ..] if !path.starts_with("--") => {
+                command.path = path.clone();
+                args = rest;
+                break;
+            }
+            _ => return Err(CliError::Usage),
+        }
+    }
+    if let [invoke, name, rest @ ..] = args {
+        if invoke == "--invoke" {
+            command.invoke = Some(name.clone());
+            args = rest;
+        }
+    }
+    command.args = args.to_vec();
+    Ok(command)
+}
+
+/// Integers can be written as signed or unsigned, like `-1` or `4294967295` for i32.
+fn parse_value(value: &str, ty: ValueType) -> Option<Value> {
+    match ty {
+        ValueType::NumType(NumberType::I32) => value
+            .parse::<i32>()
+            .ok()
+            .or_else(|| value.parse::<u32>().ok().map(|v| v as i32))
+            .map(Value::I32),
+        ValueType::NumType(NumberType::I64) => value
+            .parse::<i64>()
+            .ok()
+            .or_else(|| value.parse::<u64>().ok().map(|v| v as i64))
+            .map(Value::I64),
+        ValueType::NumType(NumberType::F32) => value.parse().ok().map(Value::F32),
+        ValueType::NumType(NumberType::F64) => value.parse().ok().map(Value::F64),
+        _ => None,
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        Value::FuncRef(None) | Value::ExternRef(None) => "null".to_string(),
+        Value::FuncRef(Some(_)) => "funcref".to_string(),
+        Value::ExternRef(Some(v)) => format!("externref {}", v),
+    }
+}
+
+fn wasi_ctx(command: &Command) -> Result<WasiCtx, CliError> {
+    let mut ctx = WasiCtx::new();
+    ctx.arg(&command.path);
+    for arg in &command.args {
+        ctx.arg(arg);
+    }
+    for (key, value) in &command.envs {
+        ctx.env(key, value);
+    }
+    for (host, guest) in &command.dirs {
+        ctx.preopen_dir(host, guest).map_err(|error| CliError::Io {
+            path: host.clone(),
+            error,
+        })?;
+    }
+    Ok(ctx)
+}
+
+/// Load and instantiate the module, either binary or `.wat` text, the start function runs
+/// while instantiating. WASI is always available to the module.
+/// Then call the function to invoke, or `_start` if it's exported.
+fn run(command: &Command) -> Result<Vec<Value>, CliError> {
+    let bytes = fs::read(&command.path).map_err(|error| CliError::Io {
+        path: command.path.clone(),
+        error,
+    })?;
+    let module = if command.path.ends_with(".wat") {
+        let source = String::from_utf8_lossy(&bytes);
+        text::parse(&source)?
+    } else {
+        WasmModule::from_bytes(&bytes)?
+    };
+    let wasi = Wasi::new(wasi_ctx(command)?);
+    let mut linker = Linker::new();
+    wasi.define(&mut linker);
+    let instance = wasi.instantiate(&linker, &module)?;
+
+    let name = match &command.invoke {
+        Some(name) => name.as_str(),
+        None if instance.get_func("_start").is_some() => {
+            instance.invoke("_start", &[])?;
+            return Ok(vec![]);
+        }
+        None => return Ok(vec![]),
+    };
+    let func = instance
+        .get_func(name)
+        .ok_or_else(|| CliError::UnknownExport(name.to_string()))?;
+    let param_types = &func.func_type().param_types;
+    if param_types.len() != command.args.len() {
+        return Err(CliError::ArgumentCount {
+            expected: param_types.len(),
+            actual: command.args.len(),
+        });
+    }
+    let args = command
+        .args
+        .iter()
+        .zip(param_types)
+        .map(|(value, ty)| {
+            parse_value(value, *ty).ok_or_else(|| CliError::InvalidArgument {
+                value: value.clone(),
+                ty: *ty,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(instance.invoke(name, &args)?)
+}
 
 fn main() {
-    println!("Luban!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match parse_command(&args).and_then(|command| run(&command)) {
+        Ok(results) => {
+            for result in &results {
+                println!("{}", format_value(result));
+            }
+        }
+        Err(CliError::Trap(Trap::Exit(code)))
+        | Err(CliError::Instantiation(InstantiationError::Trap(Trap::Exit(code)))) => {
+            std::process::exit(code)
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(error.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_command_should_work() {
+        let command = parse_command(&strings(&["run", "fib.wasm", "--invoke", "fib", "30"]));
+        assert_eq!(
+            command.unwrap(),
+            Command {
+                path: "fib.wasm".to_string(),
+                invoke: Some("fib".to_string()),
+                args: strings(&["30"]),
+                ..Command::default()
+            }
+        );
+        let command = parse_command(&strings(&[
+            "run",
+            "--dir",
+            ".",
+            "--dir",
+            "/tmp::/data",
+            "--env",
+            "A=1",
+            "app.wasm",
+            "x",
+            "--y",
+        ]));
+        assert_eq!(
+            command.unwrap(),
+            Command {
+                path: "app.wasm".to_string(),
+                invoke: None,
+                args: strings(&["x", "--y"]),
+                dirs: vec![
+                    (".".to_string(), ".".to_string()),
+                    ("/tmp".to_string(), "/data".to_string())
+                ],
+                envs: vec![("A".to_string(), "1".to_string())],
+            }
+        );
+        assert!(parse_command(&strings(&["run", "fib.wasm"])).is_ok());
+        assert!(parse_command(&strings(&["run", "--env", "A", "fib.wasm"])).is_err());
+        assert!(parse_command(&strings(&["run", "--dir"])).is_err());
+        assert!(parse_command(&strings(&["fib.wasm"])).is_err());
+    }
+
+    #[test]
+    fn run_should_invoke_export() {
+        let path = std::env::temp_dir().join(format!("luban-cli-{}.wat", std::process::id()));
+        let source = r#"
+            (module
+              (func $fib (export "fib") (param i64) (result i64)
+                (if (result i64) (i64.lt_u (local.get 0) (i64.const 2))
+                  (then (local.get 0))
+                  (else (i64.add
+                    (call $fib (i64.sub (local.get 0) (i64.const 1)))
+                    (call $fib (i64.sub (local.get 0) (i64.const 2)))))))
+              (func (export "div") (param i32 i32) (result i32)
+                (i32.div_u (local.get 0) (local.get 1))))
+        "#;
+        fs::write(&path, source).unwrap();
+        let command = |name: &str, args: &[&str]| Command {
+            path: path.to_string_lossy().into_owned(),
+            invoke: Some(name.to_string()),
+            args: strings(args),
+            ..Command::default()
+        };
+
+        let results = run(&command("fib", &["20"])).unwrap();
+        assert_eq!(results, vec![Value::I64(6765)]);
+        let results = run(&command("div", &["4294967295", "2"])).unwrap();
+        assert_eq!(format_value(&results[0]), "2147483647");
+        assert!(matches!(
+            run(&command("div", &["1", "0"])),
+            Err(CliError::Trap(Trap::IntegerDivideByZero))
+        ));
+        assert!(matches!(
+            run(&command("fib", &["x"])),
+            Err(CliError::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            run(&command("fib", &[])),
+            Err(CliError::ArgumentCount { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn run_should_start_wasi_program() {
+        let path = std::env::temp_dir().join(format!("luban-wasi-{}.wat", std::process::id()));
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+              (memory (export "memory") 1)
+              (func (export "_start")
+                (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+                (call $proc_exit (i32.load (i32.const 0)))))
+        "#;
+        fs::write(&path, source).unwrap();
+        let command = Command {
+            path: path.to_string_lossy().into_owned(),
+            args: strings(&["a", "b"]),
+            ..Command::default()
+        };
+        // the program exits with argc, including the program name.
+        assert!(matches!(run(&command), Err(CliError::Trap(Trap::Exit(3)))));
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -1,49 +1,104 @@
 use super::*;
 use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#code-section
 #[derive(Clone, Debug)]
 pub struct CodeSection<'a> {
     data: Cow<'a, [u8]>,
-    codes: Vec<Code<'a>>,
+    pub codes: Vec<Code>,
 }
 
 #[derive(Clone, Debug)]
-pub struct Code<'a> {
-    locals: Locals,
-    expr: Vec<Expression<'a>>,
+pub struct Code {
+    pub locals: Vec<Locals>,
+    /// The decoded function body, the last `end` is excluded.
+    pub expr: Expressions,
 }
 
 #[derive(Clone, Debug)]
 pub struct Locals {
-    count: u32,
-    _type: ValueType,
-}
-
-#[derive(Clone, Debug)]
-pub struct Expression<'a> {
-    expr_bytes: Cow<'a, [u8]>,
+    pub count: u32,
+    pub _type: ValueType,
 }
 
 impl<'a> CodeSection<'a> {
-    const ID: u8 = 10;
+    pub const ID: u8 = 10;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
 
-        let (len, _bytes) = calc_len_and_offset(&_bytes, 1);
-        let data = Cow::Borrowed(&bytes[offset..offset + len + 1 + 1]);
-        dbg!(&data);
+    /// Build a code section from function bodies.
+    pub fn new(codes: Vec<Code>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            codes,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
 
-        let (len, _bytes) = calc_len_and_offset(&_bytes, 0);
-        dbg!(len, _bytes);
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
 
-        todo!();
+        let (codes, end) = read_vec(bytes, content_offset, Code::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            codes,
+        })
+    }
+}
+
+impl<'a> Parse<'a> for Locals {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (count, offset) = calc_len_and_offset(bytes, offset)?;
+        let (_type, offset) = ValueType::from_bytes(bytes, offset)?;
+        Ok((
+            Self {
+                count: count as u32,
+                _type,
+            },
+            offset,
+        ))
+    }
+}
+
+impl<'a> Parse<'a> for Code {
+    type Output = Self;
+
+    // [size, locals_count, (count, type)*, expr]
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        // every function body starts with its size.
+        let (size, body_offset) = calc_len_and_offset(bytes, offset)?;
+        let (_, end) = read_bytes(bytes, body_offset, size)?;
+        let bytes = &bytes[..end];
+
+        let (locals, expr_offset) = read_vec(bytes, body_offset, Locals::from_bytes)?;
+        // the total count of locals cannot exceed u32.
+        let total = locals
+            .iter()
+            .try_fold(0u32, |total, locals| total.checked_add(locals.count));
+        if total.is_none() {
+            return Err(Error::TooManyLocals {
+                offset: body_offset,
+            });
+        }
+
+        // the body must end with the `end` which closes the whole expression.
+        let (expr, expr_end) = Expressions::from_bytes(bytes, expr_offset)?;
+        expect_end(end, expr_end, body_offset)?;
+        Ok((Self { locals, expr }, end))
     }
 }
 
@@ -54,7 +109,115 @@ mod tests {
     #[test]
     fn deserialize_code_section_should_work() {
         let code_bytes = [10, 10, 2, 2, 0, 11, 5, 0, 65, 42, 26, 11];
-        let code_section = CodeSection::from_bytes(&code_bytes, 0);
-        assert!(true);
+        let code_section = CodeSection::from_bytes(&code_bytes, 0).unwrap();
+
+        assert_eq!(code_section.codes.len(), 2);
+        assert!(code_section.codes[0].locals.is_empty());
+        assert!(code_section.codes[0].expr.instructions.is_empty());
+        assert_eq!(
+            code_section.codes[1].expr.instructions,
+            vec![
+                Instructions::NumericInstructions(
+                    NumericInstructions::I32Const,
+                    Some(Primitives::I32(42))
+                ),
+                Instructions::ParametricInstructions(ParametricInstructions::Drop, vec![]),
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn deserialize_code_with_locals_should_work() {
+        // (func (local i32 i32 i64) nop)
+        let code_bytes = [10, 9, 1, 7, 2, 2, 127, 1, 126, 1, 11];
+        let code_section = CodeSection::from_bytes(&code_bytes, 0).unwrap();
+
+        let locals = &code_section.codes[0].locals;
+        assert_eq!(locals.len(), 2);
+        assert_eq!(locals[0].count, 2);
+        assert_eq!(locals[0]._type, ValueType::NumType(NumberType::I32));
+        assert_eq!(locals[1].count, 1);
+        assert_eq!(locals[1]._type, ValueType::NumType(NumberType::I64));
+    }
+
+    #[test]
+    fn code_with_too_many_locals_should_fail() {
+        // two runs of 2^32 - 1 locals.
+        let code_bytes = [
+            10, 16, 1, 14, 2, 0xff, 0xff, 0xff, 0xff, 0x0f, 127, 0xff, 0xff, 0xff, 0xff, 0x0f, 127,
+            11,
+        ];
+        assert_eq!(
+            CodeSection::from_bytes(&code_bytes, 0).unwrap_err(),
+            Error::TooManyLocals { offset: 4 }
+        );
+    }
+
+    #[test]
+    fn decode_instructions_with_immediates_should_work() {
+        // (func (block (result i32) (loop (br_table 0 1 1 (local.get 0))) (i32.load offset=8 align=4 (i32.const 0))) call_indirect 1 0)
+        let code_bytes = [
+            10, 25, 1, 23, 0, 0x02, 0x7f, 0x03, 0x40, 0x20, 0, 0x0e, 2, 0, 1, 1, 0x0b, 0x41, 0,
+            0x28, 2, 8, 0x0b, 0x11, 1, 0, 0x0b,
+        ];
+        let code_section = CodeSection::from_bytes(&code_bytes, 0).unwrap();
+        let instructions = &code_section.codes[0].expr.instructions;
+
+        assert_eq!(
+            instructions[0],
+            Instructions::ControlInstructions(
+                ControlInstructions::Block,
+                ControlOperands::Block(BlockType::Value(ValueType::NumType(NumberType::I32)))
+            )
+        );
+        assert_eq!(
+            instructions[1],
+            Instructions::ControlInstructions(
+                ControlInstructions::Loop,
+                ControlOperands::Block(BlockType::Empty)
+            )
+        );
+        assert_eq!(
+            instructions[3],
+            Instructions::ControlInstructions(
+                ControlInstructions::BrTable,
+                ControlOperands::BrTable(vec![0, 1], 1)
+            )
+        );
+        assert_eq!(
+            instructions[6],
+            Instructions::MemoryInstructions(
+                MemoryInstructions::I32Load,
+                MemoryOperands::MemArg(MemArg {
+                    align: 2,
+                    offset: 8
+                })
+            )
+        );
+        assert_eq!(
+            instructions[8],
+            Instructions::ControlInstructions(
+                ControlInstructions::CallIndirect,
+                ControlOperands::CallIndirect(1, 0)
+            )
+        );
+        assert_eq!(instructions.len(), 9);
+    }
+
+    #[test]
+    fn code_without_end_should_fail() {
+        // the body is `i32.const 1 drop` without `end`
+        let code_bytes = [10, 6, 1, 4, 0, 65, 1, 26];
+        assert_eq!(
+            CodeSection::from_bytes(&code_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 8 }
+        );
+
+        // there are bytes after the last `end`
+        let code_bytes = [10, 5, 1, 3, 0, 11, 1];
+        assert!(matches!(
+            CodeSection::from_bytes(&code_bytes, 0).unwrap_err(),
+            Error::SectionSizeMismatch { .. }
+        ));
+    }
+}
@@ -1,13 +1,17 @@
 use super::*;
+use crate::serialize::Encode;
+use crate::utils::*;
 
 /// Reference: https://webassembly.github.io/spec/core/binary/modules.html#binary-customsec
 #[derive(Clone, Debug)]
 pub struct CustomSection<'a> {
     pub data: Cow<'a, [u8]>,
+    pub name: Cow<'a, str>,
+    pub content: Cow<'a, [u8]>,
 }
 
 impl<'a> CustomSection<'a> {
-    const ID: u8 = 0x00;
+    pub const ID: u8 = 0x00;
 
     /// Return sustom section id
     pub fn id() -> u8 {
@@ -15,6 +19,44 @@ impl<'a> CustomSection<'a> {
     }
 
     pub fn byte_count(&self) -> usize {
-        todo!();
+        self.data.len()
+    }
+
+    pub fn new(name: String, content: Vec<u8>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            name: Cow::Owned(name),
+            content: Cow::Owned(content),
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (name, content_offset) = read_name(bytes, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            name: Cow::Borrowed(name),
+            content: Cow::Borrowed(&bytes[content_offset..]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_custom_section_should_work() {
+        let custom_bytes = [0, 6, 4, 110, 97, 109, 101, 42];
+        let custom_sec = CustomSection::from_bytes(&custom_bytes, 0).unwrap();
+
+        assert_eq!(custom_sec.name, "name");
+        assert_eq!(&*custom_sec.content, &[42]);
+        assert_eq!(custom_sec.byte_count(), custom_bytes.len());
     }
 }
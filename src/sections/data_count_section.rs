@@ -0,0 +1,68 @@
+use super::*;
+use crate::serialize::Encode;
+use crate::utils::*;
+
+/// https://webassembly.github.io/spec/core/binary/modules.html#data-count-section
+/// The count of data segments, so that the code section can be validated before data section.
+#[derive(Clone, Debug)]
+pub struct DataCountSection<'a> {
+    data: Cow<'a, [u8]>,
+    pub count: u32,
+}
+
+impl<'a> DataCountSection<'a> {
+    pub const ID: u8 = 12;
+
+    pub fn id() -> u8 {
+        Self::ID
+    }
+
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a data count section with `count` segments.
+    pub fn new(count: u32) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            count,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (count, end) = calc_len_and_offset(bytes, content_offset)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            count: count as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_data_count_section_should_work() {
+        let data_count_bytes = [12, 2, 0x80, 0x01];
+        let data_count_sec = DataCountSection::from_bytes(&data_count_bytes, 0).unwrap();
+        assert_eq!(data_count_sec.count, 128);
+
+        let data_count_bytes = [12, 3, 1, 0, 0];
+        assert_eq!(
+            DataCountSection::from_bytes(&data_count_bytes, 0).unwrap_err(),
+            Error::SectionSizeMismatch {
+                expected: 3,
+                actual: 1,
+                offset: 3
+            }
+        );
+    }
+}
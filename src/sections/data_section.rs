@@ -1,13 +1,109 @@
 use super::*;
 use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#data-section
 #[derive(Clone, Debug)]
 pub struct DataSection<'a> {
     data: Cow<'a, [u8]>,
-    mem_idx: usize,
-    offset: Expressions,
-    init: Vec<u8>,
+    pub segments: Vec<Data<'a>>,
+}
+
+/// https://webassembly.github.io/spec/core/syntax/modules.html#data-segments
+#[derive(Clone, Debug)]
+pub struct Data<'a> {
+    pub mode: DataMode,
+    pub init: Cow<'a, [u8]>,
+}
+
+/// Active segments are copied into memory while instantiating,
+/// passive segments can only be used by `memory.init`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataMode {
+    Passive,
+    Active { mem_idx: usize, offset: Expressions },
+}
+
+impl<'a> Parse<'a> for Data<'a> {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (flag, next) = calc_len_and_offset(bytes, offset)?;
+        let (mode, next) = match flag {
+            // active segment for memory 0
+            0 => {
+                let (offset_expr, next) = Expressions::from_const_bytes(bytes, next)?;
+                let mode = DataMode::Active {
+                    mem_idx: 0,
+                    offset: offset_expr,
+                };
+                (mode, next)
+            }
+            1 => (DataMode::Passive, next),
+            // active segment with explicit memory index
+            2 => {
+                let (mem_idx, next) = calc_len_and_offset(bytes, next)?;
+                let (offset_expr, next) = Expressions::from_const_bytes(bytes, next)?;
+                let mode = DataMode::Active {
+                    mem_idx,
+                    offset: offset_expr,
+                };
+                (mode, next)
+            }
+            _ => {
+                return Err(Error::InvalidTag {
+                    tag: flag as u8,
+                    offset,
+                })
+            }
+        };
+
+        let (len, next) = calc_len_and_offset(bytes, next)?;
+        let (init, next) = read_bytes(bytes, next, len)?;
+        Ok((
+            Self {
+                mode,
+                init: Cow::Borrowed(init),
+            },
+            next,
+        ))
+    }
+}
+
+impl<'a> DataSection<'a> {
+    pub const ID: u8 = 11;
+
+    pub fn id() -> u8 {
+        Self::ID
+    }
+
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a data section from segments.
+    pub fn new(segments: Vec<Data<'a>>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            segments,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (segments, end) = read_vec(bytes, content_offset, Data::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            segments,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -17,5 +113,53 @@ mod tests {
     #[test]
     fn deserialize_data_section_should_work() {
         let data_bytes = [11, 8, 1, 0, 65, 0, 11, 2, 104, 105];
+        let data_sec = DataSection::from_bytes(&data_bytes, 0).unwrap();
+
+        assert_eq!(data_sec.segments.len(), 1);
+        assert!(matches!(
+            data_sec.segments[0].mode,
+            DataMode::Active { mem_idx: 0, .. }
+        ));
+        assert_eq!(&*data_sec.segments[0].init, b"hi");
+    }
+
+    #[test]
+    fn deserialize_all_data_modes_should_work() {
+        // passive "a", active (memory 1) (i32.const 8) "b", and an invalid flag 3.
+        let data_bytes = [11, 11, 2, 1, 1, 97, 2, 1, 65, 8, 11, 1, 98];
+        let data_sec = DataSection::from_bytes(&data_bytes, 0).unwrap();
+
+        assert_eq!(data_sec.segments[0].mode, DataMode::Passive);
+        assert_eq!(&*data_sec.segments[0].init, b"a");
+        let offset = Expressions {
+            end: 0x0b,
+            instructions: vec![Instructions::NumericInstructions(
+                NumericInstructions::I32Const,
+                Some(Primitives::I32(8)),
+            )],
+        };
+        assert_eq!(
+            data_sec.segments[1].mode,
+            DataMode::Active { mem_idx: 1, offset }
+        );
+        assert_eq!(&*data_sec.segments[1].init, b"b");
+        // encoded with the same flags.
+        assert_eq!(data_sec.to_bytes(), data_bytes);
+
+        let data_bytes = [11, 3, 1, 3, 0];
+        assert_eq!(
+            DataSection::from_bytes(&data_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 3, offset: 3 }
+        );
+    }
+
+    #[test]
+    fn truncated_data_should_fail() {
+        // the segment claims 3 bytes, but only 2 bytes in this section.
+        let data_bytes = [11, 8, 1, 0, 65, 0, 11, 3, 104, 105, 106];
+        assert_eq!(
+            DataSection::from_bytes(&data_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 10 }
+        );
     }
 }
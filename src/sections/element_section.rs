@@ -1,15 +1,242 @@
 use super::*;
 use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#element-section
 #[derive(Clone, Debug)]
 pub struct ElementSection<'a> {
-    data: Cow::<'a, [u8]>,
-    elem: Vec<Element>,
+    data: Cow<'a, [u8]>,
+    pub elem: Vec<Element>,
 }
 
-#[derive(Clone, Debug)]
+/// https://webassembly.github.io/spec/core/syntax/modules.html#element-segments
+#[derive(Clone, Debug, PartialEq)]
 pub struct Element {
-    table_idx: usize,
-    offset_expr: Expression,
-}
\ No newline at end of file
+    pub ref_type: ReferenceType,
+    pub init: ElementItems,
+    pub mode: ElementMode,
+}
+
+/// The initial references, the binary format encodes them in either way.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementItems {
+    Functions(Vec<usize>),
+    /// Constant expressions, like `ref.func` or `ref.null`.
+    Expressions(Vec<Expressions>),
+}
+
+/// Active segments are copied into a table while instantiating, passive segments can only be
+/// used by `table.init`, and declarative segments only declare the functions for `ref.func`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementMode {
+    Passive,
+    Active {
+        table_idx: usize,
+        offset: Expressions,
+    },
+    Declarative,
+}
+
+impl Element {
+    /// The functions referenced by this segment.
+    pub fn funcs(&self) -> Vec<usize> {
+        match &self.init {
+            ElementItems::Functions(funcs) => funcs.clone(),
+            ElementItems::Expressions(exprs) => exprs
+                .iter()
+                .filter_map(|expr| match expr.instructions[..] {
+                    [Instructions::ReferenceInstructions(_, ReferenceOperands::Func(func_idx))] => {
+                        Some(func_idx as usize)
+                    }
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Only funcref is defined as the kind of function indices.
+fn read_elem_kind(bytes: &[u8], offset: usize) -> Result<(ReferenceType, usize), Error> {
+    match read_u8(bytes, offset)? {
+        (0x00, next) => Ok((ReferenceType::FuncRef, next)),
+        (tag, _) => Err(Error::InvalidTag { tag, offset }),
+    }
+}
+
+impl<'a> Parse<'a> for Element {
+    type Output = Self;
+
+    /// The bit 0 of flag means passive or declarative, or explicit table index for active segments,
+    /// bit 1 means declarative, or explicit table index and element kind,
+    /// and bit 2 means the items are expressions.
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (flag, next) = calc_len_and_offset(bytes, offset)?;
+        if flag > 7 {
+            return Err(Error::InvalidTag {
+                tag: flag as u8,
+                offset,
+            });
+        }
+        let uses_exprs = flag & 0b100 != 0;
+
+        let (mode, next) = if flag & 0b001 == 0 {
+            let (table_idx, next) = if flag & 0b010 == 0 {
+                (0, next)
+            } else {
+                calc_len_and_offset(bytes, next)?
+            };
+            let (offset_expr, next) = Expressions::from_const_bytes(bytes, next)?;
+            let mode = ElementMode::Active {
+                table_idx,
+                offset: offset_expr,
+            };
+            (mode, next)
+        } else if flag & 0b010 == 0 {
+            (ElementMode::Passive, next)
+        } else {
+            (ElementMode::Declarative, next)
+        };
+
+        // the short form of active segments has no type, it's always funcref.
+        let (ref_type, next) = match (flag & 0b011, uses_exprs) {
+            (0, _) => (ReferenceType::FuncRef, next),
+            (_, false) => read_elem_kind(bytes, next)?,
+            (_, true) => ReferenceType::from_bytes(bytes, next)?,
+        };
+
+        let (init, next) = if uses_exprs {
+            let (exprs, next) = read_vec(bytes, next, Expressions::from_const_bytes)?;
+            (ElementItems::Expressions(exprs), next)
+        } else {
+            let (funcs, next) = read_vec(bytes, next, calc_len_and_offset)?;
+            (ElementItems::Functions(funcs), next)
+        };
+        Ok((
+            Self {
+                ref_type,
+                init,
+                mode,
+            },
+            next,
+        ))
+    }
+}
+
+impl<'a> ElementSection<'a> {
+    pub const ID: u8 = 9;
+
+    pub fn id() -> u8 {
+        Self::ID
+    }
+
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build an element section from segments.
+    pub fn new(elem: Vec<Element>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            elem,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (elem, end) = read_vec(bytes, content_offset, Element::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            elem,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_element_section_should_work() {
+        // (elem (i32.const 1) func 0 2)
+        let element_bytes = [9, 8, 1, 0, 65, 1, 11, 2, 0, 2];
+        let element_sec = ElementSection::from_bytes(&element_bytes, 0).unwrap();
+
+        assert_eq!(element_sec.elem.len(), 1);
+        assert!(matches!(
+            element_sec.elem[0].mode,
+            ElementMode::Active { table_idx: 0, .. }
+        ));
+        assert_eq!(
+            element_sec.elem[0].init,
+            ElementItems::Functions(vec![0, 2])
+        );
+    }
+
+    #[test]
+    fn deserialize_all_element_flags_should_work() {
+        let offset = [65, 1, 11];
+        let ref_func = [0xd2, 3, 11];
+        let ref_null = [0xd0, 0x6f, 11];
+        let segments: [&[&[u8]]; 8] = [
+            &[&[0], &offset, &[1, 3]],
+            &[&[1, 0, 1, 3]],
+            &[&[2, 1], &offset, &[0, 1, 3]],
+            &[&[3, 0, 1, 3]],
+            &[&[4], &offset, &[1], &ref_func],
+            &[&[5, 0x6f, 1], &ref_null],
+            &[&[6, 1], &offset, &[0x70, 1], &ref_func],
+            &[&[7, 0x70, 1], &ref_func],
+        ];
+        for (flag, segment) in segments.iter().enumerate() {
+            let segment = segment.concat();
+            let content = [&[1][..], &segment].concat();
+            let element_bytes = [&[9, content.len() as u8][..], &content].concat();
+            let element_sec = ElementSection::from_bytes(&element_bytes, 0).unwrap();
+            let element = &element_sec.elem[0];
+
+            let table_idx = match &element.mode {
+                ElementMode::Active { table_idx, .. } => Some(*table_idx),
+                _ => None,
+            };
+            let expected_table = match flag {
+                0 | 4 => Some(0),
+                2 | 6 => Some(1),
+                _ => None,
+            };
+            assert_eq!(table_idx, expected_table, "flag {}", flag);
+            assert_eq!(
+                flag == 3 || flag == 7,
+                element.mode == ElementMode::Declarative
+            );
+            if flag == 5 {
+                assert_eq!(element.ref_type, ReferenceType::ExternRef);
+                assert!(element.funcs().is_empty());
+            } else {
+                assert_eq!(element.ref_type, ReferenceType::FuncRef);
+                assert_eq!(element.funcs(), vec![3]);
+            }
+
+            // encoded with the same flag.
+            assert_eq!(element_sec.to_bytes(), element_bytes, "flag {}", flag);
+        }
+
+        // invalid flag and element kind
+        let element_bytes = [9, 2, 1, 8];
+        assert_eq!(
+            ElementSection::from_bytes(&element_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 8, offset: 3 }
+        );
+        let element_bytes = [9, 4, 1, 1, 1, 0];
+        assert_eq!(
+            ElementSection::from_bytes(&element_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 1, offset: 4 }
+        );
+    }
+}
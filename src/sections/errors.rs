@@ -1,4 +1,54 @@
 use thiserror::Error;
 
-#[derive(Debug, Error)]
-pub enum Error {}
+/// Errors while decoding a webassembly binary module.
+/// Every error carries the byte offset in the module where it happened.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("unexpected end of bytes at offset {offset}")]
+    UnexpectedEof { offset: usize },
+    #[error("bad magic number at offset {offset}")]
+    BadMagic { offset: usize },
+    #[error("unsupported version {version} at offset {offset}")]
+    UnsupportedVersion { version: u32, offset: usize },
+    #[error("invalid section id {id} at offset {offset}")]
+    InvalidSectionId { id: u8, offset: usize },
+    #[error("section {id} is out of order or duplicated at offset {offset}")]
+    SectionOutOfOrder { id: u8, offset: usize },
+    #[error("section size mismatch at offset {offset}, expected {expected} bytes, consumed {actual} bytes")]
+    SectionSizeMismatch {
+        expected: usize,
+        actual: usize,
+        offset: usize,
+    },
+    #[error("invalid value type {byte:#x} at offset {offset}")]
+    InvalidValueType { byte: u8, offset: usize },
+    #[error("malformed leb128 number at offset {offset}")]
+    MalformedLeb128 { offset: usize },
+    #[error("invalid utf-8 string at offset {offset}")]
+    InvalidUtf8 { offset: usize },
+    #[error("invalid tag {tag:#x} at offset {offset}")]
+    InvalidTag { tag: u8, offset: usize },
+    #[error("unsupported instruction {opcode:#x} at offset {offset}")]
+    UnsupportedInstruction { opcode: u8, offset: usize },
+    #[error("expected end of expression at offset {offset}")]
+    ExpectedEnd { offset: usize },
+    #[error("too many locals at offset {offset}")]
+    TooManyLocals { offset: usize },
+    #[error("{functions} functions but {codes} function bodies, at offset {offset}")]
+    FunctionCountMismatch {
+        functions: usize,
+        codes: usize,
+        offset: usize,
+    },
+    #[error("data count {count} but {segments} data segments, at offset {offset}")]
+    DataCountMismatch {
+        count: u32,
+        segments: usize,
+        offset: usize,
+    },
+    #[error("unsupported {feature} at offset {offset}")]
+    Unsupported {
+        feature: &'static str,
+        offset: usize,
+    },
+}
@@ -1,22 +1,23 @@
 use super::*;
-use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
 // only can export 4 types: function, table, memory, global
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#export-section
 #[derive(Clone, Debug)]
 pub struct ExportSection<'a> {
     data: Cow<'a, [u8]>,
-    exports: Vec<Export>,
+    pub exports: Vec<Export>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Export {
-    name: String,
-    desc: ExportDesc,
+    pub name: String,
+    pub desc: ExportDesc,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ExportType {
     Func = 0x00,
     Table = 0x01,
@@ -24,62 +25,78 @@ pub enum ExportType {
     Global = 0x03,
 }
 
-impl From<u8> for ExportType {
-    fn from(u: u8) -> Self {
-        match u {
+impl<'a> Parse<'a> for ExportType {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (tag, next) = read_u8(bytes, offset)?;
+        let export_type = match tag {
             0x00 => Self::Func,
             0x01 => Self::Table,
             0x02 => Self::Mem,
             0x03 => Self::Global,
-            _ => panic!("Unknow tag."),
-        }
+            _ => return Err(Error::InvalidTag { tag, offset }),
+        };
+        Ok((export_type, next))
+    }
+}
+
+impl<'a> Parse<'a> for Export {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (name, offset) = read_name(bytes, offset)?;
+        let (export_type, offset) = ExportType::from_bytes(bytes, offset)?;
+        let (index, offset) = calc_len_and_offset(bytes, offset)?;
+        let desc = ExportDesc { index, export_type };
+        Ok((
+            Self {
+                name: name.to_string(),
+                desc,
+            },
+            offset,
+        ))
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct ExportDesc {
-    index: usize,
-    export_type: ExportType,
+    pub index: usize,
+    pub export_type: ExportType,
 }
 
 impl<'a> ExportSection<'a> {
-    const ID: u8 = 7;
+    pub const ID: u8 = 7;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
-
-        let (leb_len, _bytes) = calc_len_and_offset(_bytes, 1);
-        // first 1 for id
-        let data = Cow::Borrowed(&bytes[offset..offset + leb_len + 1 + 1]);
-
-        // skip section id and content length
-        let (export_count, mut _bytes) = calc_len_and_offset(&data, 2);
-
-        let mut exports = Vec::with_capacity(export_count);
-
-        loop {
-            if _bytes.len() <= 0 {
-                break;
-            }
-
-            let (leb_len, rest_bytes) = calc_len_and_offset(_bytes, 0);
-            // let name = std::str::from_utf8(&rest_bytes[..leb_len]).unwrap();
-            let name = unsafe { String::from_utf8_unchecked(rest_bytes[..leb_len].to_vec()) };
-            let export_type = ExportType::from(rest_bytes[leb_len]);
-            let index = rest_bytes[leb_len + 1] as usize;
-            let desc = ExportDesc { index, export_type };
-            let export = Export { name, desc };
-            exports.push(export);
-            // first 1 means tag, second 1 means index
-            _bytes = &rest_bytes[leb_len + 1 + 1..];
-        }
-
-        Self { data, exports }
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build an export section.
+    pub fn new(exports: Vec<Export>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            exports,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (exports, end) = read_vec(bytes, content_offset, Export::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            exports,
+        })
     }
 }
 
@@ -90,7 +107,11 @@ mod tests {
     #[test]
     fn deserialize_export_section_should_work() {
         let exports_bytes = [7, 9, 1, 5, 102, 117, 110, 99, 49, 0, 1];
-        let export_section = ExportSection::from_bytes(&exports_bytes, 0);
-        dbg!(export_section);
+        let export_section = ExportSection::from_bytes(&exports_bytes, 0).unwrap();
+
+        assert_eq!(export_section.exports.len(), 1);
+        assert_eq!(export_section.exports[0].name, "func1");
+        assert_eq!(export_section.exports[0].desc.export_type, ExportType::Func);
+        assert_eq!(export_section.exports[0].desc.index, 1);
     }
 }
@@ -1,9 +1,68 @@
 use super::*;
-use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#function-section
 #[derive(Clone, Debug)]
 pub struct FunctionSection<'a> {
     data: Cow<'a, [u8]>,
-    type_idx: Vec<usize>,
+    pub type_idx: Vec<usize>,
+}
+
+impl<'a> FunctionSection<'a> {
+    pub const ID: u8 = 3;
+
+    pub fn id() -> u8 {
+        Self::ID
+    }
+
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a function section from the type index of each function.
+    pub fn new(type_idx: Vec<usize>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            type_idx,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (type_idx, end) = read_vec(bytes, content_offset, calc_len_and_offset)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            type_idx,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_function_section_should_work() {
+        let function_bytes = [3, 4, 3, 0, 1, 0];
+        let function_sec = FunctionSection::from_bytes(&function_bytes, 0).unwrap();
+
+        assert_eq!(function_sec.type_idx, vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn function_section_with_huge_count_should_fail() {
+        // the count claims 2^32 - 1 functions, but only a few bytes follow.
+        let function_bytes = [3, 7, 0xff, 0xff, 0xff, 0xff, 0x0f, 0, 1];
+        assert_eq!(
+            FunctionSection::from_bytes(&function_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 9 }
+        );
+    }
 }
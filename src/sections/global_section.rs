@@ -1,9 +1,10 @@
 use super::*;
 use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
 /// https://webassembly.github.io/spec/core/binary/types.html#global-types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GlobalType {
     pub val_type: ValueType,
     pub mutable: Mutable,
@@ -13,126 +14,85 @@ pub struct GlobalType {
 #[derive(Clone, Debug)]
 pub struct GlobalSection<'a> {
     data: Cow<'a, [u8]>,
-    globals: Vec<Global>,
+    pub globals: Vec<Global>,
 }
 
 /// https://webassembly.github.io/spec/core/binary/modules.html#binary-global
 #[derive(Clone, Debug)]
 pub struct Global {
-    global_type: GlobalType,
-    expressions: Expressions,
+    pub global_type: GlobalType,
+    pub expressions: Expressions,
 }
 
 impl<'a> Parse<'a> for GlobalType {
     type Output = GlobalType;
-    fn from_bytes(bytes: &'a [u8], offset: usize) -> Self::Output {
-        let val_type = ValueType::NumType(NumberType::from(bytes[0]));
-        let mutable = match bytes[1] {
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self::Output, usize), Error> {
+        let (val_type, offset) = ValueType::from_bytes(bytes, offset)?;
+        let (mutable, next) = read_u8(bytes, offset)?;
+        let mutable = match mutable {
             0 => Mutable::No,
             1 => Mutable::Yes,
+            // the variable either mutable or imutable, cannot be another mutable status.
             _ => {
-                panic!("the variable either mutable or imutable, cannot be another mutable status.")
+                return Err(Error::InvalidTag {
+                    tag: mutable,
+                    offset,
+                })
             }
         };
 
-        Self { val_type, mutable }
+        Ok((Self { val_type, mutable }, next))
+    }
+}
+
+impl<'a> Parse<'a> for Global {
+    type Output = Global;
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self::Output, usize), Error> {
+        let (global_type, offset) = GlobalType::from_bytes(bytes, offset)?;
+        let (expressions, offset) = Expressions::from_const_bytes(bytes, offset)?;
+        Ok((
+            Self {
+                global_type,
+                expressions,
+            },
+            offset,
+        ))
     }
 }
 
 impl<'a> GlobalSection<'a> {
-    const ID: u8 = 0x06;
+    pub const ID: u8 = 0x06;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a global section.
+    pub fn new(globals: Vec<Global>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            globals,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
 
-        let (leb_len, _bytes) = calc_len_and_offset(_bytes, 1);
-        // first 1 for id, second 1 for content length
-        let data = Cow::Borrowed(&bytes[offset..offset + leb_len + 1 + 1]);
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
 
         // how many global items
-        let (globals_count, _bytes) = calc_len_and_offset(&data, 2);
-
-        let mut globals = Vec::with_capacity(globals_count);
-        for expr in _bytes.split_inclusive(|b| *b == 0x0b) {
-            let global_type = GlobalType::from_bytes(&expr[..2], 0);
-            let len = expr.len();
-
-            let expressions = match expr[2] {
-                0x41 => {
-                    let op = NumericInstructions::I32Const;
-                    // skip value type, tag, operand, they occupy 3 bytes.
-                    let (result, offset) = unsigned_leb128_decode(&expr[3..]);
-                    assert_eq!(expr[offset + 3..][0], 0x0b);
-                    assert_eq!(expr[offset + 3..].len(), 1);
-                    let instructions = vec![Instructions::NumericInstructions(
-                        op,
-                        Primitives::I32(result as i32),
-                    )];
-                    let expressions = Expressions {
-                        end: _bytes[0],
-                        instructions,
-                    };
-                    expressions
-                }
-                0x42 => {
-                    let op = NumericInstructions::I64Const;
-                    let (result, offset) = unsigned_leb128_decode(&expr[3..]);
-                    assert_eq!(expr[offset + 3..][0], 0x0b);
-                    assert_eq!(expr[offset + 3..].len(), 1);
-                    let instructions = vec![Instructions::NumericInstructions(
-                        op,
-                        Primitives::I64(result as i64),
-                    )];
-                    let expressions = Expressions {
-                        end: _bytes[0],
-                        instructions,
-                    };
-                    expressions
-                }
-                0x43 => {
-                    let op = NumericInstructions::F32Const;
-                    let (result, offset) = unsigned_leb128_decode(&expr[3..]);
-                    assert_eq!(expr[offset + 3..][0], 0x0b);
-                    assert_eq!(expr[offset + 3..].len(), 1);
-                    let instructions = vec![Instructions::NumericInstructions(
-                        op,
-                        Primitives::F32(result as f32),
-                    )];
-                    let expressions = Expressions {
-                        end: _bytes[0],
-                        instructions,
-                    };
-                    expressions
-                }
-                0x44 => {
-                    let op = NumericInstructions::F64Const;
-                    let (result, offset) = unsigned_leb128_decode(&expr[3..]);
-                    assert_eq!(expr[offset + 3..][0], 0x0b);
-                    assert_eq!(expr[offset + 3..].len(), 1);
-                    let instructions = vec![Instructions::NumericInstructions(
-                        op,
-                        Primitives::F64(result as f64),
-                    )];
-                    let expressions = Expressions {
-                        end: _bytes[0],
-                        instructions,
-                    };
-                    expressions
-                }
-                _ => panic!("unsupport instruction"),
-            };
-            globals.push(Global {
-                global_type,
-                expressions,
-            });
-        }
+        let (globals, end) = read_vec(bytes, content_offset, Global::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
 
-        Self { data, globals }
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            globals,
+        })
     }
 }
 
@@ -147,6 +107,87 @@ mod tests {
             65, 160, 132, 192, 0, 11,
         ];
 
-        let global_section = GlobalSection::from_bytes(&global_bytes, 0);
+        let global_section = GlobalSection::from_bytes(&global_bytes, 0).unwrap();
+
+        assert_eq!(global_section.globals.len(), 3);
+        assert_eq!(global_section.globals[0].global_type.mutable, Mutable::Yes);
+        assert_eq!(global_section.globals[1].global_type.mutable, Mutable::No);
+        assert!(matches!(
+            global_section.globals[0].expressions.instructions[..],
+            [Instructions::NumericInstructions(
+                NumericInstructions::I32Const,
+                Some(Primitives::I32(1048576))
+            )]
+        ));
+    }
+
+    #[test]
+    fn global_constants_should_be_exact() {
+        // (global i32 (i32.const -892)) (global i64 (i64.const -1))
+        // (global f32 (f32.const nan:0x200001)) (global f64 (f64.const -0.5))
+        let global_bytes = [
+            6, 32, 4, 127, 0, 65, 0x84, 0x79, 11, 126, 0, 66, 0x7f, 11, 125, 0, 67, 0x01, 0x00,
+            0xe0, 0x7f, 11, 124, 0, 68, 0, 0, 0, 0, 0, 0, 0xe0, 0xbf, 11,
+        ];
+        let global_section = GlobalSection::from_bytes(&global_bytes, 0).unwrap();
+        let constants: Vec<_> = global_section
+            .globals
+            .iter()
+            .map(|global| global.expressions.instructions[0].clone())
+            .collect();
+
+        assert_eq!(
+            constants[0],
+            Instructions::NumericInstructions(
+                NumericInstructions::I32Const,
+                Some(Primitives::I32(-892))
+            )
+        );
+        assert_eq!(
+            constants[1],
+            Instructions::NumericInstructions(
+                NumericInstructions::I64Const,
+                Some(Primitives::I64(-1))
+            )
+        );
+        match constants[2] {
+            Instructions::NumericInstructions(_, Some(Primitives::F32(v))) => {
+                assert_eq!(v.to_bits(), 0x7fe0_0001)
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            constants[3],
+            Instructions::NumericInstructions(
+                NumericInstructions::F64Const,
+                Some(Primitives::F64(-0.5))
+            )
+        );
+    }
+
+    #[test]
+    fn global_with_bad_mutable_or_expression_should_fail() {
+        let global_bytes = [6, 6, 1, 127, 2, 65, 0, 11];
+        assert_eq!(
+            GlobalSection::from_bytes(&global_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 2, offset: 4 }
+        );
+
+        // i32.add is not a constant instruction.
+        let global_bytes = [6, 6, 1, 127, 0, 106, 0, 11];
+        assert_eq!(
+            GlobalSection::from_bytes(&global_bytes, 0).unwrap_err(),
+            Error::UnsupportedInstruction {
+                opcode: 106,
+                offset: 5
+            }
+        );
+
+        // missing end.
+        let global_bytes = [6, 6, 1, 127, 0, 65, 0, 26];
+        assert_eq!(
+            GlobalSection::from_bytes(&global_bytes, 0).unwrap_err(),
+            Error::ExpectedEnd { offset: 7 }
+        );
     }
 }
@@ -1,52 +1,93 @@
 use super::*;
+use crate::serialize::Encode;
 use crate::utils::*;
-use core::str;
 
 /// https://webassembly.github.io/spec/core/binary/modules.html#import-section
 #[derive(Clone, Debug)]
 pub struct ImportSection<'a> {
     data: Cow<'a, [u8]>,
-    desc: Vec<ImportDescription<'a>>,
+    pub desc: Vec<ImportDescription<'a>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ImportDescription<'a> {
-    module: Cow<'a, [u8]>,
-    name: Cow<'a, [u8]>,
-    tag: ImportTag,
-    desc: ImportDesc,
+    module: Cow<'a, str>,
+    name: Cow<'a, str>,
+    pub tag: ImportTag,
+    pub desc: ImportDesc,
 }
 
-impl<'a> ImportDescription<'a> {
-    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
+impl<'a> Parse<'a> for ImportDescription<'a> {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
         // handle module
-        let (leb_len, _bytes) = calc_len_and_offset(bytes, offset);
-        let module = Cow::Borrowed(&_bytes[..leb_len]);
+        let (module, offset) = read_name(bytes, offset)?;
 
         // handle name
-        let (leb_len, _bytes) = calc_len_and_offset(_bytes, 0);
-        let name = Cow::Borrowed(&_bytes[..leb_len]);
+        let (name, offset) = read_name(bytes, offset)?;
 
         // tag
-        let tag = ImportTag::from(_bytes[leb_len]);
+        let (tag, desc_offset) = ImportTag::from_bytes(bytes, offset)?;
 
         // description
-        let desc = ImportDesc::Func(_bytes[leb_len + 1] as u32);
+        let (desc, offset) = match tag {
+            ImportTag::Func => {
+                let (type_idx, offset) = calc_len_and_offset(bytes, desc_offset)?;
+                (ImportDesc::Func(type_idx as TypeIndex), offset)
+            }
+            ImportTag::Table => {
+                let (table_type, offset) = TableType::from_bytes(bytes, desc_offset)?;
+                (ImportDesc::Table(table_type), offset)
+            }
+            ImportTag::Mem => {
+                let (limits, offset) = Limits::from_bytes(bytes, desc_offset)?;
+                (ImportDesc::Memory(limits), offset)
+            }
+            ImportTag::Global => {
+                let (global_type, offset) = GlobalType::from_bytes(bytes, desc_offset)?;
+                (ImportDesc::Global(global_type), offset)
+            }
+        };
+
+        Ok((
+            Self {
+                module: Cow::Borrowed(module),
+                name: Cow::Borrowed(name),
+                tag,
+                desc,
+            },
+            offset,
+        ))
+    }
+}
 
+impl<'a> ImportDescription<'a> {
+    pub fn new(module: String, name: String, desc: ImportDesc) -> Self {
+        let tag = match desc {
+            ImportDesc::Func(_) => ImportTag::Func,
+            ImportDesc::Table(_) => ImportTag::Table,
+            ImportDesc::Memory(_) => ImportTag::Mem,
+            ImportDesc::Global(_) => ImportTag::Global,
+        };
         Self {
-            module,
-            name,
+            module: Cow::Owned(module),
+            name: Cow::Owned(name),
             tag,
             desc,
         }
     }
 
     pub fn module(&self) -> &str {
-        str::from_utf8(&self.module).unwrap()
+        &self.module
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ImportTag {
     Func = 0x00,
     Table = 0x01,
@@ -54,26 +95,30 @@ pub enum ImportTag {
     Global = 0x03,
 }
 
-impl From<u8> for ImportTag {
-    fn from(u: u8) -> Self {
-        match u {
+impl<'a> Parse<'a> for ImportTag {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (tag, next) = read_u8(bytes, offset)?;
+        let tag = match tag {
             0x00 => Self::Func,
             0x01 => Self::Table,
             0x02 => Self::Mem,
             0x03 => Self::Global,
-            _ => panic!("not supported tag"),
-        }
+            _ => return Err(Error::InvalidTag { tag, offset }),
+        };
+        Ok((tag, next))
     }
 }
 
 pub type TypeIndex = u32;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ImportDesc {
     Func(TypeIndex),
-    // Table(),
-    // Mem = 0x02,
-    // Global = 0x03,
+    Table(TableType),
+    Memory(Limits),
+    Global(GlobalType),
 }
 
 impl<'a> ImportSection<'a> {
@@ -83,48 +128,31 @@ impl<'a> ImportSection<'a> {
         self.data[0]
     }
 
-    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
-
-        // get the length of content for type section
-        // let (content_length, leb_offset) = uleb128_decode(&_bytes[1..]);
-        // let content_bytes = &_bytes[1 + leb_offset + 1..1 + leb_offset + content_length];
-
-        // let import_count = _bytes[2] as usize;
-        // let mut description: Vec<ImportDescription> = Vec::with_capacity(import_count);
-
-        // // module
-        // let mod_bytes_len = content_bytes[0] as usize;
-        // let mod_bytes = &content_bytes[1..1 + mod_bytes_len];
-        // let module_name = Cow::Borrowed(mod_bytes);
-
-        // // name
-        // let name_bytes_len = content_bytes[1 + mod_bytes_len..][0] as usize;
-        // let name_bytes = &content_bytes[1 + mod_bytes_len..1 + mod_bytes_len + 1 + name_bytes_len];
-        // let name = Cow::Borrowed(name_bytes);
-
-        // 1 means we should skip id, because we already visited it.
-        let (len, _bytes) = calc_len_and_offset(_bytes, 1);
-        // dbg!(len, _bytes);
-
-        // how many items
-        let (len, _bytes) = calc_len_and_offset(_bytes, 1);
-        // dbg!(len, &_bytes[..len]);
-        let name = str::from_utf8(&_bytes[..len]).unwrap();
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
 
-        let (len, _bytes) = calc_len_and_offset(&_bytes[len..], 0);
-        let name = str::from_utf8(&_bytes[..len]).unwrap();
+    /// Build an import section.
+    pub fn new(desc: Vec<ImportDescription<'a>>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            desc,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
 
-        let tag = _bytes[len];
-        let index = _bytes[len + 1];
+    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
 
-        // module
+        let (desc, end) = read_vec(bytes, content_offset, ImportDescription::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
 
-        Self {
-            data: Cow::Borrowed(_bytes),
-            desc: vec![],
-        }
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            desc,
+        })
     }
 }
 
@@ -136,7 +164,72 @@ mod tests {
     // #[ignore]
     fn deserialoze_import_section_should_work() {
         let import_sec_bytes = [2u8, 11, 1, 3, 102, 111, 111, 3, 98, 97, 114, 0, 0];
-        let import_sec = ImportSection::from_bytes(&import_sec_bytes, 0);
+        let import_sec = ImportSection::from_bytes(&import_sec_bytes, 0).unwrap();
+
+        assert_eq!(import_sec.id(), ImportSection::ID);
+        assert_eq!(import_sec.desc.len(), 1);
+        assert_eq!(import_sec.desc[0].module(), "foo");
+        assert_eq!(import_sec.desc[0].name(), "bar");
+        assert_eq!(import_sec.desc[0].desc, ImportDesc::Func(0));
+
+        // (import "foo" "bar" (global (mut i64)))
+        let import_sec_bytes = [2u8, 12, 1, 3, 102, 111, 111, 3, 98, 97, 114, 3, 0x7e, 1];
+        let import_sec = ImportSection::from_bytes(&import_sec_bytes, 0).unwrap();
+        assert_eq!(
+            import_sec.desc[0].desc,
+            ImportDesc::Global(GlobalType {
+                val_type: ValueType::NumType(NumberType::I64),
+                mutable: Mutable::Yes,
+            })
+        );
+        assert_eq!(import_sec.desc[0].to_bytes(), &import_sec_bytes[3..]);
+
+        // (import "env" "__indirect_function_table" (table 1 funcref))
+        // (import "env" "memory" (memory 256 32768))
+        let mut import_sec_bytes = vec![2u8, 53, 2, 3, 101, 110, 118, 25];
+        import_sec_bytes.extend_from_slice(b"__indirect_function_table");
+        import_sec_bytes.extend_from_slice(&[1, 0x70, 0, 1, 3, 101, 110, 118, 6]);
+        import_sec_bytes.extend_from_slice(b"memory");
+        import_sec_bytes.extend_from_slice(&[2, 1, 0x80, 2, 0x80, 0x80, 2]);
+        let import_sec = ImportSection::from_bytes(&import_sec_bytes, 0).unwrap();
+        assert_eq!(import_sec.desc.len(), 2);
+        assert_eq!(import_sec.desc[0].name(), "__indirect_function_table");
+        assert_eq!(
+            import_sec.desc[0].desc,
+            ImportDesc::Table(TableType {
+                ref_type: ReferenceType::FuncRef,
+                limits: Limits {
+                    tag: Tag::Zero,
+                    min: Some(1),
+                    max: None,
+                },
+            })
+        );
+        assert_eq!(import_sec.desc[1].tag, ImportTag::Mem);
+        assert_eq!(
+            import_sec.desc[1].desc,
+            ImportDesc::Memory(Limits {
+                tag: Tag::One,
+                min: Some(256),
+                max: Some(32768),
+            })
+        );
+        assert_eq!(import_sec.to_bytes(), import_sec_bytes);
+    }
+
+    #[test]
+    fn import_with_bad_tag_or_name_should_fail() {
+        let import_sec_bytes = [2u8, 11, 1, 3, 102, 111, 111, 3, 98, 97, 114, 4, 0];
+        assert_eq!(
+            ImportSection::from_bytes(&import_sec_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 4, offset: 11 }
+        );
+
+        let import_sec_bytes = [2u8, 11, 1, 3, 102, 0xc0, 111, 3, 98, 97, 114, 0, 0];
+        assert_eq!(
+            ImportSection::from_bytes(&import_sec_bytes, 0).unwrap_err(),
+            Error::InvalidUtf8 { offset: 5 }
+        );
     }
 
     // #[test]
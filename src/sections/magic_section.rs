@@ -1,6 +1,7 @@
 use super::*;
+use crate::utils::*;
 
-/// Reference:
+/// Reference: https://webassembly.github.io/spec/core/binary/modules.html#binary-module
 #[derive(Clone, Debug)]
 pub struct MagicSection<'a> {
     pub(crate) magic_number: Cow<'a, [u8]>,
@@ -9,14 +10,38 @@ pub struct MagicSection<'a> {
 
 impl<'a> MagicSection<'a> {
     const LENGTH: usize = 8;
+    pub(crate) const MAGIC: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
+    pub(crate) const VERSION: u32 = 1;
 
-    pub(crate) fn from_bytes(bytes: &'a [u8]) -> Self {
+    /// The header of a module which isn't decoded from bytes.
+    pub fn new() -> Self {
         Self {
-            magic_number: Cow::Borrowed(&bytes[..4]),
-            version_number: Cow::Borrowed(&bytes[4..Self::LENGTH]),
+            magic_number: Cow::Borrowed(&Self::MAGIC),
+            version_number: Cow::Owned(Self::VERSION.to_le_bytes().to_vec()),
         }
     }
 
+    pub(crate) fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let (magic_number, offset) =
+            read_bytes(bytes, 0, 4).map_err(|_| Error::BadMagic { offset: 0 })?;
+        if magic_number != Self::MAGIC {
+            return Err(Error::BadMagic { offset: 0 });
+        }
+
+        let (version_number, _) = read_bytes(bytes, offset, 4)?;
+        let magic_sec = Self {
+            magic_number: Cow::Borrowed(magic_number),
+            version_number: Cow::Borrowed(version_number),
+        };
+        if magic_sec.version() != Self::VERSION {
+            return Err(Error::UnsupportedVersion {
+                version: magic_sec.version(),
+                offset,
+            });
+        }
+        Ok(magic_sec)
+    }
+
     /// Get magic number
     pub fn magic_number(&self) -> &str {
         core::str::from_utf8(&self.magic_number[..4]).unwrap()
@@ -25,7 +50,7 @@ impl<'a> MagicSection<'a> {
     /// The version of wasm
     pub fn version(&self) -> u32 {
         let mut le = [0u8; 4];
-        le.copy_from_slice(&*self.version_number);
+        le.copy_from_slice(&self.version_number);
         u32::from_le_bytes(le)
     }
 
@@ -38,6 +63,12 @@ impl<'a> MagicSection<'a> {
     }
 }
 
+impl<'a> Default for MagicSection<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,7 +76,7 @@ mod tests {
     #[test]
     fn magic_section_should_work() {
         let magic_bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
-        let magic_sec = MagicSection::from_bytes(&magic_bytes);
+        let magic_sec = MagicSection::from_bytes(&magic_bytes).unwrap();
 
         // check magic number
         assert_eq!(magic_sec.magic_number(), "\u{0}asm");
@@ -56,4 +87,34 @@ mod tests {
         // check length of magic section
         assert_eq!(magic_sec.length(), 8);
     }
+
+    #[test]
+    fn bad_magic_or_version_should_fail() {
+        let magic_bytes = [0x00, 0x61, 0x73, 0x6e, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(
+            MagicSection::from_bytes(&magic_bytes).unwrap_err(),
+            Error::BadMagic { offset: 0 }
+        );
+
+        let magic_bytes = [0x00, 0x61, 0x73];
+        assert_eq!(
+            MagicSection::from_bytes(&magic_bytes).unwrap_err(),
+            Error::BadMagic { offset: 0 }
+        );
+
+        let magic_bytes = [0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00];
+        assert_eq!(
+            MagicSection::from_bytes(&magic_bytes).unwrap_err(),
+            Error::UnsupportedVersion {
+                version: 2,
+                offset: 4
+            }
+        );
+
+        let magic_bytes = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00];
+        assert_eq!(
+            MagicSection::from_bytes(&magic_bytes).unwrap_err(),
+            Error::UnexpectedEof { offset: 6 }
+        );
+    }
 }
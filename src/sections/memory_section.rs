@@ -1,29 +1,55 @@
 use super::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#memory-section
 #[derive(Clone, Debug)]
 pub struct MemorySection<'a> {
     data: Cow<'a, [u8]>,
-    limits: Limits,
+    pub limits: Limits,
 }
 
 impl<'a> MemorySection<'a> {
-    const ID: u8 = 5;
+    pub const ID: u8 = 5;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a memory section with the limits of the only memory.
+    pub fn new(limits: Limits) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            limits,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        // currently webassembly only supports 1 memory.
+        let (mem_count, limits_offset) = calc_len_and_offset(bytes, content_offset)?;
+        if mem_count != 1 {
+            return Err(Error::Unsupported {
+                feature: "memory count other than 1",
+                offset: content_offset,
+            });
+        }
 
-        // 1 means skip the id.
-        let (leb_len, _bytes) = calc_len_and_offset(&_bytes, 1);
-        let data = Cow::Borrowed(&_bytes[..leb_len]);
+        let (limits, end) = Limits::from_bytes(bytes, limits_offset)?;
+        expect_end(bytes.len(), end, content_offset)?;
 
-        let limits = Limits::from_bytes(&data);
-        Self { data, limits }
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            limits,
+        })
     }
 }
 
@@ -33,10 +59,28 @@ mod tests {
 
     #[test]
     fn deserialize_memory_section_should_work() {
+        // one memory, limits without max: (memory 17)
         let memory_bytes = [5, 3, 1, 0, 17];
-        let memory_sec = MemorySection::from_bytes(&memory_bytes, 0);
+        let memory_sec = MemorySection::from_bytes(&memory_bytes, 0).unwrap();
+        assert_eq!(memory_sec.limits.tag, Tag::Zero);
+        assert_eq!(memory_sec.limits.min, Some(memory_bytes[4] as u32));
+        assert_eq!(memory_sec.limits.max, None);
+
+        // one memory, limits with max: (memory 1 2)
+        let memory_bytes = [5, 4, 1, 1, 1, 2];
+        let memory_sec = MemorySection::from_bytes(&memory_bytes, 0).unwrap();
         assert_eq!(memory_sec.limits.tag, Tag::One);
-        assert_eq!(memory_sec.limits.min, Some(memory_bytes[3] as u32));
-        assert_eq!(memory_sec.limits.max, Some(memory_bytes[4] as u32));
+        assert_eq!(memory_sec.limits.min, Some(1));
+        assert_eq!(memory_sec.limits.max, Some(2));
+    }
+
+    #[test]
+    fn truncated_memory_section_should_fail() {
+        // limits with max, but max is missing.
+        let memory_bytes = [5, 3, 1, 1, 1];
+        assert_eq!(
+            MemorySection::from_bytes(&memory_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 5 }
+        );
     }
 }
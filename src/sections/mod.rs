@@ -1,10 +1,10 @@
 use alloc::borrow::Cow;
 
-use self::custom_section::CustomSection;
-
 pub mod code_section;
 pub mod custom_section;
+pub mod data_count_section;
 pub mod data_section;
+pub mod element_section;
 pub mod errors;
 pub mod export_section;
 pub mod function_section;
@@ -12,22 +12,36 @@ pub mod global_section;
 pub mod import_section;
 pub mod magic_section;
 pub mod memory_section;
+pub mod name_section;
 pub mod start_section;
 pub mod table_section;
 pub mod type_section;
 pub mod types;
 
+pub use self::errors::Error;
 pub use self::{
-    code_section::*, custom_section::*, data_section::*, export_section::*, function_section::*,
-    global_section::*, import_section::*, magic_section::*, memory_section::*, start_section::*,
-    table_section::*, type_section::*, types::*,
+    code_section::*, custom_section::*, data_count_section::*, data_section::*, element_section::*,
+    export_section::*, function_section::*, global_section::*, import_section::*, magic_section::*,
+    memory_section::*, name_section::*, start_section::*, table_section::*, type_section::*,
+    types::*,
 };
 
 #[derive(Clone, Debug)]
 pub struct WasmModule<'a> {
     pub magic_section: MagicSection<'a>,
-    pub custom_section: Option<CustomSection<'a>>,
+    pub custom_sections: Vec<CustomSection<'a>>,
     pub type_section: Option<TypeSection<'a>>,
+    pub import_section: Option<ImportSection<'a>>,
+    pub function_section: Option<FunctionSection<'a>>,
+    pub table_section: Option<TableSection<'a>>,
+    pub memory_section: Option<MemorySection<'a>>,
+    pub global_section: Option<GlobalSection<'a>>,
+    pub export_section: Option<ExportSection<'a>>,
+    pub start_section: Option<StartSection<'a>>,
+    pub element_section: Option<ElementSection<'a>>,
+    pub data_count_section: Option<DataCountSection<'a>>,
+    pub code_section: Option<CodeSection<'a>>,
+    pub data_section: Option<DataSection<'a>>,
 }
 
 /*
@@ -38,59 +52,340 @@ Each section consists of:
 */
 
 impl<'a> WasmModule<'a> {
-    pub fn from_bytes(bytes: &'a [u8]) -> Self {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
         // handle magic section first
-        let magic_sec = Self::magic_section(&bytes[..8]);
-        let bytes = &bytes[8..];
-        loop {
+        let magic_section = Self::magic_section(bytes)?;
+        let mut module = Self {
+            magic_section,
+            custom_sections: vec![],
+            type_section: None,
+            import_section: None,
+            function_section: None,
+            table_section: None,
+            memory_section: None,
+            global_section: None,
+            export_section: None,
+            start_section: None,
+            element_section: None,
+            data_count_section: None,
+            code_section: None,
+            data_section: None,
+        };
+
+        let mut offset = module.magic_section.length();
+        // non-custom sections must appear at most once, and in the order of `SECTION_ORDER`.
+        let mut last_order = 0;
+        while offset < bytes.len() {
             // the start of every section is id.
-            let section_id = bytes[0];
-            match section_id {
+            let section_id = bytes[offset];
+            if section_id != CustomSection::id() {
+                let order = SECTION_ORDER
+                    .iter()
+                    .position(|id| *id == section_id)
+                    .ok_or(Error::InvalidSectionId {
+                        id: section_id,
+                        offset,
+                    })?
+                    + 1;
+                if order <= last_order {
+                    return Err(Error::SectionOutOfOrder {
+                        id: section_id,
+                        offset,
+                    });
+                }
+                last_order = order;
+            }
+
+            let byte_count = match section_id {
                 // Custom section starts with 0
-                0 => (),
+                0 => {
+                    let section = CustomSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.custom_sections.push(section);
+                    byte_count
+                }
                 // Type section starts with 1
-                1 => (),
+                1 => {
+                    let section = TypeSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.type_section = Some(section);
+                    byte_count
+                }
                 // Import section starts with 2
-                2 => (),
+                2 => {
+                    let section = ImportSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.import_section = Some(section);
+                    byte_count
+                }
                 // Function section starts with 3
-                3 => (),
+                3 => {
+                    let section = FunctionSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.function_section = Some(section);
+                    byte_count
+                }
                 // Table section starts with 4
-                4 => (),
+                4 => {
+                    let section = TableSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.table_section = Some(section);
+                    byte_count
+                }
                 // Memory section starts with 5
-                5 => (),
+                5 => {
+                    let section = MemorySection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.memory_section = Some(section);
+                    byte_count
+                }
                 // Global section starts with 6
-                6 => (),
+                6 => {
+                    let section = GlobalSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.global_section = Some(section);
+                    byte_count
+                }
                 // Export section starts with 7
-                7 => (),
+                7 => {
+                    let section = ExportSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.export_section = Some(section);
+                    byte_count
+                }
                 // Start section starts with 8
-                8 => (),
+                8 => {
+                    let section = StartSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.start_section = Some(section);
+                    byte_count
+                }
                 // Element section starts with 9
-                9 => (),
+                9 => {
+                    let section = ElementSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.element_section = Some(section);
+                    byte_count
+                }
                 // Code section starts with 10
-                10 => (),
+                10 => {
+                    let section = CodeSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.code_section = Some(section);
+                    byte_count
+                }
                 // Data section starts with 11
-                11 => (),
+                11 => {
+                    let section = DataSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.data_section = Some(section);
+                    byte_count
+                }
                 // Data count section starts with 12
-                12 => (),
                 _ => {
-                    println!("Unsupported section");
+                    let section = DataCountSection::from_bytes(bytes, offset)?;
+                    let byte_count = section.byte_count();
+                    module.data_count_section = Some(section);
+                    byte_count
                 }
-            }
+            };
+            offset += byte_count;
+        }
 
-            if section_id >= 11 {
-                break;
+        // every function declared in function section must have a body.
+        let functions = module
+            .function_section
+            .as_ref()
+            .map_or(0, |section| section.type_idx.len());
+        let codes = module
+            .code_section
+            .as_ref()
+            .map_or(0, |section| section.codes.len());
+        if functions != codes {
+            return Err(Error::FunctionCountMismatch {
+                functions,
+                codes,
+                offset,
+            });
+        }
+
+        // the data count must be the same as the count of data segments.
+        if let Some(section) = &module.data_count_section {
+            let segments = module
+                .data_section
+                .as_ref()
+                .map_or(0, |section| section.segments.len());
+            if section.count as usize != segments {
+                return Err(Error::DataCountMismatch {
+                    count: section.count,
+                    segments,
+                    offset,
+                });
             }
         }
-        todo!();
+
+        Ok(module)
     }
 
-    fn magic_section(bytes: &'a [u8]) -> MagicSection<'a> {
+    fn magic_section(bytes: &'a [u8]) -> Result<MagicSection<'a>, Error> {
         MagicSection::from_bytes(bytes)
     }
+
+    /// All function types declared in type section.
+    pub fn func_types(&self) -> &[FunctionType] {
+        self.type_section
+            .as_ref()
+            .map_or(&[], |section| &section.func_types[..])
+    }
 }
 
+/// The order of non-custom sections, data count section must be placed between element section and code section.
+const SECTION_ORDER: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 10, 11];
+
 pub(crate) trait Parse<'a> {
     type Output;
 
-    fn from_bytes(bytes: &'a [u8], offset: usize) -> Self::Output;
+    /// Parse from `bytes[offset]`, return the output with the offset right after it.
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self::Output, usize), Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::*;
+
+    #[test]
+    fn deserialize_module_should_work() {
+        // (module
+        //   (@custom "name" "\01\02\03")
+        //   (type (func (param i32 i32) (result i32)))
+        //   (type (func))
+        //   (import "env" "log" (func (type 1)))
+        //   (func (type 0) local.get 0 local.get 1 i32.add)
+        //   (func (type 1))
+        //   (table 1 1 funcref)
+        //   (memory 17)
+        //   (global (mut i32) (i32.const 1048576))
+        //   (export "add" (func 1))
+        //   (export "memory" (memory 0))
+        //   (start 2)
+        //   (elem (i32.const 0) func 1)
+        //   (data (i32.const 16) "hi"))
+        let module_bytes = [
+            0, 97, 115, 109, 1, 0, 0, 0, 0, 8, 4, 110, 97, 109, 101, 1, 2, 3, 1, 10, 2, 96, 2, 127,
+            127, 1, 127, 96, 0, 0, 2, 11, 1, 3, 101, 110, 118, 3, 108, 111, 103, 0, 1, 3, 3, 2, 0,
+            1, 4, 5, 1, 112, 1, 1, 1, 5, 3, 1, 0, 17, 6, 9, 1, 127, 1, 65, 128, 128, 192, 0, 11, 7,
+            16, 2, 3, 97, 100, 100, 0, 1, 6, 109, 101, 109, 111, 114, 121, 2, 0, 8, 1, 2, 9, 7, 1,
+            0, 65, 0, 11, 1, 1, 10, 12, 2, 7, 0, 32, 0, 32, 1, 106, 11, 2, 0, 11, 11, 8, 1, 0, 65,
+            16, 11, 2, 104, 105,
+        ];
+        let module = WasmModule::from_bytes(&module_bytes).unwrap();
+
+        assert_eq!(module.magic_section.version(), 1);
+        assert_eq!(module.custom_sections.len(), 1);
+        assert_eq!(module.custom_sections[0].name, "name");
+        assert_eq!(module.func_types().len(), 2);
+
+        let imports = &module.import_section.unwrap().desc;
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].module(), "env");
+        assert_eq!(imports[0].name(), "log");
+
+        assert_eq!(module.function_section.unwrap().type_idx, vec![0, 1]);
+        let table_types = module.table_section.unwrap().table_types;
+        assert_eq!(table_types[0].ref_type, ReferenceType::FuncRef);
+        assert_eq!(module.memory_section.unwrap().limits.min, Some(17));
+        assert_eq!(module.global_section.unwrap().globals.len(), 1);
+
+        let exports = module.export_section.unwrap().exports;
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[1].name, "memory");
+        assert_eq!(exports[1].desc.export_type, ExportType::Mem);
+
+        assert_eq!(module.start_section.unwrap().func_idx, 2);
+        assert_eq!(module.element_section.unwrap().elem[0].funcs(), vec![1]);
+
+        let codes = module.code_section.unwrap().codes;
+        assert_eq!(codes.len(), 2);
+        assert_eq!(
+            codes[0].expr.instructions,
+            vec![
+                Instructions::VariableInstructions(VariableInstructions::LocalGet, 0),
+                Instructions::VariableInstructions(VariableInstructions::LocalGet, 1),
+                Instructions::NumericInstructions(NumericInstructions::I32Add, None),
+            ]
+        );
+
+        let segments = module.data_section.unwrap().segments;
+        assert_eq!(&*segments[0].init, b"hi");
+    }
+
+    #[test]
+    fn malformed_module_should_fail() {
+        let header = [0, 97, 115, 109, 1, 0, 0, 0];
+
+        // unknown section id
+        let module_bytes = [&header[..], &[13, 0]].concat();
+        assert_eq!(
+            WasmModule::from_bytes(&module_bytes).unwrap_err(),
+            Error::InvalidSectionId { id: 13, offset: 8 }
+        );
+
+        // type section after function section
+        let module_bytes = [&header[..], &[3, 1, 0, 1, 1, 0]].concat();
+        assert_eq!(
+            WasmModule::from_bytes(&module_bytes).unwrap_err(),
+            Error::SectionOutOfOrder { id: 1, offset: 11 }
+        );
+
+        // one function without body
+        let module_bytes = [&header[..], &[1, 4, 1, 96, 0, 0, 3, 2, 1, 0]].concat();
+        assert_eq!(
+            WasmModule::from_bytes(&module_bytes).unwrap_err(),
+            Error::FunctionCountMismatch {
+                functions: 1,
+                codes: 0,
+                offset: 18
+            }
+        );
+
+        // data count 2, but only one passive segment
+        let module_bytes = [&header[..], &[12, 1, 2, 11, 4, 1, 1, 1, 97]].concat();
+        assert_eq!(
+            WasmModule::from_bytes(&module_bytes).unwrap_err(),
+            Error::DataCountMismatch {
+                count: 2,
+                segments: 1,
+                offset: 17
+            }
+        );
+        let module_bytes = [&header[..], &[12, 1, 1, 11, 4, 1, 1, 1, 97]].concat();
+        let module = WasmModule::from_bytes(&module_bytes).unwrap();
+        assert_eq!(module.data_count_section.unwrap().count, 1);
+
+        // truncated section
+        let module_bytes = [&header[..], &[1, 4, 1, 96]].concat();
+        assert_eq!(
+            WasmModule::from_bytes(&module_bytes).unwrap_err(),
+            Error::UnexpectedEof { offset: 12 }
+        );
+    }
+
+    #[test]
+    fn truncated_module_should_never_panic() {
+        let module_bytes = [
+            0, 97, 115, 109, 1, 0, 0, 0, 1, 10, 2, 96, 2, 127, 127, 1, 127, 96, 0, 0, 3, 3, 2, 0,
+            1, 7, 7, 1, 3, 97, 100, 100, 0, 0, 10, 12, 2, 7, 0, 32, 0, 32, 1, 106, 11, 2, 0, 11,
+        ];
+        assert!(WasmModule::from_bytes(&module_bytes).is_ok());
+        // cutting after the header or the type section leaves a valid module,
+        // every other prefix cuts a section or leaves functions without bodies.
+        let valid_prefixes = [8, 20];
+        for len in valid_prefixes {
+            assert!(WasmModule::from_bytes(&module_bytes[..len]).is_ok());
+        }
+        for len in (0..module_bytes.len()).filter(|len| !valid_prefixes.contains(len)) {
+            assert!(WasmModule::from_bytes(&module_bytes[..len]).is_err());
+        }
+    }
 }
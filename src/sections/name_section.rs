@@ -0,0 +1,125 @@
+use super::*;
+use crate::utils::*;
+use std::collections::HashMap;
+
+/// https://webassembly.github.io/spec/core/appendix/custom.html#name-section
+/// The debug names of a module, decoded from the custom section `name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameSection {
+    pub module_name: Option<String>,
+    pub func_names: HashMap<u32, String>,
+    /// The local names of every function, indexed by function index.
+    pub local_names: HashMap<u32, HashMap<u32, String>>,
+}
+
+/// https://webassembly.github.io/spec/core/appendix/custom.html#binary-namemap
+fn read_name_map(bytes: &[u8], offset: usize) -> Result<(HashMap<u32, String>, usize), Error> {
+    let (names, offset) = read_vec(bytes, offset, |bytes, offset| {
+        let (index, offset) = calc_len_and_offset(bytes, offset)?;
+        let (name, offset) = read_name(bytes, offset)?;
+        Ok(((index as u32, name.to_string()), offset))
+    })?;
+    Ok((names.into_iter().collect(), offset))
+}
+
+impl NameSection {
+    pub const NAME: &'static str = "name";
+
+    const MODULE_NAME: u8 = 0;
+    const FUNC_NAMES: u8 = 1;
+    const LOCAL_NAMES: u8 = 2;
+
+    /// Decode the content of custom section `name`, the subsections of other ids are skipped.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut section = Self::default();
+        let mut offset = 0;
+        let mut last_id = None;
+        while offset < bytes.len() {
+            let (id, next) = read_u8(bytes, offset)?;
+            // the subsections appear at most once, in the order of id.
+            if last_id.map_or(false, |last_id| id <= last_id) {
+                return Err(Error::SectionOutOfOrder { id, offset });
+            }
+            last_id = Some(id);
+
+            let (size, content_offset) = calc_len_and_offset(bytes, next)?;
+            let (_, end) = read_bytes(bytes, content_offset, size)?;
+            let content = &bytes[..end];
+            let next = match id {
+                Self::MODULE_NAME => {
+                    let (name, next) = read_name(content, content_offset)?;
+                    section.module_name = Some(name.to_string());
+                    next
+                }
+                Self::FUNC_NAMES => {
+                    let (names, next) = read_name_map(content, content_offset)?;
+                    section.func_names = names;
+                    next
+                }
+                Self::LOCAL_NAMES => {
+                    let (names, next) = read_vec(content, content_offset, |bytes, offset| {
+                        let (func_idx, offset) = calc_len_and_offset(bytes, offset)?;
+                        let (names, offset) = read_name_map(bytes, offset)?;
+                        Ok(((func_idx as u32, names), offset))
+                    })?;
+                    section.local_names = names.into_iter().collect();
+                    next
+                }
+                _ => end,
+            };
+            expect_end(end, next, content_offset)?;
+            offset = end;
+        }
+        Ok(section)
+    }
+}
+
+impl<'a> WasmModule<'a> {
+    /// The decoded name section, `None` if it's absent or malformed.
+    pub fn name_section(&self) -> Option<NameSection> {
+        self.custom_sections
+            .iter()
+            .find(|section| section.name == NameSection::NAME)
+            .and_then(|section| NameSection::from_bytes(&section.content).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_name_section_should_work() {
+        let bytes = [
+            // module name "m"
+            0, 2, 1, 109, //
+            // function 1 is "add"
+            1, 6, 1, 1, 3, 97, 100, 100, //
+            // a subsection of label names is skipped
+            3, 1, 0, //
+            // local 0 of function 1 is "x"
+            2, 6, 1, 1, 1, 0, 1, 120,
+        ];
+        assert_eq!(
+            NameSection::from_bytes(&bytes).unwrap_err(),
+            Error::SectionOutOfOrder { id: 2, offset: 15 }
+        );
+
+        let bytes = [&bytes[..12], &bytes[15..], &bytes[12..15]].concat();
+        let section = NameSection::from_bytes(&bytes).unwrap();
+        assert_eq!(section.module_name.as_deref(), Some("m"));
+        assert_eq!(section.func_names[&1], "add");
+        assert_eq!(section.local_names[&1][&0], "x");
+
+        // the size of function names is too large.
+        let bytes = [1, 7, 1, 1, 3, 97, 100, 100, 0];
+        assert_eq!(
+            NameSection::from_bytes(&bytes).unwrap_err(),
+            Error::SectionSizeMismatch {
+                expected: 7,
+                actual: 6,
+                offset: 8
+            }
+        );
+    }
+}
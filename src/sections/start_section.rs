@@ -1,32 +1,58 @@
-
 use super::*;
-use crate::instructions::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
-// only can export 4 types: function, table, memory, global
-
+/// https://webassembly.github.io/spec/core/binary/modules.html#start-section
 #[derive(Clone, Debug)]
 pub struct StartSection<'a> {
     data: Cow<'a, [u8]>,
-    func_idx: usize,
+    pub func_idx: usize,
 }
 
 impl<'a> StartSection<'a> {
-    const ID: u8 = 8;
+    pub const ID: u8 = 8;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Build a start section calling `func_idx`.
+    pub fn new(func_idx: usize) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            func_idx,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
+
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
+
+        let (index, end) = calc_len_and_offset(bytes, content_offset)?;
+        expect_end(bytes.len(), end, content_offset)?;
+
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            func_idx: index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_start_section_should_work() {
+        let start_bytes = [8, 2, 0x81, 0x01];
+        let start_sec = StartSection::from_bytes(&start_bytes, 0).unwrap();
 
-        let data = Cow::Borrowed(&_bytes[offset..offset + 3]);
-        let (len, off) = unsigned_leb128_decode(&data);
-        let index = data[2] as usize;
-        Self {
-            data, func_idx: index
-        }
+        assert_eq!(start_sec.func_idx, 129);
     }
 }
@@ -1,82 +1,99 @@
 use super::*;
+use crate::serialize::Encode;
 use crate::utils::*;
 
-impl Limits {
-    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
-        match bytes[0] {
+impl<'a> Parse<'a> for Limits {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (tag, next) = read_u8(bytes, offset)?;
+        match tag {
             0 => {
                 let tag = Tag::Zero;
-                let min = Some(bytes[1] as u32);
-                let max = None;
-                Self { tag, min, max }
+                let (min, next) = calc_len_and_offset(bytes, next)?;
+                Ok((
+                    Self {
+                        tag,
+                        min: Some(min as u32),
+                        max: None,
+                    },
+                    next,
+                ))
             }
             1 => {
                 let tag = Tag::One;
-                let min = Some(bytes[1] as u32);
-                let max = Some(bytes[2] as u32);
-                Self { tag, min, max }
+                let (min, next) = calc_len_and_offset(bytes, next)?;
+                let (max, next) = calc_len_and_offset(bytes, next)?;
+                Ok((
+                    Self {
+                        tag,
+                        min: Some(min as u32),
+                        max: Some(max as u32),
+                    },
+                    next,
+                ))
             }
-            _ => panic!("unsupported tag"),
+            _ => Err(Error::InvalidTag { tag, offset }),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ReferenceType {
-    FuncRef = 0x70,
-    ExternRef = 0x6f,
+/// https://webassembly.github.io/spec/core/binary/types.html#table-types
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableType {
+    pub ref_type: ReferenceType,
+    pub limits: Limits,
 }
 
-impl From<u8> for ReferenceType {
-    fn from(u: u8) -> Self {
-        match u {
-            0x70 => Self::FuncRef,
-            0x6f => Self::ExternRef,
-            _ => panic!("Uknown reference type."),
-        }
-    }
-}
+impl<'a> Parse<'a> for TableType {
+    type Output = Self;
 
-#[derive(Clone, Debug)]
-pub struct TableType {
-    ref_type: ReferenceType,
-    limits: Limits,
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (ref_type, offset) = ReferenceType::from_bytes(bytes, offset)?;
+        let (limits, offset) = Limits::from_bytes(bytes, offset)?;
+        Ok((TableType { ref_type, limits }, offset))
+    }
 }
 
+/// https://webassembly.github.io/spec/core/binary/modules.html#table-section
 #[derive(Clone, Debug)]
 pub struct TableSection<'a> {
     data: Cow<'a, [u8]>,
-    table_types: [TableType; 1],
+    pub table_types: Vec<TableType>,
 }
 
 impl<'a> TableSection<'a> {
-    const ID: u8 = 4;
+    pub const ID: u8 = 4;
 
     pub fn id() -> u8 {
         Self::ID
     }
 
-    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        assert_eq!(_bytes[0], Self::ID);
-
-        // 1 means skip the id.
-        let (leb_len, _bytes) = calc_len_and_offset(_bytes, 1);
+    pub(crate) fn byte_count(&self) -> usize {
+        self.data.len()
+    }
 
-        let data = Cow::Borrowed(&_bytes[..leb_len]);
+    /// Build a table section with the types of tables.
+    pub fn new(table_types: Vec<TableType>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            table_types,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
 
-        let (leb_len, _bytes) = calc_len_and_offset(&data, 0);
-        // currently webassembly only supports 1 table.
-        assert_eq!(leb_len, 1);
+    pub fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        let bytes = &bytes[..offset + data.len()];
 
-        let ref_type = ReferenceType::from(_bytes[0]);
-        let limits = Limits::from_bytes(&_bytes[1..]);
-        let table_type = TableType { ref_type, limits };
+        let (table_types, end) = read_vec(bytes, content_offset, TableType::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
 
-        Self {
-            data,
-            table_types: [table_type],
-        }
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            table_types,
+        })
     }
 }
 
@@ -87,19 +104,35 @@ mod tests {
     #[test]
     fn deserialize_limits_should_work() {
         let limits_bytes_with_max = [1, 13, 13];
-        let limits = Limits::from_bytes(&limits_bytes_with_max);
+        let (limits, _) = Limits::from_bytes(&limits_bytes_with_max, 0).unwrap();
         let tag = Tag::One;
         assert_eq!(limits.tag, tag);
         assert_eq!(limits.min, Some(limits_bytes_with_max[1] as u32));
         assert_eq!(limits.max, Some(limits_bytes_with_max[2] as u32));
     }
 
+    #[test]
+    fn limits_with_bad_tag_should_fail() {
+        let limits_bytes = [2, 13, 13];
+        assert_eq!(
+            Limits::from_bytes(&limits_bytes, 0).unwrap_err(),
+            Error::InvalidTag { tag: 2, offset: 0 }
+        );
+    }
+
     #[test]
     fn deserialize_table_section_should_work() {
         let table_bytes = [4, 5, 1, 112, 1, 13, 13];
-        let table_sec = TableSection::from_bytes(&table_bytes, 0);
+        let table_sec = TableSection::from_bytes(&table_bytes, 0).unwrap();
 
         assert_eq!(table_sec.table_types.len(), 1);
         assert_eq!(table_sec.table_types[0].ref_type, ReferenceType::FuncRef);
+
+        // (table 1 funcref) (table 0 2 externref)
+        let table_bytes = [4, 8, 2, 112, 0, 1, 111, 1, 0, 2];
+        let table_sec = TableSection::from_bytes(&table_bytes, 0).unwrap();
+        assert_eq!(table_sec.table_types.len(), 2);
+        assert_eq!(table_sec.table_types[1].ref_type, ReferenceType::ExternRef);
+        assert_eq!(table_sec.table_types[1].limits.max, Some(2));
     }
 }
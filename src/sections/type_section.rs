@@ -1,5 +1,6 @@
 use super::*;
-use crate::utils::unsigned_leb128_decode;
+use crate::serialize::Encode;
+use crate::utils::*;
 
 /// Reference: https://webassembly.github.io/spec/core/binary/modules.html#type-section
 /// Currently, All types are function.
@@ -10,7 +11,7 @@ pub struct TypeSection<'a> {
 }
 
 impl<'a> TypeSection<'a> {
-    const ID: u8 = 0x01;
+    pub const ID: u8 = 0x01;
 
     /// Return type segmemnt id
     pub fn id(&self) -> u8 {
@@ -21,36 +22,30 @@ impl<'a> TypeSection<'a> {
         self.data.len()
     }
 
-    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Self {
-        let _bytes = &bytes[offset..];
-        // ensure the id is valid.
-        assert_eq!(_bytes[0], Self::ID);
-
-        // get the length of content for type section
-        let (content_length, leb_offset) = unsigned_leb128_decode(&_bytes[1..]);
+    /// Build a type section, the raw bytes are encoded from the function types.
+    pub fn new(func_types: Vec<FunctionType>) -> Self {
+        let mut section = Self {
+            data: Cow::Borrowed(&[]),
+            func_types,
+        };
+        section.data = Cow::Owned(section.to_bytes());
+        section
+    }
 
-        let len = 1 + leb_offset + content_length;
-        let data = Cow::Borrowed(&bytes[offset..offset + len]);
+    pub(crate) fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<Self, Error> {
+        // ensure the id is valid.
+        // [id, content_length, func_count, ...]
+        let (data, content_offset) = section_bytes(bytes, offset, Self::ID)?;
+        // never read beyond this section.
+        let bytes = &bytes[..offset + data.len()];
 
-        let func_types = {
-            // [id, leb_offset, func_count, ...]
-            let func_bytes = &data[2 + leb_offset..];
-            let func_types_count = func_bytes[0] as usize;
+        let (func_types, end) = read_vec(bytes, content_offset, FunctionType::from_bytes)?;
+        expect_end(bytes.len(), end, content_offset)?;
 
-            let mut func_types: Vec<FunctionType> = Vec::with_capacity(func_types_count);
-            for _func in func_bytes.split(|byte| *byte as u32 == FunctionType::ID) {
-                if _func == [] {
-                    continue;
-                }
-                // Todo, imrpove this line. I don't like concat.
-                let func = [&[0x60], _func].concat();
-                let func_type = FunctionType::from_bytes(&func);
-                func_types.push(func_type)
-            }
-            func_types
-        };
-
-        Self { data, func_types }
+        Ok(Self {
+            data: Cow::Borrowed(data),
+            func_types,
+        })
     }
 }
 
@@ -62,10 +57,31 @@ mod tests {
     fn deserialize_type_scetion_should_work() {
         // three functions in the type section
         let type_sec_bytes = [1, 13, 3, 96, 1, 125, 0, 96, 1, 127, 1, 127, 96, 0, 0];
-        let type_sec = TypeSection::from_bytes(&type_sec_bytes, 0);
+        let type_sec = TypeSection::from_bytes(&type_sec_bytes, 0).unwrap();
 
         assert_eq!(type_sec.id(), type_sec_bytes[0]);
         assert_eq!(type_sec.byte_count(), type_sec_bytes.len());
         assert_eq!(type_sec.func_types.len(), 3);
     }
+
+    #[test]
+    fn type_section_with_wrong_size_should_fail() {
+        // the size says 5 bytes, but the content only occupies 4 bytes.
+        let type_sec_bytes = [1, 5, 1, 96, 0, 0, 0];
+        assert_eq!(
+            TypeSection::from_bytes(&type_sec_bytes, 0).unwrap_err(),
+            Error::SectionSizeMismatch {
+                expected: 5,
+                actual: 4,
+                offset: 6
+            }
+        );
+
+        // the size exceeds the whole bytes.
+        let type_sec_bytes = [1, 13, 1, 96, 0, 0];
+        assert_eq!(
+            TypeSection::from_bytes(&type_sec_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 6 }
+        );
+    }
 }
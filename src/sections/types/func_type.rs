@@ -1,7 +1,9 @@
 use super::*;
+use crate::sections::{Error, Parse};
+use crate::utils::*;
 
 /// https://webassembly.github.io/spec/core/syntax/types.html#function-types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FunctionType {
     pub param_types: ParamsType,
     pub return_types: ResultType,
@@ -14,50 +16,36 @@ impl FunctionType {
     pub fn id() -> u32 {
         Self::ID
     }
+}
+
+impl<'a> Parse<'a> for FunctionType {
+    type Output = Self;
 
     // [0x60, cnt, .., cnt, .., 0x60, cnt, .., cnt, ..]
-    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
         // the most simple function like this => func() -> (), no param, no return
         // [0x60, 0, 0]
-        assert!(bytes.len() >= 3);
-        assert_eq!(bytes[0] as u32, Self::ID);
+        let (id, offset) = read_u8(bytes, offset)?;
+        if id as u32 != Self::ID {
+            return Err(Error::InvalidTag {
+                tag: id,
+                offset: offset - 1,
+            });
+        }
 
         // params
-        let params_count = bytes[1] as usize;
-        let param_types = {
-            if params_count == 0 {
-                vec![]
-            } else {
-                let mut param_types = Vec::with_capacity(params_count);
-                let params_bytes = &bytes[2..2 + params_count];
-                for ty in params_bytes {
-                    let param = ValueType::NumType(NumberType::from(*ty));
-                    param_types.push(param);
-                }
-                param_types
-            }
-        };
+        let (param_types, offset) = read_vec(bytes, offset, ValueType::from_bytes)?;
 
         // returns
-        let return_bytes = &bytes[1 + 1 + params_count..];
-        let return_count = return_bytes[0] as usize;
-        let return_types = {
-            if return_count == 0 {
-                vec![]
-            } else {
-                let mut return_types = Vec::with_capacity(return_count);
-                for ty in &return_bytes[1..] {
-                    let _return = ValueType::NumType(NumberType::from(*ty));
-                    return_types.push(_return);
-                }
-                return_types
-            }
-        };
-
-        Self {
-            param_types,
-            return_types,
-        }
+        let (return_types, offset) = read_vec(bytes, offset, ValueType::from_bytes)?;
+
+        Ok((
+            Self {
+                param_types,
+                return_types,
+            },
+            offset,
+        ))
     }
 }
 
@@ -68,7 +56,8 @@ mod tests {
     #[test]
     fn function_no_param_no_return_should_work() {
         let func_bytes = [0x60, 0, 0u8];
-        let func = FunctionType::from_bytes(&func_bytes);
+        let (func, offset) = FunctionType::from_bytes(&func_bytes, 0).unwrap();
+        assert_eq!(offset, func_bytes.len());
 
         assert_eq!(func.param_types.len(), 0);
         assert_eq!(func.return_types.len(), 0);
@@ -77,7 +66,8 @@ mod tests {
     #[test]
     fn function_no_param_one_return_should_work() {
         let func_bytes = [0x60, 0, 1, 0x7c];
-        let func = FunctionType::from_bytes(&func_bytes);
+        let (func, offset) = FunctionType::from_bytes(&func_bytes, 0).unwrap();
+        assert_eq!(offset, func_bytes.len());
 
         assert_eq!(func.param_types.len(), 0);
         assert_eq!(func.return_types.len(), 1);
@@ -86,7 +76,8 @@ mod tests {
     #[test]
     fn function_one_param_no_return_should_work() {
         let func_bytes = [0x60, 1, 0x7c, 0];
-        let func = FunctionType::from_bytes(&func_bytes);
+        let (func, offset) = FunctionType::from_bytes(&func_bytes, 0).unwrap();
+        assert_eq!(offset, func_bytes.len());
 
         assert_eq!(func.param_types.len(), 1);
         assert_eq!(func.return_types.len(), 0);
@@ -96,9 +87,37 @@ mod tests {
     fn function_four_params_four_return_should_work() {
         // In latest webassembly spec, the feature multi values is stablized.
         let func_bytes = [0x60, 4, 0x7c, 0x7d, 0x7e, 0x7f, 4, 0x7c, 0x7d, 0x7e, 0x7f];
-        let func = FunctionType::from_bytes(&func_bytes);
+        let (func, offset) = FunctionType::from_bytes(&func_bytes, 0).unwrap();
+        assert_eq!(offset, func_bytes.len());
 
         assert_eq!(func.param_types.len(), 4);
         assert_eq!(func.return_types.len(), 4);
     }
+
+    #[test]
+    fn function_with_bad_tag_or_type_should_fail() {
+        let func_bytes = [0x61, 0, 0u8];
+        assert_eq!(
+            FunctionType::from_bytes(&func_bytes, 0).unwrap_err(),
+            Error::InvalidTag {
+                tag: 0x61,
+                offset: 0
+            }
+        );
+
+        let func_bytes = [0x60, 1, 0x55, 0u8];
+        assert_eq!(
+            FunctionType::from_bytes(&func_bytes, 0).unwrap_err(),
+            Error::InvalidValueType {
+                byte: 0x55,
+                offset: 2
+            }
+        );
+
+        let func_bytes = [0x60, 2, 0x7f];
+        assert_eq!(
+            FunctionType::from_bytes(&func_bytes, 0).unwrap_err(),
+            Error::UnexpectedEof { offset: 3 }
+        );
+    }
 }
@@ -1,4 +1,5 @@
 pub mod func_type;
+#[allow(clippy::module_inception)]
 pub mod types;
 
 pub use self::func_type::*;
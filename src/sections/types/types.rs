@@ -1,9 +1,12 @@
+use crate::sections::{Error, Parse};
+use crate::utils::*;
+
 /// https://webassembly.github.io/spec/core/syntax/values.html#bytes
 pub type Byte = u8;
 
 /// Reference: https://webassembly.github.io/spec/core/syntax/types.html#number-types
 /// https://webassembly.github.io/spec/core/binary/types.html#number-types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NumberType {
     F64 = 0x7c,
     F32 = 0x7d,
@@ -11,53 +14,90 @@ pub enum NumberType {
     I32 = 0x7f,
 }
 
-impl From<u8> for NumberType {
-    fn from(ty: u8) -> Self {
-        match ty {
+impl<'a> Parse<'a> for NumberType {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (ty, next) = read_u8(bytes, offset)?;
+        let ty = match ty {
             0x7c => Self::F64,
             0x7d => Self::F32,
             0x7e => Self::I64,
             0x7f => Self::I32,
-            _ => panic!("Currently webassembly doesn't suppurt this type"),
-        }
+            _ => return Err(Error::InvalidValueType { byte: ty, offset }),
+        };
+        Ok((ty, next))
     }
 }
 
 /// Reference: https://webassembly.github.io/spec/core/syntax/types.html#reference-types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReferenceType {
     FuncRef = 0x70,
     ExternRef = 0x6f,
 }
 
+impl<'a> Parse<'a> for ReferenceType {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (ty, next) = read_u8(bytes, offset)?;
+        let ty = match ty {
+            0x70 => Self::FuncRef,
+            0x6f => Self::ExternRef,
+            _ => return Err(Error::InvalidValueType { byte: ty, offset }),
+        };
+        Ok((ty, next))
+    }
+}
+
 /// https://webassembly.github.io/spec/core/syntax/types.html#value-types
 /// https://webassembly.github.io/spec/core/binary/types.html#value-types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ValueType {
     NumType(NumberType),
     VectorType,
     RefType(ReferenceType),
 }
 
+impl<'a> Parse<'a> for ValueType {
+    type Output = Self;
+
+    fn from_bytes(bytes: &'a [u8], offset: usize) -> Result<(Self, usize), Error> {
+        let (ty, next) = read_u8(bytes, offset)?;
+        match ty {
+            0x7b => Ok((Self::VectorType, next)),
+            0x70 | 0x6f => {
+                let (ty, next) = ReferenceType::from_bytes(bytes, offset)?;
+                Ok((Self::RefType(ty), next))
+            }
+            _ => {
+                let (ty, next) = NumberType::from_bytes(bytes, offset)?;
+                Ok((Self::NumType(ty), next))
+            }
+        }
+    }
+}
+
 /// https://webassembly.github.io/spec/core/syntax/types.html#result-types
 pub type ResultType = Vec<ValueType>;
 pub type ParamsType = ResultType;
 
 /// https://webassembly.github.io/spec/core/syntax/types.html#limits
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Limits {
     pub tag: Tag,
     pub min: Option<u32>,
     pub max: Option<u32>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Tag {
     Zero,
     One,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mutable {
     No = 0x00,
     Yes = 0x01,
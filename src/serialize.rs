@@ -1 +1,624 @@
+//! https://webassembly.github.io/spec/core/binary/index.html
+//! Encode a module back into the canonical binary format.
 
+use crate::instructions::*;
+use crate::sections::*;
+
+/// Types which can be written in the binary format.
+pub trait Encode {
+    /// Append the binary encoding to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+/// Sizes, counts and indices are encoded as unsigned leb128.
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    // writing to a vector never fails.
+    let _ = leb128::write::unsigned(buf, value as u64);
+}
+
+fn write_i64(buf: &mut Vec<u8>, value: i64) {
+    let _ = leb128::write::signed(buf, value);
+}
+
+/// The opcode of instruction, with its prefix if any.
+fn write_opcode(buf: &mut Vec<u8>, discriminant: u32) {
+    match split_opcode(discriminant) {
+        (Some(prefix), opcode) => {
+            buf.push(prefix);
+            write_u32(buf, opcode);
+        }
+        (None, opcode) => buf.push(opcode as u8),
+    }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_vec<T: Encode>(buf: &mut Vec<u8>, items: &[T]) {
+    write_u32(buf, items.len() as u32);
+    for item in items {
+        item.encode(buf);
+    }
+}
+
+fn write_indices(buf: &mut Vec<u8>, indices: &[usize]) {
+    write_u32(buf, indices.len() as u32);
+    for index in indices {
+        write_u32(buf, *index as u32);
+    }
+}
+
+/// Write the section id, then the size of content, then the content.
+fn write_section(buf: &mut Vec<u8>, id: u8, content: &[u8]) {
+    buf.push(id);
+    write_bytes(buf, content);
+}
+
+impl Encode for ValueType {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let byte = match self {
+            ValueType::NumType(ty) => *ty as u8,
+            ValueType::VectorType => 0x7b,
+            ValueType::RefType(ty) => *ty as u8,
+        };
+        buf.push(byte);
+    }
+}
+
+impl Encode for FunctionType {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(FunctionType::ID as u8);
+        write_vec(buf, &self.param_types);
+        write_vec(buf, &self.return_types);
+    }
+}
+
+impl Encode for Limits {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self.max {
+            Some(max) => {
+                buf.push(0x01);
+                write_u32(buf, self.min.unwrap_or(0));
+                write_u32(buf, max);
+            }
+            None => {
+                buf.push(0x00);
+                write_u32(buf, self.min.unwrap_or(0));
+            }
+        }
+    }
+}
+
+impl Encode for TableType {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.ref_type as u8);
+        self.limits.encode(buf);
+    }
+}
+
+impl Encode for GlobalType {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.val_type.encode(buf);
+        buf.push(self.mutable as u8);
+    }
+}
+
+impl Encode for BlockType {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            BlockType::Empty => buf.push(0x40),
+            BlockType::Value(ty) => ty.encode(buf),
+            // the type index is a positive signed 33 bits integer.
+            BlockType::TypeIndex(type_idx) => write_i64(buf, *type_idx as i64),
+        }
+    }
+}
+
+impl Encode for MemArg {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_u32(buf, self.align);
+        write_u32(buf, self.offset);
+    }
+}
+
+impl Encode for Primitives {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Primitives::I32(v) => write_i64(buf, *v as i64),
+            Primitives::I64(v) => write_i64(buf, *v),
+            // floats are written by bits, so the NaN payloads are kept.
+            Primitives::F32(v) => buf.extend_from_slice(&v.to_bits().to_le_bytes()),
+            Primitives::F64(v) => buf.extend_from_slice(&v.to_bits().to_le_bytes()),
+        }
+    }
+}
+
+impl Encode for Instructions {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Instructions::ControlInstructions(op, operands) => {
+                buf.push(*op as u8);
+                match operands {
+                    ControlOperands::None => (),
+                    ControlOperands::Block(block_type) => block_type.encode(buf),
+                    ControlOperands::Label(label) => write_u32(buf, *label),
+                    ControlOperands::BrTable(labels, default) => {
+                        write_u32(buf, labels.len() as u32);
+                        for label in labels {
+                            write_u32(buf, *label);
+                        }
+                        write_u32(buf, *default);
+                    }
+                    ControlOperands::Func(func_idx) => write_u32(buf, *func_idx),
+                    ControlOperands::CallIndirect(type_idx, table_idx) => {
+                        write_u32(buf, *type_idx);
+                        write_u32(buf, *table_idx);
+                    }
+                }
+            }
+            Instructions::ReferenceInstructions(op, operands) => {
+                buf.push(*op as u8);
+                match operands {
+                    ReferenceOperands::None => (),
+                    ReferenceOperands::RefType(ty) => buf.push(*ty as u8),
+                    ReferenceOperands::Func(func_idx) => write_u32(buf, *func_idx),
+                }
+            }
+            Instructions::ParametricInstructions(op, types) => {
+                buf.push(*op as u8);
+                if *op == ParametricInstructions::SelectV {
+                    write_vec(buf, types);
+                }
+            }
+            Instructions::VariableInstructions(op, index) => {
+                buf.push(*op as u8);
+                write_u32(buf, *index);
+            }
+            Instructions::TableInstructions(op, operands) => {
+                write_opcode(buf, *op as u32);
+                match operands {
+                    TableOperands::Table(table_idx) => write_u32(buf, *table_idx),
+                    TableOperands::Init(table_idx, elem_idx) => {
+                        write_u32(buf, *elem_idx);
+                        write_u32(buf, *table_idx);
+                    }
+                    TableOperands::Elem(elem_idx) => write_u32(buf, *elem_idx),
+                    TableOperands::Copy(dst, src) => {
+                        write_u32(buf, *dst);
+                        write_u32(buf, *src);
+                    }
+                }
+            }
+            Instructions::MemoryInstructions(op, operands) => {
+                write_opcode(buf, *op as u32);
+                match operands {
+                    MemoryOperands::MemArg(mem_arg) => mem_arg.encode(buf),
+                    MemoryOperands::Data(data_idx) => {
+                        write_u32(buf, *data_idx);
+                        if *op == MemoryInstructions::Init {
+                            buf.push(0x00);
+                        }
+                    }
+                    // the zero bytes of memory indices.
+                    MemoryOperands::None => match op {
+                        MemoryInstructions::Copy => buf.extend_from_slice(&[0x00, 0x00]),
+                        _ => buf.push(0x00),
+                    },
+                }
+            }
+            Instructions::NumericInstructions(op, primitive) => {
+                write_opcode(buf, *op as u32);
+                if let Some(primitive) = primitive {
+                    primitive.encode(buf);
+                }
+            }
+            Instructions::VectorInstructions(op) => match *op {},
+        }
+    }
+}
+
+impl Encode for Expressions {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for instruction in &self.instructions {
+            instruction.encode(buf);
+        }
+        buf.push(Expressions::END);
+    }
+}
+
+impl<'a> Encode for ImportDescription<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_bytes(buf, self.module().as_bytes());
+        write_bytes(buf, self.name().as_bytes());
+        buf.push(self.tag as u8);
+        match &self.desc {
+            ImportDesc::Func(type_idx) => write_u32(buf, *type_idx),
+            ImportDesc::Table(table_type) => table_type.encode(buf),
+            ImportDesc::Memory(limits) => limits.encode(buf),
+            ImportDesc::Global(global_type) => global_type.encode(buf),
+        }
+    }
+}
+
+impl Encode for Global {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.global_type.encode(buf);
+        self.expressions.encode(buf);
+    }
+}
+
+impl Encode for Export {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_bytes(buf, self.name.as_bytes());
+        buf.push(self.desc.export_type as u8);
+        write_u32(buf, self.desc.index as u32);
+    }
+}
+
+impl Encode for Element {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let uses_exprs = matches!(self.init, ElementItems::Expressions(_));
+        let exprs_flag = if uses_exprs { 0b100 } else { 0 };
+        match &self.mode {
+            // active segment of table 0 with funcref has the short form.
+            ElementMode::Active {
+                table_idx: 0,
+                offset,
+            } if self.ref_type == ReferenceType::FuncRef => {
+                buf.push(exprs_flag);
+                offset.encode(buf);
+            }
+            ElementMode::Active { table_idx, offset } => {
+                buf.push(exprs_flag | 0b010);
+                write_u32(buf, *table_idx as u32);
+                offset.encode(buf);
+                self.encode_kind(buf, uses_exprs);
+            }
+            ElementMode::Passive => {
+                buf.push(exprs_flag | 0b001);
+                self.encode_kind(buf, uses_exprs);
+            }
+            ElementMode::Declarative => {
+                buf.push(exprs_flag | 0b011);
+                self.encode_kind(buf, uses_exprs);
+            }
+        }
+        match &self.init {
+            ElementItems::Functions(funcs) => write_indices(buf, funcs),
+            ElementItems::Expressions(exprs) => write_vec(buf, exprs),
+        }
+    }
+}
+
+impl Element {
+    /// The element kind of function indices, or the reference type of expressions.
+    fn encode_kind(&self, buf: &mut Vec<u8>, uses_exprs: bool) {
+        if uses_exprs {
+            buf.push(self.ref_type as u8);
+        } else {
+            // only funcref is defined as element kind.
+            buf.push(0x00);
+        }
+    }
+}
+
+impl Encode for Locals {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_u32(buf, self.count);
+        self._type.encode(buf);
+    }
+}
+
+impl Encode for Code {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut body = vec![];
+        write_vec(&mut body, &self.locals);
+        self.expr.encode(&mut body);
+        write_bytes(buf, &body);
+    }
+}
+
+impl<'a> Encode for Data<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match &self.mode {
+            // active segment of memory 0 has the short form.
+            DataMode::Active { mem_idx: 0, offset } => {
+                buf.push(0x00);
+                offset.encode(buf);
+            }
+            DataMode::Passive => buf.push(0x01),
+            DataMode::Active { mem_idx, offset } => {
+                buf.push(0x02);
+                write_u32(buf, *mem_idx as u32);
+                offset.encode(buf);
+            }
+        }
+        write_bytes(buf, &self.init);
+    }
+}
+
+impl<'a> Encode for CustomSection<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut content = vec![];
+        write_bytes(&mut content, self.name.as_bytes());
+        content.extend_from_slice(&self.content);
+        write_section(buf, CustomSection::id(), &content);
+    }
+}
+
+/// Sections consist of a vector of items.
+macro_rules! encode_vec_section {
+    ($section: ident, $items: ident) => {
+        impl<'a> Encode for $section<'a> {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                let mut content = vec![];
+                write_vec(&mut content, &self.$items);
+                write_section(buf, $section::ID, &content);
+            }
+        }
+    };
+}
+
+encode_vec_section!(TypeSection, func_types);
+encode_vec_section!(ImportSection, desc);
+encode_vec_section!(TableSection, table_types);
+encode_vec_section!(GlobalSection, globals);
+encode_vec_section!(ExportSection, exports);
+encode_vec_section!(ElementSection, elem);
+encode_vec_section!(CodeSection, codes);
+encode_vec_section!(DataSection, segments);
+
+impl<'a> Encode for FunctionSection<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut content = vec![];
+        write_indices(&mut content, &self.type_idx);
+        write_section(buf, FunctionSection::ID, &content);
+    }
+}
+
+impl<'a> Encode for MemorySection<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut content = vec![];
+        write_u32(&mut content, 1);
+        self.limits.encode(&mut content);
+        write_section(buf, MemorySection::ID, &content);
+    }
+}
+
+impl<'a> Encode for DataCountSection<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut content = vec![];
+        write_u32(&mut content, self.count);
+        write_section(buf, DataCountSection::ID, &content);
+    }
+}
+
+impl<'a> Encode for StartSection<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut content = vec![];
+        write_u32(&mut content, self.func_idx as u32);
+        write_section(buf, StartSection::ID, &content);
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/modules.html#binary-module
+/// The custom sections are placed at the end.
+impl<'a> Encode for WasmModule<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&MagicSection::MAGIC);
+        buf.extend_from_slice(&MagicSection::VERSION.to_le_bytes());
+
+        fn encode_section<T: Encode>(buf: &mut Vec<u8>, section: &Option<T>) {
+            if let Some(section) = section {
+                section.encode(buf);
+            }
+        }
+        encode_section(buf, &self.type_section);
+        encode_section(buf, &self.import_section);
+        encode_section(buf, &self.function_section);
+        encode_section(buf, &self.table_section);
+        encode_section(buf, &self.memory_section);
+        encode_section(buf, &self.global_section);
+        encode_section(buf, &self.export_section);
+        encode_section(buf, &self.start_section);
+        encode_section(buf, &self.element_section);
+        encode_section(buf, &self.data_count_section);
+        encode_section(buf, &self.code_section);
+        encode_section(buf, &self.data_section);
+        for section in &self.custom_sections {
+            section.encode(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (module
+    //   (@custom "name" "\01\02\03")
+    //   (type (func (param i32 i32) (result i32)))
+    //   (type (func))
+    //   (import "env" "log" (func (type 1)))
+    //   (func (type 0) local.get 0 local.get 1 i32.add)
+    //   (func (type 1))
+    //   (table 1 1 funcref)
+    //   (memory 17)
+    //   (global (mut i32) (i32.const 1048576))
+    //   (export "add" (func 1))
+    //   (export "memory" (memory 0))
+    //   (start 2)
+    //   (elem (i32.const 0) func 1)
+    //   (data (i32.const 16) "hi"))
+    const MODULE: [u8; 125] = [
+        0, 97, 115, 109, 1, 0, 0, 0, 0, 8, 4, 110, 97, 109, 101, 1, 2, 3, 1, 10, 2, 96, 2, 127,
+        127, 1, 127, 96, 0, 0, 2, 11, 1, 3, 101, 110, 118, 3, 108, 111, 103, 0, 1, 3, 3, 2, 0, 1,
+        4, 5, 1, 112, 1, 1, 1, 5, 3, 1, 0, 17, 6, 9, 1, 127, 1, 65, 128, 128, 192, 0, 11, 7, 16, 2,
+        3, 97, 100, 100, 0, 1, 6, 109, 101, 109, 111, 114, 121, 2, 0, 8, 1, 2, 9, 7, 1, 0, 65, 0,
+        11, 1, 1, 10, 12, 2, 7, 0, 32, 0, 32, 1, 106, 11, 2, 0, 11, 11, 8, 1, 0, 65, 16, 11, 2,
+        104, 105,
+    ];
+
+    #[test]
+    fn encode_module_should_round_trip() {
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        let encoded = module.to_bytes();
+
+        // the custom section moves to the end, everything else is kept.
+        let custom = &MODULE[8..18];
+        let expected = [&MODULE[..8], &MODULE[18..], custom].concat();
+        assert_eq!(encoded, expected);
+
+        // decode the encoded bytes again, it's the same module.
+        let decoded = WasmModule::from_bytes(&encoded).unwrap();
+        assert_eq!(decoded.to_bytes(), encoded);
+        assert_eq!(decoded.custom_sections[0].name, "name");
+        assert_eq!(decoded.func_types(), module.func_types());
+    }
+
+    /// A tiny xorshift generator, so the random cases are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    fn random_instructions(rng: &mut Rng) -> Vec<Instructions> {
+        let i32_type = ValueType::NumType(NumberType::I32);
+        let mut instructions = vec![];
+        let mut depth = 0;
+        for _ in 0..rng.below(64) {
+            let instruction = match rng.below(15) {
+                0 => Instructions::NumericInstructions(
+                    NumericInstructions::I32Const,
+                    Some(Primitives::I32(rng.next() as i32)),
+                ),
+                1 => Instructions::NumericInstructions(
+                    NumericInstructions::I64Const,
+                    Some(Primitives::I64(rng.next() as i64)),
+                ),
+                // any bits, NaN payloads included.
+                2 => Instructions::NumericInstructions(
+                    NumericInstructions::F32Const,
+                    Some(Primitives::F32(f32::from_bits(rng.next() as u32))),
+                ),
+                3 => Instructions::NumericInstructions(
+                    NumericInstructions::F64Const,
+                    Some(Primitives::F64(f64::from_bits(rng.next()))),
+                ),
+                4 => Instructions::VariableInstructions(
+                    VariableInstructions::LocalTee,
+                    rng.next() as u32,
+                ),
+                5 => Instructions::MemoryInstructions(
+                    MemoryInstructions::I64Store32,
+                    MemoryOperands::MemArg(MemArg {
+                        align: rng.below(3) as u32,
+                        offset: rng.next() as u32,
+                    }),
+                ),
+                6 => {
+                    depth += 1;
+                    let block_type = match rng.below(3) {
+                        0 => BlockType::Empty,
+                        1 => BlockType::Value(i32_type),
+                        _ => BlockType::TypeIndex(rng.next() as u32),
+                    };
+                    Instructions::ControlInstructions(
+                        ControlInstructions::Loop,
+                        ControlOperands::Block(block_type),
+                    )
+                }
+                7 if depth > 0 => {
+                    depth -= 1;
+                    Instructions::ControlInstructions(
+                        ControlInstructions::End,
+                        ControlOperands::None,
+                    )
+                }
+                8 => {
+                    let labels = (0..rng.below(4)).map(|_| rng.next() as u32).collect();
+                    Instructions::ControlInstructions(
+                        ControlInstructions::BrTable,
+                        ControlOperands::BrTable(labels, rng.next() as u32),
+                    )
+                }
+                9 => Instructions::ControlInstructions(
+                    ControlInstructions::CallIndirect,
+                    ControlOperands::CallIndirect(rng.next() as u32, 0),
+                ),
+                10 => Instructions::ParametricInstructions(
+                    ParametricInstructions::SelectV,
+                    vec![ValueType::RefType(ReferenceType::ExternRef)],
+                ),
+                11 => Instructions::MemoryInstructions(
+                    MemoryInstructions::Init,
+                    MemoryOperands::Data(rng.next() as u32),
+                ),
+                12 => {
+                    Instructions::MemoryInstructions(MemoryInstructions::Copy, MemoryOperands::None)
+                }
+                13 => Instructions::NumericInstructions(
+                    NumericInstructions::I64TruncSatF64Unsigned,
+                    None,
+                ),
+                _ => {
+                    Instructions::MemoryInstructions(MemoryInstructions::Grow, MemoryOperands::None)
+                }
+            };
+            instructions.push(instruction);
+        }
+        for _ in 0..depth {
+            instructions.push(Instructions::ControlInstructions(
+                ControlInstructions::End,
+                ControlOperands::None,
+            ));
+        }
+        instructions
+    }
+
+    #[test]
+    fn random_function_bodies_should_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let expr = Expressions {
+                end: Expressions::END,
+                instructions: random_instructions(&mut rng),
+            };
+            let code = Code {
+                locals: vec![Locals {
+                    count: rng.next() as u32 >> 8,
+                    _type: ValueType::NumType(NumberType::F64),
+                }],
+                expr,
+            };
+
+            let mut codes = vec![];
+            write_vec(&mut codes, &[code]);
+            let mut bytes = vec![0, 97, 115, 109, 1, 0, 0, 0];
+            write_section(&mut bytes, TypeSection::ID, &[1, 0x60, 0, 0]);
+            write_section(&mut bytes, FunctionSection::ID, &[1, 0]);
+            write_section(&mut bytes, CodeSection::ID, &codes);
+
+            let module = WasmModule::from_bytes(&bytes).unwrap();
+            assert_eq!(module.to_bytes(), bytes);
+        }
+    }
+}
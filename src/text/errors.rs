@@ -0,0 +1,59 @@
+use core::fmt;
+use thiserror::Error;
+
+/// The line and column in the source text, both start from 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Errors while parsing the text format, the messages start with the ones of the official spec tests.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("unexpected end of input at {pos}")]
+    UnexpectedEof { pos: Position },
+    #[error("unexpected token at {pos}")]
+    UnexpectedToken { pos: Position },
+    #[error("unknown operator {name} at {pos}")]
+    UnknownOperator { name: String, pos: Position },
+    #[error("unknown {kind} {id} at {pos}")]
+    UnknownIdentifier {
+        kind: &'static str,
+        id: String,
+        pos: Position,
+    },
+    #[error("duplicate {kind} {id} at {pos}")]
+    DuplicateIdentifier {
+        kind: &'static str,
+        id: String,
+        pos: Position,
+    },
+    #[error("constant out of range at {pos}")]
+    ConstantOutOfRange { pos: Position },
+    #[error("malformed string at {pos}")]
+    MalformedString { pos: Position },
+    #[error("malformed UTF-8 encoding at {pos}")]
+    MalformedUtf8 { pos: Position },
+    #[error("alignment must be a power of two at {pos}")]
+    InvalidAlignment { pos: Position },
+    #[error("mismatching label at {pos}")]
+    MismatchingLabel { pos: Position },
+    #[error("inline function type mismatches the type use at {pos}")]
+    InlineFunctionType { pos: Position },
+    #[error("import after {kind} at {pos}")]
+    ImportAfterDefinition { kind: &'static str, pos: Position },
+    #[error("multiple start sections at {pos}")]
+    MultipleStart { pos: Position },
+    #[error("unsupported {feature} at {pos}")]
+    Unsupported {
+        feature: &'static str,
+        pos: Position,
+    },
+}
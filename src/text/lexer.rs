@@ -0,0 +1,606 @@
+//! https://webassembly.github.io/spec/core/text/lexical.html
+//! Split the source text into tokens, and group them by parentheses.
+
+use super::errors::{ParseError, Position};
+
+/// A token or a parenthesized list of them.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Sexpr {
+    /// Keywords, numbers and the other reserved tokens.
+    Keyword(String, Position),
+    /// `$name`, the `$` is excluded.
+    Id(String, Position),
+    /// The decoded bytes of a string.
+    Str(Vec<u8>, Position),
+    List(Vec<Sexpr>, Position),
+}
+
+impl Sexpr {
+    pub fn pos(&self) -> Position {
+        match self {
+            Sexpr::Keyword(_, pos)
+            | Sexpr::Id(_, pos)
+            | Sexpr::Str(_, pos)
+            | Sexpr::List(_, pos) => *pos,
+        }
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a [u8],
+    offset: usize,
+    line: usize,
+    line_start: usize,
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#text-idchar
+pub(crate) fn is_idchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&byte)
+}
+
+impl<'a> Lexer<'a> {
+    fn pos(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.offset - self.line_start + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.line_start = self.offset;
+        }
+        Some(byte)
+    }
+
+    fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.source[self.offset..].starts_with(prefix)
+    }
+
+    /// Skip white spaces, line comments `;;` and block comments `(; ;)`, which can be nested.
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            if self.starts_with(b";;") {
+                while !matches!(self.peek(), None | Some(b'\n')) {
+                    self.bump();
+                }
+            } else if self.starts_with(b"(;") {
+                let pos = self.pos();
+                let mut depth = 0;
+                loop {
+                    if self.starts_with(b"(;") {
+                        self.offset += 2;
+                        depth += 1;
+                    } else if self.starts_with(b";)") {
+                        self.offset += 2;
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if self.bump().is_none() {
+                        return Err(ParseError::UnexpectedEof { pos });
+                    }
+                }
+            } else if matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+                self.bump();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parse the items until the closing parenthesis or the end of source.
+    fn items(&mut self, open: Option<Position>) -> Result<Vec<Sexpr>, ParseError> {
+        let mut items = vec![];
+        loop {
+            self.skip_trivia()?;
+            let pos = self.pos();
+            match self.peek() {
+                None => match open {
+                    Some(pos) => return Err(ParseError::UnexpectedEof { pos }),
+                    None => return Ok(items),
+                },
+                Some(b'(') => {
+                    self.bump();
+                    items.push(Sexpr::List(self.items(Some(pos))?, pos));
+                }
+                Some(b')') => match open {
+                    Some(_) => {
+                        self.bump();
+                        return Ok(items);
+                    }
+                    None => return Err(ParseError::UnexpectedToken { pos }),
+                },
+                Some(b'"') => items.push(Sexpr::Str(self.string()?, pos)),
+                Some(byte) if is_idchar(byte) => {
+                    let start = self.offset;
+                    while self.peek().map_or(false, is_idchar) {
+                        self.bump();
+                    }
+                    // the source is valid utf-8 and idchars are ascii.
+                    let token = core::str::from_utf8(&self.source[start..self.offset])
+                        .unwrap()
+                        .to_string();
+                    // tokens must be separated by white spaces or parentheses.
+                    if self.peek() == Some(b'"') {
+                        return Err(ParseError::UnexpectedToken { pos: self.pos() });
+                    }
+                    match token.strip_prefix('$') {
+                        Some("") => return Err(ParseError::UnexpectedToken { pos }),
+                        Some(id) => items.push(Sexpr::Id(id.to_string(), pos)),
+                        None => items.push(Sexpr::Keyword(token, pos)),
+                    }
+                }
+                Some(_) => return Err(ParseError::UnexpectedToken { pos }),
+            }
+        }
+    }
+
+    /// https://webassembly.github.io/spec/core/text/values.html#strings
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let pos = self.pos();
+        self.bump();
+        let mut bytes = vec![];
+        loop {
+            let byte = self.bump().ok_or(ParseError::UnexpectedEof { pos })?;
+            match byte {
+                b'"' => return Ok(bytes),
+                b'\\' => {
+                    let escape_pos = self.pos();
+                    let malformed = ParseError::MalformedString { pos: escape_pos };
+                    match self.bump().ok_or(malformed.clone())? {
+                        b't' => bytes.push(b'\t'),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b'"' => bytes.push(b'"'),
+                        b'\'' => bytes.push(b'\''),
+                        b'\\' => bytes.push(b'\\'),
+                        b'u' => {
+                            if self.bump() != Some(b'{') {
+                                return Err(malformed);
+                            }
+                            let start = self.offset;
+                            while self
+                                .peek()
+                                .map_or(false, |b| b.is_ascii_hexdigit() || b == b'_')
+                            {
+                                self.bump();
+                            }
+                            let digits = core::str::from_utf8(&self.source[start..self.offset])
+                                .unwrap()
+                                .to_string();
+                            let c = parse_unsigned(&digits, 16)
+                                .and_then(|value| u32::try_from(value).ok())
+                                .and_then(char::from_u32)
+                                .ok_or(malformed.clone())?;
+                            if self.bump() != Some(b'}') {
+                                return Err(malformed);
+                            }
+                            let mut buf = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        }
+                        high => {
+                            let low = self.bump().ok_or(malformed.clone())?;
+                            let digit = |b: u8| (b as char).to_digit(16);
+                            match (digit(high), digit(low)) {
+                                (Some(high), Some(low)) => bytes.push((high * 16 + low) as u8),
+                                _ => return Err(malformed),
+                            }
+                        }
+                    }
+                }
+                // control characters must be escaped.
+                byte if byte < 0x20 || byte == 0x7f => {
+                    return Err(ParseError::MalformedString { pos })
+                }
+                byte => bytes.push(byte),
+            }
+        }
+    }
+}
+
+/// Tokenize the source, return all top level items.
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Sexpr>, ParseError> {
+    let mut lexer = Lexer {
+        source: source.as_bytes(),
+        offset: 0,
+        line: 1,
+        line_start: 0,
+    };
+    lexer.items(None)
+}
+
+/// Remove the underscores between digits, return `None` if any underscore isn't between two digits.
+fn strip_underscores(text: &str, radix: u32) -> Option<String> {
+    let bytes = text.as_bytes();
+    let is_digit = |i: usize| bytes.get(i).map_or(false, |b| (*b as char).is_digit(radix));
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte == b'_' && (i == 0 || !is_digit(i - 1) || !is_digit(i + 1)) {
+            return None;
+        }
+    }
+    Some(text.replace('_', ""))
+}
+
+/// Parse digits which may be separated by underscores, return `None` on overflow too.
+fn parse_unsigned(digits: &str, radix: u32) -> Option<u64> {
+    let digits = strip_underscores(digits, radix)?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u64::from_str_radix(&digits, radix).ok()
+}
+
+/// The result of parsing a number token.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Number {
+    Value(u64),
+    /// The token looks like a number, but it's too large.
+    OutOfRange,
+    /// The token isn't a number at all.
+    Malformed,
+}
+
+fn split_sign(text: &str) -> (bool, &str) {
+    match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    }
+}
+
+fn unsigned_magnitude(text: &str) -> Number {
+    let (digits, radix) = match text.strip_prefix("0x") {
+        Some(digits) => (digits, 16),
+        None => (text, 10),
+    };
+    match strip_underscores(digits, radix) {
+        Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) => {
+            match u64::from_str_radix(&digits, radix) {
+                Ok(value) => Number::Value(value),
+                Err(_) => Number::OutOfRange,
+            }
+        }
+        _ => Number::Malformed,
+    }
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#integers
+/// Parse an unsigned integer of `bits`, like indices and alignments.
+pub(crate) fn parse_uint(text: &str, bits: u32) -> Number {
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return Number::Malformed;
+    }
+    match unsigned_magnitude(text) {
+        Number::Value(value) if bits < 64 && value >> bits != 0 => Number::OutOfRange,
+        number => number,
+    }
+}
+
+/// Parse an integer of `bits` which is either signed or unsigned, return the bits in two's complement.
+pub(crate) fn parse_int(text: &str, bits: u32) -> Number {
+    let (negative, magnitude) = split_sign(text);
+    if !magnitude.starts_with(|c: char| c.is_ascii_digit()) {
+        return Number::Malformed;
+    }
+    let value = match unsigned_magnitude(magnitude) {
+        Number::Value(value) => value,
+        number => return number,
+    };
+    let mask = u64::MAX >> (64 - bits);
+    if negative {
+        // the magnitude of a negative number is at most 2^(bits - 1).
+        if value > 1 << (bits - 1) {
+            return Number::OutOfRange;
+        }
+        Number::Value(value.wrapping_neg() & mask)
+    } else if value > mask {
+        Number::OutOfRange
+    } else {
+        Number::Value(value)
+    }
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#floating-point
+/// Parse a float with `mantissa_bits` and `exponent_bits`, return its bits.
+pub(crate) fn parse_float(text: &str, mantissa_bits: u32, exponent_bits: u32) -> Number {
+    let (negative, magnitude) = split_sign(text);
+    let sign = (negative as u64) << (mantissa_bits + exponent_bits);
+    let exponent_mask = ((1 << exponent_bits) - 1) << mantissa_bits;
+    let mantissa_mask = (1 << mantissa_bits) - 1;
+
+    let bits = if magnitude == "inf" {
+        exponent_mask
+    } else if magnitude == "nan" {
+        // the canonical NaN, only the highest bit of mantissa is set.
+        exponent_mask | 1 << (mantissa_bits - 1)
+    } else if let Some(payload) = magnitude.strip_prefix("nan:0x") {
+        match parse_unsigned(payload, 16) {
+            Some(payload) if payload != 0 && payload <= mantissa_mask => exponent_mask | payload,
+            Some(_) => return Number::OutOfRange,
+            None => return Number::Malformed,
+        }
+    } else if !magnitude.starts_with(|c: char| c.is_ascii_digit()) {
+        return Number::Malformed;
+    } else if let Some(hex) = magnitude.strip_prefix("0x") {
+        match parse_hex_float(hex, mantissa_bits, exponent_bits) {
+            Some(number) => return with_sign(number, sign),
+            None => return Number::Malformed,
+        }
+    } else {
+        match parse_decimal_float(magnitude, mantissa_bits) {
+            Some(number) => return with_sign(number, sign),
+            None => return Number::Malformed,
+        }
+    };
+    Number::Value(sign | bits)
+}
+
+fn with_sign(number: Number, sign: u64) -> Number {
+    match number {
+        Number::Value(bits) => Number::Value(sign | bits),
+        number => number,
+    }
+}
+
+fn parse_decimal_float(text: &str, mantissa_bits: u32) -> Option<Number> {
+    // the digits are checked here, the standard parser accepts more forms like `inf`.
+    let valid = text.split(['.', 'e', 'E']).all(|part| {
+        let part = part.strip_prefix(|c| c == '+' || c == '-').unwrap_or(part);
+        part.is_empty() || strip_underscores(part, 10).is_some()
+    });
+    let text = text.replace('_', "");
+    if !valid
+        || !text
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+    {
+        return None;
+    }
+    let (bits, infinite) = if mantissa_bits == 23 {
+        let value = text.parse::<f32>().ok()?;
+        (value.to_bits() as u64, value.is_infinite())
+    } else {
+        let value = text.parse::<f64>().ok()?;
+        (value.to_bits(), value.is_infinite())
+    };
+    if infinite {
+        return Some(Number::OutOfRange);
+    }
+    Some(Number::Value(bits))
+}
+
+/// Parse the digits after `0x`, like `1.8p+3`, the value is rounded to nearest, ties to even.
+fn parse_hex_float(text: &str, mantissa_bits: u32, exponent_bits: u32) -> Option<Number> {
+    let (significand, exponent) = match text.find(['p', 'P']) {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    let (integer, fraction) = match significand.find('.') {
+        Some(index) => (&significand[..index], &significand[index + 1..]),
+        None => (significand, ""),
+    };
+    if integer.is_empty()
+        || strip_underscores(integer, 16).is_none()
+        || (!fraction.is_empty() && strip_underscores(fraction, 16).is_none())
+    {
+        return None;
+    }
+
+    // keep 60 bits of the significand, the dropped bits only affect the rounding.
+    let mut mantissa = 0u64;
+    let mut exp = 0i64;
+    let mut sticky = false;
+    for (digits, is_fraction) in [(integer, false), (fraction, true)] {
+        for c in digits.chars().filter(|c| *c != '_') {
+            let digit = c.to_digit(16)? as u64;
+            if mantissa >> 60 == 0 {
+                mantissa = mantissa * 16 + digit;
+                if is_fraction {
+                    exp -= 4;
+                }
+            } else {
+                sticky |= digit != 0;
+                if !is_fraction {
+                    exp += 4;
+                }
+            }
+        }
+    }
+
+    if let Some(exponent) = exponent {
+        let (negative, digits) = split_sign(exponent);
+        let digits = strip_underscores(digits, 10)?;
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        // a huge exponent overflows or underflows anyway.
+        let value = digits.parse::<i64>().unwrap_or(i64::MAX).min(1 << 20);
+        exp += if negative { -value } else { value };
+    }
+
+    Some(round_float(
+        mantissa,
+        exp,
+        sticky,
+        mantissa_bits,
+        exponent_bits,
+    ))
+}
+
+/// Round `mantissa * 2^exp` to the float format, `sticky` tells whether some non-zero bits are dropped.
+fn round_float(
+    mantissa: u64,
+    exp: i64,
+    sticky: bool,
+    mantissa_bits: u32,
+    exponent_bits: u32,
+) -> Number {
+    if mantissa == 0 {
+        return Number::Value(0);
+    }
+    let bias = (1i64 << (exponent_bits - 1)) - 1;
+    let precision = mantissa_bits as i64 + 1;
+
+    // normalize to `1.xxx * 2^exp`, the highest bit of mantissa is the bit 127.
+    let leading_zeros = mantissa.leading_zeros() as i64;
+    let value = (mantissa as u128) << (64 + leading_zeros);
+    let mut exp = exp + 63 - leading_zeros;
+
+    // subnormal numbers keep fewer bits.
+    let subnormal = exp < 1 - bias;
+    let keep = if subnormal {
+        precision - (1 - bias - exp)
+    } else {
+        precision
+    };
+    if keep < 0 {
+        return Number::Value(0);
+    }
+    let shift = (128 - keep) as u32;
+    let (quotient, remainder, half) = if shift == 128 {
+        (0, value, 1u128 << 127)
+    } else {
+        (
+            value >> shift,
+            value & ((1 << shift) - 1),
+            1u128 << (shift - 1),
+        )
+    };
+    let round_up = remainder > half || (remainder == half && (sticky || quotient & 1 == 1));
+    let mut quotient = quotient as u64 + round_up as u64;
+
+    if subnormal {
+        // rounding up to `1 << mantissa_bits` is the smallest normal number, the bits are the same.
+        return Number::Value(quotient);
+    }
+    if quotient == 1 << precision {
+        quotient >>= 1;
+        exp += 1;
+    }
+    if exp > bias {
+        return Number::OutOfRange;
+    }
+    let mantissa_mask = (1 << mantissa_bits) - 1;
+    Number::Value(((exp + bias) as u64) << mantissa_bits | (quotient & mantissa_mask))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn tokenize_should_work() {
+        let source = "(module ;; comment\n  (; nested (; block ;) comment ;)\n  (func $f \"a\\n\\41\\u{1F600}\"))";
+        let items = tokenize(source).unwrap();
+        assert_eq!(
+            items,
+            vec![Sexpr::List(
+                vec![
+                    Sexpr::Keyword("module".to_string(), pos(1, 2)),
+                    Sexpr::List(
+                        vec![
+                            Sexpr::Keyword("func".to_string(), pos(3, 4)),
+                            Sexpr::Id("f".to_string(), pos(3, 9)),
+                            Sexpr::Str(b"a\nA\xf0\x9f\x98\x80".to_vec(), pos(3, 12)),
+                        ],
+                        pos(3, 3)
+                    ),
+                ],
+                pos(1, 1)
+            )]
+        );
+
+        assert_eq!(
+            tokenize("(func").unwrap_err(),
+            ParseError::UnexpectedEof { pos: pos(1, 1) }
+        );
+        assert_eq!(
+            tokenize("(func))").unwrap_err(),
+            ParseError::UnexpectedToken { pos: pos(1, 7) }
+        );
+        assert_eq!(
+            tokenize("\"\\q\"").unwrap_err(),
+            ParseError::MalformedString { pos: pos(1, 3) }
+        );
+    }
+
+    #[test]
+    fn parse_integers_should_work() {
+        assert_eq!(parse_int("0x7fff_ffff", 32), Number::Value(0x7fff_ffff));
+        assert_eq!(parse_int("-1", 32), Number::Value(0xffff_ffff));
+        assert_eq!(parse_int("4294967295", 32), Number::Value(0xffff_ffff));
+        assert_eq!(parse_int("-2147483648", 32), Number::Value(0x8000_0000));
+        assert_eq!(parse_int("-2147483649", 32), Number::OutOfRange);
+        assert_eq!(parse_int("0x1_0000_0000", 32), Number::OutOfRange);
+        assert_eq!(parse_int("-0x8000000000000000", 64), Number::Value(1 << 63));
+        assert_eq!(parse_int("1__0", 32), Number::Malformed);
+        assert_eq!(parse_int("_1", 32), Number::Malformed);
+        assert_eq!(parse_int("1.5", 32), Number::Malformed);
+        assert_eq!(parse_uint("+1", 32), Number::Malformed);
+    }
+
+    #[test]
+    fn parse_floats_should_be_exact() {
+        let f32_bits = |text| parse_float(text, 23, 8);
+        let f64_bits = |text| parse_float(text, 52, 11);
+
+        assert_eq!(f32_bits("1.5"), Number::Value(1.5f32.to_bits() as u64));
+        assert_eq!(f32_bits("-0"), Number::Value(0x8000_0000));
+        assert_eq!(f32_bits("1e1_0"), Number::Value(1e10f32.to_bits() as u64));
+        assert_eq!(f32_bits("nan"), Number::Value(0x7fc0_0000));
+        assert_eq!(f32_bits("-nan:0x1"), Number::Value(0xff80_0001));
+        assert_eq!(f32_bits("nan:0x80_0000"), Number::OutOfRange);
+        assert_eq!(f32_bits("inf"), Number::Value(0x7f80_0000));
+        assert_eq!(f32_bits("1e39"), Number::OutOfRange);
+        assert_eq!(f32_bits(".5"), Number::Malformed);
+
+        assert_eq!(f32_bits("0x1p-1"), Number::Value(0.5f32.to_bits() as u64));
+        assert_eq!(
+            f32_bits("0x1.fffffep127"),
+            Number::Value(f32::MAX.to_bits() as u64)
+        );
+        assert_eq!(f32_bits("0x1.ffffffp127"), Number::OutOfRange);
+        // ties to even, both up and down.
+        assert_eq!(f32_bits("0x1.000001p0"), Number::Value(0x3f80_0000));
+        assert_eq!(f32_bits("0x1.000003p0"), Number::Value(0x3f80_0002));
+        assert_eq!(
+            f32_bits("0x1.0000010000000001p0"),
+            Number::Value(0x3f80_0001)
+        );
+        // the smallest subnormal number, and halfway below it.
+        assert_eq!(f32_bits("0x1p-149"), Number::Value(1));
+        assert_eq!(f32_bits("0x1p-150"), Number::Value(0));
+        assert_eq!(f32_bits("0x1.8p-150"), Number::Value(1));
+
+        assert_eq!(
+            f64_bits("0x1.921fb54442d18p+1"),
+            Number::Value(core::f64::consts::PI.to_bits())
+        );
+        assert_eq!(f64_bits("0x.8"), Number::Malformed);
+        assert_eq!(f64_bits("0x1P-1074"), Number::Value(1));
+        assert_eq!(
+            f64_bits("-0x1.fffffffffffffp1023"),
+            Number::Value(f64::MIN.to_bits())
+        );
+        assert_eq!(
+            f64_bits("nan:0xf_ffff_ffff_ffff"),
+            Number::Value(u64::MAX >> 1)
+        );
+        assert_eq!(
+            f64_bits("123456789.25"),
+            Number::Value(123456789.25f64.to_bits())
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! https://webassembly.github.io/spec/core/text/index.html
+//! Parse the text format into the same module as the binary decoder, and print it back.
+
+pub mod errors;
+pub(crate) mod lexer;
+pub(crate) mod parser;
+pub mod printer;
+
+pub use self::errors::{ParseError, Position};
+use self::lexer::Sexpr;
+use self::parser::Cursor;
+pub use self::printer::print;
+use crate::sections::WasmModule;
+
+/// Parse a module in the text format, either `(module ...)` or only the module fields.
+pub fn parse(source: &str) -> Result<WasmModule<'static>, ParseError> {
+    let items = lexer::tokenize(source)?;
+    match items.as_slice() {
+        [Sexpr::List(fields, pos)] if matches!(fields.first(), Some(Sexpr::Keyword(keyword, _)) if keyword == "module") =>
+        {
+            let mut cursor = Cursor::new(fields, *pos);
+            cursor.next()?;
+            cursor.id();
+            parser::module(cursor)
+        }
+        _ => parser::module(Cursor::new(&items, Position { line: 1, column: 1 })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::*;
+    use crate::serialize::Encode;
+    use crate::vm::{Instance, Value};
+
+    #[test]
+    fn parse_module_should_match_binary() {
+        let source = r#"
+            (module
+              (@custom "name" "\01\02\03")
+              (type $binary (func (param i32 i32) (result i32)))
+              (type (func))
+              (import "env" "log" (func $log (type 1)))
+              (func $add (export "add") (type $binary)
+                local.get 0
+                local.get 1
+                i32.add)
+              (func $start)
+              (table 1 1 funcref)
+              (memory (export "memory") 17)
+              (global (mut i32) (i32.const 0x10_0000))
+              (start $start)
+              (elem (i32.const 0) $add)
+              (data (offset i32.const 16) "h" "i"))
+        "#;
+        let module_bytes = [
+            0, 97, 115, 109, 1, 0, 0, 0, 0, 8, 4, 110, 97, 109, 101, 1, 2, 3, 1, 10, 2, 96, 2, 127,
+            127, 1, 127, 96, 0, 0, 2, 11, 1, 3, 101, 110, 118, 3, 108, 111, 103, 0, 1, 3, 3, 2, 0,
+            1, 4, 5, 1, 112, 1, 1, 1, 5, 3, 1, 0, 17, 6, 9, 1, 127, 1, 65, 128, 128, 192, 0, 11, 7,
+            16, 2, 3, 97, 100, 100, 0, 1, 6, 109, 101, 109, 111, 114, 121, 2, 0, 8, 1, 2, 9, 7, 1,
+            0, 65, 0, 11, 1, 1, 10, 12, 2, 7, 0, 32, 0, 32, 1, 106, 11, 2, 0, 11, 11, 8, 1, 0, 65,
+            16, 11, 2, 104, 105,
+        ];
+        let module = parse(source).unwrap();
+        let decoded = WasmModule::from_bytes(&module_bytes).unwrap();
+        assert_eq!(module.to_bytes(), decoded.to_bytes());
+
+        // the module fields without `(module)` are allowed too.
+        let module = parse("(func (export \"f\") (result i64) i64.const -1)").unwrap();
+        assert_eq!(module.func_types()[0].return_types.len(), 1);
+        assert_eq!(module.export_section.unwrap().exports[0].name, "f");
+    }
+
+    #[test]
+    fn folded_and_flat_instructions_should_be_same() {
+        let flat = r#"
+            (func $f (param $n i32) (result i32)
+              block $exit (result i32)
+                loop $again
+                  local.get $n
+                  i32.eqz
+                  if
+                    i32.const 1
+                    br $exit
+                  else
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.set $n
+                  end
+                  br $again
+                end $again
+                unreachable
+              end)
+        "#;
+        let folded = r#"
+            (func $f (param $n i32) (result i32)
+              (block $exit (result i32)
+                (loop $again
+                  (if (i32.eqz (local.get $n))
+                    (then (br $exit (i32.const 1)))
+                    (else (local.set $n (i32.sub (local.get $n) (i32.const 1)))))
+                  (br $again))
+                (unreachable)))
+        "#;
+        let flat = parse(flat).unwrap();
+        let folded = parse(folded).unwrap();
+        assert_eq!(flat.to_bytes(), folded.to_bytes());
+
+        let codes = flat.code_section.unwrap().codes;
+        let instructions = &codes[0].expr.instructions;
+        // `br $exit` jumps out of if, loop and block.
+        assert_eq!(
+            instructions[6],
+            Instructions::ControlInstructions(ControlInstructions::Br, ControlOperands::Label(2))
+        );
+        assert_eq!(
+            instructions[13],
+            Instructions::ControlInstructions(ControlInstructions::Br, ControlOperands::Label(0))
+        );
+    }
+
+    #[test]
+    fn parsed_module_should_run() {
+        let source = r#"
+            (module
+              (type $fib_t (func (param i64) (result i64)))
+              (func $fib (export "fib") (type $fib_t)
+                (if (result i64) (i64.lt_u (local.get 0) (i64.const 2))
+                  (then (local.get 0))
+                  (else
+                    (i64.add
+                      (call $fib (i64.sub (local.get 0) (i64.const 1)))
+                      (call $fib (i64.sub (local.get 0) (i64.const 2)))))))
+              (memory 1)
+              (data (i32.const 8) "\2a\00\00\00")
+              (func (export "load") (result i32)
+                (i32.load offset=4 align=4 (i32.const 4))))
+        "#;
+        let module = parse(source).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        assert_eq!(
+            instance.invoke("fib", &[Value::I64(20)]).unwrap(),
+            vec![Value::I64(6765)]
+        );
+        assert_eq!(instance.invoke("load", &[]).unwrap(), vec![Value::I32(42)]);
+    }
+
+    #[test]
+    fn malformed_text_should_fail() {
+        let pos = |line, column| Position { line, column };
+        let errors = [
+            (
+                "(func br $l)",
+                ParseError::UnknownIdentifier {
+                    kind: "label",
+                    id: "$l".to_string(),
+                    pos: pos(1, 10),
+                },
+            ),
+            (
+                "(func block $a end $b)",
+                ParseError::MismatchingLabel { pos: pos(1, 20) },
+            ),
+            (
+                "(func i32.foo)",
+                ParseError::UnknownOperator {
+                    name: "i32.foo".to_string(),
+                    pos: pos(1, 7),
+                },
+            ),
+            (
+                "(func $f) (func $f)",
+                ParseError::DuplicateIdentifier {
+                    kind: "function",
+                    id: "$f".to_string(),
+                    pos: pos(1, 17),
+                },
+            ),
+            (
+                "(func) (import \"m\" \"f\" (func))",
+                ParseError::ImportAfterDefinition {
+                    kind: "function",
+                    pos: pos(1, 25),
+                },
+            ),
+            (
+                "(global i32 (i32.const 0)) (global (import \"m\" \"g\") i32)",
+                ParseError::ImportAfterDefinition {
+                    kind: "global",
+                    pos: pos(1, 29),
+                },
+            ),
+            (
+                "(type (func)) (func (type 0) (param i32))",
+                ParseError::InlineFunctionType { pos: pos(1, 21) },
+            ),
+            (
+                "(func i32.const 0x1_0000_0000)",
+                ParseError::ConstantOutOfRange { pos: pos(1, 17) },
+            ),
+            (
+                "(func i32.load align=3)",
+                ParseError::InvalidAlignment { pos: pos(1, 16) },
+            ),
+            (
+                "(func (block)",
+                ParseError::UnexpectedEof { pos: pos(1, 1) },
+            ),
+            (
+                "(start 0) (start 0)",
+                ParseError::MultipleStart { pos: pos(1, 12) },
+            ),
+        ];
+        for (source, error) in errors {
+            assert_eq!(parse(source).unwrap_err(), error, "{}", source);
+        }
+    }
+}
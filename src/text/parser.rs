@@ -0,0 +1,1406 @@
+//! https://webassembly.github.io/spec/core/text/modules.html
+//! Build a module from the s-expressions, identifiers are resolved to indices here.
+
+use alloc::borrow::Cow;
+use std::collections::HashMap;
+
+use super::errors::{ParseError, Position};
+use super::lexer::*;
+use crate::instructions::*;
+use crate::sections::*;
+
+/// A cursor over the items of a list.
+#[derive(Clone, Debug)]
+pub(crate) struct Cursor<'a> {
+    items: &'a [Sexpr],
+    index: usize,
+    /// The position of the list, it's reported when more items are expected.
+    pos: Position,
+}
+
+/// The optional identifiers of params.
+type ParamIds<'a> = Vec<Option<(&'a str, Position)>>;
+
+/// A reference to an index space, either by number or by identifier.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Index<'a> {
+    Num(u32),
+    Id(&'a str, Position),
+}
+
+pub(crate) fn number(number: Number, pos: Position) -> Result<u64, ParseError> {
+    match number {
+        Number::Value(value) => Ok(value),
+        Number::OutOfRange => Err(ParseError::ConstantOutOfRange { pos }),
+        Number::Malformed => Err(ParseError::UnexpectedToken { pos }),
+    }
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(items: &'a [Sexpr], pos: Position) -> Self {
+        Self {
+            items,
+            index: 0,
+            pos,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&'a Sexpr> {
+        self.items.get(self.index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index >= self.items.len()
+    }
+
+    pub fn next(&mut self) -> Result<&'a Sexpr, ParseError> {
+        let item = self
+            .peek()
+            .ok_or(ParseError::UnexpectedEof { pos: self.pos })?;
+        self.index += 1;
+        Ok(item)
+    }
+
+    /// All items must be consumed.
+    pub fn finish(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(item) => Err(ParseError::UnexpectedToken { pos: item.pos() }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn peek_keyword(&self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Sexpr::Keyword(keyword, _)) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(&mut self) -> Result<(&'a str, Position), ParseError> {
+        match self.next()? {
+            Sexpr::Keyword(keyword, pos) => Ok((keyword, *pos)),
+            item => Err(ParseError::UnexpectedToken { pos: item.pos() }),
+        }
+    }
+
+    pub fn expect_keyword(&mut self, expected: &str) -> Result<(), ParseError> {
+        match self.keyword()? {
+            (keyword, _) if keyword == expected => Ok(()),
+            (_, pos) => Err(ParseError::UnexpectedToken { pos }),
+        }
+    }
+
+    /// Consume the optional identifier.
+    pub fn id(&mut self) -> Option<(&'a str, Position)> {
+        match self.peek() {
+            Some(Sexpr::Id(id, pos)) => {
+                self.index += 1;
+                Some((id, *pos))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn string(&mut self) -> Result<&'a [u8], ParseError> {
+        match self.next()? {
+            Sexpr::Str(bytes, _) => Ok(bytes),
+            item => Err(ParseError::UnexpectedToken { pos: item.pos() }),
+        }
+    }
+
+    /// Names are strings of valid utf-8.
+    pub fn name(&mut self) -> Result<String, ParseError> {
+        let pos = self.peek().map_or(self.pos, Sexpr::pos);
+        let bytes = self.string()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| ParseError::MalformedUtf8 { pos })
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ParseError> {
+        let (text, pos) = self.keyword()?;
+        Ok(number(parse_uint(text, 32), pos)? as u32)
+    }
+
+    /// Whether the next item is a number or an identifier.
+    pub fn peek_index(&self) -> bool {
+        match self.peek() {
+            Some(Sexpr::Id(..)) => true,
+            Some(Sexpr::Keyword(keyword, _)) => keyword.starts_with(|c: char| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    pub fn index(&mut self) -> Result<Index<'a>, ParseError> {
+        match self.peek() {
+            Some(Sexpr::Id(id, pos)) => {
+                self.index += 1;
+                Ok(Index::Id(id, *pos))
+            }
+            _ => Ok(Index::Num(self.u32()?)),
+        }
+    }
+
+    /// Whether the next item is a list starting with `keyword`.
+    pub fn peek_list(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Sexpr::List(items, _)) => {
+                matches!(items.first(), Some(Sexpr::Keyword(first, _)) if first == keyword)
+            }
+            _ => false,
+        }
+    }
+
+    /// Consume the list starting with `keyword`, return the cursor after the keyword.
+    pub fn take_list(&mut self, keyword: &str) -> Option<Cursor<'a>> {
+        if !self.peek_list(keyword) {
+            return None;
+        }
+        let mut list = self.list().ok()?;
+        list.index = 1;
+        Some(list)
+    }
+
+    /// The next item must be a list, return the cursor of it.
+    pub fn list(&mut self) -> Result<Cursor<'a>, ParseError> {
+        match self.next()? {
+            Sexpr::List(items, pos) => Ok(Cursor::new(items, *pos)),
+            item => Err(ParseError::UnexpectedToken { pos: item.pos() }),
+        }
+    }
+}
+
+/// An index space, which maps the identifiers to indices.
+#[derive(Debug)]
+struct Names<'a> {
+    kind: &'static str,
+    ids: HashMap<&'a str, u32>,
+    count: u32,
+}
+
+impl<'a> Names<'a> {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            ids: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    /// Allocate the next index, bind the identifier to it if any.
+    fn define(&mut self, id: Option<(&'a str, Position)>) -> Result<u32, ParseError> {
+        let index = self.count;
+        if let Some((id, pos)) = id {
+            if self.ids.insert(id, index).is_some() {
+                return Err(ParseError::DuplicateIdentifier {
+                    kind: self.kind,
+                    id: format!("${}", id),
+                    pos,
+                });
+            }
+        }
+        self.count += 1;
+        Ok(index)
+    }
+
+    fn resolve(&self, index: Index<'_>) -> Result<u32, ParseError> {
+        match index {
+            Index::Num(index) => Ok(index),
+            Index::Id(id, pos) => {
+                self.ids
+                    .get(id)
+                    .copied()
+                    .ok_or_else(|| ParseError::UnknownIdentifier {
+                        kind: self.kind,
+                        id: format!("${}", id),
+                        pos,
+                    })
+            }
+        }
+    }
+}
+
+fn value_type(cursor: &mut Cursor<'_>) -> Result<ValueType, ParseError> {
+    let (keyword, pos) = cursor.keyword()?;
+    let ty = match keyword {
+        "i32" => ValueType::NumType(NumberType::I32),
+        "i64" => ValueType::NumType(NumberType::I64),
+        "f32" => ValueType::NumType(NumberType::F32),
+        "f64" => ValueType::NumType(NumberType::F64),
+        "v128" => ValueType::VectorType,
+        "funcref" => ValueType::RefType(ReferenceType::FuncRef),
+        "externref" => ValueType::RefType(ReferenceType::ExternRef),
+        _ => return Err(ParseError::UnexpectedToken { pos }),
+    };
+    Ok(ty)
+}
+
+fn ref_type(cursor: &mut Cursor<'_>) -> Result<ReferenceType, ParseError> {
+    let pos = cursor.peek().map_or(cursor.pos, Sexpr::pos);
+    match value_type(cursor)? {
+        ValueType::RefType(ty) => Ok(ty),
+        _ => Err(ParseError::UnexpectedToken { pos }),
+    }
+}
+
+/// Both `(param i32 i64)` and `(param $x i32)` are allowed, the identifiers of params are returned too.
+fn params_and_results<'a>(
+    cursor: &mut Cursor<'a>,
+) -> Result<(FunctionType, ParamIds<'a>), ParseError> {
+    let mut param_types = vec![];
+    let mut ids = vec![];
+    while let Some(mut list) = cursor.take_list("param") {
+        if let Some(id) = list.id() {
+            param_types.push(value_type(&mut list)?);
+            ids.push(Some(id));
+            list.finish()?;
+        }
+        while !list.is_empty() {
+            param_types.push(value_type(&mut list)?);
+            ids.push(None);
+        }
+    }
+    let mut return_types = vec![];
+    while let Some(mut list) = cursor.take_list("result") {
+        while !list.is_empty() {
+            return_types.push(value_type(&mut list)?);
+        }
+    }
+    let func_type = FunctionType {
+        param_types,
+        return_types,
+    };
+    Ok((func_type, ids))
+}
+
+fn limits(cursor: &mut Cursor<'_>) -> Result<Limits, ParseError> {
+    let min = cursor.u32()?;
+    let max = if cursor.peek_index() {
+        Some(cursor.u32()?)
+    } else {
+        None
+    };
+    let tag = if max.is_some() { Tag::One } else { Tag::Zero };
+    Ok(Limits {
+        tag,
+        min: Some(min),
+        max,
+    })
+}
+
+/// `limits reftype`, like `1 10 funcref`.
+fn table_type(cursor: &mut Cursor<'_>) -> Result<TableType, ParseError> {
+    let limits = limits(cursor)?;
+    let ref_type = ref_type(cursor)?;
+    Ok(TableType { ref_type, limits })
+}
+
+/// Either `i32` or `(mut i32)`.
+fn global_type(cursor: &mut Cursor<'_>) -> Result<GlobalType, ParseError> {
+    match cursor.take_list("mut") {
+        Some(mut list) => {
+            let val_type = value_type(&mut list)?;
+            list.finish()?;
+            Ok(GlobalType {
+                val_type,
+                mutable: Mutable::Yes,
+            })
+        }
+        None => Ok(GlobalType {
+            val_type: value_type(cursor)?,
+            mutable: Mutable::No,
+        }),
+    }
+}
+
+/// The strings of data are concatenated.
+fn data_strings(cursor: &mut Cursor<'_>) -> Result<Vec<u8>, ParseError> {
+    let mut bytes = vec![];
+    while !cursor.is_empty() {
+        bytes.extend_from_slice(cursor.string()?);
+    }
+    Ok(bytes)
+}
+
+fn active_expression(instruction: Instructions) -> Expressions {
+    Expressions {
+        end: Expressions::END,
+        instructions: vec![instruction],
+    }
+}
+
+/// The offset of abbreviated segments, like `(memory (data "..."))`.
+fn zero_offset() -> Expressions {
+    active_expression(Instructions::NumericInstructions(
+        NumericInstructions::I32Const,
+        Some(Primitives::I32(0)),
+    ))
+}
+
+/// The definitions of a module, the index spaces are filled before building anything,
+/// so identifiers can be used before their definitions.
+struct ModuleBuilder<'a> {
+    type_names: Names<'a>,
+    func_names: Names<'a>,
+    table_names: Names<'a>,
+    mem_names: Names<'a>,
+    global_names: Names<'a>,
+    elem_names: Names<'a>,
+    data_names: Names<'a>,
+    /// The kind of the first definition, imports can't follow it.
+    defined: Option<&'static str>,
+
+    func_types: Vec<FunctionType>,
+    imports: Vec<ImportDescription<'static>>,
+    functions: Vec<usize>,
+    codes: Vec<Code>,
+    tables: Vec<TableType>,
+    memory: Option<Limits>,
+    globals: Vec<Global>,
+    exports: Vec<Export>,
+    start: Option<usize>,
+    elements: Vec<Element>,
+    segments: Vec<Data<'static>>,
+    /// Whether `memory.init` or `data.drop` is used, they need the data count section.
+    uses_data_count: bool,
+    custom_sections: Vec<CustomSection<'static>>,
+}
+
+impl<'a> ModuleBuilder<'a> {
+    fn new() -> Self {
+        Self {
+            type_names: Names::new("type"),
+            func_names: Names::new("function"),
+            table_names: Names::new("table"),
+            mem_names: Names::new("memory"),
+            global_names: Names::new("global"),
+            elem_names: Names::new("elem segment"),
+            data_names: Names::new("data segment"),
+            defined: None,
+            func_types: vec![],
+            imports: vec![],
+            functions: vec![],
+            codes: vec![],
+            tables: vec![],
+            memory: None,
+            globals: vec![],
+            exports: vec![],
+            start: None,
+            elements: vec![],
+            segments: vec![],
+            uses_data_count: false,
+            custom_sections: vec![],
+        }
+    }
+
+    /// The first pass, allocate the indices of a module field and parse the explicit types.
+    fn declare(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        let (kind, pos) = field.keyword()?;
+        match kind {
+            "type" => {
+                let id = field.id();
+                self.type_names.define(id)?;
+                let mut func = field.list()?;
+                func.expect_keyword("func")?;
+                let (func_type, _) = params_and_results(&mut func)?;
+                func.finish()?;
+                field.finish()?;
+                self.func_types.push(func_type);
+            }
+            "import" => {
+                field.name()?;
+                field.name()?;
+                let mut desc = field.list()?;
+                let (kind, pos) = desc.keyword()?;
+                self.declare_import(kind, desc.id(), pos)?;
+            }
+            "func" => {
+                let id = field.id();
+                while field.take_list("export").is_some() {}
+                if field.peek_list("import") {
+                    self.declare_import(kind, id, pos)?;
+                } else {
+                    self.defined.get_or_insert("function");
+                    self.func_names.define(id)?;
+                }
+            }
+            "table" | "memory" | "global" => {
+                let id = field.id();
+                while field.take_list("export").is_some() {}
+                if field.peek_list("import") {
+                    return self.declare_import(kind, id, pos);
+                }
+                let (names, feature, kind) = match kind {
+                    "table" => (&mut self.table_names, "", "table"),
+                    "memory" => (&mut self.mem_names, "multiple memories", "memory"),
+                    _ => (&mut self.global_names, "", "global"),
+                };
+                self.defined.get_or_insert(kind);
+                // only one memory is supported now.
+                if names.define(id)? > 0 && !feature.is_empty() {
+                    return Err(ParseError::Unsupported { feature, pos });
+                }
+            }
+            "elem" => {
+                self.elem_names.define(field.id())?;
+            }
+            "data" => {
+                self.data_names.define(field.id())?;
+            }
+            "export" | "start" => (),
+            // annotations are ignored except the custom sections.
+            _ if kind.starts_with('@') => (),
+            _ => return Err(ParseError::UnexpectedToken { pos }),
+        }
+        Ok(())
+    }
+
+    fn declare_import(
+        &mut self,
+        kind: &str,
+        id: Option<(&'a str, Position)>,
+        pos: Position,
+    ) -> Result<(), ParseError> {
+        if let Some(kind) = self.defined {
+            return Err(ParseError::ImportAfterDefinition { kind, pos });
+        }
+        let names = match kind {
+            "func" => &mut self.func_names,
+            "table" => &mut self.table_names,
+            "memory" => &mut self.mem_names,
+            "global" => &mut self.global_names,
+            _ => return Err(ParseError::UnexpectedToken { pos }),
+        };
+        // only one memory is supported now.
+        if names.define(id)? > 0 && kind == "memory" {
+            return Err(ParseError::Unsupported {
+                feature: "multiple memories",
+                pos,
+            });
+        }
+        Ok(())
+    }
+
+    /// The count of imported items of the kind, they go first in the index space.
+    fn imported(&self, tag: ImportTag) -> usize {
+        self.imports
+            .iter()
+            .filter(|import| import.tag == tag)
+            .count()
+    }
+
+    /// The second pass, build the module field.
+    fn define(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        let (kind, pos) = field.keyword()?;
+        match kind {
+            "import" => {
+                let module = field.name()?;
+                let name = field.name()?;
+                let mut desc = field.list()?;
+                let (kind, _) = desc.keyword()?;
+                desc.id();
+                let import_desc = match kind {
+                    "table" => ImportDesc::Table(table_type(&mut desc)?),
+                    "memory" => ImportDesc::Memory(limits(&mut desc)?),
+                    "global" => ImportDesc::Global(global_type(&mut desc)?),
+                    _ => ImportDesc::Func(self.type_use(&mut desc)?.0),
+                };
+                desc.finish()?;
+                field.finish()?;
+                self.imports
+                    .push(ImportDescription::new(module, name, import_desc));
+            }
+            "func" => self.func(field)?,
+            "table" => self.table(field)?,
+            "memory" => self.memory(field)?,
+            "global" => self.global(field)?,
+            "export" => {
+                let name = field.name()?;
+                let mut desc = field.list()?;
+                let (kind, pos) = desc.keyword()?;
+                let (export_type, names) = match kind {
+                    "func" => (ExportType::Func, &self.func_names),
+                    "table" => (ExportType::Table, &self.table_names),
+                    "memory" => (ExportType::Mem, &self.mem_names),
+                    "global" => (ExportType::Global, &self.global_names),
+                    _ => return Err(ParseError::UnexpectedToken { pos }),
+                };
+                let index = names.resolve(desc.index()?)?;
+                desc.finish()?;
+                field.finish()?;
+                self.export(name, export_type, index);
+            }
+            "start" => {
+                let func_idx = self.func_names.resolve(field.index()?)?;
+                field.finish()?;
+                if self.start.replace(func_idx as usize).is_some() {
+                    return Err(ParseError::MultipleStart { pos });
+                }
+            }
+            "elem" => self.elem(field)?,
+            "data" => self.data(field)?,
+            "@custom" => {
+                let name = field.name()?;
+                // the placement is ignored, custom sections are always placed at the end.
+                field
+                    .take_list("before")
+                    .or_else(|| field.take_list("after"));
+                let content = data_strings(&mut field)?;
+                self.custom_sections.push(CustomSection::new(name, content));
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn export(&mut self, name: String, export_type: ExportType, index: u32) {
+        self.exports.push(Export {
+            name,
+            desc: ExportDesc {
+                index: index as usize,
+                export_type,
+            },
+        });
+    }
+
+    /// Inline exports like `(func (export "a") (export "b") ...)`.
+    fn inline_exports(
+        &mut self,
+        field: &mut Cursor<'a>,
+        export_type: ExportType,
+        index: u32,
+    ) -> Result<(), ParseError> {
+        while let Some(mut export) = field.take_list("export") {
+            let name = export.name()?;
+            export.finish()?;
+            self.export(name, export_type, index);
+        }
+        Ok(())
+    }
+
+    /// Return the index of `func_type`, it's appended to the types if not defined yet.
+    fn intern_type(&mut self, func_type: FunctionType) -> TypeIndex {
+        match self.func_types.iter().position(|ty| *ty == func_type) {
+            Some(type_idx) => type_idx as TypeIndex,
+            None => {
+                self.func_types.push(func_type);
+                (self.func_types.len() - 1) as TypeIndex
+            }
+        }
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#type-uses
+    /// Return the type index with the identifiers of params.
+    fn type_use(
+        &mut self,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<(TypeIndex, ParamIds<'a>), ParseError> {
+        let pos = cursor.peek().map_or(cursor.pos, Sexpr::pos);
+        let type_idx = match cursor.take_list("type") {
+            Some(mut list) => {
+                let type_idx = self.type_names.resolve(list.index()?)?;
+                list.finish()?;
+                Some(type_idx)
+            }
+            None => None,
+        };
+        let (func_type, ids) = params_and_results(cursor)?;
+
+        match type_idx {
+            None => Ok((self.intern_type(func_type), ids)),
+            Some(type_idx) => {
+                let defined = self.func_types.get(type_idx as usize);
+                if func_type.param_types.is_empty() && func_type.return_types.is_empty() {
+                    let params = defined.map_or(0, |ty| ty.param_types.len());
+                    Ok((type_idx, vec![None; params]))
+                } else if defined == Some(&func_type) {
+                    Ok((type_idx, ids))
+                } else {
+                    Err(ParseError::InlineFunctionType { pos })
+                }
+            }
+        }
+    }
+
+    fn func(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        let func_idx = (self.imported(ImportTag::Func) + self.functions.len()) as u32;
+        field.id();
+        self.inline_exports(&mut field, ExportType::Func, func_idx)?;
+
+        if let Some(mut import) = field.take_list("import") {
+            let module = import.name()?;
+            let name = import.name()?;
+            import.finish()?;
+            let (type_idx, _) = self.type_use(&mut field)?;
+            field.finish()?;
+            self.imports.push(ImportDescription::new(
+                module,
+                name,
+                ImportDesc::Func(type_idx),
+            ));
+            return Ok(());
+        }
+
+        let (type_idx, param_ids) = self.type_use(&mut field)?;
+        let mut local_names = Names::new("local");
+        for id in param_ids {
+            local_names.define(id)?;
+        }
+
+        let mut locals: Vec<Locals> = vec![];
+        while let Some(mut list) = field.take_list("local") {
+            let mut types = vec![];
+            if let Some(id) = list.id() {
+                types.push(value_type(&mut list)?);
+                local_names.define(Some(id))?;
+                list.finish()?;
+            }
+            while !list.is_empty() {
+                types.push(value_type(&mut list)?);
+                local_names.define(None)?;
+            }
+            // the same types in a row are compressed.
+            for ty in types {
+                match locals.last_mut() {
+                    Some(last) if last._type == ty => last.count += 1,
+                    _ => locals.push(Locals {
+                        count: 1,
+                        _type: ty,
+                    }),
+                }
+            }
+        }
+
+        let mut body = BodyBuilder::new(self, local_names);
+        body.instructions(&mut field)?;
+        let expr = body.finish();
+        self.functions.push(type_idx as usize);
+        self.codes.push(Code { locals, expr });
+        Ok(())
+    }
+
+    fn table(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        field.id();
+        let table_idx = self.imported(ImportTag::Table) + self.tables.len();
+        self.inline_exports(&mut field, ExportType::Table, table_idx as u32)?;
+
+        if let Some(mut import) = field.take_list("import") {
+            let module = import.name()?;
+            let name = import.name()?;
+            import.finish()?;
+            let table_type = table_type(&mut field)?;
+            field.finish()?;
+            self.imports.push(ImportDescription::new(
+                module,
+                name,
+                ImportDesc::Table(table_type),
+            ));
+            return Ok(());
+        }
+
+        // `(table funcref (elem $f $g))` defines the table with an active segment.
+        let table_type = if field.peek_keyword().map_or(false, |k| k.ends_with("ref")) {
+            let ref_type = ref_type(&mut field)?;
+            let mut elem = field.take_list("elem").ok_or(ParseError::UnexpectedToken {
+                pos: field.peek().map_or(field.pos, Sexpr::pos),
+            })?;
+            // the items are either all function indices or all expressions.
+            let init = if matches!(elem.peek(), Some(Sexpr::List(..))) {
+                let mut exprs = vec![];
+                while !elem.is_empty() {
+                    exprs.push(self.elem_expr(&mut elem)?);
+                }
+                ElementItems::Expressions(exprs)
+            } else {
+                ElementItems::Functions(self.func_indices(&mut elem)?)
+            };
+            let len = match &init {
+                ElementItems::Functions(funcs) => funcs.len(),
+                ElementItems::Expressions(exprs) => exprs.len(),
+            } as u32;
+            self.elements.push(Element {
+                ref_type,
+                init,
+                mode: ElementMode::Active {
+                    table_idx,
+                    offset: zero_offset(),
+                },
+            });
+            TableType {
+                ref_type,
+                limits: Limits {
+                    tag: Tag::One,
+                    min: Some(len),
+                    max: Some(len),
+                },
+            }
+        } else {
+            table_type(&mut field)?
+        };
+        field.finish()?;
+        self.tables.push(table_type);
+        Ok(())
+    }
+
+    fn memory(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        field.id();
+        self.inline_exports(&mut field, ExportType::Mem, 0)?;
+
+        if let Some(mut import) = field.take_list("import") {
+            let module = import.name()?;
+            let name = import.name()?;
+            import.finish()?;
+            let limits = limits(&mut field)?;
+            field.finish()?;
+            self.imports.push(ImportDescription::new(
+                module,
+                name,
+                ImportDesc::Memory(limits),
+            ));
+            return Ok(());
+        }
+
+        // `(memory (data "..."))` defines the memory which fits the data exactly.
+        let limits = match field.take_list("data") {
+            Some(mut data) => {
+                let init = data_strings(&mut data)?;
+                let pages = ((init.len() + crate::vm::PAGE_SIZE - 1) / crate::vm::PAGE_SIZE) as u32;
+                self.segments.push(Data {
+                    mode: DataMode::Active {
+                        mem_idx: 0,
+                        offset: zero_offset(),
+                    },
+                    init: Cow::Owned(init),
+                });
+                Limits {
+                    tag: Tag::One,
+                    min: Some(pages),
+                    max: Some(pages),
+                }
+            }
+            None => limits(&mut field)?,
+        };
+        field.finish()?;
+        self.memory = Some(limits);
+        Ok(())
+    }
+
+    fn global(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        field.id();
+        let global_idx = (self.imported(ImportTag::Global) + self.globals.len()) as u32;
+        self.inline_exports(&mut field, ExportType::Global, global_idx)?;
+
+        if let Some(mut import) = field.take_list("import") {
+            let module = import.name()?;
+            let name = import.name()?;
+            import.finish()?;
+            let global_type = global_type(&mut field)?;
+            field.finish()?;
+            self.imports.push(ImportDescription::new(
+                module,
+                name,
+                ImportDesc::Global(global_type),
+            ));
+            return Ok(());
+        }
+
+        let global_type = global_type(&mut field)?;
+        let expressions = self.expression(&mut field)?;
+        self.globals.push(Global {
+            global_type,
+            expressions,
+        });
+        Ok(())
+    }
+
+    /// A constant expression, made of all remaining instructions.
+    fn expression(&mut self, cursor: &mut Cursor<'a>) -> Result<Expressions, ParseError> {
+        let mut body = BodyBuilder::new(self, Names::new("local"));
+        body.instructions(cursor)?;
+        Ok(body.finish())
+    }
+
+    /// The offset of active segments, either `(offset instr*)` or a folded instruction.
+    /// `None` if there is no offset, the segment is passive.
+    fn offset(&mut self, field: &mut Cursor<'a>) -> Result<Option<Expressions>, ParseError> {
+        if let Some(mut offset) = field.take_list("offset") {
+            return self.expression(&mut offset).map(Some);
+        }
+        match field.peek() {
+            Some(Sexpr::List(..)) => self.folded_expression(field).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// A constant expression of only one folded instruction.
+    fn folded_expression(&mut self, field: &mut Cursor<'a>) -> Result<Expressions, ParseError> {
+        let list = field.list()?;
+        let mut body = BodyBuilder::new(self, Names::new("local"));
+        body.folded(list)?;
+        Ok(body.finish())
+    }
+
+    /// An element expression, either `(item instr*)` or a folded instruction.
+    fn elem_expr(&mut self, field: &mut Cursor<'a>) -> Result<Expressions, ParseError> {
+        match field.take_list("item") {
+            Some(mut item) => self.expression(&mut item),
+            None => self.folded_expression(field),
+        }
+    }
+
+    fn func_indices(&mut self, field: &mut Cursor<'a>) -> Result<Vec<usize>, ParseError> {
+        let mut funcs = vec![];
+        while !field.is_empty() {
+            funcs.push(self.func_names.resolve(field.index()?)? as usize);
+        }
+        Ok(funcs)
+    }
+
+    fn elem(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        field.id();
+        let mode = if field.peek_keyword() == Some("declare") {
+            field.next()?;
+            ElementMode::Declarative
+        } else {
+            let table_idx = match field.take_list("table") {
+                Some(mut table) => {
+                    let table_idx = self.table_names.resolve(table.index()?)?;
+                    table.finish()?;
+                    Some(table_idx)
+                }
+                None => None,
+            };
+            // the segment is passive without table and offset.
+            match (table_idx, self.offset(&mut field)?) {
+                (table_idx, Some(offset)) => ElementMode::Active {
+                    table_idx: table_idx.unwrap_or(0) as usize,
+                    offset,
+                },
+                (None, None) => ElementMode::Passive,
+                (Some(_), None) => {
+                    let pos = field.peek().map_or(field.pos, Sexpr::pos);
+                    return Err(ParseError::UnexpectedToken { pos });
+                }
+            }
+        };
+
+        // `func $f $g`, `funcref (item ref.func $f)` or only the function indices.
+        let (ref_type, init) = match field.peek_keyword() {
+            Some("func") => {
+                field.next()?;
+                let funcs = self.func_indices(&mut field)?;
+                (ReferenceType::FuncRef, ElementItems::Functions(funcs))
+            }
+            Some(keyword) if keyword.ends_with("ref") => {
+                let ref_type = ref_type(&mut field)?;
+                let mut exprs = vec![];
+                while !field.is_empty() {
+                    exprs.push(self.elem_expr(&mut field)?);
+                }
+                (ref_type, ElementItems::Expressions(exprs))
+            }
+            _ => {
+                let funcs = self.func_indices(&mut field)?;
+                (ReferenceType::FuncRef, ElementItems::Functions(funcs))
+            }
+        };
+
+        self.elements.push(Element {
+            ref_type,
+            init,
+            mode,
+        });
+        Ok(())
+    }
+
+    fn data(&mut self, mut field: Cursor<'a>) -> Result<(), ParseError> {
+        field.id();
+        let mem_idx = match field.take_list("memory") {
+            Some(mut memory) => {
+                let mem_idx = self.mem_names.resolve(memory.index()?)?;
+                memory.finish()?;
+                Some(mem_idx)
+            }
+            None => None,
+        };
+        // the segment is passive without memory and offset.
+        let mode = match (mem_idx, self.offset(&mut field)?) {
+            (mem_idx, Some(offset)) => DataMode::Active {
+                mem_idx: mem_idx.unwrap_or(0) as usize,
+                offset,
+            },
+            (None, None) => DataMode::Passive,
+            (Some(_), None) => {
+                let pos = field.peek().map_or(field.pos, Sexpr::pos);
+                return Err(ParseError::UnexpectedToken { pos });
+            }
+        };
+        let init = data_strings(&mut field)?;
+        self.segments.push(Data {
+            mode,
+            init: Cow::Owned(init),
+        });
+        Ok(())
+    }
+
+    fn build(self) -> WasmModule<'static> {
+        fn non_empty<T>(items: Vec<T>) -> Option<Vec<T>> {
+            if items.is_empty() {
+                None
+            } else {
+                Some(items)
+            }
+        }
+
+        WasmModule {
+            magic_section: MagicSection::new(),
+            custom_sections: self.custom_sections,
+            type_section: non_empty(self.func_types).map(TypeSection::new),
+            import_section: non_empty(self.imports).map(ImportSection::new),
+            function_section: non_empty(self.functions).map(FunctionSection::new),
+            table_section: non_empty(self.tables).map(TableSection::new),
+            memory_section: self.memory.map(MemorySection::new),
+            global_section: non_empty(self.globals).map(GlobalSection::new),
+            export_section: non_empty(self.exports).map(ExportSection::new),
+            start_section: self.start.map(StartSection::new),
+            element_section: non_empty(self.elements).map(ElementSection::new),
+            data_count_section: match self.uses_data_count {
+                true => Some(DataCountSection::new(self.segments.len() as u32)),
+                false => None,
+            },
+            code_section: non_empty(self.codes).map(CodeSection::new),
+            data_section: non_empty(self.segments).map(DataSection::new),
+        }
+    }
+}
+
+/// Build the instructions of a function body or a constant expression.
+struct BodyBuilder<'m, 'a> {
+    module: &'m mut ModuleBuilder<'a>,
+    locals: Names<'a>,
+    /// The labels of enclosing blocks, the innermost one is the last.
+    labels: Vec<Option<&'a str>>,
+    instructions: Vec<Instructions>,
+}
+
+impl<'m, 'a> BodyBuilder<'m, 'a> {
+    fn new(module: &'m mut ModuleBuilder<'a>, locals: Names<'a>) -> Self {
+        Self {
+            module,
+            locals,
+            labels: vec![],
+            instructions: vec![],
+        }
+    }
+
+    fn finish(self) -> Expressions {
+        Expressions {
+            end: Expressions::END,
+            instructions: self.instructions,
+        }
+    }
+
+    fn push_control(&mut self, op: ControlInstructions, operands: ControlOperands) {
+        self.instructions
+            .push(Instructions::ControlInstructions(op, operands));
+    }
+
+    /// Parse all remaining instructions, both flat and folded ones.
+    fn instructions(&mut self, cursor: &mut Cursor<'a>) -> Result<(), ParseError> {
+        while !cursor.is_empty() {
+            self.instruction(cursor)?;
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, cursor: &mut Cursor<'a>) -> Result<(), ParseError> {
+        if let Some(Sexpr::List(..)) = cursor.peek() {
+            let list = cursor.list()?;
+            return self.folded(list);
+        }
+        let (name, pos) = cursor.keyword()?;
+        match name {
+            "block" | "loop" | "if" => self.block(name, pos, cursor),
+            _ => {
+                let instruction = self.plain(name, pos, cursor)?;
+                self.instructions.push(instruction);
+                Ok(())
+            }
+        }
+    }
+
+    fn block_op(name: &str) -> ControlInstructions {
+        match name {
+            "block" => ControlInstructions::Block,
+            "loop" => ControlInstructions::Loop,
+            _ => ControlInstructions::If,
+        }
+    }
+
+    /// `block $l? blocktype instr* end $l?`, the `if` may have an `else $l? instr*`.
+    fn block(
+        &mut self,
+        name: &str,
+        pos: Position,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<(), ParseError> {
+        let op = Self::block_op(name);
+        let label = cursor.id();
+        let block_type = self.block_type(cursor)?;
+        self.push_control(op, ControlOperands::Block(block_type));
+        self.labels.push(label.map(|(id, _)| id));
+
+        let mut has_else = false;
+        loop {
+            match cursor.peek_keyword() {
+                Some("end") => break,
+                Some("else") if op == ControlInstructions::If && !has_else => {
+                    cursor.next()?;
+                    Self::end_label(label, cursor)?;
+                    self.push_control(ControlInstructions::Else, ControlOperands::None);
+                    has_else = true;
+                }
+                _ if cursor.is_empty() => return Err(ParseError::UnexpectedEof { pos }),
+                _ => self.instruction(cursor)?,
+            }
+        }
+        cursor.next()?;
+        Self::end_label(label, cursor)?;
+
+        self.labels.pop();
+        self.push_control(ControlInstructions::End, ControlOperands::None);
+        Ok(())
+    }
+
+    /// The optional label after `else` and `end` must be the one of the block.
+    fn end_label(
+        label: Option<(&str, Position)>,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<(), ParseError> {
+        if let Some((id, pos)) = cursor.id() {
+            if label.map(|(label, _)| label) != Some(id) {
+                return Err(ParseError::MismatchingLabel { pos });
+            }
+        }
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/instructions.html#folded-instructions
+    fn folded(&mut self, mut list: Cursor<'a>) -> Result<(), ParseError> {
+        let (name, pos) = list.keyword()?;
+        match name {
+            "block" | "loop" => {
+                let label = list.id();
+                let block_type = self.block_type(&mut list)?;
+                self.push_control(Self::block_op(name), ControlOperands::Block(block_type));
+                self.labels.push(label.map(|(id, _)| id));
+                self.instructions(&mut list)?;
+            }
+            "if" => {
+                let label = list.id();
+                let block_type = self.block_type(&mut list)?;
+                // the condition is made of the folded instructions before `then`.
+                while !list.peek_list("then") {
+                    let condition = list.list()?;
+                    self.folded(condition)?;
+                }
+                self.push_control(ControlInstructions::If, ControlOperands::Block(block_type));
+                self.labels.push(label.map(|(id, _)| id));
+
+                if let Some(mut then) = list.take_list("then") {
+                    self.instructions(&mut then)?;
+                }
+                if let Some(mut otherwise) = list.take_list("else") {
+                    self.push_control(ControlInstructions::Else, ControlOperands::None);
+                    self.instructions(&mut otherwise)?;
+                }
+                list.finish()?;
+            }
+            _ => {
+                // the operands are evaluated before the instruction.
+                let instruction = self.plain(name, pos, &mut list)?;
+                while !list.is_empty() {
+                    let operand = list.list()?;
+                    self.folded(operand)?;
+                }
+                self.instructions.push(instruction);
+                return Ok(());
+            }
+        }
+        self.labels.pop();
+        self.push_control(ControlInstructions::End, ControlOperands::None);
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/instructions.html#text-blocktype
+    fn block_type(&mut self, cursor: &mut Cursor<'a>) -> Result<BlockType, ParseError> {
+        if cursor.peek_list("type") || cursor.peek_list("param") {
+            let (type_idx, _) = self.module.type_use(cursor)?;
+            return Ok(BlockType::TypeIndex(type_idx));
+        }
+        let (func_type, _) = params_and_results(cursor)?;
+        let block_type = match func_type.return_types[..] {
+            [] => BlockType::Empty,
+            [ty] => BlockType::Value(ty),
+            _ => BlockType::TypeIndex(self.module.intern_type(func_type)),
+        };
+        Ok(block_type)
+    }
+
+    fn label(&self, cursor: &mut Cursor<'a>) -> Result<LabelIndex, ParseError> {
+        match cursor.index()? {
+            Index::Num(depth) => Ok(depth),
+            Index::Id(id, pos) => self
+                .labels
+                .iter()
+                .rev()
+                .position(|label| *label == Some(id))
+                .map(|depth| depth as LabelIndex)
+                .ok_or_else(|| ParseError::UnknownIdentifier {
+                    kind: "label",
+                    id: format!("${}", id),
+                    pos,
+                }),
+        }
+    }
+
+    /// The memory index of memory instructions, only memory 0 is supported now.
+    fn memory_index(&self, cursor: &mut Cursor<'a>, pos: Position) -> Result<(), ParseError> {
+        if cursor.peek_index() && self.module.mem_names.resolve(cursor.index()?)? != 0 {
+            return Err(ParseError::Unsupported {
+                feature: "multiple memories",
+                pos,
+            });
+        }
+        Ok(())
+    }
+
+    fn mem_arg(
+        &self,
+        op: MemoryInstructions,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<MemArg, ParseError> {
+        let mut mem_arg = MemArg {
+            align: op.natural_align(),
+            offset: 0,
+        };
+        if let Some(offset) = cursor
+            .peek_keyword()
+            .and_then(|k| k.strip_prefix("offset="))
+        {
+            let (_, pos) = cursor.keyword()?;
+            mem_arg.offset = number(parse_uint(offset, 32), pos)? as u32;
+        }
+        if let Some(align) = cursor.peek_keyword().and_then(|k| k.strip_prefix("align=")) {
+            let (_, pos) = cursor.keyword()?;
+            let align = number(parse_uint(align, 32), pos)? as u32;
+            if !align.is_power_of_two() {
+                return Err(ParseError::InvalidAlignment { pos });
+            }
+            mem_arg.align = align.trailing_zeros();
+        }
+        Ok(mem_arg)
+    }
+
+    /// Parse the instruction without blocks, with its immediates.
+    fn plain(
+        &mut self,
+        name: &str,
+        pos: Position,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<Instructions, ParseError> {
+        if let Some(op) = ControlInstructions::from_name(name) {
+            let operands = match op {
+                ControlInstructions::Unreachable
+                | ControlInstructions::Nop
+                | ControlInstructions::Return => ControlOperands::None,
+                ControlInstructions::Br | ControlInstructions::BrIf => {
+                    ControlOperands::Label(self.label(cursor)?)
+                }
+                ControlInstructions::BrTable => {
+                    let mut labels = vec![self.label(cursor)?];
+                    while cursor.peek_index() {
+                        labels.push(self.label(cursor)?);
+                    }
+                    // the last one is the default label.
+                    let default = labels.pop().unwrap();
+                    ControlOperands::BrTable(labels, default)
+                }
+                ControlInstructions::Call => {
+                    ControlOperands::Func(self.module.func_names.resolve(cursor.index()?)?)
+                }
+                ControlInstructions::CallIndirect => {
+                    let table_idx = if cursor.peek_index() {
+                        self.module.table_names.resolve(cursor.index()?)?
+                    } else {
+                        0
+                    };
+                    let (type_idx, _) = self.module.type_use(cursor)?;
+                    ControlOperands::CallIndirect(type_idx, table_idx)
+                }
+                // the blocks are parsed by the callers, `else` and `end` can't appear alone.
+                _ => return Err(ParseError::UnexpectedToken { pos }),
+            };
+            return Ok(Instructions::ControlInstructions(op, operands));
+        }
+
+        if let Some(op) = ReferenceInstructions::from_name(name) {
+            let operands = match op {
+                ReferenceInstructions::RefNull => {
+                    let (heap_type, pos) = cursor.keyword()?;
+                    let ref_type = match heap_type {
+                        "func" => ReferenceType::FuncRef,
+                        "extern" => ReferenceType::ExternRef,
+                        _ => return Err(ParseError::UnexpectedToken { pos }),
+                    };
+                    ReferenceOperands::RefType(ref_type)
+                }
+                ReferenceInstructions::RefFunc => {
+                    ReferenceOperands::Func(self.module.func_names.resolve(cursor.index()?)?)
+                }
+                ReferenceInstructions::RefIsNull => ReferenceOperands::None,
+            };
+            return Ok(Instructions::ReferenceInstructions(op, operands));
+        }
+
+        if let Some(op) = ParametricInstructions::from_name(name) {
+            // `select (result t)*` is the typed one.
+            if op == ParametricInstructions::Select && cursor.peek_list("result") {
+                let (func_type, _) = params_and_results(cursor)?;
+                return Ok(Instructions::ParametricInstructions(
+                    ParametricInstructions::SelectV,
+                    func_type.return_types,
+                ));
+            }
+            return Ok(Instructions::ParametricInstructions(op, vec![]));
+        }
+
+        if let Some(op) = VariableInstructions::from_name(name) {
+            let index = match op {
+                VariableInstructions::LocalGet
+                | VariableInstructions::LocalSet
+                | VariableInstructions::LocalTee => self.locals.resolve(cursor.index()?)?,
+                VariableInstructions::GlobalGet | VariableInstructions::GlobalSet => {
+                    self.module.global_names.resolve(cursor.index()?)?
+                }
+            };
+            return Ok(Instructions::VariableInstructions(op, index));
+        }
+
+        if let Some(op) = TableInstructions::from_name(name) {
+            let mut indices = vec![];
+            while indices.len() < 2 && cursor.peek_index() {
+                indices.push(cursor.index()?);
+            }
+            let tables = &self.module.table_names;
+            let elems = &self.module.elem_names;
+            let operands = match (op, &indices[..]) {
+                // the table can be omitted, the element segment can't.
+                (TableInstructions::TableInit, [elem]) => {
+                    TableOperands::Init(0, elems.resolve(*elem)?)
+                }
+                (TableInstructions::TableInit, [table, elem]) => {
+                    TableOperands::Init(tables.resolve(*table)?, elems.resolve(*elem)?)
+                }
+                (TableInstructions::ElemDrop, [elem]) => TableOperands::Elem(elems.resolve(*elem)?),
+                (TableInstructions::TableCopy, []) => TableOperands::Copy(0, 0),
+                (TableInstructions::TableCopy, [dst, src]) => {
+                    TableOperands::Copy(tables.resolve(*dst)?, tables.resolve(*src)?)
+                }
+                (
+                    TableInstructions::TableInit
+                    | TableInstructions::ElemDrop
+                    | TableInstructions::TableCopy,
+                    _,
+                ) => return Err(ParseError::UnexpectedToken { pos }),
+                (_, []) => TableOperands::Table(0),
+                (_, [table]) => TableOperands::Table(tables.resolve(*table)?),
+                _ => return Err(ParseError::UnexpectedToken { pos }),
+            };
+            return Ok(Instructions::TableInstructions(op, operands));
+        }
+
+        if let Some(op) = MemoryInstructions::from_name(name) {
+            let operands = match op {
+                // memory.init x y, the memory is x and the data segment is y.
+                MemoryInstructions::Init => {
+                    let mut index = cursor.index()?;
+                    if cursor.peek_index() {
+                        if self.module.mem_names.resolve(index)? != 0 {
+                            return Err(ParseError::Unsupported {
+                                feature: "multiple memories",
+                                pos,
+                            });
+                        }
+                        index = cursor.index()?;
+                    }
+                    self.module.uses_data_count = true;
+                    MemoryOperands::Data(self.module.data_names.resolve(index)?)
+                }
+                MemoryInstructions::Drop => {
+                    self.module.uses_data_count = true;
+                    MemoryOperands::Data(self.module.data_names.resolve(cursor.index()?)?)
+                }
+                // the destination and the source memory.
+                MemoryInstructions::Copy => {
+                    self.memory_index(cursor, pos)?;
+                    self.memory_index(cursor, pos)?;
+                    MemoryOperands::None
+                }
+                MemoryInstructions::Size | MemoryInstructions::Grow | MemoryInstructions::Fill => {
+                    self.memory_index(cursor, pos)?;
+                    MemoryOperands::None
+                }
+                _ => {
+                    self.memory_index(cursor, pos)?;
+                    MemoryOperands::MemArg(self.mem_arg(op, cursor)?)
+                }
+            };
+            return Ok(Instructions::MemoryInstructions(op, operands));
+        }
+
+        if let Some(op) = NumericInstructions::from_name(name) {
+            let primitive = match op {
+                NumericInstructions::I32Const => {
+                    let (text, pos) = cursor.keyword()?;
+                    Some(Primitives::I32(number(parse_int(text, 32), pos)? as i32))
+                }
+                NumericInstructions::I64Const => {
+                    let (text, pos) = cursor.keyword()?;
+                    Some(Primitives::I64(number(parse_int(text, 64), pos)? as i64))
+                }
+                NumericInstructions::F32Const => {
+                    let (text, pos) = cursor.keyword()?;
+                    let bits = number(parse_float(text, 23, 8), pos)?;
+                    Some(Primitives::F32(f32::from_bits(bits as u32)))
+                }
+                NumericInstructions::F64Const => {
+                    let (text, pos) = cursor.keyword()?;
+                    let bits = number(parse_float(text, 52, 11), pos)?;
+                    Some(Primitives::F64(f64::from_bits(bits)))
+                }
+                _ => None,
+            };
+            return Ok(Instructions::NumericInstructions(op, primitive));
+        }
+
+        Err(ParseError::UnknownOperator {
+            name: name.to_string(),
+            pos,
+        })
+    }
+}
+
+/// Build the module from its fields.
+pub(crate) fn module(mut cursor: Cursor<'_>) -> Result<WasmModule<'static>, ParseError> {
+    let mut builder = ModuleBuilder::new();
+    let mut fields = vec![];
+    while !cursor.is_empty() {
+        fields.push(cursor.list()?);
+    }
+    for field in &fields {
+        builder.declare(field.clone())?;
+    }
+    for field in fields {
+        builder.define(field)?;
+    }
+    Ok(builder.build())
+}
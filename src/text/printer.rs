@@ -0,0 +1,711 @@
+//! https://webassembly.github.io/spec/core/text/index.html
+//! Print a module in the text format, the output can be parsed back by `parse`.
+
+use std::collections::{HashMap, HashSet};
+
+use super::lexer::is_idchar;
+use crate::instructions::*;
+use crate::sections::*;
+
+/// Print the module in the text format, the names come from the `name` section if any.
+pub fn print(module: &WasmModule) -> String {
+    let names = module.name_section().unwrap_or_default();
+    let mut printer = Printer {
+        module,
+        out: String::new(),
+        func_names: identifiers(&names.func_names),
+        local_names: names
+            .local_names
+            .iter()
+            .map(|(func_idx, locals)| (*func_idx, identifiers(locals)))
+            .collect(),
+    };
+
+    match names.module_name.filter(|name| is_identifier(name)) {
+        Some(name) => printer.out.push_str(&format!("(module ${}\n", name)),
+        None => printer.out.push_str("(module\n"),
+    }
+    printer.module_fields();
+    printer.out.push_str(")\n");
+    printer.out
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(is_idchar)
+}
+
+/// Keep the names which are valid identifiers, and unique in the index space.
+fn identifiers(names: &HashMap<u32, String>) -> HashMap<u32, String> {
+    let mut indices: Vec<_> = names.keys().copied().collect();
+    indices.sort_unstable();
+    let mut used = HashSet::new();
+    indices
+        .into_iter()
+        .filter(|index| is_identifier(&names[index]) && used.insert(&names[index]))
+        .map(|index| (index, format!("${}", names[&index])))
+        .collect()
+}
+
+pub(crate) fn value_type(ty: &ValueType) -> &'static str {
+    match ty {
+        ValueType::NumType(NumberType::I32) => "i32",
+        ValueType::NumType(NumberType::I64) => "i64",
+        ValueType::NumType(NumberType::F32) => "f32",
+        ValueType::NumType(NumberType::F64) => "f64",
+        ValueType::VectorType => "v128",
+        ValueType::RefType(ty) => ref_type(ty),
+    }
+}
+
+pub(crate) fn ref_type(ty: &ReferenceType) -> &'static str {
+    match ty {
+        ReferenceType::FuncRef => "funcref",
+        ReferenceType::ExternRef => "externref",
+    }
+}
+
+/// Like `(result i32 i64)`, nothing if there is no type.
+pub(crate) fn types_list(keyword: &str, types: &[ValueType]) -> String {
+    if types.is_empty() {
+        return String::new();
+    }
+    let types: Vec<_> = types.iter().map(value_type).collect();
+    format!(" ({} {})", keyword, types.join(" "))
+}
+
+pub(crate) fn limits(limits: &Limits) -> String {
+    let min = limits.min.unwrap_or(0);
+    match limits.max {
+        Some(max) => format!("{} {}", min, max),
+        None => format!("{}", min),
+    }
+}
+
+pub(crate) fn global_type_text(global_type: &GlobalType) -> String {
+    let ty = value_type(&global_type.val_type);
+    match global_type.mutable {
+        Mutable::Yes => format!("(mut {})", ty),
+        Mutable::No => ty.to_string(),
+    }
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#strings
+/// Printable ascii characters are kept, the others are escaped as hex.
+fn escape(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for byte in bytes {
+        match byte {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            0x20..=0x7e => text.push(*byte as char),
+            _ => text.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// `inf`, `nan` and `nan:0x...` are printed as the text format, the others are the shortest
+/// decimal which converts back to the same float.
+fn float(
+    negative: bool,
+    is_nan: bool,
+    is_infinite: bool,
+    payload: u64,
+    canonical: u64,
+) -> Option<String> {
+    let sign = if negative { "-" } else { "" };
+    if is_nan && payload == canonical {
+        Some(format!("{}nan", sign))
+    } else if is_nan {
+        Some(format!("{}nan:{:#x}", sign, payload))
+    } else if is_infinite {
+        Some(format!("{}inf", sign))
+    } else {
+        None
+    }
+}
+
+fn f32_text(value: f32) -> String {
+    let payload = (value.to_bits() & 0x7f_ffff) as u64;
+    float(
+        value.is_sign_negative(),
+        value.is_nan(),
+        value.is_infinite(),
+        payload,
+        1 << 22,
+    )
+    .unwrap_or_else(|| value.to_string())
+}
+
+fn f64_text(value: f64) -> String {
+    let payload = value.to_bits() & 0xf_ffff_ffff_ffff;
+    float(
+        value.is_sign_negative(),
+        value.is_nan(),
+        value.is_infinite(),
+        payload,
+        1 << 51,
+    )
+    .unwrap_or_else(|| value.to_string())
+}
+
+struct Printer<'m, 'a> {
+    module: &'m WasmModule<'a>,
+    out: String,
+    func_names: HashMap<u32, String>,
+    local_names: HashMap<u32, HashMap<u32, String>>,
+}
+
+impl<'m, 'a> Printer<'m, 'a> {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// The identifier of function if named, or the index.
+    fn func(&self, func_idx: u32) -> String {
+        match self.func_names.get(&func_idx) {
+            Some(name) => name.clone(),
+            None => func_idx.to_string(),
+        }
+    }
+
+    /// The identifier with the index as a comment, like `$f (;1;)`.
+    fn func_definition(&self, func_idx: u32) -> String {
+        match self.func_names.get(&func_idx) {
+            Some(name) => format!("{} (;{};)", name, func_idx),
+            None => format!("(;{};)", func_idx),
+        }
+    }
+
+    fn local(&self, func_idx: u32, local_idx: u32) -> String {
+        match self
+            .local_names
+            .get(&func_idx)
+            .and_then(|locals| locals.get(&local_idx))
+        {
+            Some(name) => name.clone(),
+            None => local_idx.to_string(),
+        }
+    }
+
+    /// Named params or locals are printed one by one, the others are grouped.
+    fn locals(&self, func_idx: u32, keyword: &str, first: u32, types: &[ValueType]) -> String {
+        let mut text = String::new();
+        let mut unnamed = vec![];
+        for (i, ty) in types.iter().enumerate() {
+            let local_idx = first + i as u32;
+            match self
+                .local_names
+                .get(&func_idx)
+                .and_then(|locals| locals.get(&local_idx))
+            {
+                Some(name) => {
+                    text.push_str(&types_list(keyword, &unnamed));
+                    unnamed.clear();
+                    text.push_str(&format!(" ({} {} {})", keyword, name, value_type(ty)));
+                }
+                None => unnamed.push(*ty),
+            }
+        }
+        text.push_str(&types_list(keyword, &unnamed));
+        text
+    }
+
+    fn module_fields(&mut self) {
+        let module = self.module;
+        for (type_idx, func_type) in module.func_types().iter().enumerate() {
+            let text = format!(
+                "(type (;{};) (func{}{}))",
+                type_idx,
+                types_list("param", &func_type.param_types),
+                types_list("result", &func_type.return_types)
+            );
+            self.line(1, &text);
+        }
+
+        let mut func_idx = 0;
+        let mut table_idx = 0;
+        let mut global_idx = 0;
+        if let Some(section) = &module.import_section {
+            for import in &section.desc {
+                let desc = match &import.desc {
+                    &ImportDesc::Func(type_idx) => {
+                        func_idx += 1;
+                        format!(
+                            "(func {} (type {}))",
+                            self.func_definition(func_idx - 1),
+                            type_idx
+                        )
+                    }
+                    ImportDesc::Table(table_type) => {
+                        table_idx += 1;
+                        format!(
+                            "(table (;{};) {} {})",
+                            table_idx - 1,
+                            limits(&table_type.limits),
+                            ref_type(&table_type.ref_type)
+                        )
+                    }
+                    ImportDesc::Memory(memory) => format!("(memory (;0;) {})", limits(memory)),
+                    ImportDesc::Global(global_type) => {
+                        global_idx += 1;
+                        format!(
+                            "(global (;{};) {})",
+                            global_idx - 1,
+                            global_type_text(global_type)
+                        )
+                    }
+                };
+                let text = format!(
+                    "(import {} {} {})",
+                    escape(import.module().as_bytes()),
+                    escape(import.name().as_bytes()),
+                    desc
+                );
+                self.line(1, &text);
+            }
+        }
+
+        if let (Some(functions), Some(codes)) = (&module.function_section, &module.code_section) {
+            for (type_idx, code) in functions.type_idx.iter().zip(&codes.codes) {
+                self.function(func_idx, *type_idx as TypeIndex, code);
+                func_idx += 1;
+            }
+        }
+
+        if let Some(section) = &module.table_section {
+            for table_type in &section.table_types {
+                let text = format!(
+                    "(table (;{};) {} {})",
+                    table_idx,
+                    limits(&table_type.limits),
+                    ref_type(&table_type.ref_type)
+                );
+                self.line(1, &text);
+                table_idx += 1;
+            }
+        }
+        if let Some(section) = &module.memory_section {
+            self.line(1, &format!("(memory (;0;) {})", limits(&section.limits)));
+        }
+        if let Some(section) = &module.global_section {
+            for global in &section.globals {
+                let text = format!(
+                    "(global (;{};) {} {})",
+                    global_idx,
+                    global_type_text(&global.global_type),
+                    self.const_expression(&global.expressions)
+                );
+                self.line(1, &text);
+                global_idx += 1;
+            }
+        }
+
+        if let Some(section) = &module.export_section {
+            for export in &section.exports {
+                let index = export.desc.index as u32;
+                let desc = match export.desc.export_type {
+                    ExportType::Func => format!("(func {})", self.func(index)),
+                    ExportType::Table => format!("(table {})", index),
+                    ExportType::Mem => format!("(memory {})", index),
+                    ExportType::Global => format!("(global {})", index),
+                };
+                let text = format!("(export {} {})", escape(export.name.as_bytes()), desc);
+                self.line(1, &text);
+            }
+        }
+        if let Some(section) = &module.start_section {
+            let text = format!("(start {})", self.func(section.func_idx as u32));
+            self.line(1, &text);
+        }
+
+        if let Some(section) = &module.element_section {
+            for (elem_idx, element) in section.elem.iter().enumerate() {
+                let mut text = format!("(elem (;{};)", elem_idx);
+                match &element.mode {
+                    ElementMode::Active { table_idx, offset } => {
+                        if *table_idx != 0 {
+                            text.push_str(&format!(" (table {})", table_idx));
+                        }
+                        text.push(' ');
+                        text.push_str(&self.const_expression(offset));
+                    }
+                    ElementMode::Passive => (),
+                    ElementMode::Declarative => text.push_str(" declare"),
+                }
+                match &element.init {
+                    ElementItems::Functions(funcs) => {
+                        text.push_str(" func");
+                        for func_idx in funcs {
+                            text.push(' ');
+                            text.push_str(&self.func(*func_idx as u32));
+                        }
+                    }
+                    ElementItems::Expressions(exprs) => {
+                        text.push(' ');
+                        text.push_str(ref_type(&element.ref_type));
+                        for expr in exprs {
+                            text.push(' ');
+                            text.push_str(&self.const_expression(expr));
+                        }
+                    }
+                }
+                text.push(')');
+                self.line(1, &text);
+            }
+        }
+        if let Some(section) = &module.data_section {
+            for (data_idx, data) in section.segments.iter().enumerate() {
+                let mut text = format!("(data (;{};)", data_idx);
+                if let DataMode::Active { mem_idx, offset } = &data.mode {
+                    if *mem_idx != 0 {
+                        text.push_str(&format!(" (memory {})", mem_idx));
+                    }
+                    text.push(' ');
+                    text.push_str(&self.const_expression(offset));
+                }
+                let text = format!("{} {})", text, escape(&data.init));
+                self.line(1, &text);
+            }
+        }
+
+        // the name section is printed as identifiers.
+        for section in &module.custom_sections {
+            if section.name != NameSection::NAME {
+                let text = format!(
+                    "(@custom {} {})",
+                    escape(section.name.as_bytes()),
+                    escape(&section.content)
+                );
+                self.line(1, &text);
+            }
+        }
+    }
+
+    fn function(&mut self, func_idx: u32, type_idx: TypeIndex, code: &Code) {
+        let func_type = self.module.func_types().get(type_idx as usize);
+        let params = func_type.map_or(&[][..], |ty| &ty.param_types[..]);
+        let results = func_type.map_or(&[][..], |ty| &ty.return_types[..]);
+        let text = format!(
+            "(func {} (type {}){}{}",
+            self.func_definition(func_idx),
+            type_idx,
+            self.locals(func_idx, "param", 0, params),
+            types_list("result", results)
+        );
+        self.line(1, &text);
+
+        let locals: Vec<_> = code
+            .locals
+            .iter()
+            .flat_map(|locals| (0..locals.count).map(move |_| locals._type))
+            .collect();
+        if !locals.is_empty() {
+            let text = self.locals(func_idx, "local", params.len() as u32, &locals);
+            self.line(2, text.trim_start());
+        }
+
+        let mut depth = 2;
+        for instruction in &code.expr.instructions {
+            match instruction {
+                Instructions::ControlInstructions(ControlInstructions::End, _) => {
+                    depth -= 1;
+                    self.line(depth, "end");
+                }
+                Instructions::ControlInstructions(ControlInstructions::Else, _) => {
+                    self.line(depth - 1, "else");
+                }
+                Instructions::ControlInstructions(
+                    ControlInstructions::Block
+                    | ControlInstructions::Loop
+                    | ControlInstructions::If,
+                    _,
+                ) => {
+                    let text = self.instruction(Some(func_idx), instruction);
+                    self.line(depth, &text);
+                    depth += 1;
+                }
+                _ => {
+                    let text = self.instruction(Some(func_idx), instruction);
+                    self.line(depth, &text);
+                }
+            }
+        }
+        self.line(1, ")");
+    }
+
+    /// Constant expressions are printed in folded form, like `(i32.const 0)`.
+    fn const_expression(&self, expressions: &Expressions) -> String {
+        let instructions: Vec<_> = expressions
+            .instructions
+            .iter()
+            .map(|instruction| format!("({})", self.instruction(None, instruction)))
+            .collect();
+        instructions.join(" ")
+    }
+
+    /// Print the instruction with its immediates, `func_idx` is the function whose body is printed.
+    fn instruction(&self, func_idx: Option<u32>, instruction: &Instructions) -> String {
+        match instruction {
+            Instructions::ControlInstructions(op, operands) => {
+                let immediates = match operands {
+                    ControlOperands::None => String::new(),
+                    ControlOperands::Block(BlockType::Empty) => String::new(),
+                    ControlOperands::Block(BlockType::Value(ty)) => {
+                        format!(" (result {})", value_type(ty))
+                    }
+                    ControlOperands::Block(BlockType::TypeIndex(type_idx)) => {
+                        format!(" (type {})", type_idx)
+                    }
+                    ControlOperands::Label(label) => format!(" {}", label),
+                    ControlOperands::BrTable(labels, default) => {
+                        let labels: String = labels.iter().map(|l| format!(" {}", l)).collect();
+                        format!("{} {}", labels, default)
+                    }
+                    ControlOperands::Func(func_idx) => format!(" {}", self.func(*func_idx)),
+                    ControlOperands::CallIndirect(type_idx, table_idx) => {
+                        if *table_idx == 0 {
+                            format!(" (type {})", type_idx)
+                        } else {
+                            format!(" {} (type {})", table_idx, type_idx)
+                        }
+                    }
+                };
+                format!("{}{}", op.name(), immediates)
+            }
+            Instructions::ReferenceInstructions(op, operands) => match operands {
+                ReferenceOperands::None => op.name().to_string(),
+                ReferenceOperands::RefType(ReferenceType::FuncRef) => format!("{} func", op.name()),
+                ReferenceOperands::RefType(ReferenceType::ExternRef) => {
+                    format!("{} extern", op.name())
+                }
+                ReferenceOperands::Func(func_idx) => {
+                    format!("{} {}", op.name(), self.func(*func_idx))
+                }
+            },
+            Instructions::ParametricInstructions(op, types) => {
+                format!("{}{}", op.name(), types_list("result", types))
+            }
+            Instructions::VariableInstructions(op, index) => match op {
+                VariableInstructions::LocalGet
+                | VariableInstructions::LocalSet
+                | VariableInstructions::LocalTee => {
+                    let local = match func_idx {
+                        Some(func_idx) => self.local(func_idx, *index),
+                        None => index.to_string(),
+                    };
+                    format!("{} {}", op.name(), local)
+                }
+                _ => format!("{} {}", op.name(), index),
+            },
+            Instructions::TableInstructions(op, operands) => match operands {
+                TableOperands::Table(table_idx) | TableOperands::Elem(table_idx) => {
+                    format!("{} {}", op.name(), table_idx)
+                }
+                TableOperands::Init(x, y) | TableOperands::Copy(x, y) => {
+                    format!("{} {} {}", op.name(), x, y)
+                }
+            },
+            Instructions::MemoryInstructions(op, operands) => {
+                let mut text = op.name().to_string();
+                match operands {
+                    MemoryOperands::MemArg(mem_arg) => {
+                        if mem_arg.offset != 0 {
+                            text.push_str(&format!(" offset={}", mem_arg.offset));
+                        }
+                        if mem_arg.align != op.natural_align() {
+                            // the alignment is printed in bytes, it may be invalid in a malformed module.
+                            let align = 1u64.checked_shl(mem_arg.align).unwrap_or(0);
+                            text.push_str(&format!(" align={}", align));
+                        }
+                    }
+                    MemoryOperands::Data(data_idx) => text.push_str(&format!(" {}", data_idx)),
+                    MemoryOperands::None => (),
+                }
+                text
+            }
+            Instructions::NumericInstructions(op, primitive) => match primitive {
+                None => op.name().to_string(),
+                Some(Primitives::I32(value)) => format!("{} {}", op.name(), value),
+                Some(Primitives::I64(value)) => format!("{} {}", op.name(), value),
+                Some(Primitives::F32(value)) => format!("{} {}", op.name(), f32_text(*value)),
+                Some(Primitives::F64(value)) => format!("{} {}", op.name(), f64_text(*value)),
+            },
+            Instructions::VectorInstructions(op) => match *op {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::Encode;
+    use crate::text::parse;
+
+    #[test]
+    fn print_module_should_work() {
+        let module_bytes = [
+            0, 97, 115, 109, 1, 0, 0, 0, 0, 8, 4, 110, 97, 109, 101, 1, 2, 3, 1, 10, 2, 96, 2, 127,
+            127, 1, 127, 96, 0, 0, 2, 11, 1, 3, 101, 110, 118, 3, 108, 111, 103, 0, 1, 3, 3, 2, 0,
+            1, 4, 5, 1, 112, 1, 1, 1, 5, 3, 1, 0, 17, 6, 9, 1, 127, 1, 65, 128, 128, 192, 0, 11, 7,
+            16, 2, 3, 97, 100, 100, 0, 1, 6, 109, 101, 109, 111, 114, 121, 2, 0, 8, 1, 2, 9, 7, 1,
+            0, 65, 0, 11, 1, 1, 10, 12, 2, 7, 0, 32, 0, 32, 1, 106, 11, 2, 0, 11, 11, 8, 1, 0, 65,
+            16, 11, 2, 104, 105,
+        ];
+        let module = WasmModule::from_bytes(&module_bytes).unwrap();
+        // the name section is malformed, so it's ignored.
+        let expected = r#"(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (type (;1;) (func))
+  (import "env" "log" (func (;0;) (type 1)))
+  (func (;1;) (type 0) (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add
+  )
+  (func (;2;) (type 1)
+  )
+  (table (;0;) 1 1 funcref)
+  (memory (;0;) 17)
+  (global (;0;) (mut i32) (i32.const 1048576))
+  (export "add" (func 1))
+  (export "memory" (memory 0))
+  (start 2)
+  (elem (;0;) (i32.const 0) func 1)
+  (data (;0;) (i32.const 16) "hi")
+)
+"#;
+        assert_eq!(print(&module), expected);
+    }
+
+    #[test]
+    fn print_should_use_names_and_indent_blocks() {
+        let source = r#"
+            (module
+              (func (param i32) (result f64)
+                (local i64 i64)
+                (block (result f64)
+                  (if (local.get 0)
+                    (then (br 1 (f64.const -0x1p-10)))
+                    (else (loop (br_table 0 1 1 (local.get 0)))))
+                  (f64.const nan:0x4))
+                (drop (select (result f32) (f32.const -nan) (f32.const inf) (local.get 0)))
+                (i64.store8 offset=3 align=1 (i32.const 0) (local.get 2)))
+              (memory 1)
+              (data (i32.const 0) "\00a\"\\\7f"))
+        "#;
+        let mut module = parse(source).unwrap();
+        // function 0 is "$run", its param is "$n" and the second local is "$sum".
+        let names = [
+            1, 6, 1, 0, 3, 114, 117, 110, //
+            2, 11, 1, 0, 2, 0, 1, 110, 2, 3, 115, 117, 109,
+        ];
+        module.custom_sections.push(CustomSection::new(
+            NameSection::NAME.to_string(),
+            names.to_vec(),
+        ));
+
+        let expected = r#"(module
+  (type (;0;) (func (param i32) (result f64)))
+  (func $run (;0;) (type 0) (param $n i32) (result f64)
+    (local i64) (local $sum i64)
+    block (result f64)
+      local.get $n
+      if
+        f64.const -0.0009765625
+        br 1
+      else
+        loop
+          local.get $n
+          br_table 0 1 1
+        end
+      end
+      f64.const nan:0x4
+    end
+    f32.const -nan
+    f32.const inf
+    local.get $n
+    select (result f32)
+    drop
+    i32.const 0
+    local.get $sum
+    i64.store8 offset=3
+  )
+  (memory (;0;) 1)
+  (data (;0;) (i32.const 0) "\00a\"\\\7f")
+)
+"#;
+        let text = print(&module);
+        assert_eq!(text, expected);
+
+        // the printed text is parsed back to the same module.
+        let printed = parse(&text).unwrap();
+        module.custom_sections.clear();
+        assert_eq!(printed.to_bytes(), module.to_bytes());
+    }
+
+    #[test]
+    fn printed_segments_should_parse_back() {
+        let source = r#"
+            (table 2 funcref)
+            (table $t externref (elem (ref.null extern)))
+            (memory 1)
+            (func $f)
+            (elem (i32.const 1) func $f)
+            (elem (table $t) (i32.const 0) externref (ref.null extern))
+            (elem funcref (ref.func $f) (ref.null func))
+            (elem declare func $f)
+            (data "passive")
+            (data (memory 0) (i32.const 8) "active")
+        "#;
+        let module = parse(source).unwrap();
+        let text = print(&module);
+        // the segment of table abbreviation goes first.
+        assert!(text.contains("(elem (;0;) (table 1) (i32.const 0) externref (ref.null extern))"));
+        assert!(text.contains("(elem (;1;) (i32.const 1) func 0)"));
+        assert!(text.contains("(elem (;3;) funcref (ref.func 0) (ref.null func))"));
+        assert!(text.contains("(elem (;4;) declare func 0)"));
+        assert!(text.contains("(data (;0;) \"passive\")"));
+        assert_eq!(parse(&text).unwrap().to_bytes(), module.to_bytes());
+    }
+
+    #[test]
+    fn printed_globals_should_parse_back() {
+        let source = r#"
+            (global $a (import "m" "a") i32)
+            (import "m" "b" (global $b (mut f64)))
+            (global $c (export "c") i32 (global.get $a))
+        "#;
+        let module = parse(source).unwrap();
+        let text = print(&module);
+        assert!(text.contains("(import \"m\" \"b\" (global (;1;) (mut f64)))"));
+        assert!(text.contains("(global (;2;) i32 (global.get 0))"));
+        assert!(text.contains("(export \"c\" (global 2))"));
+        assert_eq!(parse(&text).unwrap().to_bytes(), module.to_bytes());
+    }
+
+    #[test]
+    fn printed_table_and_memory_imports_should_parse_back() {
+        let source = r#"
+            (import "env" "__indirect_function_table" (table $t 1 funcref))
+            (memory (import "env" "memory") 256 32768)
+            (table $u (export "u") 2 externref)
+            (elem (table $t) (i32.const 0) func $f)
+            (func $f)
+        "#;
+        let module = parse(source).unwrap();
+        let text = print(&module);
+        assert!(
+            text.contains("(import \"env\" \"__indirect_function_table\" (table (;0;) 1 funcref))")
+        );
+        assert!(text.contains("(import \"env\" \"memory\" (memory (;0;) 256 32768))"));
+        assert!(text.contains("(table (;1;) 2 externref)"));
+        assert!(text.contains("(export \"u\" (table 1))"));
+        assert_eq!(parse(&text).unwrap().to_bytes(), module.to_bytes());
+    }
+}
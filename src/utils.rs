@@ -1,44 +1,174 @@
-pub(crate) fn unsigned_leb128_decode(bytes: &[u8]) -> (usize, usize) {
-    let mut result = 0usize;
-    let mut shift = 0usize;
-    let mut offset = 0;
-    for i in bytes {
-        offset += 1;
-        let lower_7_bits = *i as usize & 0b0111_1111usize;
-        let highest_bit = (i >> 7) & 1;
-        result |= lower_7_bits << shift;
-        if highest_bit == 0 {
-            break;
-        }
+use crate::sections::Error;
+
+/// https://webassembly.github.io/spec/core/binary/values.html#integers
+/// Decode the leb128 number of `bits` bits starting at `bytes[offset]`,
+/// return the number and how many bytes it occupies.
+/// The signed number is sign extended to 64 bits, the caller truncates it to `bits`.
+fn leb128_decode(
+    bytes: &[u8],
+    offset: usize,
+    bits: u32,
+    signed: bool,
+) -> Result<(u64, usize), Error> {
+    // a number of N bits occupies ceil(N / 7) bytes at most.
+    let max_len = ((bits + 6) / 7) as usize;
+    let mut result = 0u64;
+    let mut shift = 0u32;
+    let mut count = 0;
+    loop {
+        let byte = *bytes.get(offset + count).ok_or(Error::UnexpectedEof {
+            offset: offset + count,
+        })?;
+        count += 1;
+        result |= ((byte & 0b0111_1111) as u64) << shift;
         shift += 7;
+
+        if byte & 0b1000_0000 == 0 {
+            if count == max_len {
+                // the unused bits of the last byte must be zeros, or the sign bits for signed number.
+                let used = bits - (shift - 7);
+                let unused = (byte & 0b0111_1111) >> used;
+                let expected = match signed && (byte >> (used - 1)) & 1 == 1 {
+                    true => 0b0111_1111 >> used,
+                    false => 0,
+                };
+                if unused != expected {
+                    return Err(Error::MalformedLeb128 { offset });
+                }
+            }
+            if signed && shift < 64 && byte & 0b0100_0000 != 0 {
+                result |= !0u64 << shift;
+            }
+            return Ok((result, count));
+        }
+
+        if count == max_len {
+            return Err(Error::MalformedLeb128 { offset });
+        }
     }
-    (result, offset)
 }
 
-pub(crate) fn signed_leb128_decode(bytes: &[u8]) -> (usize, usize) {
-    let mut result = 0usize;
-    let mut shift = 0usize;
-    let mut offset = 0;
-    for i in bytes {
-        offset += 1;
-        let lower_7_bits = *i as usize & 0b0111_1111usize;
-        let highest_bit = (i >> 7) & 1;
-        result |= lower_7_bits << shift;
-        if highest_bit == 0 {
-            break;
-        }
-        shift += 7;
+/// Decode the unsigned leb128 number starting at `bytes[offset]`,
+/// it's bounded by u32 like all the sizes and indices in module.
+/// Return the number and how many bytes it occupies.
+pub(crate) fn unsigned_leb128_decode(bytes: &[u8], offset: usize) -> Result<(usize, usize), Error> {
+    let (result, count) = leb128_decode(bytes, offset, 32, false)?;
+    Ok((result as usize, count))
+}
+
+/// Decode the signed leb128 number of `bits` bits starting at `bytes[offset]`,
+/// return the number and how many bytes it occupies.
+pub(crate) fn signed_leb128_decode(
+    bytes: &[u8],
+    offset: usize,
+    bits: u32,
+) -> Result<(i64, usize), Error> {
+    let (result, count) = leb128_decode(bytes, offset, bits, true)?;
+    Ok((result as i64, count))
+}
+
+pub(crate) fn read_i32(bytes: &[u8], offset: usize) -> Result<(i32, usize), Error> {
+    let (result, count) = signed_leb128_decode(bytes, offset, 32)?;
+    Ok((result as i32, offset + count))
+}
+
+pub(crate) fn read_i64(bytes: &[u8], offset: usize) -> Result<(i64, usize), Error> {
+    let (result, count) = signed_leb128_decode(bytes, offset, 64)?;
+    Ok((result, offset + count))
+}
+
+/// https://webassembly.github.io/spec/core/binary/values.html#floating-point
+/// Floats are little endian IEEE 754 bits, the NaN payloads are kept.
+pub(crate) fn read_f32(bytes: &[u8], offset: usize) -> Result<(f32, usize), Error> {
+    let (raw, offset) = read_bytes(bytes, offset, 4)?;
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(raw);
+    Ok((f32::from_bits(u32::from_le_bytes(buf)), offset))
+}
+
+pub(crate) fn read_f64(bytes: &[u8], offset: usize) -> Result<(f64, usize), Error> {
+    let (raw, offset) = read_bytes(bytes, offset, 8)?;
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(raw);
+    Ok((f64::from_bits(u64::from_le_bytes(buf)), offset))
+}
+
+/// Decode the unsigned leb128 number starting at `bytes[offset]`,
+/// return the number and the offset right after it.
+pub(crate) fn calc_len_and_offset(bytes: &[u8], offset: usize) -> Result<(usize, usize), Error> {
+    let (len, leb_offset) = unsigned_leb128_decode(bytes, offset)?;
+    Ok((len, offset + leb_offset))
+}
+
+pub(crate) fn read_u8(bytes: &[u8], offset: usize) -> Result<(u8, usize), Error> {
+    let byte = *bytes.get(offset).ok_or(Error::UnexpectedEof { offset })?;
+    Ok((byte, offset + 1))
+}
+
+/// Read `len` bytes starting at `bytes[offset]`.
+pub(crate) fn read_bytes(bytes: &[u8], offset: usize, len: usize) -> Result<(&[u8], usize), Error> {
+    let end = offset
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or(Error::UnexpectedEof {
+            offset: bytes.len(),
+        })?;
+    Ok((&bytes[offset..end], end))
+}
+
+/// https://webassembly.github.io/spec/core/binary/values.html#names
+pub(crate) fn read_name(bytes: &[u8], offset: usize) -> Result<(&str, usize), Error> {
+    let (len, name_offset) = calc_len_and_offset(bytes, offset)?;
+    let (name, offset) = read_bytes(bytes, name_offset, len)?;
+    let name = core::str::from_utf8(name).map_err(|e| Error::InvalidUtf8 {
+        offset: name_offset + e.valid_up_to(),
+    })?;
+    Ok((name, offset))
+}
+
+/// https://webassembly.github.io/spec/core/binary/conventions.html#vectors
+/// Read the count of items, then parse every item by `f`.
+pub(crate) fn read_vec<'a, T>(
+    bytes: &'a [u8],
+    offset: usize,
+    mut f: impl FnMut(&'a [u8], usize) -> Result<(T, usize), Error>,
+) -> Result<(Vec<T>, usize), Error> {
+    let (count, mut offset) = calc_len_and_offset(bytes, offset)?;
+    // every item occupies one byte at least, don't trust the count from untrusted bytes.
+    let mut items = Vec::with_capacity(count.min(bytes.len() - offset));
+    for _ in 0..count {
+        let (item, _offset) = f(bytes, offset)?;
+        items.push(item);
+        offset = _offset;
     }
-    (result, offset)
+    Ok((items, offset))
 }
 
-pub(crate) fn calc_len_and_offset(bytes: &[u8], offset: usize) -> (usize, &[u8]) {
-    // If first byte is bigger than 0x80(128, 0x1000_0000)
-    if bytes[0] < 0x80 {
-        (bytes[offset..][0] as usize, &bytes[1 + offset..])
+/// Split a section into the whole section bytes(id, size, content),
+/// return it with the offset of content. `offset` points to the section id.
+pub(crate) fn section_bytes(bytes: &[u8], offset: usize, id: u8) -> Result<(&[u8], usize), Error> {
+    let (section_id, _) = read_u8(bytes, offset)?;
+    if section_id != id {
+        return Err(Error::InvalidSectionId {
+            id: section_id,
+            offset,
+        });
+    }
+    let (content_length, content_offset) = calc_len_and_offset(bytes, offset + 1)?;
+    let (_, end) = read_bytes(bytes, content_offset, content_length)?;
+    Ok((&bytes[offset..end], content_offset))
+}
+
+/// Ensure the parser consumed exactly the bytes declared by the size.
+pub(crate) fn expect_end(expected_end: usize, offset: usize, start: usize) -> Result<(), Error> {
+    if offset == expected_end {
+        Ok(())
     } else {
-        let (len, leb_offset) = unsigned_leb128_decode(&bytes[offset..]);
-        (len, &bytes[offset + leb_offset..])
+        Err(Error::SectionSizeMismatch {
+            expected: expected_end - start,
+            actual: offset - start,
+            offset,
+        })
     }
 }
 
@@ -49,30 +179,146 @@ mod tests {
     #[test]
     fn count_zero_and_one() {
         let a = [0xfc, 0x06];
-        assert_eq!(unsigned_leb128_decode(&a), (892usize, 2));
-        assert_eq!(unsigned_leb128_decode(&[0x80, 0x7f]), (16256usize, 2));
-        assert_eq!(unsigned_leb128_decode(&[0xe0, 0x98, 0x17]), (380000usize, 3));
-        assert_eq!(unsigned_leb128_decode(&[0x0d]), (13usize, 1));
-        assert_eq!(unsigned_leb128_decode(&[128, 128, 192, 0]), (1048576usize, 4));
+        assert_eq!(unsigned_leb128_decode(&a, 0), Ok((892usize, 2)));
+        assert_eq!(
+            unsigned_leb128_decode(&[0x80, 0x7f], 0),
+            Ok((16256usize, 2))
+        );
+        assert_eq!(
+            unsigned_leb128_decode(&[0xe0, 0x98, 0x17], 0),
+            Ok((380000usize, 3))
+        );
+        assert_eq!(unsigned_leb128_decode(&[0x0d], 0), Ok((13usize, 1)));
+        assert_eq!(
+            unsigned_leb128_decode(&[128, 128, 192, 0], 0),
+            Ok((1048576usize, 4))
+        );
+    }
+
+    #[test]
+    fn truncated_or_too_long_leb128_should_fail() {
+        assert_eq!(
+            unsigned_leb128_decode(&[0x80, 0x80], 0),
+            Err(Error::UnexpectedEof { offset: 2 })
+        );
+        assert_eq!(
+            unsigned_leb128_decode(
+                &[0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+                1
+            ),
+            Err(Error::MalformedLeb128 { offset: 1 })
+        );
+    }
+
+    #[test]
+    fn unsigned_leb128_should_be_bounded_by_u32() {
+        assert_eq!(
+            unsigned_leb128_decode(&[0xff, 0xff, 0xff, 0xff, 0x0f], 0),
+            Ok((u32::MAX as usize, 5))
+        );
+        // 2^32 doesn't fit in u32.
+        assert_eq!(
+            unsigned_leb128_decode(&[0x80, 0x80, 0x80, 0x80, 0x10], 0),
+            Err(Error::MalformedLeb128 { offset: 0 })
+        );
+        // redundant zero bytes are allowed within the length limit.
+        assert_eq!(
+            unsigned_leb128_decode(&[0x83, 0x80, 0x80, 0x80, 0x00], 0),
+            Ok((3, 5))
+        );
+    }
+
+    #[test]
+    fn signed_leb128_should_work() {
+        assert_eq!(read_i32(&[0x84, 0x79], 0), Ok((-892, 2)));
+        assert_eq!(read_i32(&[0x7f], 0), Ok((-1, 1)));
+        assert_eq!(read_i32(&[0x3f], 0), Ok((63, 1)));
+        assert_eq!(read_i32(&[0xc0, 0x00], 0), Ok((64, 2)));
+        assert_eq!(
+            read_i32(&[0x80, 0x80, 0x80, 0x80, 0x78], 0),
+            Ok((i32::MIN, 5))
+        );
+        assert_eq!(
+            read_i32(&[0xff, 0xff, 0xff, 0xff, 0x07], 0),
+            Ok((i32::MAX, 5))
+        );
+        assert_eq!(
+            read_i64(
+                &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f],
+                0
+            ),
+            Ok((i64::MIN, 10))
+        );
+        assert_eq!(
+            signed_leb128_decode(&[0xff, 0xff, 0xff, 0xff, 0x0f], 0, 33),
+            Ok((u32::MAX as i64, 5))
+        );
+    }
+
+    #[test]
+    fn signed_leb128_overflow_should_fail() {
+        // the unused bits are not the sign extension.
+        assert_eq!(
+            read_i32(&[0xff, 0xff, 0xff, 0xff, 0x0f], 0),
+            Err(Error::MalformedLeb128 { offset: 0 })
+        );
+        assert_eq!(
+            read_i32(&[0x80, 0x80, 0x80, 0x80, 0x70], 0),
+            Err(Error::MalformedLeb128 { offset: 0 })
+        );
+        // too long.
+        assert_eq!(
+            read_i32(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x7f], 0),
+            Err(Error::MalformedLeb128 { offset: 0 })
+        );
+        assert_eq!(
+            read_i64(
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+                0
+            ),
+            Err(Error::MalformedLeb128 { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn floats_should_be_bit_exact() {
+        let (v, offset) = read_f32(&[0x01, 0x00, 0xc0, 0x7f], 0).unwrap();
+        assert_eq!((v.to_bits(), offset), (0x7fc0_0001, 4));
+        let (v, offset) = read_f64(&[0, 0, 0, 0, 0, 0, 0xf0, 0x3f], 0).unwrap();
+        assert_eq!((v, offset), (1.0, 8));
+        assert_eq!(
+            read_f64(&[0, 0, 0], 0),
+            Err(Error::UnexpectedEof { offset: 3 })
+        );
     }
 
     #[test]
     fn calc_len_and_offset_should_work() {
         let a = [6, 3, 4, 5, 4, 1, 8, 9];
-        let (len, _a) = calc_len_and_offset(&a, 0);
+        let (len, offset) = calc_len_and_offset(&a, 0).unwrap();
 
         assert_eq!(len, a[0] as usize);
-        assert_eq!(_a[..len], a[1..1 + len]);
+        assert_eq!(a[offset..offset + len], a[1..1 + len]);
     }
 
     #[test]
-    fn test_leb1288() {
-        use leb128;
-        use std::io::*;
+    fn read_name_should_work() {
+        assert_eq!(read_name(&[2, 104, 105, 0], 0), Ok(("hi", 3)));
+        assert_eq!(
+            read_name(&[2, 104, 0xff], 0),
+            Err(Error::InvalidUtf8 { offset: 2 })
+        );
+        assert_eq!(
+            read_name(&[3, 104, 105], 0),
+            Err(Error::UnexpectedEof { offset: 3 })
+        );
+    }
 
+    #[test]
+    fn test_leb1288() {
         let mut buf = [0; 5];
         let mut writable = &mut buf[..];
-        let s = leb128::write::signed(&mut writable, -892);
-        dbg!(&buf);
+        let written = leb128::write::signed(&mut writable, -892).unwrap();
+        assert_eq!(&buf[..written], &[0x84, 0x79]);
     }
 }
@@ -0,0 +1,54 @@
+use thiserror::Error;
+
+/// Errors while validating a module, the messages start with the ones of the official spec tests.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("type mismatch")]
+    TypeMismatch,
+    #[error("unknown type {0}")]
+    UnknownType(u32),
+    #[error("unknown function {0}")]
+    UnknownFunction(u32),
+    #[error("unknown table {0}")]
+    UnknownTable(u32),
+    #[error("unknown memory {0}")]
+    UnknownMemory(u32),
+    #[error("unknown global {0}")]
+    UnknownGlobal(u32),
+    #[error("unknown local {0}")]
+    UnknownLocal(u32),
+    #[error("unknown label {0}")]
+    UnknownLabel(u32),
+    #[error("unknown elem segment {0}")]
+    UnknownElement(u32),
+    #[error("unknown data segment {0}")]
+    UnknownData(u32),
+    #[error("data count section required")]
+    DataCountRequired,
+    #[error("global is immutable {0}")]
+    ImmutableGlobal(u32),
+    #[error("alignment must not be larger than natural")]
+    InvalidAlignment,
+    #[error("memory size must be at most 65536 pages (4GiB)")]
+    MemorySizeTooLarge,
+    #[error("size minimum must not be greater than maximum")]
+    InvalidLimits,
+    #[error("multiple memories")]
+    MultipleMemories,
+    #[error("constant expression required")]
+    ConstantExpressionRequired,
+    #[error("start function {0}")]
+    InvalidStartFunction(u32),
+    #[error("duplicate export name {0}")]
+    DuplicateExportName(String),
+    #[error("undeclared function reference {0}")]
+    UndeclaredFunctionReference(u32),
+    #[error("unsupported {0}")]
+    Unsupported(&'static str),
+    /// The error happened in the body of function.
+    #[error("{error} in function {index}")]
+    Function {
+        index: u32,
+        error: Box<ValidationError>,
+    },
+}
@@ -0,0 +1,750 @@
+//! https://webassembly.github.io/spec/core/appendix/algorithm.html
+//! The validation algorithm of function bodies.
+
+use super::*;
+
+const I32: ValueType = ValueType::NumType(NumberType::I32);
+const I64: ValueType = ValueType::NumType(NumberType::I64);
+const F32: ValueType = ValueType::NumType(NumberType::F32);
+const F64: ValueType = ValueType::NumType(NumberType::F64);
+
+/// A block, loop, if or the function body itself.
+#[derive(Clone, Debug)]
+struct ControlFrame {
+    opcode: ControlInstructions,
+    start_types: Vec<ValueType>,
+    end_types: Vec<ValueType>,
+    /// The height of operand stack when entering this frame.
+    height: usize,
+    /// The rest of this frame is unreachable, the operand stack is polymorphic.
+    unreachable: bool,
+}
+
+impl ControlFrame {
+    /// The types a branch to this frame carries.
+    fn label_types(&self) -> &[ValueType] {
+        match self.opcode {
+            ControlInstructions::Loop => &self.start_types,
+            _ => &self.end_types,
+        }
+    }
+}
+
+/// `None` is the unknown type, it matches any type.
+type Operand = Option<ValueType>;
+
+pub(crate) struct FuncValidator<'c> {
+    context: &'c Context,
+    /// Params go first, then the declared locals.
+    locals: Vec<ValueType>,
+    results: Vec<ValueType>,
+    vals: Vec<Operand>,
+    ctrls: Vec<ControlFrame>,
+}
+
+impl<'c> FuncValidator<'c> {
+    pub fn new(context: &'c Context, func_idx: u32, code: &Code) -> Result<Self, ValidationError> {
+        let func_type = context.func(func_idx)?;
+        let mut locals = func_type.param_types.clone();
+        for local in &code.locals {
+            locals.extend(core::iter::repeat(local._type).take(local.count as usize));
+        }
+        Ok(Self {
+            context,
+            locals,
+            results: func_type.return_types.clone(),
+            vals: vec![],
+            ctrls: vec![],
+        })
+    }
+
+    /// Validate the instructions of body, the last `end` is excluded.
+    pub fn validate(mut self, instructions: &[Instructions]) -> Result<(), ValidationError> {
+        let results = self.results.clone();
+        self.push_ctrl(ControlInstructions::Block, vec![], results);
+        for instruction in instructions {
+            self.validate_instruction(instruction)?;
+        }
+        // the frame of function body must be the only one left.
+        if self.ctrls.len() != 1 {
+            return Err(ValidationError::TypeMismatch);
+        }
+        self.pop_ctrl()?;
+        Ok(())
+    }
+
+    fn push_val(&mut self, val: Operand) {
+        self.vals.push(val);
+    }
+
+    fn pop_val(&mut self) -> Result<Operand, ValidationError> {
+        let frame = self.ctrls.last().ok_or(ValidationError::TypeMismatch)?;
+        if self.vals.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(ValidationError::TypeMismatch);
+        }
+        self.vals.pop().ok_or(ValidationError::TypeMismatch)
+    }
+
+    fn pop_expect(&mut self, expected: ValueType) -> Result<Operand, ValidationError> {
+        let actual = self.pop_val()?;
+        match actual {
+            Some(actual) if actual != expected => Err(ValidationError::TypeMismatch),
+            _ => Ok(actual),
+        }
+    }
+
+    fn push_vals(&mut self, types: &[ValueType]) {
+        self.vals.extend(types.iter().map(|ty| Some(*ty)));
+    }
+
+    fn pop_vals(&mut self, types: &[ValueType]) -> Result<Vec<Operand>, ValidationError> {
+        let mut popped = vec![None; types.len()];
+        for (i, ty) in types.iter().enumerate().rev() {
+            popped[i] = self.pop_expect(*ty)?;
+        }
+        Ok(popped)
+    }
+
+    fn push_ctrl(
+        &mut self,
+        opcode: ControlInstructions,
+        start_types: Vec<ValueType>,
+        end_types: Vec<ValueType>,
+    ) {
+        let height = self.vals.len();
+        self.push_vals(&start_types);
+        self.ctrls.push(ControlFrame {
+            opcode,
+            start_types,
+            end_types,
+            height,
+            unreachable: false,
+        });
+    }
+
+    fn pop_ctrl(&mut self) -> Result<ControlFrame, ValidationError> {
+        let frame = self
+            .ctrls
+            .last()
+            .ok_or(ValidationError::TypeMismatch)?
+            .clone();
+        self.pop_vals(&frame.end_types)?;
+        if self.vals.len() != frame.height {
+            return Err(ValidationError::TypeMismatch);
+        }
+        self.ctrls.pop();
+        Ok(frame)
+    }
+
+    fn unreachable(&mut self) -> Result<(), ValidationError> {
+        let frame = self.ctrls.last_mut().ok_or(ValidationError::TypeMismatch)?;
+        self.vals.truncate(frame.height);
+        frame.unreachable = true;
+        Ok(())
+    }
+
+    /// The frame of label, 0 is the innermost one.
+    fn label(&self, depth: LabelIndex) -> Result<&ControlFrame, ValidationError> {
+        let depth = depth as usize;
+        if depth >= self.ctrls.len() {
+            return Err(ValidationError::UnknownLabel(depth as u32));
+        }
+        Ok(&self.ctrls[self.ctrls.len() - 1 - depth])
+    }
+
+    fn block_type(
+        &self,
+        block_type: &BlockType,
+    ) -> Result<(Vec<ValueType>, Vec<ValueType>), ValidationError> {
+        match block_type {
+            BlockType::Empty => Ok((vec![], vec![])),
+            BlockType::Value(ty) => Ok((vec![], vec![*ty])),
+            BlockType::TypeIndex(type_idx) => {
+                let func_type = self.context.func_type(*type_idx)?;
+                Ok((
+                    func_type.param_types.clone(),
+                    func_type.return_types.clone(),
+                ))
+            }
+        }
+    }
+
+    fn local(&self, local_idx: u32) -> Result<ValueType, ValidationError> {
+        self.locals
+            .get(local_idx as usize)
+            .copied()
+            .ok_or(ValidationError::UnknownLocal(local_idx))
+    }
+
+    fn validate_instruction(&mut self, instruction: &Instructions) -> Result<(), ValidationError> {
+        match instruction {
+            Instructions::ControlInstructions(op, operands) => self.validate_control(*op, operands),
+            Instructions::ReferenceInstructions(op, operands) => {
+                self.validate_reference(*op, operands)
+            }
+            Instructions::ParametricInstructions(op, types) => self.validate_parametric(*op, types),
+            Instructions::VariableInstructions(op, index) => self.validate_variable(*op, *index),
+            Instructions::TableInstructions(op, operands) => self.validate_table(*op, *operands),
+            Instructions::MemoryInstructions(op, operands) => self.validate_memory(*op, operands),
+            Instructions::NumericInstructions(op, _) => self.validate_numeric(*op),
+            Instructions::VectorInstructions(_) => {
+                Err(ValidationError::Unsupported("vector instruction"))
+            }
+        }
+    }
+
+    fn validate_control(
+        &mut self,
+        op: ControlInstructions,
+        operands: &ControlOperands,
+    ) -> Result<(), ValidationError> {
+        match (op, operands) {
+            (ControlInstructions::Unreachable, _) => self.unreachable()?,
+            (ControlInstructions::Nop, _) => (),
+            (
+                ControlInstructions::Block | ControlInstructions::Loop,
+                ControlOperands::Block(block_type),
+            ) => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_vals(&params)?;
+                self.push_ctrl(op, params, results);
+            }
+            (ControlInstructions::If, ControlOperands::Block(block_type)) => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_expect(I32)?;
+                self.pop_vals(&params)?;
+                self.push_ctrl(op, params, results);
+            }
+            (ControlInstructions::Else, _) => {
+                let frame = self.pop_ctrl()?;
+                if frame.opcode != ControlInstructions::If {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                self.push_ctrl(
+                    ControlInstructions::Else,
+                    frame.start_types,
+                    frame.end_types,
+                );
+            }
+            (ControlInstructions::End, _) => {
+                // the frame of function body is closed by the last `end`, which is excluded.
+                if self.ctrls.len() == 1 {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                let frame = self.pop_ctrl()?;
+                // `if` without `else` must leave the same types as its params.
+                if frame.opcode == ControlInstructions::If && frame.start_types != frame.end_types {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                self.push_vals(&frame.end_types);
+            }
+            (ControlInstructions::Br, ControlOperands::Label(depth)) => {
+                let types = self.label(*depth)?.label_types().to_vec();
+                self.pop_vals(&types)?;
+                self.unreachable()?;
+            }
+            (ControlInstructions::BrIf, ControlOperands::Label(depth)) => {
+                self.pop_expect(I32)?;
+                let types = self.label(*depth)?.label_types().to_vec();
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+            }
+            (ControlInstructions::BrTable, ControlOperands::BrTable(labels, default)) => {
+                self.pop_expect(I32)?;
+                let default_types = self.label(*default)?.label_types().to_vec();
+                for depth in labels {
+                    let types = self.label(*depth)?.label_types().to_vec();
+                    if types.len() != default_types.len() {
+                        return Err(ValidationError::TypeMismatch);
+                    }
+                    let popped = self.pop_vals(&types)?;
+                    self.vals.extend(popped);
+                }
+                self.pop_vals(&default_types)?;
+                self.unreachable()?;
+            }
+            (ControlInstructions::Return, _) => {
+                let results = self.results.clone();
+                self.pop_vals(&results)?;
+                self.unreachable()?;
+            }
+            (ControlInstructions::Call, ControlOperands::Func(func_idx)) => {
+                let func_type = self.context.func(*func_idx)?.clone();
+                self.pop_vals(&func_type.param_types)?;
+                self.push_vals(&func_type.return_types);
+            }
+            (
+                ControlInstructions::CallIndirect,
+                ControlOperands::CallIndirect(type_idx, table_idx),
+            ) => {
+                let table = self.context.table(*table_idx)?;
+                if table.ref_type != ReferenceType::FuncRef {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                let func_type = self.context.func_type(*type_idx)?.clone();
+                self.pop_expect(I32)?;
+                self.pop_vals(&func_type.param_types)?;
+                self.push_vals(&func_type.return_types);
+            }
+            _ => {
+                return Err(ValidationError::Unsupported(
+                    "malformed control instruction",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_reference(
+        &mut self,
+        op: ReferenceInstructions,
+        operands: &ReferenceOperands,
+    ) -> Result<(), ValidationError> {
+        match (op, operands) {
+            (ReferenceInstructions::RefNull, ReferenceOperands::RefType(ref_type)) => {
+                self.push_val(Some(ValueType::RefType(*ref_type)));
+            }
+            (ReferenceInstructions::RefIsNull, _) => {
+                match self.pop_val()? {
+                    None | Some(ValueType::RefType(_)) => (),
+                    _ => return Err(ValidationError::TypeMismatch),
+                }
+                self.push_val(Some(I32));
+            }
+            (ReferenceInstructions::RefFunc, ReferenceOperands::Func(func_idx)) => {
+                self.context.func(*func_idx)?;
+                if !self.context.refs.contains(func_idx) {
+                    return Err(ValidationError::UndeclaredFunctionReference(*func_idx));
+                }
+                self.push_val(Some(ValueType::RefType(ReferenceType::FuncRef)));
+            }
+            _ => {
+                return Err(ValidationError::Unsupported(
+                    "malformed reference instruction",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_parametric(
+        &mut self,
+        op: ParametricInstructions,
+        types: &[ValueType],
+    ) -> Result<(), ValidationError> {
+        match op {
+            ParametricInstructions::Drop => {
+                self.pop_val()?;
+            }
+            ParametricInstructions::Select => {
+                self.pop_expect(I32)?;
+                let first = self.pop_val()?;
+                let second = self.pop_val()?;
+                // select without types only works with numbers.
+                let is_num =
+                    |operand: Operand| matches!(operand, None | Some(ValueType::NumType(_)));
+                if !is_num(first) || !is_num(second) {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                if first.is_some() && second.is_some() && first != second {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                self.push_val(first.or(second));
+            }
+            ParametricInstructions::SelectV => {
+                let ty = match types {
+                    [ty] => *ty,
+                    _ => return Err(ValidationError::TypeMismatch),
+                };
+                self.pop_expect(I32)?;
+                self.pop_expect(ty)?;
+                self.pop_expect(ty)?;
+                self.push_val(Some(ty));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_variable(
+        &mut self,
+        op: VariableInstructions,
+        index: u32,
+    ) -> Result<(), ValidationError> {
+        match op {
+            VariableInstructions::LocalGet => {
+                let ty = self.local(index)?;
+                self.push_val(Some(ty));
+            }
+            VariableInstructions::LocalSet => {
+                let ty = self.local(index)?;
+                self.pop_expect(ty)?;
+            }
+            VariableInstructions::LocalTee => {
+                let ty = self.local(index)?;
+                self.pop_expect(ty)?;
+                self.push_val(Some(ty));
+            }
+            VariableInstructions::GlobalGet => {
+                let ty = self.context.global(index)?.val_type;
+                self.push_val(Some(ty));
+            }
+            VariableInstructions::GlobalSet => {
+                let global = self.context.global(index)?;
+                if global.mutable != Mutable::Yes {
+                    return Err(ValidationError::ImmutableGlobal(index));
+                }
+                let ty = global.val_type;
+                self.pop_expect(ty)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_table(
+        &mut self,
+        op: TableInstructions,
+        operands: TableOperands,
+    ) -> Result<(), ValidationError> {
+        let table_idx = match operands {
+            TableOperands::Table(table_idx) => table_idx,
+            TableOperands::Init(table_idx, elem_idx) => {
+                if self.context.table(table_idx)?.ref_type != self.context.elem(elem_idx)? {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                table_idx
+            }
+            TableOperands::Elem(elem_idx) => {
+                self.context.elem(elem_idx)?;
+                return Ok(());
+            }
+            TableOperands::Copy(dst, src) => {
+                if self.context.table(dst)?.ref_type != self.context.table(src)?.ref_type {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                dst
+            }
+        };
+        let ty = ValueType::RefType(self.context.table(table_idx)?.ref_type);
+        match op {
+            TableInstructions::TableGet => {
+                self.pop_expect(I32)?;
+                self.push_val(Some(ty));
+            }
+            TableInstructions::TableSet => {
+                self.pop_expect(ty)?;
+                self.pop_expect(I32)?;
+            }
+            TableInstructions::TableGrow => {
+                self.pop_expect(I32)?;
+                self.pop_expect(ty)?;
+                self.push_val(Some(I32));
+            }
+            TableInstructions::TableSize => self.push_val(Some(I32)),
+            TableInstructions::TableInit | TableInstructions::TableCopy => {
+                self.pop_expect(I32)?;
+                self.pop_expect(I32)?;
+                self.pop_expect(I32)?;
+            }
+            TableInstructions::ElemDrop => {}
+            TableInstructions::TableFill => {
+                self.pop_expect(I32)?;
+                self.pop_expect(ty)?;
+                self.pop_expect(I32)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_memory(
+        &mut self,
+        op: MemoryInstructions,
+        operands: &MemoryOperands,
+    ) -> Result<(), ValidationError> {
+        use MemoryInstructions::*;
+
+        if let (Drop, MemoryOperands::Data(data_idx)) = (op, operands) {
+            return self.context.data(*data_idx);
+        }
+        self.context.mem(0)?;
+        let mem_arg = match operands {
+            MemoryOperands::MemArg(mem_arg) => *mem_arg,
+            MemoryOperands::Data(data_idx) => {
+                self.context.data(*data_idx)?;
+                MemArg::default()
+            }
+            MemoryOperands::None => MemArg::default(),
+        };
+        // (the value type, how many bytes are accessed, whether it's a load)
+        let (ty, width, is_load) = match op {
+            Size => {
+                self.push_val(Some(I32));
+                return Ok(());
+            }
+            Grow => {
+                self.pop_expect(I32)?;
+                self.push_val(Some(I32));
+                return Ok(());
+            }
+            Init | Copy | Fill => {
+                self.pop_vals(&[I32, I32, I32])?;
+                return Ok(());
+            }
+            Drop => return Err(ValidationError::TypeMismatch),
+            I32Load => (I32, 4, true),
+            I64Load => (I64, 8, true),
+            F32Load => (F32, 4, true),
+            F64Load => (F64, 8, true),
+            I32Load8Signed | I32Load8Unsigned => (I32, 1, true),
+            I32Load16Signed | I32Load16Unsigned => (I32, 2, true),
+            I64Load8Signed | I64Load8Unsigned => (I64, 1, true),
+            I64Load16Signed | I64Load16Unsigned => (I64, 2, true),
+            I64Load32Signed | I64Load32Unsigned => (I64, 4, true),
+            I32Store => (I32, 4, false),
+            I64Store => (I64, 8, false),
+            F32Store => (F32, 4, false),
+            F64Store => (F64, 8, false),
+            I32Store8 => (I32, 1, false),
+            I32Store16 => (I32, 2, false),
+            I64Store8 => (I64, 1, false),
+            I64Store16 => (I64, 2, false),
+            I64Store32 => (I64, 4, false),
+        };
+
+        // the alignment is the exponent of 2.
+        if mem_arg.align >= 32 || 1u32 << mem_arg.align > width {
+            return Err(ValidationError::InvalidAlignment);
+        }
+        if is_load {
+            self.pop_expect(I32)?;
+            self.push_val(Some(ty));
+        } else {
+            self.pop_expect(ty)?;
+            self.pop_expect(I32)?;
+        }
+        Ok(())
+    }
+
+    fn validate_numeric(&mut self, op: NumericInstructions) -> Result<(), ValidationError> {
+        let (params, result) = numeric_signature(op);
+        self.pop_vals(params)?;
+        self.push_val(Some(result));
+        Ok(())
+    }
+}
+
+/// The params and result of numeric instructions.
+fn numeric_signature(op: NumericInstructions) -> (&'static [ValueType], ValueType) {
+    use NumericInstructions::*;
+
+    match op {
+        I32Const => (&[], I32),
+        I64Const => (&[], I64),
+        F32Const => (&[], F32),
+        F64Const => (&[], F64),
+
+        I32Eqz => (&[I32], I32),
+        I32Eq | I32Ne | I32LtSigned | I32LtUnsigned | I32GtSigned | I32GtUnsigned | I32LeSigned
+        | I32LeUnsigned | I32GeSigned | I32GeUnsigned => (&[I32, I32], I32),
+        I64Eqz => (&[I64], I32),
+        I64Eq | I64Ne | I64LtSigned | I64LtUnsigned | I64GtSigned | I64GtUnsigned | I64LeSigned
+        | I64LeUnsigned | I64GeSigned | I64GeUnsigned => (&[I64, I64], I32),
+        F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge => (&[F32, F32], I32),
+        F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge => (&[F64, F64], I32),
+
+        I32Clz | I32Ctz | I32PopCnt => (&[I32], I32),
+        I32Add | I32Sub | I32Mul | I32DivSigned | I32DivUnsigned | I32RemSigned
+        | I32RemUnsigned | I32And | I32Or | I32Xor | I32Shl | I32ShrSigned | I32ShrUnsigned
+        | I32Rotl | I32Rotr => (&[I32, I32], I32),
+        I64Clz | I64Ctz | I64PopCnt => (&[I64], I64),
+        I64Add | I64Sub | I64Mul | I64DivSigned | I64DivUnsigned | I64RemSigned
+        | I64RemUnsigned | I64And | I64Or | I64Xor | I64Shl | I64ShrSigned | I64ShrUnsigned
+        | I64Rotl | I64Rotr => (&[I64, I64], I64),
+        F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt => (&[F32], F32),
+        F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32CopySign => (&[F32, F32], F32),
+        F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => (&[F64], F64),
+        F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Mx | F64CopySign => (&[F64, F64], F64),
+
+        I32WrapI64 => (&[I64], I32),
+        I32TruncF32Signed | I32TruncF32Unsigned | I32ReinterpretF32 => (&[F32], I32),
+        I32TruncF64Signed | I32TruncF64Unsigned => (&[F64], I32),
+        I64ExtendI32Signed | I64ExtendI32Unsigned => (&[I32], I64),
+        I64TruncF32Signed | I64TruncF32Unsigned => (&[F32], I64),
+        I64TruncF64Signed | I64TruncF64Unsigned | I64ReinterpretF64 => (&[F64], I64),
+        F32ConvertI32Signed | F32ConvertI32Unsigned | F32ReinterpretI32 => (&[I32], F32),
+        F32ConvertI64Signed | F32ConvertI64Unsigned => (&[I64], F32),
+        F32DemoteF64 => (&[F64], F32),
+        F64ConvertI32Signed | F64ConvertI32Unsigned => (&[I32], F64),
+        F64ConvertI64Signed | F64ConvertI64Unsigned | F64ReinterpretI64 => (&[I64], F64),
+        F64PromoteF32 => (&[F32], F64),
+
+        I32Extend8Signed | I32Extend16Signed => (&[I32], I32),
+        I64Extend8Signed | I64Extend16Signed | I64Extend32Signed => (&[I64], I64),
+
+        I32TruncSatF32Signed | I32TruncSatF32Unsigned => (&[F32], I32),
+        I32TruncSatF64Signed | I32TruncSatF64Unsigned => (&[F64], I32),
+        I64TruncSatF32Signed | I64TruncSatF32Unsigned => (&[F32], I64),
+        I64TruncSatF64Signed | I64TruncSatF64Unsigned => (&[F64], I64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validate the module, return the error inside the function.
+    fn validate_text(source: &str) -> Result<(), ValidationError> {
+        validate(&crate::text::parse(source).unwrap()).map_err(|error| match error {
+            ValidationError::Function { error, .. } => *error,
+            error => error,
+        })
+    }
+
+    fn check(cases: &[(&str, Result<(), ValidationError>)]) {
+        for (source, expected) in cases {
+            assert_eq!(&validate_text(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn unreachable_stack_should_be_polymorphic() {
+        check(&[
+            ("(func (result i32) unreachable i32.add)", Ok(())),
+            ("(func (result i32 i64) unreachable)", Ok(())),
+            ("(func (result i32) (br 0 (i32.const 1)) (i32.eqz))", Ok(())),
+            ("(func (result i32) (return (i32.const 1)) (drop))", Ok(())),
+            // the known operands are still checked.
+            (
+                "(func (result i32) unreachable (i64.const 0) i32.add)",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result i32) unreachable (i64.const 0))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // only the rest of the current block is unreachable.
+            (
+                "(func (result i32) (block unreachable) i32.eqz)",
+                Err(ValidationError::TypeMismatch),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn br_table_should_check_arity() {
+        check(&[
+            (
+                "(func (result i32) (block (result i32) (i32.const 1) (i32.const 0) (br_table 0 1 0)))",
+                Ok(()),
+            ),
+            (
+                "(func (param i32) (block (result i32) (i32.const 1) (local.get 0) (br_table 0 1)) drop)",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (block (result i64) (block (result i32) (i32.const 1) (i32.const 0) (br_table 0 1)) drop) drop)",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // the labels of an unreachable br_table only need the same arity.
+            (
+                "(func (block (result i64) (block (result i32) unreachable (br_table 0 1)) drop (i64.const 0)) drop)",
+                Ok(()),
+            ),
+            (
+                "(func (block (result i32) (block unreachable (br_table 0 1)) drop))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (block (br_table 0 1 (i32.const 0))))",
+                Ok(()),
+            ),
+            (
+                "(func (block (br_table 0 2 (i32.const 0))))",
+                Err(ValidationError::UnknownLabel(2)),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn blocks_should_take_params() {
+        check(&[
+            (
+                "(func (result i32) (i32.const 1) (block (param i32) (result i32) (i32.const 2) i32.add))",
+                Ok(()),
+            ),
+            // the params are popped when entering the block.
+            (
+                "(func (result i32) (block (param i32) (result i32) (i32.const 2) i32.add))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result i32) (i64.const 1) (block (param i32) (result i32)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // the operands outside the block can't be used inside.
+            (
+                "(func (result i32) (i32.const 1) (block (result i32) (i32.eqz)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // branching to a loop takes its params.
+            (
+                "(func (param i32) (i32.const 1) (loop (param i32) (br_if 0 (local.get 0)) drop))",
+                Ok(()),
+            ),
+            (
+                "(func (param i32) (i32.const 1) (loop (param i32) (drop) (br_if 0 (local.get 0))))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result i32) (i32.const 1) (i32.const 0) (if (param i32) (result i32) (then) (else (i32.eqz))))",
+                Ok(()),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn select_should_check_types() {
+        check(&[
+            (
+                "(func (result i64) (select (i64.const 1) (i64.const 2) (i32.const 0)))",
+                Ok(()),
+            ),
+            (
+                "(func (result i32) (select (i32.const 1) (i64.const 2) (i32.const 0)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result i32) (select (i32.const 1) (i32.const 2) (i64.const 0)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // references need the typed select.
+            (
+                "(func (result externref) (select (ref.null extern) (ref.null extern) (i32.const 0)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result externref) (select (result externref) (ref.null extern) (ref.null extern) (i32.const 0)))",
+                Ok(()),
+            ),
+            (
+                "(func (result funcref) (select (result funcref) (ref.null extern) (ref.null func) (i32.const 0)))",
+                Err(ValidationError::TypeMismatch),
+            ),
+            // the type comes from the known operand on polymorphic stack.
+            (
+                "(func (result f32) unreachable (f32.const 0) (i32.const 0) select)",
+                Ok(()),
+            ),
+            (
+                "(func (result i32) unreachable (f32.const 0) (i32.const 0) select)",
+                Err(ValidationError::TypeMismatch),
+            ),
+            (
+                "(func (result i32) unreachable (ref.null func) (i32.const 0) select)",
+                Err(ValidationError::TypeMismatch),
+            ),
+        ]);
+    }
+}
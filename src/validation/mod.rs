@@ -0,0 +1,514 @@
+//! https://webassembly.github.io/spec/core/valid/index.html
+//! Check a decoded module is well-typed before instantiating it.
+
+use std::collections::HashSet;
+
+use crate::instructions::*;
+use crate::sections::*;
+
+pub mod errors;
+pub mod func;
+
+pub use self::errors::ValidationError;
+use self::func::FuncValidator;
+
+/// The max pages of a memory, 4GiB in total.
+pub const MAX_PAGES: u32 = 65536;
+
+/// https://webassembly.github.io/spec/core/valid/conventions.html#contexts
+/// All index spaces of a module.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Context {
+    pub types: Vec<FunctionType>,
+    /// The type index of every function, imported functions go first.
+    pub funcs: Vec<TypeIndex>,
+    pub tables: Vec<TableType>,
+    pub mems: Vec<Limits>,
+    /// The type of every global, imported globals go first.
+    pub globals: Vec<GlobalType>,
+    /// The count of imported globals, only they can be read by constant expressions.
+    pub imported_globals: usize,
+    /// The functions which can be referenced by `ref.func` in function bodies.
+    pub refs: HashSet<u32>,
+    /// The reference type of every element segment.
+    pub elems: Vec<ReferenceType>,
+    /// The count of data segments, only known if the data count section is present.
+    pub data_count: Option<u32>,
+}
+
+impl Context {
+    fn new(module: &WasmModule) -> Self {
+        let mut context = Self {
+            types: module.func_types().to_vec(),
+            ..Default::default()
+        };
+
+        if let Some(section) = &module.import_section {
+            for description in &section.desc {
+                match &description.desc {
+                    ImportDesc::Func(type_idx) => context.funcs.push(*type_idx),
+                    ImportDesc::Table(table_type) => context.tables.push(table_type.clone()),
+                    ImportDesc::Memory(limits) => context.mems.push(limits.clone()),
+                    ImportDesc::Global(global_type) => context.globals.push(*global_type),
+                }
+            }
+        }
+        context.imported_globals = context.globals.len();
+        if let Some(section) = &module.function_section {
+            context.funcs.extend(
+                section
+                    .type_idx
+                    .iter()
+                    .map(|type_idx| *type_idx as TypeIndex),
+            );
+        }
+        if let Some(section) = &module.table_section {
+            context.tables.extend(section.table_types.iter().cloned());
+        }
+        if let Some(section) = &module.memory_section {
+            context.mems.push(section.limits.clone());
+        }
+        if let Some(section) = &module.global_section {
+            context
+                .globals
+                .extend(section.globals.iter().map(|global| global.global_type));
+        }
+
+        context.data_count = module
+            .data_count_section
+            .as_ref()
+            .map(|section| section.count);
+
+        // the functions referenced outside of function bodies are declared.
+        if let Some(section) = &module.element_section {
+            for element in &section.elem {
+                context
+                    .refs
+                    .extend(element.funcs().iter().map(|func_idx| *func_idx as u32));
+                context.elems.push(element.ref_type);
+            }
+        }
+        if let Some(section) = &module.global_section {
+            for global in &section.globals {
+                for instruction in &global.expressions.instructions {
+                    if let Instructions::ReferenceInstructions(
+                        _,
+                        ReferenceOperands::Func(func_idx),
+                    ) = instruction
+                    {
+                        context.refs.insert(*func_idx);
+                    }
+                }
+            }
+        }
+        if let Some(section) = &module.export_section {
+            for export in &section.exports {
+                if export.desc.export_type == ExportType::Func {
+                    context.refs.insert(export.desc.index as u32);
+                }
+            }
+        }
+        context
+    }
+
+    pub fn func_type(&self, type_idx: TypeIndex) -> Result<&FunctionType, ValidationError> {
+        self.types
+            .get(type_idx as usize)
+            .ok_or(ValidationError::UnknownType(type_idx))
+    }
+
+    /// The signature of the function.
+    pub fn func(&self, func_idx: u32) -> Result<&FunctionType, ValidationError> {
+        let type_idx = self
+            .funcs
+            .get(func_idx as usize)
+            .ok_or(ValidationError::UnknownFunction(func_idx))?;
+        self.func_type(*type_idx)
+    }
+
+    pub fn table(&self, table_idx: u32) -> Result<&TableType, ValidationError> {
+        self.tables
+            .get(table_idx as usize)
+            .ok_or(ValidationError::UnknownTable(table_idx))
+    }
+
+    pub fn mem(&self, mem_idx: u32) -> Result<&Limits, ValidationError> {
+        self.mems
+            .get(mem_idx as usize)
+            .ok_or(ValidationError::UnknownMemory(mem_idx))
+    }
+
+    pub fn elem(&self, elem_idx: u32) -> Result<ReferenceType, ValidationError> {
+        self.elems
+            .get(elem_idx as usize)
+            .copied()
+            .ok_or(ValidationError::UnknownElement(elem_idx))
+    }
+
+    /// Data segments can only be referenced in function bodies with the data count section.
+    pub fn data(&self, data_idx: u32) -> Result<(), ValidationError> {
+        match self.data_count {
+            None => Err(ValidationError::DataCountRequired),
+            Some(count) if data_idx >= count => Err(ValidationError::UnknownData(data_idx)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn global(&self, global_idx: u32) -> Result<&GlobalType, ValidationError> {
+        self.globals
+            .get(global_idx as usize)
+            .ok_or(ValidationError::UnknownGlobal(global_idx))
+    }
+}
+
+/// https://webassembly.github.io/spec/core/valid/types.html#limits
+fn validate_limits(limits: &Limits, bound: Option<u32>) -> Result<(), ValidationError> {
+    let min = limits.min.unwrap_or(0);
+    if let Some(bound) = bound {
+        if min > bound || limits.max.map_or(false, |max| max > bound) {
+            return Err(ValidationError::MemorySizeTooLarge);
+        }
+    }
+    if limits.max.map_or(false, |max| min > max) {
+        return Err(ValidationError::InvalidLimits);
+    }
+    Ok(())
+}
+
+/// https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+/// Return the type of the constant expression.
+fn validate_const(context: &Context, expr: &Expressions) -> Result<ValueType, ValidationError> {
+    match expr.instructions[..] {
+        [Instructions::NumericInstructions(op, Some(_))] => Ok(match op {
+            NumericInstructions::I32Const => ValueType::NumType(NumberType::I32),
+            NumericInstructions::I64Const => ValueType::NumType(NumberType::I64),
+            NumericInstructions::F32Const => ValueType::NumType(NumberType::F32),
+            _ => ValueType::NumType(NumberType::F64),
+        }),
+        [Instructions::ReferenceInstructions(_, ReferenceOperands::RefType(ref_type))] => {
+            Ok(ValueType::RefType(ref_type))
+        }
+        [Instructions::ReferenceInstructions(_, ReferenceOperands::Func(func_idx))] => {
+            context.func(func_idx)?;
+            Ok(ValueType::RefType(ReferenceType::FuncRef))
+        }
+        [Instructions::VariableInstructions(VariableInstructions::GlobalGet, global_idx)] => {
+            if global_idx as usize >= context.imported_globals {
+                return Err(ValidationError::UnknownGlobal(global_idx));
+            }
+            let global = context.global(global_idx)?;
+            if global.mutable != Mutable::No {
+                return Err(ValidationError::ConstantExpressionRequired);
+            }
+            Ok(global.val_type)
+        }
+        _ => Err(ValidationError::ConstantExpressionRequired),
+    }
+}
+
+fn expect_type(actual: ValueType, expected: ValueType) -> Result<(), ValidationError> {
+    if actual != expected {
+        return Err(ValidationError::TypeMismatch);
+    }
+    Ok(())
+}
+
+/// https://webassembly.github.io/spec/core/valid/modules.html#valid-module
+pub fn validate(module: &WasmModule) -> Result<(), ValidationError> {
+    let context = Context::new(module);
+
+    for type_idx in &context.funcs {
+        context.func_type(*type_idx)?;
+    }
+    for table in &context.tables {
+        validate_limits(&table.limits, None)?;
+    }
+    for limits in &context.mems {
+        validate_limits(limits, Some(MAX_PAGES))?;
+    }
+    if context.mems.len() > 1 {
+        return Err(ValidationError::MultipleMemories);
+    }
+
+    if let Some(section) = &module.global_section {
+        for global in &section.globals {
+            let ty = validate_const(&context, &global.expressions)?;
+            expect_type(ty, global.global_type.val_type)?;
+        }
+    }
+
+    if let Some(section) = &module.element_section {
+        for element in &section.elem {
+            if let ElementMode::Active { table_idx, offset } = &element.mode {
+                let table = context.table(*table_idx as u32)?;
+                if table.ref_type != element.ref_type {
+                    return Err(ValidationError::TypeMismatch);
+                }
+                let ty = validate_const(&context, offset)?;
+                expect_type(ty, ValueType::NumType(NumberType::I32))?;
+            }
+            match &element.init {
+                ElementItems::Functions(funcs) => {
+                    for func_idx in funcs {
+                        context.func(*func_idx as u32)?;
+                    }
+                }
+                ElementItems::Expressions(exprs) => {
+                    for expr in exprs {
+                        let ty = validate_const(&context, expr)?;
+                        expect_type(ty, ValueType::RefType(element.ref_type))?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(section) = &module.data_section {
+        for segment in &section.segments {
+            if let DataMode::Active { mem_idx, offset } = &segment.mode {
+                context.mem(*mem_idx as u32)?;
+                let ty = validate_const(&context, offset)?;
+                expect_type(ty, ValueType::NumType(NumberType::I32))?;
+            }
+        }
+    }
+
+    if let Some(section) = &module.start_section {
+        let func_idx = section.func_idx as u32;
+        let func_type = context.func(func_idx)?;
+        if !func_type.param_types.is_empty() || !func_type.return_types.is_empty() {
+            return Err(ValidationError::InvalidStartFunction(func_idx));
+        }
+    }
+
+    if let Some(section) = &module.export_section {
+        let mut names = HashSet::new();
+        for export in &section.exports {
+            if !names.insert(export.name.as_str()) {
+                return Err(ValidationError::DuplicateExportName(export.name.clone()));
+            }
+            let index = export.desc.index as u32;
+            match export.desc.export_type {
+                ExportType::Func => context.func(index).map(|_| ())?,
+                ExportType::Table => context.table(index).map(|_| ())?,
+                ExportType::Mem => context.mem(index).map(|_| ())?,
+                ExportType::Global => context.global(index).map(|_| ())?,
+            }
+        }
+    }
+
+    // the imported functions go first in the index space.
+    let imported = context.funcs.len() - module.code_section.as_ref().map_or(0, |s| s.codes.len());
+    if let Some(section) = &module.code_section {
+        for (i, code) in section.codes.iter().enumerate() {
+            let index = (imported + i) as u32;
+            FuncValidator::new(&context, index, code)
+                .and_then(|validator| validator.validate(&code.expr.instructions))
+                .map_err(|error| ValidationError::Function {
+                    index,
+                    error: Box::new(error),
+                })?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOID: [u8; 3] = [0x60, 0, 0];
+    const RETURN_I32: [u8; 4] = [0x60, 0, 1, 0x7f];
+
+    fn section(id: u8, content: &[u8]) -> Vec<u8> {
+        [&[id, content.len() as u8][..], content].concat()
+    }
+
+    /// A module with one function whose body is `body`(the last `end` included),
+    /// a memory, an immutable i32 global and a mutable i32 global.
+    fn module_with(func_type: &[u8], body: &[u8], extra: &[u8]) -> Vec<u8> {
+        let code = [&[body.len() as u8 + 1, 0][..], body].concat();
+        [
+            &[0, 97, 115, 109, 1, 0, 0, 0][..],
+            &section(1, &[&[1][..], func_type].concat()),
+            &section(3, &[1, 0]),
+            &section(5, &[1, 0, 1]),
+            &section(6, &[2, 0x7f, 0, 0x41, 0, 0x0b, 0x7f, 1, 0x41, 0, 0x0b]),
+            extra,
+            &section(10, &[&[1][..], &code].concat()),
+        ]
+        .concat()
+    }
+
+    /// Validate the function body, return the error inside the function.
+    fn validate_body(func_type: &[u8], body: &[u8]) -> Result<(), ValidationError> {
+        let bytes = module_with(func_type, body, &[]);
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        validate(&module).map_err(|error| match error {
+            ValidationError::Function { index: 0, error } => *error,
+            error => error,
+        })
+    }
+
+    #[test]
+    fn valid_bodies_should_pass() {
+        let bodies: [(&[u8], &[u8]); 7] = [
+            // i32.const 1 i32.const 2 i32.add
+            (&RETURN_I32, &[0x41, 1, 0x41, 2, 0x6a, 0x0b]),
+            // block (result i32) i32.const 1 end
+            (&RETURN_I32, &[0x02, 0x7f, 0x41, 1, 0x0b, 0x0b]),
+            // loop br_if 0 (i32.const 0) end i32.const 1
+            (
+                &RETURN_I32,
+                &[0x03, 0x40, 0x41, 0, 0x0d, 0, 0x0b, 0x41, 1, 0x0b],
+            ),
+            // unreachable makes the stack polymorphic: unreachable i32.add
+            (&RETURN_I32, &[0x00, 0x6a, 0x0b]),
+            // if (result i32) i32.const 1 else i32.const 2 end, the condition is global 0
+            (
+                &RETURN_I32,
+                &[0x23, 0, 0x04, 0x7f, 0x41, 1, 0x05, 0x41, 2, 0x0b, 0x0b],
+            ),
+            // select (i32.const 1) (i32.const 2) (i32.const 0)
+            (&RETURN_I32, &[0x41, 1, 0x41, 2, 0x41, 0, 0x1b, 0x0b]),
+            // block br_table 0 1 (i32.const 0) end, store to memory then set global 1
+            (
+                &VOID,
+                &[
+                    0x02, 0x40, 0x41, 0, 0x0e, 1, 0, 1, 0x0b, 0x41, 0, 0x41, 1, 0x36, 2, 0, 0x41,
+                    1, 0x24, 1, 0x0b,
+                ],
+            ),
+        ];
+        for (func_type, body) in bodies {
+            assert_eq!(validate_body(func_type, body), Ok(()));
+        }
+    }
+
+    #[test]
+    fn invalid_bodies_should_fail() {
+        let bodies: [(&[u8], &[u8], ValidationError); 9] = [
+            // returns nothing
+            (&RETURN_I32, &[0x0b], ValidationError::TypeMismatch),
+            // i64.const 0 for i32 result
+            (&RETURN_I32, &[0x42, 0, 0x0b], ValidationError::TypeMismatch),
+            // i32.add with one operand
+            (
+                &RETURN_I32,
+                &[0x41, 1, 0x6a, 0x0b],
+                ValidationError::TypeMismatch,
+            ),
+            // too many values left
+            (&VOID, &[0x41, 1, 0x0b], ValidationError::TypeMismatch),
+            (
+                &VOID,
+                &[0x20, 0, 0x1a, 0x0b],
+                ValidationError::UnknownLocal(0),
+            ),
+            (&VOID, &[0x0c, 1, 0x0b], ValidationError::UnknownLabel(1)),
+            (
+                &VOID,
+                &[0x41, 0, 0x24, 0, 0x0b],
+                ValidationError::ImmutableGlobal(0),
+            ),
+            // i32.load with 8 bytes alignment
+            (
+                &RETURN_I32,
+                &[0x41, 0, 0x28, 3, 0, 0x0b],
+                ValidationError::InvalidAlignment,
+            ),
+            // if (result i32) without else
+            (
+                &RETURN_I32,
+                &[0x41, 0, 0x04, 0x7f, 0x41, 1, 0x0b, 0x0b],
+                ValidationError::TypeMismatch,
+            ),
+        ];
+        for (func_type, body, error) in bodies {
+            assert_eq!(validate_body(func_type, body), Err(error));
+        }
+
+        assert_eq!(
+            validate_body(&VOID, &[0x10, 5, 0x0b]),
+            Err(ValidationError::UnknownFunction(5))
+        );
+    }
+
+    #[test]
+    fn invalid_module_should_fail() {
+        // export function 0 twice
+        let exports = section(7, &[2, 1, b'f', 0, 0, 1, b'f', 0, 0]);
+        let bytes = module_with(&VOID, &[0x0b], &exports);
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            validate(&module),
+            Err(ValidationError::DuplicateExportName("f".to_string()))
+        );
+
+        // the start function must be [] -> []
+        let start = section(8, &[0]);
+        let bytes = module_with(&RETURN_I32, &[0x41, 0, 0x0b], &start);
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            validate(&module),
+            Err(ValidationError::InvalidStartFunction(0))
+        );
+
+        // (memory 65537)
+        let bytes = [0, 97, 115, 109, 1, 0, 0, 0, 5, 5, 1, 0, 0x81, 0x80, 0x04];
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        assert_eq!(validate(&module), Err(ValidationError::MemorySizeTooLarge));
+    }
+
+    #[test]
+    fn global_initializers_should_only_read_immutable_imports() {
+        // (import "m" "g" (global i32)) (import "m" "mg" (global (mut i32)))
+        let imports = section(
+            2,
+            &[
+                2, 1, b'm', 1, b'g', 3, 0x7f, 0, 1, b'm', 2, b'm', b'g', 3, 0x7f, 1,
+            ],
+        );
+        let module_with_global = |init: &[u8]| {
+            let global = section(6, &[&[1, 0x7f, 0][..], init, &[0x0b]].concat());
+            let bytes = [&[0, 97, 115, 109, 1, 0, 0, 0][..], &imports, &global].concat();
+            validate(&WasmModule::from_bytes(&bytes).unwrap())
+        };
+
+        assert_eq!(module_with_global(&[0x23, 0]), Ok(()));
+        assert_eq!(
+            module_with_global(&[0x23, 1]),
+            Err(ValidationError::ConstantExpressionRequired)
+        );
+        // the global itself is not imported.
+        assert_eq!(
+            module_with_global(&[0x23, 2]),
+            Err(ValidationError::UnknownGlobal(2))
+        );
+    }
+
+    #[test]
+    fn ref_func_should_be_declared_outside_of_bodies() {
+        fn validate_text(source: &str) -> Result<(), ValidationError> {
+            validate(&crate::text::parse(source).unwrap()).map_err(|error| match error {
+                ValidationError::Function { error, .. } => *error,
+                error => error,
+            })
+        }
+
+        let body = "(func (result funcref) (ref.func $f)) (func $f)";
+        assert_eq!(
+            validate_text(body),
+            Err(ValidationError::UndeclaredFunctionReference(1))
+        );
+        let declarations = [
+            "(elem declare func $f)",
+            "(export \"f\" (func $f))",
+            "(global funcref (ref.func $f))",
+        ];
+        for declaration in declarations {
+            assert_eq!(validate_text(&format!("{} {}", body, declaration)), Ok(()));
+        }
+    }
+}
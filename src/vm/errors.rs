@@ -0,0 +1,39 @@
+use super::*;
+use crate::validation::ValidationError;
+use thiserror::Error;
+
+/// Errors while instantiating a module, the messages follow the official spec tests.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum InstantiationError {
+    #[error("invalid module: {0}")]
+    Invalid(#[from] ValidationError),
+    #[error("unknown import {module}.{name}")]
+    UnknownImport { module: String, name: String },
+    #[error("incompatible import type for {module}.{name}: expected {expected}, got {actual}")]
+    IncompatibleImportType {
+        module: String,
+        name: String,
+        expected: Box<ExternType>,
+        actual: Box<ExternType>,
+    },
+    /// The initial size of memory or table is refused by the resource limiter.
+    #[error("{0} exceeds the resource limits")]
+    ResourceLimitExceeded(&'static str),
+    /// The start function or the initialization of segments trapped.
+    #[error("{0}")]
+    Trap(#[from] Trap),
+}
+
+/// Errors while looking up an exported function with a static signature.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum TypedFuncError {
+    /// No function is exported by the name.
+    #[error("unknown export {0}")]
+    UnknownExport(String),
+    #[error("signature mismatch for {name}: expected {}, got {}", ExternType::Func(.expected.clone()), ExternType::Func(.actual.clone()))]
+    SignatureMismatch {
+        name: String,
+        expected: FunctionType,
+        actual: FunctionType,
+    },
+}
@@ -0,0 +1,134 @@
+use super::*;
+
+/// An instruction without its operands, the key of fuel costs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Control(ControlInstructions),
+    Reference(ReferenceInstructions),
+    Parametric(ParametricInstructions),
+    Variable(VariableInstructions),
+    Table(TableInstructions),
+    Memory(MemoryInstructions),
+    Numeric(NumericInstructions),
+    Vector(VectorInstructions),
+}
+
+impl From<&Instructions> for Opcode {
+    fn from(instruction: &Instructions) -> Self {
+        match instruction {
+            Instructions::ControlInstructions(op, _) => Opcode::Control(*op),
+            Instructions::ReferenceInstructions(op, _) => Opcode::Reference(*op),
+            Instructions::ParametricInstructions(op, _) => Opcode::Parametric(*op),
+            Instructions::VariableInstructions(op, _) => Opcode::Variable(*op),
+            Instructions::TableInstructions(op, _) => Opcode::Table(*op),
+            Instructions::MemoryInstructions(op, _) => Opcode::Memory(*op),
+            Instructions::NumericInstructions(op, _) => Opcode::Numeric(*op),
+            Instructions::VectorInstructions(op) => Opcode::Vector(*op),
+        }
+    }
+}
+
+macro_rules! opcode_from {
+    ($($ty: ty => $variant: ident),*) => {
+        $(impl From<$ty> for Opcode {
+            fn from(op: $ty) -> Self {
+                Opcode::$variant(op)
+            }
+        })*
+    };
+}
+
+opcode_from!(
+    ControlInstructions => Control,
+    ReferenceInstructions => Reference,
+    ParametricInstructions => Parametric,
+    VariableInstructions => Variable,
+    TableInstructions => Table,
+    MemoryInstructions => Memory,
+    NumericInstructions => Numeric,
+    VectorInstructions => Vector
+);
+
+/// How much fuel each instruction consumes, every instruction costs 1 unless it's set.
+#[derive(Clone, Debug)]
+pub struct FuelCosts {
+    default: u64,
+    costs: HashMap<Opcode, u64>,
+}
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self {
+            default: 1,
+            costs: HashMap::new(),
+        }
+    }
+}
+
+impl FuelCosts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cost of instructions which are not set.
+    pub fn set_default(&mut self, cost: u64) -> &mut Self {
+        self.default = cost;
+        self
+    }
+
+    pub fn set(&mut self, op: impl Into<Opcode>, cost: u64) -> &mut Self {
+        self.costs.insert(op.into(), cost);
+        self
+    }
+
+    pub fn cost(&self, instruction: &Instructions) -> u64 {
+        self.costs
+            .get(&Opcode::from(instruction))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// The fuel state of an [`Instance`], shared by its clones.
+#[derive(Debug, Default)]
+pub(crate) struct Metering {
+    /// `None` means unlimited, the running calls consume it in place.
+    pub fuel: Rc<Cell<Option<u64>>>,
+    pub costs: Rc<FuelCosts>,
+    /// The call which ran out of fuel, it continues after refueling.
+    pub suspended: Option<WasmVM>,
+    /// How many calls are running, host functions may call the instance again.
+    pub active: usize,
+}
+
+impl Metering {
+    pub fn new(fuel: Option<u64>, costs: Rc<FuelCosts>) -> Self {
+        Self {
+            fuel: Rc::new(Cell::new(fuel)),
+            costs,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuel_costs_should_be_configurable() {
+        let mut costs = FuelCosts::new();
+        costs
+            .set(NumericInstructions::I32DivSigned, 10)
+            .set(ControlInstructions::Call, 5);
+        let div = Instructions::NumericInstructions(NumericInstructions::I32DivSigned, None);
+        let call =
+            Instructions::ControlInstructions(ControlInstructions::Call, ControlOperands::Func(0));
+        let add = Instructions::NumericInstructions(NumericInstructions::I32Add, None);
+        assert_eq!(costs.cost(&div), 10);
+        assert_eq!(costs.cost(&call), 5);
+        assert_eq!(costs.cost(&add), 1);
+        costs.set_default(2);
+        assert_eq!(costs.cost(&add), 2);
+    }
+}
@@ -0,0 +1,143 @@
+use super::*;
+use crate::sections::Code;
+use std::collections::HashMap;
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#function-instances
+#[derive(Clone)]
+pub struct Func(pub(crate) Rc<FuncInstance>);
+
+/// The signature of host functions, the arguments are checked before calling,
+/// and the results are checked after returning.
+pub type HostFunc = dyn Fn(&[Value]) -> Result<Vec<Value>, Trap>;
+
+pub(crate) enum FuncInstance {
+    Wasm {
+        func_type: FunctionType,
+        /// The module instance which defines this function.
+        instance: Weak<ModuleInstance>,
+        body: Rc<FunctionBody>,
+    },
+    Host {
+        func_type: FunctionType,
+        callback: Box<HostFunc>,
+    },
+}
+
+impl Func {
+    /// Define a host function with the signature.
+    pub fn new<F>(func_type: FunctionType, callback: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    {
+        Func(Rc::new(FuncInstance::Host {
+            func_type,
+            callback: Box::new(callback),
+        }))
+    }
+
+    pub fn func_type(&self) -> &FunctionType {
+        match &*self.0 {
+            FuncInstance::Wasm { func_type, .. } | FuncInstance::Host { func_type, .. } => {
+                func_type
+            }
+        }
+    }
+
+    /// The instance which defines this function, `None` for host functions.
+    pub(crate) fn instance(&self) -> Option<Rc<ModuleInstance>> {
+        match &*self.0 {
+            FuncInstance::Wasm { instance, .. } => instance.upgrade(),
+            FuncInstance::Host { .. } => None,
+        }
+    }
+}
+
+/// Two references are equal only if they point to the same function instance.
+impl PartialEq for Func {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::fmt::Debug for Func {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let kind = match &*self.0 {
+            FuncInstance::Wasm { .. } => "wasm",
+            FuncInstance::Host { .. } => "host",
+        };
+        f.debug_struct("Func")
+            .field("kind", &kind)
+            .field("func_type", self.func_type())
+            .finish()
+    }
+}
+
+/// A function body ready to run: the locals declared by the function(params excluded),
+/// the instructions without the last `end`, and where every structured instruction ends.
+#[derive(Clone, Debug)]
+pub struct FunctionBody {
+    pub locals: Vec<ValueType>,
+    pub instructions: Vec<Instructions>,
+    // the position of block/loop/if/else => the position of its `end`
+    ends: HashMap<usize, usize>,
+    // the position of if => the position of its `else`
+    elses: HashMap<usize, usize>,
+}
+
+impl FunctionBody {
+    pub fn new(locals: Vec<ValueType>, instructions: Vec<Instructions>) -> Self {
+        let mut ends = HashMap::new();
+        let mut elses = HashMap::new();
+        // the positions of block/loop/if which are not closed yet.
+        let mut blocks: Vec<usize> = vec![];
+        for (pc, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Instructions::ControlInstructions(
+                    ControlInstructions::Block
+                    | ControlInstructions::Loop
+                    | ControlInstructions::If,
+                    _,
+                ) => blocks.push(pc),
+                Instructions::ControlInstructions(ControlInstructions::Else, _) => {
+                    if let Some(start) = blocks.last() {
+                        elses.insert(*start, pc);
+                    }
+                }
+                Instructions::ControlInstructions(ControlInstructions::End, _) => {
+                    if let Some(start) = blocks.pop() {
+                        ends.insert(start, pc);
+                        if let Some(else_pc) = elses.get(&start) {
+                            ends.insert(*else_pc, pc);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Self {
+            locals,
+            instructions,
+            ends,
+            elses,
+        }
+    }
+
+    /// Prepare a function body decoded from code section.
+    pub fn from_code(code: &Code) -> Self {
+        let locals = code
+            .locals
+            .iter()
+            .flat_map(|locals| core::iter::repeat(locals._type).take(locals.count as usize))
+            .collect();
+        Self::new(locals, code.expr.instructions.clone())
+    }
+
+    pub(crate) fn end_of(&self, pc: usize) -> Option<usize> {
+        self.ends.get(&pc).copied()
+    }
+
+    pub(crate) fn else_of(&self, pc: usize) -> Option<usize> {
+        self.elses.get(&pc).copied()
+    }
+}
@@ -0,0 +1,144 @@
+use super::*;
+use crate::sections::{GlobalType, Mutable};
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#global-instances
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct GlobalInstance {
+    global_type: GlobalType,
+    value: Value,
+}
+
+/// A reference to global instance, a mutable global is shared by every instance importing it.
+#[derive(Clone)]
+pub struct Global(pub(crate) Rc<RefCell<GlobalInstance>>);
+
+impl Global {
+    /// Allocate a global, the value must match the type.
+    pub fn new(global_type: GlobalType, value: Value) -> Result<Self, Trap> {
+        if value.value_type() != global_type.val_type {
+            return Err(Trap::TypeMismatch);
+        }
+        Ok(Self(Rc::new(RefCell::new(GlobalInstance {
+            global_type,
+            value,
+        }))))
+    }
+
+    pub fn global_type(&self) -> GlobalType {
+        self.0.borrow().global_type
+    }
+
+    pub fn get(&self) -> Value {
+        self.0.borrow().value.clone()
+    }
+
+    /// Replace the value of a mutable global.
+    pub fn set(&self, value: Value) -> Result<(), Trap> {
+        if self.global_type().mutable != Mutable::Yes {
+            return Err(Trap::ImmutableGlobal);
+        }
+        self.set_unchecked(value)
+    }
+
+    /// The mutability is checked by validation for `global.set`, only the type is checked here.
+    pub(crate) fn set_unchecked(&self, value: Value) -> Result<(), Trap> {
+        let mut global = self.0.borrow_mut();
+        if value.value_type() != global.global_type.val_type {
+            return Err(Trap::TypeMismatch);
+        }
+        global.value = value;
+        Ok(())
+    }
+}
+
+/// Two references are equal only if they point to the same global instance.
+impl PartialEq for Global {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::fmt::Debug for Global {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let global = self.0.borrow();
+        f.debug_struct("Global")
+            .field("global_type", &global.global_type)
+            .field("value", &global.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse;
+
+    const I32: ValueType = ValueType::NumType(NumberType::I32);
+
+    #[test]
+    fn global_should_check_type_and_mutability() {
+        let immutable = GlobalType {
+            val_type: I32,
+            mutable: Mutable::No,
+        };
+        assert!(Global::new(immutable, Value::I64(1)).is_err());
+        let global = Global::new(immutable, Value::I32(1)).unwrap();
+        assert_eq!(global.set(Value::I32(2)), Err(Trap::ImmutableGlobal));
+        assert_eq!(global.get(), Value::I32(1));
+
+        let global = Global::new(
+            GlobalType {
+                mutable: Mutable::Yes,
+                ..immutable
+            },
+            Value::I32(1),
+        )
+        .unwrap();
+        assert_eq!(global.set(Value::F32(2.0)), Err(Trap::TypeMismatch));
+        global.set(Value::I32(2)).unwrap();
+        assert_eq!(global.get(), Value::I32(2));
+    }
+
+    #[test]
+    fn mutable_globals_should_be_shared() {
+        let counter = r#"
+            (module
+              (global $count (export "count") (mut i32) (i32.const 0))
+              (global (export "base") i32 (i32.const 40))
+              (func (export "inc") (result i32)
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (global.get $count)))
+        "#;
+        let counter = Instance::new(&parse(counter).unwrap(), &[]).unwrap();
+        let count = counter.get_global("count").unwrap();
+
+        let user = r#"
+            (module
+              (import "counter" "count" (global $count (mut i32)))
+              (global $base (import "counter" "base") i32)
+              (global $start i32 (global.get $base))
+              (func (export "add") (result i32)
+                (global.set $count (i32.add (global.get $count) (global.get $start)))
+                (global.get $count)))
+        "#;
+        let user = parse(user).unwrap();
+        let imports = [
+            Extern::Global(count.clone()),
+            counter.export("base").cloned().unwrap(),
+        ];
+        let user = Instance::new(&user, &imports).unwrap();
+
+        assert_eq!(counter.invoke("inc", &[]), Ok(vec![Value::I32(1)]));
+        assert_eq!(user.invoke("add", &[]), Ok(vec![Value::I32(41)]));
+        assert_eq!(counter.invoke("inc", &[]), Ok(vec![Value::I32(42)]));
+        count.set(Value::I32(0)).unwrap();
+        assert_eq!(user.invoke("add", &[]), Ok(vec![Value::I32(40)]));
+
+        // the mutability must match exactly.
+        let module = parse(r#"(import "m" "g" (global i32))"#).unwrap();
+        assert!(matches!(
+            Instance::new(&module, &[Extern::Global(count)]).unwrap_err(),
+            InstantiationError::IncompatibleImportType { .. }
+        ));
+    }
+}
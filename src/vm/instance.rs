@@ -0,0 +1,839 @@
+use super::*;
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#module-instances
+/// Everything a running function can access.
+#[derive(Debug, Default)]
+pub struct ModuleInstance {
+    pub func_types: Vec<FunctionType>,
+    pub funcs: RefCell<Vec<Func>>,
+    pub globals: RefCell<Vec<Global>>,
+    pub memory: RefCell<Option<Memory>>,
+    pub tables: RefCell<Vec<Table>>,
+    /// The references of every element segment, they're empty once the segment is dropped.
+    pub elems: RefCell<Vec<Vec<Value>>>,
+    /// The bytes of every data segment, they're empty once the segment is dropped.
+    pub datas: RefCell<Vec<Vec<u8>>>,
+    /// The functions only hold weak references to their instances,
+    /// so the instances which define the imported items are kept alive here.
+    pub(crate) dependencies: RefCell<Vec<Rc<ModuleInstance>>>,
+}
+
+impl ModuleInstance {
+    pub fn new(func_types: Vec<FunctionType>) -> Rc<Self> {
+        Rc::new(Self {
+            func_types,
+            ..Default::default()
+        })
+    }
+
+    /// Define a function in this instance, it can be called by the returned index.
+    pub fn push_function(self: &Rc<Self>, type_idx: u32, body: FunctionBody) -> Result<Func, Trap> {
+        let func_type = self.func_type(type_idx)?.clone();
+        let func = Func(Rc::new(FuncInstance::Wasm {
+            func_type,
+            instance: Rc::downgrade(self),
+            body: Rc::new(body),
+        }));
+        self.funcs.borrow_mut().push(func.clone());
+        Ok(func)
+    }
+
+    /// Keep the instance which defines the function alive as long as this one.
+    fn depend_on(&self, func: &Func) {
+        if let Some(owner) = func.instance() {
+            let mut dependencies = self.dependencies.borrow_mut();
+            if !dependencies
+                .iter()
+                .any(|instance| Rc::ptr_eq(instance, &owner))
+            {
+                dependencies.push(owner);
+            }
+        }
+    }
+
+    pub fn func(&self, index: u32) -> Result<Func, Trap> {
+        self.funcs
+            .borrow()
+            .get(index as usize)
+            .cloned()
+            .ok_or(Trap::UnknownIndex {
+                kind: "function",
+                index,
+            })
+    }
+
+    pub fn table(&self, index: u32) -> Result<Table, Trap> {
+        self.tables
+            .borrow()
+            .get(index as usize)
+            .cloned()
+            .ok_or(Trap::UnknownIndex {
+                kind: "table",
+                index,
+            })
+    }
+
+    pub fn global(&self, index: u32) -> Result<Global, Trap> {
+        self.globals
+            .borrow()
+            .get(index as usize)
+            .cloned()
+            .ok_or(Trap::UnknownIndex {
+                kind: "global",
+                index,
+            })
+    }
+
+    pub fn memory(&self) -> Result<Memory, Trap> {
+        self.memory.borrow().clone().ok_or(Trap::UnknownIndex {
+            kind: "memory",
+            index: 0,
+        })
+    }
+
+    pub fn func_type(&self, index: u32) -> Result<&FunctionType, Trap> {
+        self.func_types
+            .get(index as usize)
+            .ok_or(Trap::UnknownIndex {
+                kind: "type",
+                index,
+            })
+    }
+}
+
+/// Evaluate a constant expression, like the initializer of global, the offset of segments.
+fn eval_const(instance: &ModuleInstance, expr: &Expressions) -> Result<Value, Trap> {
+    match expr.instructions.first() {
+        Some(Instructions::NumericInstructions(_, Some(primitive))) => Ok((*primitive).into()),
+        Some(Instructions::ReferenceInstructions(_, ReferenceOperands::RefType(ref_type))) => {
+            Value::default_of(ValueType::RefType(*ref_type)).ok_or(Trap::TypeMismatch)
+        }
+        Some(Instructions::ReferenceInstructions(_, ReferenceOperands::Func(func_idx))) => {
+            Ok(Value::FuncRef(Some(instance.func(*func_idx)?)))
+        }
+        Some(Instructions::VariableInstructions(VariableInstructions::GlobalGet, global_idx)) => {
+            Ok(instance.global(*global_idx)?.get())
+        }
+        _ => Err(Trap::Unsupported("constant expression")),
+    }
+}
+
+/// The offset of active segments, an i32 treated as unsigned.
+fn eval_offset(instance: &ModuleInstance, expr: &Expressions) -> Result<u32, Trap> {
+    match eval_const(instance, expr)? {
+        Value::I32(offset) => Ok(offset as u32),
+        _ => Err(Trap::TypeMismatch),
+    }
+}
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#external-values
+/// What can be imported into or exported from an instance.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Extern {
+    Func(Func),
+    Table(Table),
+    Memory(Memory),
+    Global(Global),
+}
+
+impl Extern {
+    /// The type of the value now, the minimum of table or memory is its current size.
+    pub fn ty(&self) -> ExternType {
+        let limits = |min, max: Option<u32>| Limits {
+            tag: if max.is_some() { Tag::One } else { Tag::Zero },
+            min: Some(min),
+            max,
+        };
+        match self {
+            Extern::Func(func) => ExternType::Func(func.func_type().clone()),
+            Extern::Table(table) => ExternType::Table(TableType {
+                ref_type: table.ref_type(),
+                limits: limits(table.size(), table.max()),
+            }),
+            Extern::Memory(memory) => ExternType::Memory(limits(memory.size(), memory.max())),
+            Extern::Global(global) => ExternType::Global(global.global_type()),
+        }
+    }
+}
+
+/// The type of an external value, it's displayed in the text format like `(memory 1 2)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExternType {
+    Func(FunctionType),
+    Table(TableType),
+    Memory(Limits),
+    Global(GlobalType),
+}
+
+impl ExternType {
+    /// https://webassembly.github.io/spec/core/valid/types.html#import-subtyping
+    /// Whether a value of this type can be imported as `required`,
+    /// tables and memories can be larger than required, the others must be the same.
+    pub fn matches(&self, required: &ExternType) -> bool {
+        match (self, required) {
+            (ExternType::Table(provided), ExternType::Table(required)) => {
+                provided.ref_type == required.ref_type
+                    && limits_match(&provided.limits, &required.limits)
+            }
+            (ExternType::Memory(provided), ExternType::Memory(required)) => {
+                limits_match(provided, required)
+            }
+            _ => self == required,
+        }
+    }
+}
+
+/// https://webassembly.github.io/spec/core/valid/types.html#limits
+fn limits_match(provided: &Limits, required: &Limits) -> bool {
+    let min = provided.min.unwrap_or(0) >= required.min.unwrap_or(0);
+    let max = match (provided.max, required.max) {
+        (_, None) => true,
+        (Some(provided), Some(required)) => provided <= required,
+        (None, Some(_)) => false,
+    };
+    min && max
+}
+
+impl core::fmt::Display for ExternType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use crate::text::printer::{global_type_text, limits, ref_type, types_list};
+        match self {
+            ExternType::Func(func_type) => write!(
+                f,
+                "(func{}{})",
+                types_list("param", &func_type.param_types),
+                types_list("result", &func_type.return_types)
+            ),
+            ExternType::Table(table_type) => write!(
+                f,
+                "(table {} {})",
+                limits(&table_type.limits),
+                ref_type(&table_type.ref_type)
+            ),
+            ExternType::Memory(memory) => write!(f, "(memory {})", limits(memory)),
+            ExternType::Global(global_type) => {
+                write!(f, "(global {})", global_type_text(global_type))
+            }
+        }
+    }
+}
+
+/// An instantiated module, its exports can be invoked by name.
+#[derive(Clone, Debug)]
+pub struct Instance {
+    inner: Rc<ModuleInstance>,
+    exports: HashMap<String, Extern>,
+    metering: Rc<RefCell<Metering>>,
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+}
+
+impl Instance {
+    /// https://webassembly.github.io/spec/core/exec/modules.html#instantiation
+    /// The module is validated first, the `imports` must be given in the same order of import section.
+    pub fn new(module: &WasmModule, imports: &[Extern]) -> Result<Self, InstantiationError> {
+        let instance = Self::instantiate(module, imports, None, Metering::default())?;
+        instance.start(module)?;
+        Ok(instance)
+    }
+
+    /// Instantiate with the limiter, it also applies to the calls of this instance.
+    pub fn with_limiter(
+        module: &WasmModule,
+        imports: &[Extern],
+        limiter: Rc<dyn ResourceLimiter>,
+    ) -> Result<Self, InstantiationError> {
+        let instance = Self::instantiate(module, imports, Some(limiter), Metering::default())?;
+        instance.start(module)?;
+        Ok(instance)
+    }
+
+    /// Instantiate without running the start function, [`Instance::start`] completes it.
+    pub(crate) fn instantiate(
+        module: &WasmModule,
+        imports: &[Extern],
+        limiter: Option<Rc<dyn ResourceLimiter>>,
+        metering: Metering,
+    ) -> Result<Self, InstantiationError> {
+        validate(module)?;
+        let limits: &dyn ResourceLimiter = limiter.as_deref().unwrap_or(&ResourceLimits::DEFAULT);
+        let inner = ModuleInstance::new(module.func_types().to_vec());
+
+        // resolve imports
+        let descriptions = module
+            .import_section
+            .as_ref()
+            .map_or(&[][..], |section| &section.desc[..]);
+        for (i, description) in descriptions.iter().enumerate() {
+            let import = imports
+                .get(i)
+                .ok_or_else(|| InstantiationError::UnknownImport {
+                    module: description.module().to_string(),
+                    name: description.name().to_string(),
+                })?;
+            let expected = match &description.desc {
+                ImportDesc::Func(type_idx) => ExternType::Func(inner.func_type(*type_idx)?.clone()),
+                ImportDesc::Table(table_type) => ExternType::Table(table_type.clone()),
+                ImportDesc::Memory(limits) => ExternType::Memory(limits.clone()),
+                ImportDesc::Global(global_type) => ExternType::Global(*global_type),
+            };
+            let actual = import.ty();
+            if !actual.matches(&expected) {
+                return Err(InstantiationError::IncompatibleImportType {
+                    module: description.module().to_string(),
+                    name: description.name().to_string(),
+                    expected: Box::new(expected),
+                    actual: Box::new(actual),
+                });
+            }
+            match import {
+                Extern::Func(func) => {
+                    inner.depend_on(func);
+                    inner.funcs.borrow_mut().push(func.clone());
+                }
+                // the functions already in the shared table or global are reachable from here,
+                // the ones stored later are kept alive by the `Store` which owns every instance.
+                Extern::Table(table) => {
+                    for index in 0..table.size() {
+                        if let Ok(Value::FuncRef(Some(func))) = table.get(index) {
+                            inner.depend_on(&func);
+                        }
+                    }
+                    inner.tables.borrow_mut().push(table.clone());
+                }
+                Extern::Memory(memory) => *inner.memory.borrow_mut() = Some(memory.clone()),
+                Extern::Global(global) => {
+                    if let Value::FuncRef(Some(func)) = global.get() {
+                        inner.depend_on(&func);
+                    }
+                    inner.globals.borrow_mut().push(global.clone());
+                }
+            }
+        }
+
+        // define functions
+        if let (Some(functions), Some(codes)) = (&module.function_section, &module.code_section) {
+            for (type_idx, code) in functions.type_idx.iter().zip(codes.codes.iter()) {
+                let body = FunctionBody::from_code(code);
+                inner.push_function(*type_idx as u32, body)?;
+            }
+        }
+
+        if let Some(section) = &module.table_section {
+            for table_type in &section.table_types {
+                let (min, max) = (table_type.limits.min.unwrap_or(0), table_type.limits.max);
+                if !limits.table_growing(0, min, max) {
+                    return Err(InstantiationError::ResourceLimitExceeded("table"));
+                }
+                let table = Table::from_table_type(table_type)?;
+                table.set_limiter(limiter.clone());
+                inner.tables.borrow_mut().push(table);
+            }
+        }
+
+        if let Some(section) = &module.memory_section {
+            let (min, max) = (section.limits.min.unwrap_or(0), section.limits.max);
+            if !limits.memory_growing(0, min, max) {
+                return Err(InstantiationError::ResourceLimitExceeded("memory"));
+            }
+            let memory = Memory::from_limits(&section.limits)?;
+            memory.set_limiter(limiter.clone());
+            *inner.memory.borrow_mut() = Some(memory);
+        }
+
+        if let Some(section) = &module.global_section {
+            for global in &section.globals {
+                let value = eval_const(&inner, &global.expressions)?;
+                let global = Global::new(global.global_type, value)?;
+                inner.globals.borrow_mut().push(global);
+            }
+        }
+
+        let mut exports = HashMap::new();
+        if let Some(section) = &module.export_section {
+            for export in &section.exports {
+                let index = export.desc.index as u32;
+                let value = match export.desc.export_type {
+                    ExportType::Func => Extern::Func(inner.func(index)?),
+                    ExportType::Table => Extern::Table(inner.table(index)?),
+                    ExportType::Mem => Extern::Memory(inner.memory()?),
+                    ExportType::Global => Extern::Global(inner.global(index)?),
+                };
+                exports.insert(export.name.clone(), value);
+            }
+        }
+
+        // active and declarative segments are dropped once instantiated,
+        // passive segments are kept for `table.init`.
+        if let Some(section) = &module.element_section {
+            for element in &section.elem {
+                let values = match &element.init {
+                    ElementItems::Functions(funcs) => funcs
+                        .iter()
+                        .map(|func_idx| Ok(Value::FuncRef(Some(inner.func(*func_idx as u32)?))))
+                        .collect::<Result<Vec<_>, Trap>>()?,
+                    ElementItems::Expressions(exprs) => exprs
+                        .iter()
+                        .map(|expr| eval_const(&inner, expr))
+                        .collect::<Result<Vec<_>, Trap>>()?,
+                };
+                match &element.mode {
+                    ElementMode::Active { table_idx, offset } => {
+                        let offset = eval_offset(&inner, offset)?;
+                        inner.table(*table_idx as u32)?.init(offset, &values)?;
+                        inner.elems.borrow_mut().push(vec![]);
+                    }
+                    ElementMode::Passive => inner.elems.borrow_mut().push(values),
+                    ElementMode::Declarative => inner.elems.borrow_mut().push(vec![]),
+                }
+            }
+        }
+
+        if let Some(section) = &module.data_section {
+            // active segments are dropped once instantiated, passive segments are kept for `memory.init`.
+            for segment in &section.segments {
+                match &segment.mode {
+                    DataMode::Active { offset, .. } => {
+                        let offset = eval_offset(&inner, offset)?;
+                        inner.memory()?.write(offset as usize, &segment.init)?;
+                        inner.datas.borrow_mut().push(vec![]);
+                    }
+                    DataMode::Passive => inner.datas.borrow_mut().push(segment.init.to_vec()),
+                }
+            }
+        }
+
+        Ok(Self {
+            inner,
+            exports,
+            metering: Rc::new(RefCell::new(metering)),
+            limiter,
+        })
+    }
+
+    /// Run the start function of the module with the fuel and the limiter of this instance.
+    pub(crate) fn start(&self, module: &WasmModule) -> Result<(), InstantiationError> {
+        if let Some(section) = &module.start_section {
+            let func = self.inner.func(section.func_idx as u32)?;
+            let mut vm = {
+                let metering = self.metering.borrow();
+                WasmVM::with_metering(metering.fuel.clone(), metering.costs.clone())
+            };
+            vm.set_limiter(self.limiter.clone());
+            vm.invoke(&func, &[])?;
+        }
+        Ok(())
+    }
+
+    /// The runtime state shared by all functions of this instance.
+    pub fn module_instance(&self) -> &Rc<ModuleInstance> {
+        &self.inner
+    }
+
+    /// Get the exported item by name.
+    pub fn export(&self, name: &str) -> Option<&Extern> {
+        self.exports.get(name)
+    }
+
+    /// All exported items with their names.
+    pub fn exports(&self) -> impl Iterator<Item = (&str, &Extern)> {
+        self.exports
+            .iter()
+            .map(|(name, item)| (name.as_str(), item))
+    }
+
+    /// Get the exported function by name.
+    pub fn get_func(&self, name: &str) -> Option<Func> {
+        match self.export(name)? {
+            Extern::Func(func) => Some(func.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the exported table by name.
+    pub fn get_table(&self, name: &str) -> Option<Table> {
+        match self.export(name)? {
+            Extern::Table(table) => Some(table.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the exported memory by name, the host can read and write it directly.
+    pub fn get_memory(&self, name: &str) -> Option<Memory> {
+        match self.export(name)? {
+            Extern::Memory(memory) => Some(memory.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the exported global by name.
+    pub fn get_global(&self, name: &str) -> Option<Global> {
+        match self.export(name)? {
+            Extern::Global(global) => Some(global.clone()),
+            _ => None,
+        }
+    }
+
+    /// Call the exported function by name, the arguments and results are checked against its signature.
+    pub fn invoke(&self, name: &str, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let func = self
+            .get_func(name)
+            .ok_or_else(|| Trap::UnknownExport(name.to_string()))?;
+        self.call(&func, args)
+    }
+
+    /// Call the function with the fuel and the limiter of this instance.
+    pub(crate) fn call(&self, func: &Func, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let func_type = func.func_type();
+        check_types(args, &func_type.param_types)?;
+        let mut vm = {
+            let mut metering = self.metering.borrow_mut();
+            metering.active += 1;
+            WasmVM::with_metering(metering.fuel.clone(), metering.costs.clone())
+        };
+        vm.set_limiter(self.limiter.clone());
+        let results = vm.invoke(func, args);
+        let results = self.finish(vm, results)?;
+        check_types(&results, &func_type.return_types)?;
+        Ok(results)
+    }
+
+    /// Continue the last call which ran out of fuel, with the fuel set after it.
+    pub fn resume(&self) -> Result<Vec<Value>, Trap> {
+        let mut vm = {
+            let mut metering = self.metering.borrow_mut();
+            let vm = metering
+                .suspended
+                .take()
+                .ok_or(Trap::Unsupported("resuming without a call out of fuel"))?;
+            metering.active += 1;
+            vm
+        };
+        let results = vm.resume();
+        self.finish(vm, results)
+    }
+
+    /// Keep the call if it ran out of fuel, only one call can be suspended
+    /// and a nested call can't be resumed without the host function which made it.
+    fn finish(&self, vm: WasmVM, results: Result<Vec<Value>, Trap>) -> Result<Vec<Value>, Trap> {
+        let mut metering = self.metering.borrow_mut();
+        metering.active -= 1;
+        if !vm.is_suspended() {
+            return results;
+        }
+        if metering.active > 0 {
+            return Err(Trap::Unsupported("suspending a nested call out of fuel"));
+        }
+        if metering.suspended.is_some() {
+            return Err(Trap::Unsupported(
+                "suspending a call out of fuel while another one is suspended",
+            ));
+        }
+        metering.suspended = Some(vm);
+        results
+    }
+
+    /// The fuel left for calls through this instance, `None` if it's unlimited which is the default.
+    pub fn fuel(&self) -> Option<u64> {
+        self.metering.borrow().fuel.get()
+    }
+
+    /// Enable fuel metering, or refill the fuel.
+    pub fn set_fuel(&self, fuel: u64) {
+        self.metering.borrow().fuel.set(Some(fuel));
+    }
+
+    pub fn set_fuel_costs(&self, costs: FuelCosts) {
+        self.metering.borrow_mut().costs = Rc::new(costs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (module (func (export "id") (param i32) (result i32) local.get 0))
+    const ID_MODULE: [u8; 36] = [
+        0, 97, 115, 109, 1, 0, 0, 0, 1, 6, 1, 96, 1, 127, 1, 127, 3, 2, 1, 0, 7, 6, 1, 2, 105, 100,
+        0, 0, 10, 6, 1, 4, 0, 32, 0, 11,
+    ];
+
+    // (module
+    //   (import "env" "id" (func $id (param i32) (result i32)))
+    //   (memory 1)
+    //   (global i32 (i32.const 42))
+    //   (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add)
+    //   (func (export "answer") (result i32) global.get 0)
+    //   (func (export "call_id") (param i32) (result i32) local.get 0 call $id)
+    //   (func (export "load") (param i32) (result i32) local.get 0 i32.load8_u)
+    //   (data (i32.const 1) "\2a"))
+    const MODULE: [u8; 133] = [
+        0, 97, 115, 109, 1, 0, 0, 0, 1, 16, 3, 96, 2, 127, 127, 1, 127, 96, 0, 1, 127, 96, 1, 127,
+        1, 127, 2, 10, 1, 3, 101, 110, 118, 2, 105, 100, 0, 2, 3, 5, 4, 0, 1, 2, 2, 5, 3, 1, 0, 1,
+        6, 6, 1, 127, 0, 65, 42, 11, 7, 33, 4, 3, 97, 100, 100, 0, 1, 6, 97, 110, 115, 119, 101,
+        114, 0, 2, 7, 99, 97, 108, 108, 95, 105, 100, 0, 3, 4, 108, 111, 97, 100, 0, 4, 10, 29, 4,
+        7, 0, 32, 0, 32, 1, 106, 11, 4, 0, 35, 0, 11, 6, 0, 32, 0, 16, 0, 11, 7, 0, 32, 0, 45, 0,
+        0, 11, 11, 7, 1, 0, 65, 1, 11, 1, 42,
+    ];
+
+    fn instantiate() -> Instance {
+        let id_module = WasmModule::from_bytes(&ID_MODULE).unwrap();
+        let id_instance = Instance::new(&id_module, &[]).unwrap();
+        let id = id_instance.export("id").cloned().unwrap();
+
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        Instance::new(&module, &[id]).unwrap()
+    }
+
+    #[test]
+    fn invoke_exports_should_work() {
+        let instance = instantiate();
+
+        let args = [Value::I32(1), Value::I32(2)];
+        assert_eq!(instance.invoke("add", &args), Ok(vec![Value::I32(3)]));
+        assert_eq!(instance.invoke("answer", &[]), Ok(vec![Value::I32(42)]));
+        assert_eq!(
+            instance.invoke("call_id", &[Value::I32(7)]),
+            Ok(vec![Value::I32(7)])
+        );
+        // initialized by data segment
+        assert_eq!(
+            instance.invoke("load", &[Value::I32(1)]),
+            Ok(vec![Value::I32(42)])
+        );
+        assert_eq!(
+            instance.invoke("load", &[Value::I32(65536)]),
+            Err(Trap::MemoryOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn invoke_should_check_signature() {
+        let instance = instantiate();
+
+        assert_eq!(
+            instance.invoke("add", &[Value::I32(1)]),
+            Err(Trap::TypeMismatch)
+        );
+        assert_eq!(
+            instance.invoke("add", &[Value::I32(1), Value::I64(2)]),
+            Err(Trap::TypeMismatch)
+        );
+        assert_eq!(
+            instance.invoke("sub", &[]),
+            Err(Trap::UnknownExport("sub".to_string()))
+        );
+    }
+
+    #[test]
+    fn data_segments_should_initialize_memory() {
+        let source = r#"
+            (module
+              (memory (export "memory") 1)
+              (data (i32.const 2) "ab")
+              (data "passive")
+              (data (memory 0) (offset i32.const 65534) "cd"))
+        "#;
+        let module = crate::text::parse(source).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        let memory = instance.get_memory("memory").unwrap();
+        assert_eq!(&memory.data()[..5], b"\0\0ab\0");
+        assert_eq!(&memory.data()[65534..], b"cd");
+
+        // the segment is out of bounds by one byte.
+        let source = r#"(memory 1) (data (i32.const 0) "a") (data (i32.const 65535) "bc")"#;
+        let module = crate::text::parse(source).unwrap();
+        assert_eq!(
+            Instance::new(&module, &[]).unwrap_err(),
+            InstantiationError::Trap(Trap::MemoryOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn instantiate_should_check_imports() {
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        assert_eq!(
+            Instance::new(&module, &[]).unwrap_err(),
+            InstantiationError::UnknownImport {
+                module: "env".to_string(),
+                name: "id".to_string()
+            }
+        );
+
+        // import "answer" whose type is () -> i32 as "id"
+        let instance = instantiate();
+        let answer = instance.export("answer").cloned().unwrap();
+        assert_eq!(
+            Instance::new(&module, &[answer]).unwrap_err().to_string(),
+            "incompatible import type for env.id: \
+             expected (func (param i32) (result i32)), got (func (result i32))"
+        );
+    }
+
+    #[test]
+    fn fuel_should_bound_execution() {
+        // sum 1..=n with a loop, every iteration executes 16 instructions.
+        let source = r#"
+            (module
+              (global $calls (mut i32) (i32.const 0))
+              (func (export "sum") (param $n i32) (result i32) (local $acc i32)
+                (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+                (block
+                  (loop
+                    (br_if 1 (i32.eqz (local.get $n)))
+                    (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br 0)))
+                (local.get $acc))
+              (func (export "calls") (result i32) (global.get $calls)))
+        "#;
+        let module = crate::text::parse(source).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        assert_eq!(instance.fuel(), None);
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(100)]),
+            Ok(vec![Value::I32(5050)])
+        );
+
+        instance.set_fuel(1000);
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(10)]),
+            Ok(vec![Value::I32(55)])
+        );
+        let used = 1000 - instance.fuel().unwrap();
+        assert!(used > 10 * 10 && used < 10 * 20, "{}", used);
+
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(1000)]),
+            Err(Trap::OutOfFuel)
+        );
+        assert_eq!(instance.fuel(), Some(0));
+        // other calls don't disturb the suspended one.
+        instance.set_fuel(100);
+        assert_eq!(instance.invoke("calls", &[]), Ok(vec![Value::I32(3)]));
+        instance.set_fuel(u64::MAX);
+        assert_eq!(instance.resume(), Ok(vec![Value::I32(500500)]));
+        assert_eq!(instance.invoke("calls", &[]), Ok(vec![Value::I32(3)]));
+        assert!(instance.resume().is_err());
+
+        // the costs are per opcode.
+        let mut costs = FuelCosts::new();
+        costs.set_default(0).set(ControlInstructions::Br, 1);
+        instance.set_fuel_costs(costs);
+        instance.set_fuel(10);
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(10)]),
+            Ok(vec![Value::I32(55)])
+        );
+        assert_eq!(instance.fuel(), Some(0));
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(11)]),
+            Err(Trap::OutOfFuel)
+        );
+    }
+
+    #[test]
+    fn nested_calls_should_share_fuel() {
+        let source = r#"
+            (module
+              (import "env" "reenter" (func $reenter (param i32)))
+              (func $sum (export "sum") (param $n i32) (result i32) (local $acc i32)
+                (block
+                  (loop
+                    (br_if 1 (i32.eqz (local.get $n)))
+                    (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br 0)))
+                (local.get $acc))
+              (func (export "run") (param i32 i32) (result i32)
+                (call $reenter (local.get 0))
+                (call $sum (local.get 1))))
+        "#;
+        let slot: Rc<RefCell<Option<Instance>>> = Rc::default();
+        let this = slot.clone();
+        let mut linker = Linker::new();
+        let func_type = FunctionType {
+            param_types: vec![ValueType::NumType(NumberType::I32)],
+            return_types: vec![],
+        };
+        linker.func("env", "reenter", func_type, move |args| {
+            let instance = this.borrow().clone().unwrap();
+            instance.invoke("sum", args)?;
+            Ok(vec![])
+        });
+        let instance = linker
+            .instantiate(&crate::text::parse(source).unwrap())
+            .unwrap();
+        *slot.borrow_mut() = Some(instance.clone());
+
+        instance.set_fuel(1000);
+        instance.invoke("sum", &[Value::I32(10)]).unwrap();
+        let sum = 1000 - instance.fuel().unwrap();
+        instance.set_fuel(1000);
+        assert_eq!(
+            instance.invoke("run", &[Value::I32(10), Value::I32(10)]),
+            Ok(vec![Value::I32(55)])
+        );
+        let used = 1000 - instance.fuel().unwrap();
+        assert!(used > 2 * sum && used < 2 * sum + 10, "{} {}", used, sum);
+
+        // the nested call can't be suspended, so the outer call traps.
+        instance.set_fuel(sum / 2);
+        assert_eq!(
+            instance.invoke("run", &[Value::I32(10), Value::I32(0)]),
+            Err(Trap::Unsupported("suspending a nested call out of fuel"))
+        );
+        assert!(instance.resume().is_err());
+
+        // the suspended call is kept.
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(10)]),
+            Err(Trap::OutOfFuel)
+        );
+        assert_eq!(
+            instance.invoke("sum", &[Value::I32(20)]),
+            Err(Trap::Unsupported(
+                "suspending a call out of fuel while another one is suspended"
+            ))
+        );
+        instance.set_fuel(1000);
+        assert_eq!(instance.resume(), Ok(vec![Value::I32(55)]));
+        *slot.borrow_mut() = None;
+    }
+
+    #[test]
+    fn imported_memory_and_table_should_be_shared() {
+        let source = r#"
+            (module
+              (import "env" "memory" (memory 1))
+              (import "env" "__indirect_function_table" (table 2 funcref))
+              (type $get (func (result i32)))
+              (elem (i32.const 1) $answer)
+              (func $answer (result i32) (i32.const 42))
+              (func (export "store") (i32.store (i32.const 4) (call_indirect (type $get) (i32.const 1)))))
+        "#;
+        let memory = Memory::new(1, None).unwrap();
+        let table = Table::new(ReferenceType::FuncRef, 2, None).unwrap();
+        let mut linker = Linker::new();
+        linker
+            .define("env", "memory", Extern::Memory(memory.clone()))
+            .define(
+                "env",
+                "__indirect_function_table",
+                Extern::Table(table.clone()),
+            );
+        let module = crate::text::parse(source).unwrap();
+        let instance = linker.instantiate(&module).unwrap();
+
+        assert!(matches!(table.get(1), Ok(Value::FuncRef(Some(_)))));
+        assert_eq!(instance.invoke("store", &[]), Ok(vec![]));
+        assert_eq!(&memory.data()[4..8], &[42, 0, 0, 0]);
+
+        let table = Table::new(ReferenceType::ExternRef, 2, None).unwrap();
+        linker.define("env", "__indirect_function_table", Extern::Table(table));
+        assert_eq!(
+            linker.instantiate(&module).unwrap_err().to_string(),
+            "incompatible import type for env.__indirect_function_table: \
+             expected (table 2 funcref), got (table 2 externref)"
+        );
+    }
+}
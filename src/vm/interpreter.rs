@@ -0,0 +1,579 @@
+use super::*;
+
+/// What the running frame asks the engine to do.
+pub(crate) enum Control {
+    Call(Func),
+    Return,
+}
+
+impl WasmVM {
+    /// Push a new frame for the function, the arguments are on the top of operand stack.
+    pub(crate) fn call(&mut self, func: Func) -> Result<(), Trap> {
+        let limiter = self.limiter();
+        if self.call_stack.len() >= limiter.max_call_depth() {
+            return Err(Trap::CallStackExhausted);
+        }
+        if self.operand_stack.len() > limiter.max_stack_size() {
+            return Err(Trap::StackOverflow);
+        }
+
+        let func_type = func.func_type();
+        let mut locals = self.operand_stack.pop_n(func_type.param_types.len())?;
+        check_types(&locals, &func_type.param_types)?;
+
+        let (instance, body) = match &*func.0 {
+            FuncInstance::Wasm { instance, body, .. } => {
+                let instance = instance.upgrade().ok_or(Trap::Unsupported(
+                    "calling a function whose instance is dropped",
+                ))?;
+                (instance, body.clone())
+            }
+            // host functions return immediately, no frame is needed.
+            FuncInstance::Host { callback, .. } => {
+                // the host function can't continue from where it ran out of fuel.
+                let results = callback(&locals).map_err(|trap| match trap {
+                    Trap::OutOfFuel => Trap::Unsupported("resuming a host function out of fuel"),
+                    trap => trap,
+                })?;
+                check_types(&results, &func_type.return_types)?;
+                self.operand_stack.extend(results);
+                return Ok(());
+            }
+        };
+        for ty in &body.locals {
+            locals.push(Value::default_of(*ty).ok_or(Trap::Unsupported("vector type"))?);
+        }
+
+        let frame = Frame {
+            body,
+            instance,
+            pc: 0,
+            locals,
+            labels: vec![],
+            height: self.operand_stack.len(),
+            arity: func_type.return_types.len(),
+        };
+        self.call_stack.push(frame);
+        Ok(())
+    }
+
+    /// Run until the frames above `depth` all return.
+    pub(crate) fn execute(&mut self, depth: usize) -> Result<(), Trap> {
+        while self.call_stack.len() > depth {
+            let mut frame = match self.call_stack.pop() {
+                Some(frame) => frame,
+                None => break,
+            };
+            let control = match self.run(&mut frame) {
+                Ok(control) => control,
+                // keep the frame to resume.
+                Err(Trap::OutOfFuel) => {
+                    self.call_stack.push(frame);
+                    return Err(Trap::OutOfFuel);
+                }
+                Err(trap) => return Err(trap),
+            };
+            match control {
+                Control::Call(func) => {
+                    self.call_stack.push(frame);
+                    self.call(func)?;
+                }
+                Control::Return => {
+                    let results = self.operand_stack.pop_n(frame.arity)?;
+                    self.operand_stack.truncate(frame.height);
+                    self.operand_stack.extend(results);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the frame until it calls another function or returns.
+    fn run(&mut self, frame: &mut Frame) -> Result<Control, Trap> {
+        let body = frame.body.clone();
+        let instance = frame.instance.clone();
+        let max_stack_size = self.limiter().max_stack_size();
+
+        while let Some(instruction) = body.instructions.get(frame.pc) {
+            if let Some(fuel) = self.fuel.get() {
+                let cost = self.costs.cost(instruction);
+                if fuel < cost {
+                    return Err(Trap::OutOfFuel);
+                }
+                self.fuel.set(Some(fuel - cost));
+            }
+            let pc = frame.pc;
+            frame.pc += 1;
+            match instruction {
+                Instructions::ControlInstructions(op, operands) => {
+                    if let Some(control) =
+                        self.execute_control(frame, &body, &instance, pc, *op, operands)?
+                    {
+                        return Ok(control);
+                    }
+                }
+                Instructions::ReferenceInstructions(op, operands) => {
+                    self.execute_reference(&instance, *op, operands)?
+                }
+                Instructions::ParametricInstructions(op, _) => self.execute_parametric(*op)?,
+                Instructions::VariableInstructions(op, index) => {
+                    self.execute_variable(frame, &instance, *op, *index)?
+                }
+                Instructions::TableInstructions(op, operands) => {
+                    self.execute_table(&instance, *op, *operands)?
+                }
+                Instructions::MemoryInstructions(op, operands) => {
+                    self.execute_memory(&instance, *op, operands)?
+                }
+                Instructions::NumericInstructions(op, primitive) => {
+                    self.execute_numeric(*op, primitive)?
+                }
+                Instructions::VectorInstructions(_) => {
+                    return Err(Trap::Unsupported("vector instruction"))
+                }
+            }
+            // an instruction pushes at most one value, except the results of host functions.
+            if self.operand_stack.len() > max_stack_size {
+                return Err(Trap::StackOverflow);
+            }
+        }
+
+        // reach the end of function
+        Ok(Control::Return)
+    }
+
+    /// The count of params and results of a block.
+    fn block_arity(
+        instance: &ModuleInstance,
+        block_type: &BlockType,
+    ) -> Result<(usize, usize), Trap> {
+        match block_type {
+            BlockType::Empty => Ok((0, 0)),
+            BlockType::Value(_) => Ok((0, 1)),
+            BlockType::TypeIndex(index) => {
+                let func_type = instance.func_type(*index)?;
+                Ok((func_type.param_types.len(), func_type.return_types.len()))
+            }
+        }
+    }
+
+    /// Branch to the label, `depth` counts from the innermost label.
+    /// Branching to the label of function body means returning.
+    fn branch(&mut self, frame: &mut Frame, depth: u32) -> Result<Option<Control>, Trap> {
+        let depth = depth as usize;
+        if depth >= frame.labels.len() {
+            return Ok(Some(Control::Return));
+        }
+
+        let index = frame.labels.len() - 1 - depth;
+        let label = frame.labels[index];
+        let values = self.operand_stack.pop_n(label.arity)?;
+        self.operand_stack.truncate(label.height);
+        self.operand_stack.extend(values);
+        // the label of loop is still active after branching to it.
+        if label.is_loop {
+            frame.labels.truncate(index + 1);
+        } else {
+            frame.labels.truncate(index);
+        }
+        frame.pc = label.continuation;
+        Ok(None)
+    }
+
+    fn execute_control(
+        &mut self,
+        frame: &mut Frame,
+        body: &FunctionBody,
+        instance: &ModuleInstance,
+        pc: usize,
+        op: ControlInstructions,
+        operands: &ControlOperands,
+    ) -> Result<Option<Control>, Trap> {
+        let missing_end = Trap::Unsupported("block without end");
+        match (op, operands) {
+            (ControlInstructions::Unreachable, _) => return Err(Trap::Unreachable),
+            (ControlInstructions::Nop, _) => (),
+            (ControlInstructions::Block, ControlOperands::Block(block_type)) => {
+                let (params, results) = Self::block_arity(instance, block_type)?;
+                let end = body.end_of(pc).ok_or(missing_end)?;
+                frame.labels.push(Label {
+                    arity: results,
+                    height: self.operand_stack.len().saturating_sub(params),
+                    continuation: end + 1,
+                    is_loop: false,
+                });
+            }
+            (ControlInstructions::Loop, ControlOperands::Block(block_type)) => {
+                let (params, _) = Self::block_arity(instance, block_type)?;
+                frame.labels.push(Label {
+                    arity: params,
+                    height: self.operand_stack.len().saturating_sub(params),
+                    continuation: pc + 1,
+                    is_loop: true,
+                });
+            }
+            (ControlInstructions::If, ControlOperands::Block(block_type)) => {
+                let condition = self.operand_stack.pop_i32()?;
+                let (params, results) = Self::block_arity(instance, block_type)?;
+                let end = body.end_of(pc).ok_or(missing_end)?;
+                frame.labels.push(Label {
+                    arity: results,
+                    height: self.operand_stack.len().saturating_sub(params),
+                    continuation: end + 1,
+                    is_loop: false,
+                });
+                if condition == 0 {
+                    // run else branch, or reach end directly without else branch.
+                    frame.pc = match body.else_of(pc) {
+                        Some(else_pc) => else_pc + 1,
+                        None => end,
+                    };
+                }
+            }
+            // the then branch finished, skip the else branch.
+            (ControlInstructions::Else, _) => {
+                frame.pc = body.end_of(pc).ok_or(missing_end)?;
+            }
+            (ControlInstructions::End, _) => {
+                if frame.labels.pop().is_none() {
+                    return Ok(Some(Control::Return));
+                }
+            }
+            (ControlInstructions::Br, ControlOperands::Label(depth)) => {
+                return self.branch(frame, *depth);
+            }
+            (ControlInstructions::BrIf, ControlOperands::Label(depth)) => {
+                if self.operand_stack.pop_i32()? != 0 {
+                    return self.branch(frame, *depth);
+                }
+            }
+            (ControlInstructions::BrTable, ControlOperands::BrTable(labels, default)) => {
+                let index = self.operand_stack.pop_i32()? as u32 as usize;
+                let depth = labels.get(index).unwrap_or(default);
+                return self.branch(frame, *depth);
+            }
+            (ControlInstructions::Return, _) => return Ok(Some(Control::Return)),
+            (ControlInstructions::Call, ControlOperands::Func(func_idx)) => {
+                return Ok(Some(Control::Call(instance.func(*func_idx)?)));
+            }
+            (
+                ControlInstructions::CallIndirect,
+                ControlOperands::CallIndirect(type_idx, table_idx),
+            ) => {
+                let expected = instance.func_type(*type_idx)?;
+                let table = instance.table(*table_idx)?;
+                let index = self.operand_stack.pop_i32()? as u32;
+                let func = match table.get(index) {
+                    Err(_) => return Err(Trap::UndefinedElement),
+                    Ok(Value::FuncRef(None)) => return Err(Trap::UninitializedElement),
+                    Ok(Value::FuncRef(Some(func))) => func,
+                    Ok(_) => return Err(Trap::TypeMismatch),
+                };
+                if func.func_type() != expected {
+                    return Err(Trap::IndirectCallTypeMismatch);
+                }
+                return Ok(Some(Control::Call(func)));
+            }
+            _ => return Err(Trap::Unsupported("malformed control instruction")),
+        }
+        Ok(None)
+    }
+
+    fn execute_reference(
+        &mut self,
+        instance: &ModuleInstance,
+        op: ReferenceInstructions,
+        operands: &ReferenceOperands,
+    ) -> Result<(), Trap> {
+        match (op, operands) {
+            (
+                ReferenceInstructions::RefNull,
+                ReferenceOperands::RefType(ReferenceType::FuncRef),
+            ) => self.operand_stack.push(Value::FuncRef(None)),
+            (
+                ReferenceInstructions::RefNull,
+                ReferenceOperands::RefType(ReferenceType::ExternRef),
+            ) => self.operand_stack.push(Value::ExternRef(None)),
+            (ReferenceInstructions::RefIsNull, _) => {
+                let value = self.operand_stack.pop()?;
+                let is_null = value.is_null().ok_or(Trap::TypeMismatch)?;
+                self.operand_stack.push(is_null as i32);
+            }
+            (ReferenceInstructions::RefFunc, ReferenceOperands::Func(func_idx)) => {
+                let func = instance.func(*func_idx)?;
+                self.operand_stack.push(Value::FuncRef(Some(func)));
+            }
+            _ => return Err(Trap::Unsupported("malformed reference instruction")),
+        }
+        Ok(())
+    }
+
+    fn execute_parametric(&mut self, op: ParametricInstructions) -> Result<(), Trap> {
+        match op {
+            ParametricInstructions::Drop => {
+                self.operand_stack.pop()?;
+            }
+            ParametricInstructions::Select | ParametricInstructions::SelectV => {
+                let condition = self.operand_stack.pop_i32()?;
+                let second = self.operand_stack.pop()?;
+                let first = self.operand_stack.pop()?;
+                if condition != 0 {
+                    self.operand_stack.push(first);
+                } else {
+                    self.operand_stack.push(second);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_variable(
+        &mut self,
+        frame: &mut Frame,
+        instance: &ModuleInstance,
+        op: VariableInstructions,
+        index: u32,
+    ) -> Result<(), Trap> {
+        let unknown_local = Trap::UnknownIndex {
+            kind: "local",
+            index,
+        };
+        match op {
+            VariableInstructions::LocalGet => {
+                let value = frame.locals.get(index as usize).ok_or(unknown_local)?;
+                self.operand_stack.push(value.clone());
+            }
+            VariableInstructions::LocalSet | VariableInstructions::LocalTee => {
+                let value = self.operand_stack.pop()?;
+                let local = frame.locals.get_mut(index as usize).ok_or(unknown_local)?;
+                if local.value_type() != value.value_type() {
+                    return Err(Trap::TypeMismatch);
+                }
+                *local = value.clone();
+                if op == VariableInstructions::LocalTee {
+                    self.operand_stack.push(value);
+                }
+            }
+            VariableInstructions::GlobalGet => {
+                let value = instance.global(index)?.get();
+                self.operand_stack.push(value);
+            }
+            VariableInstructions::GlobalSet => {
+                let value = self.operand_stack.pop()?;
+                instance.global(index)?.set_unchecked(value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_table(
+        &mut self,
+        instance: &ModuleInstance,
+        op: TableInstructions,
+        operands: TableOperands,
+    ) -> Result<(), Trap> {
+        let table_idx = match operands {
+            TableOperands::Table(table_idx) => table_idx,
+            TableOperands::Init(table_idx, elem_idx) => {
+                let n = self.operand_stack.pop_i32()? as u32 as usize;
+                let src = self.operand_stack.pop_i32()? as u32 as usize;
+                let dst = self.operand_stack.pop_i32()? as u32;
+                let values = instance
+                    .elems
+                    .borrow()
+                    .get(elem_idx as usize)
+                    .and_then(|elem| elem.get(src..src.checked_add(n)?).map(<[_]>::to_vec))
+                    .ok_or(Trap::TableOutOfBounds)?;
+                return instance.table(table_idx)?.init(dst, &values);
+            }
+            TableOperands::Elem(elem_idx) => {
+                if let Some(elem) = instance.elems.borrow_mut().get_mut(elem_idx as usize) {
+                    *elem = vec![];
+                }
+                return Ok(());
+            }
+            TableOperands::Copy(dst_idx, src_idx) => {
+                let n = self.operand_stack.pop_i32()? as u32;
+                let src = self.operand_stack.pop_i32()? as u32;
+                let dst = self.operand_stack.pop_i32()? as u32;
+                // the source is read entirely first, so overlapping ranges are fine.
+                let values = instance.table(src_idx)?.read(src, n)?;
+                return instance.table(dst_idx)?.init(dst, &values);
+            }
+        };
+        let table = instance.table(table_idx)?;
+        match op {
+            TableInstructions::TableGet => {
+                let index = self.operand_stack.pop_i32()? as u32;
+                self.operand_stack.push(table.get(index)?);
+            }
+            TableInstructions::TableSet => {
+                let value = self.operand_stack.pop()?;
+                let index = self.operand_stack.pop_i32()? as u32;
+                table.set(index, value)?;
+            }
+            TableInstructions::TableGrow => {
+                let delta = self.operand_stack.pop_i32()? as u32;
+                let init = self.operand_stack.pop()?;
+                let current = table.size();
+                let allowed = current.checked_add(delta).map_or(false, |desired| {
+                    self.limiter().table_growing(current, desired, table.max())
+                });
+                let result = if allowed {
+                    table.grow(delta, init).map_or(-1, |size| size as i32)
+                } else {
+                    -1
+                };
+                self.operand_stack.push(result);
+            }
+            TableInstructions::TableSize => self.operand_stack.push(table.size() as i32),
+            TableInstructions::TableFill => {
+                let n = self.operand_stack.pop_i32()? as u32;
+                let value = self.operand_stack.pop()?;
+                let index = self.operand_stack.pop_i32()? as u32;
+                table.fill(index, n, value)?;
+            }
+            // their operands aren't a single table.
+            TableInstructions::TableInit
+            | TableInstructions::ElemDrop
+            | TableInstructions::TableCopy => return Err(Trap::TypeMismatch),
+        }
+        Ok(())
+    }
+
+    fn execute_memory(
+        &mut self,
+        instance: &ModuleInstance,
+        op: MemoryInstructions,
+        operands: &MemoryOperands,
+    ) -> Result<(), Trap> {
+        use MemoryInstructions::*;
+
+        let mem_arg = match operands {
+            MemoryOperands::MemArg(mem_arg) => *mem_arg,
+            MemoryOperands::Data(data_idx) => return self.execute_data(instance, op, *data_idx),
+            MemoryOperands::None => MemArg::default(),
+        };
+        let memory = instance.memory()?;
+        match op {
+            Size => {
+                self.operand_stack.push(memory.size() as i32);
+                return Ok(());
+            }
+            Grow => {
+                let delta = self.operand_stack.pop_i32()? as u32;
+                let current = memory.size();
+                let allowed = current.checked_add(delta).map_or(false, |desired| {
+                    self.limiter()
+                        .memory_growing(current, desired, memory.max())
+                });
+                let result = if allowed {
+                    memory.grow(delta).map_or(-1, |pages| pages as i32)
+                } else {
+                    -1
+                };
+                self.operand_stack.push(result);
+                return Ok(());
+            }
+            Copy => {
+                let n = self.operand_stack.pop_i32()? as u32;
+                let src = self.operand_stack.pop_i32()? as u32;
+                let dst = self.operand_stack.pop_i32()? as u32;
+                return memory.copy(dst as usize, src as usize, n as usize);
+            }
+            Fill => {
+                let n = self.operand_stack.pop_i32()? as u32;
+                let value = self.operand_stack.pop_i32()?;
+                let dst = self.operand_stack.pop_i32()? as u32;
+                return memory.fill(dst as usize, n as usize, value as u8);
+            }
+            Init | Drop => return Err(Trap::TypeMismatch),
+            _ => (),
+        }
+
+        match op {
+            I32Load | I64Load | F32Load | F64Load | I32Load8Signed | I32Load8Unsigned
+            | I32Load16Signed | I32Load16Unsigned | I64Load8Signed | I64Load8Unsigned
+            | I64Load16Signed | I64Load16Unsigned | I64Load32Signed | I64Load32Unsigned => {
+                let base = self.operand_stack.pop_i32()? as u32 as u64;
+                let address = base + mem_arg.offset as u64;
+                let memory = memory.0.borrow();
+                let value = match op {
+                    I32Load => Value::I32(i32::from_le_bytes(memory.load(address)?)),
+                    I64Load => Value::I64(i64::from_le_bytes(memory.load(address)?)),
+                    F32Load => Value::F32(f32::from_le_bytes(memory.load(address)?)),
+                    F64Load => Value::F64(f64::from_le_bytes(memory.load(address)?)),
+                    I32Load8Signed => Value::I32(i8::from_le_bytes(memory.load(address)?) as i32),
+                    I32Load8Unsigned => Value::I32(u8::from_le_bytes(memory.load(address)?) as i32),
+                    I32Load16Signed => Value::I32(i16::from_le_bytes(memory.load(address)?) as i32),
+                    I32Load16Unsigned => {
+                        Value::I32(u16::from_le_bytes(memory.load(address)?) as i32)
+                    }
+                    I64Load8Signed => Value::I64(i8::from_le_bytes(memory.load(address)?) as i64),
+                    I64Load8Unsigned => Value::I64(u8::from_le_bytes(memory.load(address)?) as i64),
+                    I64Load16Signed => Value::I64(i16::from_le_bytes(memory.load(address)?) as i64),
+                    I64Load16Unsigned => {
+                        Value::I64(u16::from_le_bytes(memory.load(address)?) as i64)
+                    }
+                    I64Load32Signed => Value::I64(i32::from_le_bytes(memory.load(address)?) as i64),
+                    _ => Value::I64(u32::from_le_bytes(memory.load(address)?) as i64),
+                };
+                self.operand_stack.push(value);
+            }
+            _ => {
+                let value = self.operand_stack.pop()?;
+                let base = self.operand_stack.pop_i32()? as u32 as u64;
+                let address = base + mem_arg.offset as u64;
+                let mut memory = memory.0.borrow_mut();
+                match (op, value) {
+                    (I32Store, Value::I32(v)) => memory.store(address, &v.to_le_bytes())?,
+                    (I64Store, Value::I64(v)) => memory.store(address, &v.to_le_bytes())?,
+                    (F32Store, Value::F32(v)) => memory.store(address, &v.to_le_bytes())?,
+                    (F64Store, Value::F64(v)) => memory.store(address, &v.to_le_bytes())?,
+                    (I32Store8, Value::I32(v)) => {
+                        memory.store(address, &(v as u8).to_le_bytes())?
+                    }
+                    (I32Store16, Value::I32(v)) => {
+                        memory.store(address, &(v as u16).to_le_bytes())?
+                    }
+                    (I64Store8, Value::I64(v)) => {
+                        memory.store(address, &(v as u8).to_le_bytes())?
+                    }
+                    (I64Store16, Value::I64(v)) => {
+                        memory.store(address, &(v as u16).to_le_bytes())?
+                    }
+                    (I64Store32, Value::I64(v)) => {
+                        memory.store(address, &(v as u32).to_le_bytes())?
+                    }
+                    _ => return Err(Trap::TypeMismatch),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_data(
+        &mut self,
+        instance: &ModuleInstance,
+        op: MemoryInstructions,
+        data_idx: DataIndex,
+    ) -> Result<(), Trap> {
+        let mut datas = instance.datas.borrow_mut();
+        let data = datas.get_mut(data_idx as usize).ok_or(Trap::UnknownIndex {
+            kind: "data segment",
+            index: data_idx,
+        })?;
+        if op == MemoryInstructions::Drop {
+            *data = vec![];
+            return Ok(());
+        }
+        let n = self.operand_stack.pop_i32()? as u32 as usize;
+        let src = self.operand_stack.pop_i32()? as u32 as usize;
+        let dst = self.operand_stack.pop_i32()? as u32 as usize;
+        let bytes = src
+            .checked_add(n)
+            .and_then(|end| data.get(src..end))
+            .ok_or(Trap::MemoryOutOfBounds)?;
+        instance.memory()?.write(dst, bytes)
+    }
+}
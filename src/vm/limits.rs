@@ -0,0 +1,174 @@
+use super::*;
+use crate::validation::MAX_PAGES;
+
+/// Decide how much the guest can allocate and how deep it can run,
+/// it's consulted before growing memories and tables, including the initial sizes when instantiating,
+/// and the growth of them by the host through [`Memory::grow`] and [`Table::grow`].
+pub trait ResourceLimiter: core::fmt::Debug {
+    /// Whether the memory can grow from `current` to `desired` pages,
+    /// `maximum` is declared by the memory type.
+    fn memory_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// Whether the table can grow from `current` to `desired` elements.
+    fn table_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// How many frames can be active at the same time.
+    fn max_call_depth(&self) -> usize {
+        MAX_CALL_DEPTH
+    }
+
+    /// How many values the operand stack can hold, it's checked after every instruction.
+    fn max_stack_size(&self) -> usize {
+        MAX_STACK_SIZE
+    }
+}
+
+/// The fixed limits, the defaults only restrict what the spec restricts,
+/// except tables which are bounded by [`MAX_TABLE_ELEMENTS`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub memory_pages: u32,
+    pub table_elements: u32,
+    pub call_depth: usize,
+    pub stack_size: usize,
+}
+
+impl ResourceLimits {
+    pub const DEFAULT: Self = Self {
+        memory_pages: MAX_PAGES,
+        table_elements: MAX_TABLE_ELEMENTS,
+        call_depth: MAX_CALL_DEPTH,
+        stack_size: MAX_STACK_SIZE,
+    };
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl ResourceLimiter for ResourceLimits {
+    fn memory_growing(&self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        desired <= self.memory_pages
+    }
+
+    fn table_growing(&self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        desired <= self.table_elements
+    }
+
+    fn max_call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    fn max_stack_size(&self) -> usize {
+        self.stack_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instantiate(source: &str, limits: ResourceLimits) -> Result<Instance, InstantiationError> {
+        let module = crate::text::parse(source).unwrap();
+        Linker::new().limiter(limits).instantiate(&module)
+    }
+
+    #[test]
+    fn resource_limits_should_be_enforced() {
+        let limits = ResourceLimits {
+            memory_pages: 4,
+            table_elements: 10,
+            call_depth: 100,
+            stack_size: 50,
+        };
+        assert_eq!(
+            instantiate("(memory 5)", limits).unwrap_err(),
+            InstantiationError::ResourceLimitExceeded("memory")
+        );
+        assert_eq!(
+            instantiate("(table 11 funcref)", limits).unwrap_err(),
+            InstantiationError::ResourceLimitExceeded("table")
+        );
+
+        let source = r#"
+            (module
+              (memory (export "memory") 1)
+              (table (export "table") 5 funcref)
+              (func (export "grow") (param i32) (result i32)
+                (memory.grow (local.get 0)))
+              (func (export "grow_table") (param i32) (result i32)
+                (table.grow (ref.null func) (local.get 0)))
+              ;; every level keeps a value on the operand stack while calling the next one
+              (func $depth (export "depth") (param i32) (result i32)
+                (if (result i32) (i32.eqz (local.get 0))
+                  (then (i32.const 0))
+                  (else (i32.add (i32.const 1)
+                    (call $depth (i32.sub (local.get 0) (i32.const 1))))))))
+        "#;
+        let instance = instantiate(source, limits).unwrap();
+        let invoke = |name, arg| instance.invoke(name, &[Value::I32(arg)]);
+        assert_eq!(invoke("grow", 3), Ok(vec![Value::I32(1)]));
+        assert_eq!(invoke("grow", 1), Ok(vec![Value::I32(-1)]));
+        assert_eq!(instance.get_memory("memory").unwrap().grow(1), None);
+        assert_eq!(invoke("grow_table", 4), Ok(vec![Value::I32(5)]));
+        assert_eq!(invoke("grow_table", 2), Ok(vec![Value::I32(-1)]));
+        let table = instance.get_table("table").unwrap();
+        assert_eq!(table.grow(1, Value::FuncRef(None)), Some(9));
+        assert_eq!(table.grow(1, Value::FuncRef(None)), None);
+        assert_eq!(invoke("depth", 40), Ok(vec![Value::I32(40)]));
+        assert_eq!(invoke("depth", 60), Err(Trap::StackOverflow));
+
+        // no call at all, the values are pushed by a single frame.
+        let push = format!(
+            "(module (func (export \"push\") {} {}))",
+            "(i32.const 0)".repeat(60),
+            "(drop)".repeat(60)
+        );
+        let instance = instantiate(&push, limits).unwrap();
+        assert_eq!(instance.invoke("push", &[]), Err(Trap::StackOverflow));
+
+        let limits = ResourceLimits {
+            stack_size: MAX_STACK_SIZE,
+            ..limits
+        };
+        let instance = instantiate(source, limits).unwrap();
+        let invoke = |arg| instance.invoke("depth", &[Value::I32(arg)]);
+        assert_eq!(invoke(99), Ok(vec![Value::I32(99)]));
+        assert_eq!(invoke(100), Err(Trap::CallStackExhausted));
+    }
+
+    #[test]
+    fn default_limits_should_bound_tables() {
+        let new = |source| Instance::new(&crate::text::parse(source).unwrap(), &[]);
+        assert_eq!(
+            new("(table 100000000 funcref)").unwrap_err(),
+            InstantiationError::ResourceLimitExceeded("table")
+        );
+
+        let source = r#"
+            (module
+              (table (export "table") 10000 funcref)
+              (func (export "grow") (param i32) (result i32)
+                (table.grow (ref.null func) (local.get 0))))
+        "#;
+        let instance = new(source).unwrap();
+        assert_eq!(
+            instance.invoke("grow", &[Value::I32(1)]),
+            Ok(vec![Value::I32(-1)])
+        );
+        let table = instance.get_table("table").unwrap();
+        assert_eq!(table.grow(1, Value::FuncRef(None)), None);
+
+        let limits = ResourceLimits {
+            table_elements: 20_000,
+            ..ResourceLimits::DEFAULT
+        };
+        let instance = instantiate(source, limits).unwrap();
+        assert_eq!(
+            instance.invoke("grow", &[Value::I32(10_000)]),
+            Ok(vec![Value::I32(10_000)])
+        );
+    }
+}
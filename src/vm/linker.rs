@@ -0,0 +1,230 @@
+use super::*;
+
+/// Resolve imports by name, the items are registered under `(module, name)` pairs.
+#[derive(Clone, Debug, Default)]
+pub struct Linker {
+    definitions: HashMap<(String, String), Extern>,
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+    /// `None` means unlimited.
+    fuel: Option<u64>,
+    fuel_costs: Rc<FuelCosts>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an item, the previous one with the same name is replaced.
+    pub fn define(&mut self, module: &str, name: &str, item: Extern) -> &mut Self {
+        self.definitions
+            .insert((module.to_string(), name.to_string()), item);
+        self
+    }
+
+    /// Register a host function with its signature.
+    pub fn func<F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func_type: FunctionType,
+        callback: F,
+    ) -> &mut Self
+    where
+        F: Fn(&[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    {
+        self.define(module, name, Extern::Func(Func::new(func_type, callback)))
+    }
+
+    /// Limit the resources of instances created by this linker.
+    pub fn limiter(&mut self, limiter: impl ResourceLimiter + 'static) -> &mut Self {
+        self.limiter = Some(Rc::new(limiter));
+        self
+    }
+
+    /// Meter the instances created by this linker from their start functions,
+    /// each instance gets its own fuel.
+    pub fn fuel(&mut self, fuel: u64) -> &mut Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn fuel_costs(&mut self, costs: FuelCosts) -> &mut Self {
+        self.fuel_costs = Rc::new(costs);
+        self
+    }
+
+    pub fn get(&self, module: &str, name: &str) -> Option<&Extern> {
+        self.definitions
+            .get(&(module.to_string(), name.to_string()))
+    }
+
+    /// Resolve every import of the module, then instantiate it.
+    /// The caller keeps the instance alive while its functions are stored in shared tables
+    /// or globals, a [`Store`] does it for all of its instances.
+    pub fn instantiate(&self, module: &WasmModule) -> Result<Instance, InstantiationError> {
+        let instance =
+            self.instantiate_with(module, |module, name| self.get(module, name).cloned())?;
+        instance.start(module)?;
+        Ok(instance)
+    }
+
+    /// Instantiate the module with the limiter and the fuel of this linker,
+    /// the imports are resolved by `lookup`. The start function isn't run yet.
+    pub(crate) fn instantiate_with<F>(
+        &self,
+        module: &WasmModule,
+        lookup: F,
+    ) -> Result<Instance, InstantiationError>
+    where
+        F: Fn(&str, &str) -> Option<Extern>,
+    {
+        let descriptions = module
+            .import_section
+            .as_ref()
+            .map_or(&[][..], |section| &section.desc[..]);
+        let imports = descriptions
+            .iter()
+            .map(|description| {
+                lookup(description.module(), description.name()).ok_or_else(|| {
+                    InstantiationError::UnknownImport {
+                        module: description.module().to_string(),
+                        name: description.name().to_string(),
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let metering = Metering::new(self.fuel, self.fuel_costs.clone());
+        Instance::instantiate(module, &imports, self.limiter.clone(), metering)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I32: ValueType = ValueType::NumType(NumberType::I32);
+
+    // (module
+    //   (import "env" "print" (func $print (param i32)))
+    //   (import "env" "double" (func $double (param i32) (result i32)))
+    //   (func (export "run") (param i32) (result i32)
+    //     local.get 0 call $print local.get 0 call $double))
+    const MODULE: [u8; 75] = [
+        0, 97, 115, 109, 1, 0, 0, 0, 1, 10, 2, 96, 1, 127, 0, 96, 1, 127, 1, 127, 2, 26, 2, 3, 101,
+        110, 118, 5, 112, 114, 105, 110, 116, 0, 0, 3, 101, 110, 118, 6, 100, 111, 117, 98, 108,
+        101, 0, 1, 3, 2, 1, 1, 7, 7, 1, 3, 114, 117, 110, 0, 2, 10, 12, 1, 10, 0, 32, 0, 16, 0, 32,
+        0, 16, 1, 11,
+    ];
+
+    fn func_type(params: &[ValueType], results: &[ValueType]) -> FunctionType {
+        FunctionType {
+            param_types: params.to_vec(),
+            return_types: results.to_vec(),
+        }
+    }
+
+    #[test]
+    fn host_functions_should_work() {
+        let printed = Rc::new(RefCell::new(vec![]));
+        let output = printed.clone();
+        let mut linker = Linker::new();
+        linker
+            .func("env", "print", func_type(&[I32], &[]), move |args| {
+                output.borrow_mut().push(args[0].clone());
+                Ok(vec![])
+            })
+            .func(
+                "env",
+                "double",
+                func_type(&[I32], &[I32]),
+                |args| match args {
+                    [Value::I32(v)] if *v < 100 => Ok(vec![Value::I32(v * 2)]),
+                    _ => Err(Trap::Host("too large".to_string())),
+                },
+            );
+
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        let instance = linker.instantiate(&module).unwrap();
+        assert_eq!(
+            instance.invoke("run", &[Value::I32(21)]),
+            Ok(vec![Value::I32(42)])
+        );
+        assert_eq!(
+            instance.invoke("run", &[Value::I32(100)]),
+            Err(Trap::Host("too large".to_string()))
+        );
+        assert_eq!(*printed.borrow(), vec![Value::I32(21), Value::I32(100)]);
+    }
+
+    #[test]
+    fn host_function_results_should_be_checked() {
+        let mut linker = Linker::new();
+        linker
+            .func("env", "print", func_type(&[I32], &[]), |_| Ok(vec![]))
+            .func("env", "double", func_type(&[I32], &[I32]), |_| {
+                Ok(vec![Value::I64(0)])
+            });
+
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        let instance = linker.instantiate(&module).unwrap();
+        assert_eq!(
+            instance.invoke("run", &[Value::I32(1)]),
+            Err(Trap::TypeMismatch)
+        );
+    }
+
+    #[test]
+    fn unresolved_imports_should_fail() {
+        let module = WasmModule::from_bytes(&MODULE).unwrap();
+        let mut linker = Linker::new();
+        linker.func("env", "print", func_type(&[I32], &[]), |_| Ok(vec![]));
+        assert_eq!(
+            linker.instantiate(&module).unwrap_err(),
+            InstantiationError::UnknownImport {
+                module: "env".to_string(),
+                name: "double".to_string()
+            }
+        );
+
+        linker.func("env", "double", func_type(&[I32], &[]), |_| Ok(vec![]));
+        assert_eq!(
+            linker.instantiate(&module).unwrap_err().to_string(),
+            "incompatible import type for env.double: \
+             expected (func (param i32) (result i32)), got (func (param i32))"
+        );
+    }
+
+    #[test]
+    fn start_function_should_consume_fuel() {
+        let source = r#"
+            (module
+              (global $n (mut i32) (i32.const 100))
+              (func $start
+                (loop
+                  (global.set $n (i32.sub (global.get $n) (i32.const 1)))
+                  (br_if 0 (global.get $n))))
+              (func (export "n") (result i32) (global.get $n))
+              (start $start))
+        "#;
+        let module = crate::text::parse(source).unwrap();
+        let mut linker = Linker::new();
+        linker.fuel(100);
+        assert_eq!(
+            linker.instantiate(&module).unwrap_err(),
+            InstantiationError::Trap(Trap::OutOfFuel)
+        );
+
+        linker.fuel(1000);
+        let instance = linker.instantiate(&module).unwrap();
+        let fuel = instance.fuel().unwrap();
+        assert!(fuel > 0 && fuel < 1000 - 100 * 5, "{}", fuel);
+        assert_eq!(instance.invoke("n", &[]), Ok(vec![Value::I32(0)]));
+        assert!(instance.fuel().unwrap() < fuel);
+
+        let mut costs = FuelCosts::new();
+        costs.set_default(0);
+        linker.fuel(0).fuel_costs(costs);
+        assert!(linker.instantiate(&module).is_ok());
+    }
+}
@@ -0,0 +1,365 @@
+use super::*;
+use crate::sections::Limits;
+use crate::validation::MAX_PAGES;
+use core::cell::{Ref, RefMut};
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#memory-instances
+/// A linear memory, its size is always a multiple of `PAGE_SIZE`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MemoryInstance {
+    data: Vec<u8>,
+    max: Option<u32>,
+    /// The limiter of the instance which defines this memory.
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+}
+
+impl MemoryInstance {
+    /// The effective address is `base + offset`, it's checked against the size of memory
+    /// before accessing, so the sum never wraps around.
+    fn range(&self, address: u64, len: usize) -> Result<core::ops::Range<usize>, Trap> {
+        let start = usize::try_from(address).map_err(|_| Trap::MemoryOutOfBounds)?;
+        start
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .map(|end| start..end)
+            .ok_or(Trap::MemoryOutOfBounds)
+    }
+
+    pub(crate) fn load<const N: usize>(&self, address: u64) -> Result<[u8; N], Trap> {
+        let range = self.range(address, N)?;
+        let mut buf = [0u8; N];
+        buf.copy_from_slice(&self.data[range]);
+        Ok(buf)
+    }
+
+    pub(crate) fn store(&mut self, address: u64, bytes: &[u8]) -> Result<(), Trap> {
+        let range = self.range(address, bytes.len())?;
+        self.data[range].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// A reference to memory instance, it can be exported to the host or other modules.
+#[derive(Clone)]
+pub struct Memory(pub(crate) Rc<RefCell<MemoryInstance>>);
+
+impl Memory {
+    /// Allocate a memory of `min` pages, it can grow up to `max` pages, or 4GiB if no max.
+    pub fn new(min: u32, max: Option<u32>) -> Result<Self, Trap> {
+        if min > max.unwrap_or(MAX_PAGES).min(MAX_PAGES) {
+            return Err(Trap::MemoryOutOfBounds);
+        }
+        let memory = Self(Rc::new(RefCell::new(MemoryInstance {
+            data: vec![],
+            max,
+            limiter: None,
+        })));
+        match memory.grow(min) {
+            Some(_) => Ok(memory),
+            None => Err(Trap::MemoryOutOfBounds),
+        }
+    }
+
+    /// Allocate a memory described by the limits of memory section.
+    pub fn from_limits(limits: &Limits) -> Result<Self, Trap> {
+        Self::new(limits.min.unwrap_or(0), limits.max)
+    }
+
+    /// The current size in pages.
+    pub fn size(&self) -> u32 {
+        (self.0.borrow().data.len() / PAGE_SIZE) as u32
+    }
+
+    /// The current size in bytes.
+    pub fn data_size(&self) -> usize {
+        self.0.borrow().data.len()
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        self.0.borrow().max
+    }
+
+    /// https://webassembly.github.io/spec/core/exec/modules.html#grow-mem
+    /// Grow by `delta` pages and return the previous size, the new pages are zeroed.
+    /// `None` if it would exceed the max, the resource limiter refuses, or the host fails to allocate.
+    pub fn grow(&self, delta: u32) -> Option<u32> {
+        let mut memory = self.0.borrow_mut();
+        let pages = (memory.data.len() / PAGE_SIZE) as u32;
+        let max = memory.max.unwrap_or(MAX_PAGES).min(MAX_PAGES);
+        let new_pages = pages.checked_add(delta).filter(|pages| *pages <= max)?;
+        if let Some(limiter) = &memory.limiter {
+            if !limiter.memory_growing(pages, new_pages, memory.max) {
+                return None;
+            }
+        }
+        let new_len = new_pages as usize * PAGE_SIZE;
+        let additional = new_len - memory.data.len();
+        memory.data.try_reserve_exact(additional).ok()?;
+        memory.data.resize(new_len, 0);
+        Some(pages)
+    }
+
+    /// Every growth is consulted with the limiter, no matter it's grown by the guest or the host.
+    pub(crate) fn set_limiter(&self, limiter: Option<Rc<dyn ResourceLimiter>>) {
+        self.0.borrow_mut().limiter = limiter;
+    }
+
+    /// Copy the bytes at `offset` into `buf`, trap if the range is out of bounds.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Result<(), Trap> {
+        let memory = self.0.borrow();
+        let range = memory.range(offset as u64, buf.len())?;
+        buf.copy_from_slice(&memory.data[range]);
+        Ok(())
+    }
+
+    /// Copy `bytes` to the memory at `offset`, trap if the range is out of bounds.
+    pub fn write(&self, offset: usize, bytes: &[u8]) -> Result<(), Trap> {
+        self.0.borrow_mut().store(offset as u64, bytes)
+    }
+
+    /// Set `n` bytes to `value` starting from `offset`, nothing is written if it's out of bounds.
+    pub fn fill(&self, offset: usize, n: usize, value: u8) -> Result<(), Trap> {
+        let mut memory = self.0.borrow_mut();
+        let range = memory.range(offset as u64, n)?;
+        memory.data[range].fill(value);
+        Ok(())
+    }
+
+    /// Copy `n` bytes from `src` to `dst`, the ranges may overlap.
+    pub(crate) fn copy(&self, dst: usize, src: usize, n: usize) -> Result<(), Trap> {
+        let mut memory = self.0.borrow_mut();
+        let src = memory.range(src as u64, n)?;
+        let dst = memory.range(dst as u64, n)?;
+        memory.data.copy_within(src, dst.start);
+        Ok(())
+    }
+
+    /// Borrow the whole memory, it must be released before calling into the guest again.
+    pub fn data(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.0.borrow(), |memory| &memory.data[..])
+    }
+
+    pub fn data_mut(&self) -> RefMut<'_, [u8]> {
+        RefMut::map(self.0.borrow_mut(), |memory| &mut memory.data[..])
+    }
+}
+
+/// Two references are equal only if they point to the same memory instance.
+impl PartialEq for Memory {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::fmt::Debug for Memory {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Memory")
+            .field("size", &self.size())
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse;
+
+    #[test]
+    fn grow_memory_should_check_max() {
+        let memory = Memory::new(1, Some(3)).unwrap();
+        assert_eq!(memory.size(), 1);
+        assert_eq!(memory.data_size(), PAGE_SIZE);
+        assert_eq!(memory.grow(2), Some(1));
+        assert_eq!(memory.grow(1), None);
+        assert_eq!(memory.grow(0), Some(3));
+        assert_eq!(memory.size(), 3);
+
+        let memory = Memory::new(0, None).unwrap();
+        assert_eq!(memory.grow(MAX_PAGES + 1), None);
+        assert_eq!(memory.grow(u32::MAX), None);
+        assert!(Memory::new(2, Some(1)).is_err());
+    }
+
+    #[test]
+    fn host_access_should_check_bounds() {
+        let memory = Memory::new(1, None).unwrap();
+        memory.write(PAGE_SIZE - 2, &[1, 2]).unwrap();
+        let mut buf = [0; 2];
+        memory.read(PAGE_SIZE - 2, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(memory.data()[PAGE_SIZE - 1], 2);
+
+        assert_eq!(
+            memory.write(PAGE_SIZE - 1, &[1, 2]),
+            Err(Trap::MemoryOutOfBounds)
+        );
+        assert_eq!(
+            memory.read(usize::MAX, &mut buf),
+            Err(Trap::MemoryOutOfBounds)
+        );
+        // the memory is not touched by the failed write.
+        assert_eq!(memory.data()[PAGE_SIZE - 1], 2);
+
+        memory.data_mut()[0] = 7;
+        memory.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [7, 0]);
+    }
+
+    #[test]
+    fn memory_instructions_should_work() {
+        let source = r#"
+            (module
+              (memory (export "memory") 1 2)
+              (data (i32.const 0) "\80\ff\ff\ff\ff\ff\ff\ff")
+              (func (export "loads") (result i64)
+                (i64.load8_s (i32.const 0))
+                (i64.add (i64.load8_u (i32.const 0)))
+                (i64.add (i64.load16_s (i32.const 0)))
+                (i64.add (i64.load16_u (i32.const 0)))
+                (i64.add (i64.load32_s (i32.const 0)))
+                (i64.add (i64.load32_u (i32.const 0)))
+                (i64.add (i64.extend_i32_s (i32.load8_s (i32.const 0))))
+                (i64.add (i64.extend_i32_u (i32.load16_u (i32.const 0)))))
+              (func (export "store") (param i32 i64)
+                (i64.store32 offset=2 (local.get 0) (local.get 1))
+                (i32.store8 (local.get 0) (i32.const 0x1234))
+                (f64.store offset=8 (local.get 0) (f64.const 1.5)))
+              (func (export "load_f64") (param i32) (result f64)
+                (f64.load offset=8 (local.get 0)))
+              (func (export "size") (result i32) (memory.size))
+              (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0))))
+        "#;
+        let module = parse(source).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        let memory = instance.get_memory("memory").unwrap();
+
+        // the signed loads are all -128, the unsigned ones are 0x80, 0xff80, 0xffff_ff80 and 0xff80.
+        let expected = -128 * 4 + 0x80 + 0xff80 * 2 + 0xffff_ff80;
+        assert_eq!(
+            instance.invoke("loads", &[]),
+            Ok(vec![Value::I64(expected)])
+        );
+
+        let args = [Value::I32(16), Value::I64(0x0102_0304_0506)];
+        assert_eq!(instance.invoke("store", &args), Ok(vec![]));
+        let mut buf = [0; 6];
+        memory.read(16, &mut buf).unwrap();
+        assert_eq!(buf, [0x34, 0, 6, 5, 4, 3]);
+        assert_eq!(
+            instance.invoke("load_f64", &[Value::I32(16)]),
+            Ok(vec![Value::F64(1.5)])
+        );
+
+        // the effective address is out of bounds, even if the base is not.
+        let args = [Value::I32(PAGE_SIZE as i32 - 8), Value::I64(0)];
+        assert_eq!(
+            instance.invoke("store", &args),
+            Err(Trap::MemoryOutOfBounds)
+        );
+        let args = [Value::I32(-1), Value::I64(0)];
+        assert_eq!(
+            instance.invoke("store", &args),
+            Err(Trap::MemoryOutOfBounds)
+        );
+
+        assert_eq!(instance.invoke("size", &[]), Ok(vec![Value::I32(1)]));
+        assert_eq!(
+            instance.invoke("grow", &[Value::I32(1)]),
+            Ok(vec![Value::I32(1)])
+        );
+        assert_eq!(
+            instance.invoke("grow", &[Value::I32(1)]),
+            Ok(vec![Value::I32(-1)])
+        );
+        assert_eq!(
+            instance.invoke("grow", &[Value::I32(-1)]),
+            Ok(vec![Value::I32(-1)])
+        );
+        assert_eq!(memory.size(), 2);
+        assert_eq!(
+            instance.invoke("load_f64", &[Value::I32(PAGE_SIZE as i32 * 2 - 16)]),
+            Ok(vec![Value::F64(0.0)])
+        );
+    }
+
+    #[test]
+    fn bulk_memory_instructions_should_work() {
+        use crate::serialize::Encode;
+        use crate::validation::{validate, ValidationError};
+
+        let source = r#"
+            (module
+              (memory (export "memory") 1)
+              (data $passive "\01\02\03\04")
+              (data $active (i32.const 0) "\ff")
+              (func (export "init") (param i32 i32 i32)
+                (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "drop") (data.drop $passive))
+              (func (export "init_active") (param i32)
+                (memory.init 0 $active (i32.const 0) (i32.const 0) (local.get 0)))
+              (func (export "copy") (param i32 i32 i32)
+                (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "fill") (param i32 i32 i32)
+                (memory.fill (local.get 0) (local.get 1) (local.get 2))))
+        "#;
+        // the data count section is emitted, and the operands survive encoding.
+        let bytes = parse(source).unwrap().to_bytes();
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        assert_eq!(module.data_count_section.as_ref().unwrap().count, 2);
+        let instance = Instance::new(&module, &[]).unwrap();
+        let memory = instance.get_memory("memory").unwrap();
+        let invoke = |name, args: &[i32]| {
+            let args = args.iter().map(|arg| Value::I32(*arg)).collect::<Vec<_>>();
+            instance.invoke(name, &args)
+        };
+        let read = |offset| {
+            let mut buf = [0; 4];
+            memory.read(offset, &mut buf).unwrap();
+            buf
+        };
+
+        assert_eq!(invoke("init", &[8, 1, 3]), Ok(vec![]));
+        assert_eq!(read(8), [2, 3, 4, 0]);
+        // nothing is written if any byte is out of bounds.
+        assert_eq!(invoke("init", &[16, 2, 3]), Err(Trap::MemoryOutOfBounds));
+        let end = PAGE_SIZE as i32 - 2;
+        assert_eq!(invoke("init", &[end, 0, 4]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(read(16), [0; 4]);
+        assert_eq!(read(PAGE_SIZE - 4), [0; 4]);
+
+        // overlapping ranges are copied as if through a temporary buffer.
+        assert_eq!(invoke("copy", &[9, 8, 3]), Ok(vec![]));
+        assert_eq!(read(8), [2, 2, 3, 4]);
+        assert_eq!(invoke("copy", &[7, 8, 3]), Ok(vec![]));
+        assert_eq!(read(7), [2, 2, 3, 3]);
+        assert_eq!(invoke("copy", &[end, 0, 4]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(invoke("fill", &[12, 0x1ff, 2]), Ok(vec![]));
+        assert_eq!(read(12), [0xff, 0xff, 0, 0]);
+        assert_eq!(invoke("fill", &[end, 0, 4]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(invoke("fill", &[PAGE_SIZE as i32, 0, 0]), Ok(vec![]));
+
+        // dropped and active segments are empty.
+        assert_eq!(invoke("drop", &[]), Ok(vec![]));
+        assert_eq!(invoke("init", &[0, 0, 1]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(invoke("init", &[0, 0, 0]), Ok(vec![]));
+        assert_eq!(invoke("init_active", &[1]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(read(0)[0], 0xff);
+
+        // data segments can't be referenced without the data count section.
+        let mut module = parse("(memory 1) (data \"\") (func (data.drop 1))").unwrap();
+        match validate(&module) {
+            Err(ValidationError::Function { error, .. }) => {
+                assert_eq!(*error, ValidationError::UnknownData(1))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        module.data_count_section = None;
+        match validate(&module) {
+            Err(ValidationError::Function { error, .. }) => {
+                assert_eq!(*error, ValidationError::DataCountRequired)
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
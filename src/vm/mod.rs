@@ -0,0 +1,333 @@
+use alloc::rc::{Rc, Weak};
+use core::cell::{Cell, RefCell};
+
+use crate::instructions::*;
+use crate::sections::{
+    DataMode, ElementItems, ElementMode, ExportType, FunctionType, GlobalType, ImportDesc, Limits,
+    NumberType, ReferenceType, TableType, Tag, ValueType, WasmModule,
+};
+use crate::validation::validate;
+use std::collections::HashMap;
+
+pub mod errors;
+pub mod fuel;
+pub mod function;
+pub mod global;
+pub mod instance;
+pub mod interpreter;
+pub mod limits;
+pub mod linker;
+pub mod memory;
+pub mod numeric;
+pub mod stack;
+pub mod store;
+pub mod table;
+pub mod trap;
+pub mod typed_func;
+pub mod value;
+
+pub(crate) use self::fuel::Metering;
+pub(crate) use self::stack::*;
+pub use self::{
+    errors::*, fuel::*, function::*, global::Global, instance::*, limits::*, linker::*,
+    memory::Memory, stack::OperandStack, store::Store, table::Table, trap::*, typed_func::*,
+    value::*,
+};
+
+/// How many frames can be active at the same time by default.
+pub const MAX_CALL_DEPTH: usize = 1 << 14;
+
+/// How many values the operand stack can hold by default.
+pub const MAX_STACK_SIZE: usize = 1 << 20;
+
+/// How many elements a table can hold by default, every element takes host memory eagerly.
+pub const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+/// The size of a memory page, 64KiB.
+pub const PAGE_SIZE: usize = 65536;
+
+/// The execution engine, the frames live in the heap, so deep recursion of guest code
+/// never overflows the host stack.
+#[derive(Clone, Debug, Default)]
+pub struct WasmVM {
+    operand_stack: OperandStack,
+    call_stack: Vec<Frame>,
+    /// `None` means unlimited, the counter is shared by the calls through the same instance.
+    fuel: Rc<Cell<Option<u64>>>,
+    costs: Rc<FuelCosts>,
+    suspended: Option<Suspended>,
+    /// `None` means the default limits.
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+}
+
+/// The call which ran out of fuel.
+#[derive(Clone, Debug)]
+struct Suspended {
+    func: Func,
+    height: usize,
+    depth: usize,
+}
+
+impl WasmVM {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every executed instruction consumes fuel by the costs,
+    /// [`Trap::OutOfFuel`] is raised when the fuel isn't enough for the next instruction.
+    pub fn with_fuel(fuel: u64, costs: Rc<FuelCosts>) -> Self {
+        Self::with_metering(Rc::new(Cell::new(Some(fuel))), costs)
+    }
+
+    /// Consume the fuel of the shared counter, the nested calls of host functions use it too.
+    pub(crate) fn with_metering(fuel: Rc<Cell<Option<u64>>>, costs: Rc<FuelCosts>) -> Self {
+        Self {
+            fuel,
+            costs,
+            ..Self::default()
+        }
+    }
+
+    /// The fuel left, `None` if it's unlimited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel.get()
+    }
+
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel.set(fuel);
+    }
+
+    pub fn set_limiter(&mut self, limiter: Option<Rc<dyn ResourceLimiter>>) {
+        self.limiter = limiter;
+    }
+
+    pub(crate) fn limiter(&self) -> &dyn ResourceLimiter {
+        self.limiter.as_deref().unwrap_or(&ResourceLimits::DEFAULT)
+    }
+
+    /// Call the function with arguments, return the results.
+    /// The stacks are restored to the state before invoking if trapped,
+    /// except running out of fuel, the call can be resumed then.
+    pub fn invoke(&mut self, func: &Func, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let suspended = Suspended {
+            func: func.clone(),
+            height: self.operand_stack.len(),
+            depth: self.call_stack.len(),
+        };
+        self.operand_stack.extend(args.to_vec());
+        let result = self
+            .call(func.clone())
+            .and_then(|_| self.execute(suspended.depth));
+        self.complete(suspended, result)
+    }
+
+    /// Continue the call which ran out of fuel, it should be refueled first.
+    pub fn resume(&mut self) -> Result<Vec<Value>, Trap> {
+        let suspended = self
+            .suspended
+            .take()
+            .ok_or(Trap::Unsupported("resuming without a call out of fuel"))?;
+        let result = self.execute(suspended.depth);
+        self.complete(suspended, result)
+    }
+
+    /// Whether a call ran out of fuel and can be resumed.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    fn complete(
+        &mut self,
+        suspended: Suspended,
+        result: Result<(), Trap>,
+    ) -> Result<Vec<Value>, Trap> {
+        let Suspended {
+            func,
+            height,
+            depth,
+        } = &suspended;
+        let result = result.and_then(|_| {
+            let arity = func.func_type().return_types.len();
+            if self.operand_stack.len() != height + arity {
+                return Err(Trap::TypeMismatch);
+            }
+            self.operand_stack.pop_n(arity)
+        });
+
+        match result {
+            Err(Trap::OutOfFuel) if self.call_stack.len() > *depth => {
+                self.suspended = Some(suspended)
+            }
+            Err(_) => {
+                self.operand_stack.truncate(*height);
+                self.call_stack.truncate(*depth);
+            }
+            Ok(_) => (),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I32: ValueType = ValueType::NumType(NumberType::I32);
+
+    fn func_type(params: &[ValueType], results: &[ValueType]) -> FunctionType {
+        FunctionType {
+            param_types: params.to_vec(),
+            return_types: results.to_vec(),
+        }
+    }
+
+    /// Define functions with the same type in a fresh instance, the bodies include the last `end`.
+    fn define(
+        func_type: FunctionType,
+        locals: &[ValueType],
+        bodies: &[&[u8]],
+    ) -> (Rc<ModuleInstance>, Vec<Func>) {
+        let instance = ModuleInstance::new(vec![func_type]);
+        let funcs = bodies
+            .iter()
+            .map(|bytes| {
+                let (expr, _) = Expressions::from_bytes(bytes, 0).unwrap();
+                let body = FunctionBody::new(locals.to_vec(), expr.instructions);
+                instance.push_function(0, body).unwrap()
+            })
+            .collect();
+        (instance, funcs)
+    }
+
+    fn invoke(func: &Func, args: &[i32]) -> Result<Vec<Value>, Trap> {
+        let args: Vec<Value> = args.iter().map(|arg| Value::I32(*arg)).collect();
+        WasmVM::new().invoke(func, &args)
+    }
+
+    #[test]
+    fn add_should_work() {
+        // local.get 0, local.get 1, i32.add
+        let body = [0x20, 0, 0x20, 1, 0x6a, 0x0b];
+        let (_instance, funcs) = define(func_type(&[I32, I32], &[I32]), &[], &[&body]);
+
+        assert_eq!(invoke(&funcs[0], &[1, 2]), Ok(vec![Value::I32(3)]));
+        assert_eq!(
+            invoke(&funcs[0], &[i32::MAX, 1]),
+            Ok(vec![Value::I32(i32::MIN)])
+        );
+        // wrong arguments
+        assert_eq!(invoke(&funcs[0], &[1]), Err(Trap::StackUnderflow));
+    }
+
+    #[test]
+    fn loop_and_branch_should_work() {
+        // factorial with a loop:
+        // acc = 1; block loop (br_if 1 (n == 0)) acc *= n; n -= 1; br 0 end end; acc
+        let body = [
+            0x41, 1, 0x21, 1, 0x02, 0x40, 0x03, 0x40, 0x20, 0, 0x45, 0x0d, 1, 0x20, 1, 0x20, 0,
+            0x6c, 0x21, 1, 0x20, 0, 0x41, 1, 0x6b, 0x21, 0, 0x0c, 0, 0x0b, 0x0b, 0x20, 1, 0x0b,
+        ];
+        let (_instance, funcs) = define(func_type(&[I32], &[I32]), &[I32], &[&body]);
+
+        assert_eq!(invoke(&funcs[0], &[0]), Ok(vec![Value::I32(1)]));
+        assert_eq!(invoke(&funcs[0], &[5]), Ok(vec![Value::I32(120)]));
+    }
+
+    #[test]
+    fn recursive_call_should_work() {
+        // fib(n) = if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+        let body = [
+            0x20, 0, 0x41, 2, 0x49, 0x04, 0x7f, 0x20, 0, 0x05, 0x20, 0, 0x41, 1, 0x6b, 0x10, 0,
+            0x20, 0, 0x41, 2, 0x6b, 0x10, 0, 0x6a, 0x0b, 0x0b,
+        ];
+        let (_instance, funcs) = define(func_type(&[I32], &[I32]), &[], &[&body]);
+
+        assert_eq!(invoke(&funcs[0], &[1]), Ok(vec![Value::I32(1)]));
+        assert_eq!(invoke(&funcs[0], &[20]), Ok(vec![Value::I32(6765)]));
+    }
+
+    #[test]
+    fn br_table_should_work() {
+        // block block block (br_table 0 1 2 (local.get 0)) end
+        // return 10 end return 11 end 12
+        let body = [
+            0x02, 0x40, 0x02, 0x40, 0x02, 0x40, 0x20, 0, 0x0e, 2, 0, 1, 2, 0x0b, 0x41, 10, 0x0f,
+            0x0b, 0x41, 11, 0x0f, 0x0b, 0x41, 12, 0x0b,
+        ];
+        let (_instance, funcs) = define(func_type(&[I32], &[I32]), &[], &[&body]);
+
+        assert_eq!(invoke(&funcs[0], &[0]), Ok(vec![Value::I32(10)]));
+        assert_eq!(invoke(&funcs[0], &[1]), Ok(vec![Value::I32(11)]));
+        assert_eq!(invoke(&funcs[0], &[7]), Ok(vec![Value::I32(12)]));
+    }
+
+    #[test]
+    fn traps_should_stop_execution() {
+        let div = [0x20, 0, 0x20, 1, 0x6d, 0x0b];
+        let unreachable = [0x00, 0x0b];
+        // call itself forever
+        let recursion = [0x20, 0, 0x20, 1, 0x10, 2, 0x0b];
+        let (_instance, funcs) = define(
+            func_type(&[I32, I32], &[I32]),
+            &[],
+            &[&div, &unreachable, &recursion],
+        );
+
+        assert_eq!(invoke(&funcs[0], &[1, 0]), Err(Trap::IntegerDivideByZero));
+        assert_eq!(
+            invoke(&funcs[0], &[i32::MIN, -1]),
+            Err(Trap::IntegerOverflow)
+        );
+        assert_eq!(invoke(&funcs[0], &[-7, 2]), Ok(vec![Value::I32(-3)]));
+        assert_eq!(invoke(&funcs[1], &[0, 0]), Err(Trap::Unreachable));
+        assert_eq!(invoke(&funcs[2], &[0, 0]), Err(Trap::CallStackExhausted));
+
+        // the vm still works after trapping
+        let mut vm = WasmVM::new();
+        let args = [Value::I32(1), Value::I32(0)];
+        assert!(vm.invoke(&funcs[0], &args).is_err());
+        let args = [Value::I32(6), Value::I32(3)];
+        assert_eq!(vm.invoke(&funcs[0], &args), Ok(vec![Value::I32(2)]));
+    }
+
+    #[test]
+    fn memory_access_should_work() {
+        // i32.store (i32.const 8) (local.get 0); i32.load offset=4 (i32.const 4)
+        let store_load = [0x41, 8, 0x20, 0, 0x36, 2, 0, 0x41, 4, 0x28, 2, 4, 0x0b];
+        // i32.load (local.get 0)
+        let load = [0x20, 0, 0x28, 2, 0, 0x0b];
+        let (instance, funcs) = define(func_type(&[I32], &[I32]), &[], &[&store_load, &load]);
+        let memory = Memory::new(1, None).unwrap();
+        *instance.memory.borrow_mut() = Some(memory.clone());
+
+        assert_eq!(invoke(&funcs[0], &[0x1234]), Ok(vec![Value::I32(0x1234)]));
+        assert_eq!(&memory.data()[8..12], &[0x34, 0x12, 0, 0]);
+        assert_eq!(invoke(&funcs[1], &[65532]), Ok(vec![Value::I32(0)]));
+        assert_eq!(invoke(&funcs[1], &[65533]), Err(Trap::MemoryOutOfBounds));
+        assert_eq!(invoke(&funcs[1], &[-1]), Err(Trap::MemoryOutOfBounds));
+    }
+
+    #[test]
+    fn call_indirect_should_check_type() {
+        // call_indirect (type 0) (local.get 1) (local.get 0)
+        let call = [0x20, 0, 0x20, 1, 0x11, 0, 0, 0x0b];
+        let (instance, funcs) = define(func_type(&[I32, I32], &[I32]), &[], &[&call]);
+        // a function with a different type in the table
+        let (_other, others) = define(func_type(&[], &[]), &[], &[&[0x0b]]);
+        let table = Table::new(ReferenceType::FuncRef, 3, None).unwrap();
+        table
+            .set(0, Value::FuncRef(Some(funcs[0].clone())))
+            .unwrap();
+        table
+            .set(1, Value::FuncRef(Some(others[0].clone())))
+            .unwrap();
+        instance.tables.borrow_mut().push(table);
+
+        assert_eq!(
+            invoke(&funcs[0], &[0, 1]),
+            Err(Trap::IndirectCallTypeMismatch)
+        );
+        assert_eq!(invoke(&funcs[0], &[0, 2]), Err(Trap::UninitializedElement));
+        assert_eq!(invoke(&funcs[0], &[0, 3]), Err(Trap::UndefinedElement));
+    }
+}
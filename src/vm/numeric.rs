@@ -0,0 +1,317 @@
+use super::*;
+
+/// https://webassembly.github.io/spec/core/exec/numerics.html
+/// Pop operands with the given types, push the result of the expression.
+macro_rules! unary {
+    ($self: ident, $pop: ident, |$a: ident| $e: expr) => {{
+        let $a = $self.operand_stack.$pop()?;
+        $self.operand_stack.push($e);
+    }};
+}
+
+macro_rules! binary {
+    ($self: ident, $pop: ident, |$a: ident, $b: ident| $e: expr) => {{
+        let $b = $self.operand_stack.$pop()?;
+        let $a = $self.operand_stack.$pop()?;
+        $self.operand_stack.push($e);
+    }};
+}
+
+/// Like `binary`, but the expression may trap.
+macro_rules! try_binary {
+    ($self: ident, $pop: ident, |$a: ident, $b: ident| $e: expr) => {{
+        let $b = $self.operand_stack.$pop()?;
+        let $a = $self.operand_stack.$pop()?;
+        $self.operand_stack.push($e?);
+    }};
+}
+
+macro_rules! float_helpers {
+    ($min: ident, $max: ident, $nearest: ident, $ty: ty) => {
+        /// Any NaN operand gives NaN, and -0 is less than +0.
+        fn $min(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                <$ty>::NAN
+            } else if a == b {
+                // pick the negative zero if they're zeros
+                <$ty>::from_bits(a.to_bits() | b.to_bits())
+            } else {
+                a.min(b)
+            }
+        }
+
+        fn $max(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                <$ty>::NAN
+            } else if a == b {
+                // pick the positive zero if they're zeros
+                <$ty>::from_bits(a.to_bits() & b.to_bits())
+            } else {
+                a.max(b)
+            }
+        }
+
+        /// Round to the nearest integer, ties to even.
+        fn $nearest(a: $ty) -> $ty {
+            if a.is_nan() || a.is_infinite() || a == 0.0 {
+                return a;
+            }
+            let rounded = a.round();
+            let result = if (a - a.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+                rounded - a.signum()
+            } else {
+                rounded
+            };
+            // keep the sign, e.g. nearest(-0.4) is -0.
+            result.copysign(a)
+        }
+    };
+}
+
+float_helpers!(f32_min, f32_max, f32_nearest, f32);
+float_helpers!(f64_min, f64_max, f64_nearest, f64);
+
+/// Truncate a float to integer, trap if it's NaN or out of range.
+/// The bounds are exclusive and exactly representable by the float type.
+macro_rules! trunc {
+    ($name: ident, $from: ty, $to: ty, $lower: expr, $upper: expr) => {
+        fn $name(a: $from) -> Result<$to, Trap> {
+            if a.is_nan() {
+                return Err(Trap::InvalidConversionToInteger);
+            }
+            if a <= $lower || a >= $upper {
+                return Err(Trap::IntegerOverflow);
+            }
+            Ok(a as $to)
+        }
+    };
+}
+
+trunc!(i32_trunc_f32, f32, i32, -2147483904.0, 2147483648.0);
+trunc!(u32_trunc_f32, f32, u32, -1.0, 4294967296.0);
+trunc!(i32_trunc_f64, f64, i32, -2147483649.0, 2147483648.0);
+trunc!(u32_trunc_f64, f64, u32, -1.0, 4294967296.0);
+trunc!(
+    i64_trunc_f32,
+    f32,
+    i64,
+    -9223373136366403584.0,
+    9223372036854775808.0
+);
+trunc!(u64_trunc_f32, f32, u64, -1.0, 18446744073709551616.0);
+trunc!(
+    i64_trunc_f64,
+    f64,
+    i64,
+    -9223372036854777856.0,
+    9223372036854775808.0
+);
+trunc!(u64_trunc_f64, f64, u64, -1.0, 18446744073709551616.0);
+
+macro_rules! div_rem {
+    ($div_s: ident, $div_u: ident, $rem_s: ident, $rem_u: ident, $signed: ty, $unsigned: ty) => {
+        fn $div_s(a: $signed, b: $signed) -> Result<$signed, Trap> {
+            if b == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+            a.checked_div(b).ok_or(Trap::IntegerOverflow)
+        }
+
+        fn $div_u(a: $signed, b: $signed) -> Result<$signed, Trap> {
+            if b == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+            Ok(((a as $unsigned) / (b as $unsigned)) as $signed)
+        }
+
+        /// The remainder of MIN and -1 is 0, it doesn't overflow.
+        fn $rem_s(a: $signed, b: $signed) -> Result<$signed, Trap> {
+            if b == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+            Ok(a.wrapping_rem(b))
+        }
+
+        fn $rem_u(a: $signed, b: $signed) -> Result<$signed, Trap> {
+            if b == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+            Ok(((a as $unsigned) % (b as $unsigned)) as $signed)
+        }
+    };
+}
+
+div_rem!(i32_div_s, i32_div_u, i32_rem_s, i32_rem_u, i32, u32);
+div_rem!(i64_div_s, i64_div_u, i64_rem_s, i64_rem_u, i64, u64);
+
+impl WasmVM {
+    pub(crate) fn execute_numeric(
+        &mut self,
+        op: NumericInstructions,
+        primitive: &Option<Primitives>,
+    ) -> Result<(), Trap> {
+        use NumericInstructions::*;
+
+        match op {
+            I32Const | I64Const | F32Const | F64Const => {
+                let primitive = (*primitive).ok_or(Trap::Unsupported("const without immediate"))?;
+                self.operand_stack.push(primitive);
+            }
+
+            I32Eqz => unary!(self, pop_i32, |a| (a == 0) as i32),
+            I32Eq => binary!(self, pop_i32, |a, b| (a == b) as i32),
+            I32Ne => binary!(self, pop_i32, |a, b| (a != b) as i32),
+            I32LtSigned => binary!(self, pop_i32, |a, b| (a < b) as i32),
+            I32LtUnsigned => binary!(self, pop_i32, |a, b| ((a as u32) < (b as u32)) as i32),
+            I32GtSigned => binary!(self, pop_i32, |a, b| (a > b) as i32),
+            I32GtUnsigned => binary!(self, pop_i32, |a, b| ((a as u32) > (b as u32)) as i32),
+            I32LeSigned => binary!(self, pop_i32, |a, b| (a <= b) as i32),
+            I32LeUnsigned => binary!(self, pop_i32, |a, b| ((a as u32) <= (b as u32)) as i32),
+            I32GeSigned => binary!(self, pop_i32, |a, b| (a >= b) as i32),
+            I32GeUnsigned => binary!(self, pop_i32, |a, b| ((a as u32) >= (b as u32)) as i32),
+
+            I64Eqz => unary!(self, pop_i64, |a| (a == 0) as i32),
+            I64Eq => binary!(self, pop_i64, |a, b| (a == b) as i32),
+            I64Ne => binary!(self, pop_i64, |a, b| (a != b) as i32),
+            I64LtSigned => binary!(self, pop_i64, |a, b| (a < b) as i32),
+            I64LtUnsigned => binary!(self, pop_i64, |a, b| ((a as u64) < (b as u64)) as i32),
+            I64GtSigned => binary!(self, pop_i64, |a, b| (a > b) as i32),
+            I64GtUnsigned => binary!(self, pop_i64, |a, b| ((a as u64) > (b as u64)) as i32),
+            I64LeSigned => binary!(self, pop_i64, |a, b| (a <= b) as i32),
+            I64LeUnsigned => binary!(self, pop_i64, |a, b| ((a as u64) <= (b as u64)) as i32),
+            I64GeSigned => binary!(self, pop_i64, |a, b| (a >= b) as i32),
+            I64GeUnsigned => binary!(self, pop_i64, |a, b| ((a as u64) >= (b as u64)) as i32),
+
+            F32Eq => binary!(self, pop_f32, |a, b| (a == b) as i32),
+            F32Ne => binary!(self, pop_f32, |a, b| (a != b) as i32),
+            F32Lt => binary!(self, pop_f32, |a, b| (a < b) as i32),
+            F32Gt => binary!(self, pop_f32, |a, b| (a > b) as i32),
+            F32Le => binary!(self, pop_f32, |a, b| (a <= b) as i32),
+            F32Ge => binary!(self, pop_f32, |a, b| (a >= b) as i32),
+
+            F64Eq => binary!(self, pop_f64, |a, b| (a == b) as i32),
+            F64Ne => binary!(self, pop_f64, |a, b| (a != b) as i32),
+            F64Lt => binary!(self, pop_f64, |a, b| (a < b) as i32),
+            F64Gt => binary!(self, pop_f64, |a, b| (a > b) as i32),
+            F64Le => binary!(self, pop_f64, |a, b| (a <= b) as i32),
+            F64Ge => binary!(self, pop_f64, |a, b| (a >= b) as i32),
+
+            I32Clz => unary!(self, pop_i32, |a| a.leading_zeros() as i32),
+            I32Ctz => unary!(self, pop_i32, |a| a.trailing_zeros() as i32),
+            I32PopCnt => unary!(self, pop_i32, |a| a.count_ones() as i32),
+            I32Add => binary!(self, pop_i32, |a, b| a.wrapping_add(b)),
+            I32Sub => binary!(self, pop_i32, |a, b| a.wrapping_sub(b)),
+            I32Mul => binary!(self, pop_i32, |a, b| a.wrapping_mul(b)),
+            I32DivSigned => try_binary!(self, pop_i32, |a, b| i32_div_s(a, b)),
+            I32DivUnsigned => try_binary!(self, pop_i32, |a, b| i32_div_u(a, b)),
+            I32RemSigned => try_binary!(self, pop_i32, |a, b| i32_rem_s(a, b)),
+            I32RemUnsigned => try_binary!(self, pop_i32, |a, b| i32_rem_u(a, b)),
+            I32And => binary!(self, pop_i32, |a, b| a & b),
+            I32Or => binary!(self, pop_i32, |a, b| a | b),
+            I32Xor => binary!(self, pop_i32, |a, b| a ^ b),
+            I32Shl => binary!(self, pop_i32, |a, b| a.wrapping_shl(b as u32)),
+            I32ShrSigned => binary!(self, pop_i32, |a, b| a.wrapping_shr(b as u32)),
+            I32ShrUnsigned => {
+                binary!(self, pop_i32, |a, b| (a as u32).wrapping_shr(b as u32)
+                    as i32)
+            }
+            I32Rotl => binary!(self, pop_i32, |a, b| a.rotate_left(b as u32)),
+            I32Rotr => binary!(self, pop_i32, |a, b| a.rotate_right(b as u32)),
+
+            I64Clz => unary!(self, pop_i64, |a| a.leading_zeros() as i64),
+            I64Ctz => unary!(self, pop_i64, |a| a.trailing_zeros() as i64),
+            I64PopCnt => unary!(self, pop_i64, |a| a.count_ones() as i64),
+            I64Add => binary!(self, pop_i64, |a, b| a.wrapping_add(b)),
+            I64Sub => binary!(self, pop_i64, |a, b| a.wrapping_sub(b)),
+            I64Mul => binary!(self, pop_i64, |a, b| a.wrapping_mul(b)),
+            I64DivSigned => try_binary!(self, pop_i64, |a, b| i64_div_s(a, b)),
+            I64DivUnsigned => try_binary!(self, pop_i64, |a, b| i64_div_u(a, b)),
+            I64RemSigned => try_binary!(self, pop_i64, |a, b| i64_rem_s(a, b)),
+            I64RemUnsigned => try_binary!(self, pop_i64, |a, b| i64_rem_u(a, b)),
+            I64And => binary!(self, pop_i64, |a, b| a & b),
+            I64Or => binary!(self, pop_i64, |a, b| a | b),
+            I64Xor => binary!(self, pop_i64, |a, b| a ^ b),
+            I64Shl => binary!(self, pop_i64, |a, b| a.wrapping_shl(b as u32)),
+            I64ShrSigned => binary!(self, pop_i64, |a, b| a.wrapping_shr(b as u32)),
+            I64ShrUnsigned => {
+                binary!(self, pop_i64, |a, b| (a as u64).wrapping_shr(b as u32)
+                    as i64)
+            }
+            I64Rotl => binary!(self, pop_i64, |a, b| a.rotate_left(b as u32)),
+            I64Rotr => binary!(self, pop_i64, |a, b| a.rotate_right(b as u32)),
+
+            F32Abs => unary!(self, pop_f32, |a| a.abs()),
+            F32Neg => unary!(self, pop_f32, |a| -a),
+            F32Ceil => unary!(self, pop_f32, |a| a.ceil()),
+            F32Floor => unary!(self, pop_f32, |a| a.floor()),
+            F32Trunc => unary!(self, pop_f32, |a| a.trunc()),
+            F32Nearest => unary!(self, pop_f32, |a| f32_nearest(a)),
+            F32Sqrt => unary!(self, pop_f32, |a| a.sqrt()),
+            F32Add => binary!(self, pop_f32, |a, b| a + b),
+            F32Sub => binary!(self, pop_f32, |a, b| a - b),
+            F32Mul => binary!(self, pop_f32, |a, b| a * b),
+            F32Div => binary!(self, pop_f32, |a, b| a / b),
+            F32Min => binary!(self, pop_f32, |a, b| f32_min(a, b)),
+            F32Max => binary!(self, pop_f32, |a, b| f32_max(a, b)),
+            F32CopySign => binary!(self, pop_f32, |a, b| a.copysign(b)),
+
+            F64Abs => unary!(self, pop_f64, |a| a.abs()),
+            F64Neg => unary!(self, pop_f64, |a| -a),
+            F64Ceil => unary!(self, pop_f64, |a| a.ceil()),
+            F64Floor => unary!(self, pop_f64, |a| a.floor()),
+            F64Trunc => unary!(self, pop_f64, |a| a.trunc()),
+            F64Nearest => unary!(self, pop_f64, |a| f64_nearest(a)),
+            F64Sqrt => unary!(self, pop_f64, |a| a.sqrt()),
+            F64Add => binary!(self, pop_f64, |a, b| a + b),
+            F64Sub => binary!(self, pop_f64, |a, b| a - b),
+            F64Mul => binary!(self, pop_f64, |a, b| a * b),
+            F64Div => binary!(self, pop_f64, |a, b| a / b),
+            F64Min => binary!(self, pop_f64, |a, b| f64_min(a, b)),
+            F64Mx => binary!(self, pop_f64, |a, b| f64_max(a, b)),
+            F64CopySign => binary!(self, pop_f64, |a, b| a.copysign(b)),
+
+            I32WrapI64 => unary!(self, pop_i64, |a| a as i32),
+            I32TruncF32Signed => unary!(self, pop_f32, |a| i32_trunc_f32(a)?),
+            I32TruncF32Unsigned => unary!(self, pop_f32, |a| u32_trunc_f32(a)? as i32),
+            I32TruncF64Signed => unary!(self, pop_f64, |a| i32_trunc_f64(a)?),
+            I32TruncF64Unsigned => unary!(self, pop_f64, |a| u32_trunc_f64(a)? as i32),
+            I64ExtendI32Signed => unary!(self, pop_i32, |a| a as i64),
+            I64ExtendI32Unsigned => unary!(self, pop_i32, |a| a as u32 as i64),
+            I64TruncF32Signed => unary!(self, pop_f32, |a| i64_trunc_f32(a)?),
+            I64TruncF32Unsigned => unary!(self, pop_f32, |a| u64_trunc_f32(a)? as i64),
+            I64TruncF64Signed => unary!(self, pop_f64, |a| i64_trunc_f64(a)?),
+            I64TruncF64Unsigned => unary!(self, pop_f64, |a| u64_trunc_f64(a)? as i64),
+            F32ConvertI32Signed => unary!(self, pop_i32, |a| a as f32),
+            F32ConvertI32Unsigned => unary!(self, pop_i32, |a| a as u32 as f32),
+            F32ConvertI64Signed => unary!(self, pop_i64, |a| a as f32),
+            F32ConvertI64Unsigned => unary!(self, pop_i64, |a| a as u64 as f32),
+            F32DemoteF64 => unary!(self, pop_f64, |a| a as f32),
+            F64ConvertI32Signed => unary!(self, pop_i32, |a| a as f64),
+            F64ConvertI32Unsigned => unary!(self, pop_i32, |a| a as u32 as f64),
+            F64ConvertI64Signed => unary!(self, pop_i64, |a| a as f64),
+            F64ConvertI64Unsigned => unary!(self, pop_i64, |a| a as u64 as f64),
+            F64PromoteF32 => unary!(self, pop_f32, |a| a as f64),
+            I32ReinterpretF32 => unary!(self, pop_f32, |a| a.to_bits() as i32),
+            I64ReinterpretF64 => unary!(self, pop_f64, |a| a.to_bits() as i64),
+            F32ReinterpretI32 => unary!(self, pop_i32, |a| f32::from_bits(a as u32)),
+            F64ReinterpretI64 => unary!(self, pop_i64, |a| f64::from_bits(a as u64)),
+
+            I32Extend8Signed => unary!(self, pop_i32, |a| a as i8 as i32),
+            I32Extend16Signed => unary!(self, pop_i32, |a| a as i16 as i32),
+            I64Extend8Signed => unary!(self, pop_i64, |a| a as i8 as i64),
+            I64Extend16Signed => unary!(self, pop_i64, |a| a as i16 as i64),
+            I64Extend32Signed => unary!(self, pop_i64, |a| a as i32 as i64),
+
+            // `as` saturates and turns NaN into 0, exactly what they need.
+            I32TruncSatF32Signed => unary!(self, pop_f32, |a| a as i32),
+            I32TruncSatF32Unsigned => unary!(self, pop_f32, |a| a as u32 as i32),
+            I32TruncSatF64Signed => unary!(self, pop_f64, |a| a as i32),
+            I32TruncSatF64Unsigned => unary!(self, pop_f64, |a| a as u32 as i32),
+            I64TruncSatF32Signed => unary!(self, pop_f32, |a| a as i64),
+            I64TruncSatF32Unsigned => unary!(self, pop_f32, |a| a as u64 as i64),
+            I64TruncSatF64Signed => unary!(self, pop_f64, |a| a as i64),
+            I64TruncSatF64Unsigned => unary!(self, pop_f64, |a| a as u64 as i64),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use super::*;
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#stack
+/// The values of all active frames live in one operand stack.
+#[derive(Clone, Debug, Default)]
+pub struct OperandStack {
+    values: Vec<Value>,
+}
+
+macro_rules! pop_typed {
+    ($name: ident, $variant: ident, $ty: ty) => {
+        pub fn $name(&mut self) -> Result<$ty, Trap> {
+            match self.pop()? {
+                Value::$variant(v) => Ok(v),
+                _ => Err(Trap::TypeMismatch),
+            }
+        }
+    };
+}
+
+impl OperandStack {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn push(&mut self, value: impl Into<Value>) {
+        self.values.push(value.into());
+    }
+
+    pub fn pop(&mut self) -> Result<Value, Trap> {
+        self.values.pop().ok_or(Trap::StackUnderflow)
+    }
+
+    pop_typed!(pop_i32, I32, i32);
+    pop_typed!(pop_i64, I64, i64);
+    pop_typed!(pop_f32, F32, f32);
+    pop_typed!(pop_f64, F64, f64);
+
+    /// Pop `n` values, they keep the order in the stack.
+    pub fn pop_n(&mut self, n: usize) -> Result<Vec<Value>, Trap> {
+        if n > self.values.len() {
+            return Err(Trap::StackUnderflow);
+        }
+        Ok(self.values.split_off(self.values.len() - n))
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+    }
+
+    pub fn extend(&mut self, values: Vec<Value>) {
+        self.values.extend(values);
+    }
+}
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#labels
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Label {
+    /// How many values a branch to this label carries.
+    pub arity: usize,
+    /// The height of operand stack when entering this block, excluding params.
+    pub height: usize,
+    /// Where to continue after a branch to this label.
+    pub continuation: usize,
+    pub is_loop: bool,
+}
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#frames
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    pub body: Rc<FunctionBody>,
+    pub instance: Rc<ModuleInstance>,
+    pub pc: usize,
+    pub locals: Vec<Value>,
+    pub labels: Vec<Label>,
+    /// The height of operand stack when entering this function, excluding params.
+    pub height: usize,
+    /// How many values this function returns.
+    pub arity: usize,
+}
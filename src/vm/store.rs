@@ -0,0 +1,196 @@
+use super::*;
+
+/// Instances registered by name, the imports of later modules are resolved to their exports,
+/// then to the items defined in the linker.
+///
+/// The imported functions of other instances are called by the interpreter directly,
+/// and the imported memories, tables and globals are shared rather than copied.
+///
+/// Every instance created or registered here lives as long as the store,
+/// so the functions stored in shared tables and globals can always be called.
+#[derive(Clone, Debug, Default)]
+pub struct Store {
+    linker: Linker,
+    instances: HashMap<String, Instance>,
+    owned: Vec<Rc<ModuleInstance>>,
+}
+
+impl Store {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The host functions and the limiter come from `linker`.
+    pub fn with_linker(linker: Linker) -> Self {
+        Self {
+            linker,
+            instances: HashMap::new(),
+            owned: vec![],
+        }
+    }
+
+    pub fn linker(&self) -> &Linker {
+        &self.linker
+    }
+
+    pub fn linker_mut(&mut self) -> &mut Linker {
+        &mut self.linker
+    }
+
+    /// Register an instance, the previous one with the same name is replaced,
+    /// but it's still alive for the functions it defined.
+    pub fn register(&mut self, name: &str, instance: Instance) -> &mut Self {
+        self.own(&instance);
+        self.instances.insert(name.to_string(), instance);
+        self
+    }
+
+    fn own(&mut self, instance: &Instance) {
+        let inner = instance.module_instance();
+        if !self.owned.iter().any(|owned| Rc::ptr_eq(owned, inner)) {
+            self.owned.push(inner.clone());
+        }
+    }
+
+    pub fn instance(&self, name: &str) -> Option<&Instance> {
+        self.instances.get(name)
+    }
+
+    /// Find the export of a registered instance, or the item defined in the linker.
+    pub fn get(&self, module: &str, name: &str) -> Option<&Extern> {
+        match self.instances.get(module) {
+            Some(instance) => instance.export(name),
+            None => self.linker.get(module, name),
+        }
+    }
+
+    /// Instantiate the module without registering it.
+    pub fn instantiate(&mut self, module: &WasmModule) -> Result<Instance, InstantiationError> {
+        let instance = self
+            .linker
+            .instantiate_with(module, |module, name| self.get(module, name).cloned())?;
+        // the start function may store the functions of the instance in shared tables.
+        self.own(&instance);
+        instance.start(module)?;
+        Ok(instance)
+    }
+
+    /// Instantiate the module, then register it as `name`.
+    pub fn instantiate_as(
+        &mut self,
+        name: &str,
+        module: &WasmModule,
+    ) -> Result<Instance, InstantiationError> {
+        let instance = self.instantiate(module)?;
+        self.register(name, instance.clone());
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> WasmModule<'_> {
+        crate::text::parse(source).unwrap()
+    }
+
+    #[test]
+    fn modules_should_link_to_each_other() {
+        let mut store = Store::new();
+        let counter = parse(
+            r#"
+            (module
+              (memory (export "memory") 1)
+              (table (export "table") 2 funcref)
+              (global $count (export "count") (mut i32) (i32.const 0))
+              (elem (i32.const 0) $get)
+              (func $get (result i32) (global.get $count))
+              (func (export "incr") (param i32) (result i32)
+                (global.set $count (i32.add (global.get $count) (local.get 0)))
+                (i32.store (i32.const 0) (global.get $count))
+                (global.get $count)))
+            "#,
+        );
+        let counter = store.instantiate_as("counter", &counter).unwrap();
+
+        let user = parse(
+            r#"
+            (module
+              (import "counter" "incr" (func $incr (param i32) (result i32)))
+              (import "counter" "memory" (memory 1))
+              (import "counter" "table" (table 2 funcref))
+              (import "counter" "count" (global $count (mut i32)))
+              (type $get (func (result i32)))
+              (func (export "run") (result i32)
+                (drop (call $incr (i32.const 5)))
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (i32.add (i32.load (i32.const 0))
+                  (call_indirect (type $get) (i32.const 0)))))
+            "#,
+        );
+        let user = store.instantiate(&user).unwrap();
+        // 5 stored in the shared memory, plus 6 read from the shared global by the table element.
+        assert_eq!(user.invoke("run", &[]), Ok(vec![Value::I32(11)]));
+        assert_eq!(
+            counter.invoke("incr", &[Value::I32(1)]),
+            Ok(vec![Value::I32(7)])
+        );
+
+        assert_eq!(
+            store
+                .instantiate(&parse(r#"(module (import "counter" "none" (func)))"#))
+                .unwrap_err(),
+            InstantiationError::UnknownImport {
+                module: "counter".to_string(),
+                name: "none".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn shared_tables_should_keep_functions_alive() {
+        let mut store = Store::new();
+        let a = parse(
+            r#"
+            (module
+              (table (export "table") 2 funcref)
+              (elem (i32.const 0) $answer)
+              (func $answer (result i32) (i32.const 42)))
+            "#,
+        );
+        let a = store.instantiate_as("a", &a).unwrap();
+        let b = parse(
+            r#"
+            (module
+              (import "a" "table" (table $table 2 funcref))
+              (export "table" (table $table))
+              (type $t (func (result i32)))
+              (func $seven (result i32) (i32.const 7))
+              (elem (i32.const 1) $seven)
+              (func (export "call") (param i32) (result i32)
+                (call_indirect (type $t) (local.get 0))))
+            "#,
+        );
+        let b = store.instantiate(&b).unwrap();
+        drop(a);
+        // the instance registered as "a" is replaced, its function is still in the table.
+        let other = store.instantiate(&parse("(module)")).unwrap();
+        store.register("a", other);
+        assert_eq!(b.invoke("call", &[Value::I32(0)]), Ok(vec![Value::I32(42)]));
+        assert_eq!(b.invoke("call", &[Value::I32(1)]), Ok(vec![Value::I32(7)]));
+
+        // nothing depends on c, its function in the table is kept alive by the store.
+        store.register("b", b.clone());
+        let c = parse(
+            r#"
+            (module
+              (import "b" "table" (table 2 funcref))
+              (func $nine (result i32) (i32.const 9))
+              (elem (i32.const 1) $nine))
+            "#,
+        );
+        drop(store.instantiate(&c).unwrap());
+        assert_eq!(b.invoke("call", &[Value::I32(1)]), Ok(vec![Value::I32(9)]));
+    }
+}
@@ -0,0 +1,354 @@
+use super::*;
+use crate::sections::TableType;
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#table-instances
+/// A vector of references, all of the same reference type.
+#[derive(Clone, Debug)]
+pub(crate) struct TableInstance {
+    ref_type: ReferenceType,
+    elements: Vec<Value>,
+    max: Option<u32>,
+    /// The limiter of the instance which defines this table.
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+}
+
+/// A reference to table instance, it can be exported to the host or other modules.
+#[derive(Clone)]
+pub struct Table(pub(crate) Rc<RefCell<TableInstance>>);
+
+impl Table {
+    /// Allocate a table of `min` null references, it can grow up to `max` elements.
+    /// The growth is consulted with the default [`ResourceLimits`] until it's set by an instance.
+    pub fn new(ref_type: ReferenceType, min: u32, max: Option<u32>) -> Result<Self, Trap> {
+        if max.map_or(false, |max| min > max) {
+            return Err(Trap::TableOutOfBounds);
+        }
+        let null = Value::default_of(ValueType::RefType(ref_type)).ok_or(Trap::TypeMismatch)?;
+        let mut elements = vec![];
+        elements
+            .try_reserve_exact(min as usize)
+            .map_err(|_| Trap::TableOutOfBounds)?;
+        elements.resize(min as usize, null);
+        Ok(Self(Rc::new(RefCell::new(TableInstance {
+            ref_type,
+            elements,
+            max,
+            limiter: None,
+        }))))
+    }
+
+    /// Allocate a table described by the table section.
+    pub fn from_table_type(table_type: &TableType) -> Result<Self, Trap> {
+        let limits = &table_type.limits;
+        Self::new(table_type.ref_type, limits.min.unwrap_or(0), limits.max)
+    }
+
+    pub fn ref_type(&self) -> ReferenceType {
+        self.0.borrow().ref_type
+    }
+
+    /// The current count of elements.
+    pub fn size(&self) -> u32 {
+        self.0.borrow().elements.len() as u32
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        self.0.borrow().max
+    }
+
+    /// https://webassembly.github.io/spec/core/exec/modules.html#grow-table
+    /// Grow by `delta` elements filled with `init`, and return the previous size.
+    /// `None` if it would exceed the max, the resource limiter refuses, or the host fails to allocate.
+    pub fn grow(&self, delta: u32, init: Value) -> Option<u32> {
+        let mut table = self.0.borrow_mut();
+        if init.value_type() != ValueType::RefType(table.ref_type) {
+            return None;
+        }
+        let size = table.elements.len() as u32;
+        let max = table.max.unwrap_or(u32::MAX);
+        let new_size = size.checked_add(delta).filter(|size| *size <= max)?;
+        let limiter = table.limiter.as_deref().unwrap_or(&ResourceLimits::DEFAULT);
+        if !limiter.table_growing(size, new_size, table.max) {
+            return None;
+        }
+        table.elements.try_reserve_exact(delta as usize).ok()?;
+        table.elements.resize(new_size as usize, init);
+        Some(size)
+    }
+
+    /// Every growth is consulted with the limiter, no matter it's grown by the guest or the host.
+    pub(crate) fn set_limiter(&self, limiter: Option<Rc<dyn ResourceLimiter>>) {
+        self.0.borrow_mut().limiter = limiter;
+    }
+
+    pub fn get(&self, index: u32) -> Result<Value, Trap> {
+        self.0
+            .borrow()
+            .elements
+            .get(index as usize)
+            .cloned()
+            .ok_or(Trap::TableOutOfBounds)
+    }
+
+    /// Replace the element at `index`, the value must be a reference of the table type.
+    pub fn set(&self, index: u32, value: Value) -> Result<(), Trap> {
+        self.init(index, &[value])
+    }
+
+    /// Set `n` elements to `value` starting from `index`, nothing is written if it's out of bounds.
+    pub fn fill(&self, index: u32, n: u32, value: Value) -> Result<(), Trap> {
+        let mut table = self.0.borrow_mut();
+        if value.value_type() != ValueType::RefType(table.ref_type) {
+            return Err(Trap::TypeMismatch);
+        }
+        let start = index as usize;
+        let elements = start
+            .checked_add(n as usize)
+            .and_then(|end| table.elements.get_mut(start..end))
+            .ok_or(Trap::TableOutOfBounds)?;
+        elements.fill(value);
+        Ok(())
+    }
+
+    /// Read `n` elements starting from `index`.
+    pub(crate) fn read(&self, index: u32, n: u32) -> Result<Vec<Value>, Trap> {
+        let start = index as usize;
+        start
+            .checked_add(n as usize)
+            .and_then(|end| self.0.borrow().elements.get(start..end).map(<[_]>::to_vec))
+            .ok_or(Trap::TableOutOfBounds)
+    }
+
+    /// Copy the references into the table starting from `offset`,
+    /// nothing is written if any of them is out of bounds.
+    pub(crate) fn init(&self, offset: u32, values: &[Value]) -> Result<(), Trap> {
+        let mut table = self.0.borrow_mut();
+        let ty = ValueType::RefType(table.ref_type);
+        if values.iter().any(|value| value.value_type() != ty) {
+            return Err(Trap::TypeMismatch);
+        }
+        let start = offset as usize;
+        let elements = start
+            .checked_add(values.len())
+            .and_then(|end| table.elements.get_mut(start..end))
+            .ok_or(Trap::TableOutOfBounds)?;
+        elements.clone_from_slice(values);
+        Ok(())
+    }
+}
+
+/// Two references are equal only if they point to the same table instance.
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::fmt::Debug for Table {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Table")
+            .field("ref_type", &self.ref_type())
+            .field("size", &self.size())
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse;
+    use crate::validation::{validate, ValidationError};
+
+    #[test]
+    fn table_should_check_bounds_and_types() {
+        let table = Table::new(ReferenceType::ExternRef, 2, Some(3)).unwrap();
+        assert_eq!(table.size(), 2);
+        assert_eq!(table.get(1), Ok(Value::ExternRef(None)));
+        assert_eq!(table.get(2), Err(Trap::TableOutOfBounds));
+
+        table.set(1, Value::ExternRef(Some(7))).unwrap();
+        assert_eq!(table.get(1), Ok(Value::ExternRef(Some(7))));
+        assert_eq!(table.set(0, Value::FuncRef(None)), Err(Trap::TypeMismatch));
+
+        // the whole range is checked before writing.
+        let values = [Value::ExternRef(Some(1)), Value::ExternRef(Some(2))];
+        assert_eq!(table.init(1, &values), Err(Trap::TableOutOfBounds));
+        assert_eq!(table.get(1), Ok(Value::ExternRef(Some(7))));
+
+        assert_eq!(table.grow(1, Value::ExternRef(Some(3))), Some(2));
+        assert_eq!(table.get(2), Ok(Value::ExternRef(Some(3))));
+        assert_eq!(table.grow(1, Value::ExternRef(None)), None);
+        assert_eq!(table.grow(0, Value::FuncRef(None)), None);
+        assert!(Table::new(ReferenceType::FuncRef, 2, Some(1)).is_err());
+
+        assert_eq!(table.fill(1, 2, Value::ExternRef(Some(4))), Ok(()));
+        assert_eq!(table.get(2), Ok(Value::ExternRef(Some(4))));
+        assert_eq!(
+            table.fill(2, 2, Value::ExternRef(None)),
+            Err(Trap::TableOutOfBounds)
+        );
+        assert_eq!(table.get(2), Ok(Value::ExternRef(Some(4))));
+    }
+
+    #[test]
+    fn table_instructions_should_work() {
+        use crate::serialize::Encode;
+
+        let source = r#"
+            (module
+              (table $t 1 3 externref)
+              (func (export "grow") (param i32) (result i32)
+                (table.grow $t (ref.null extern) (local.get 0)))
+              (func (export "size") (result i32) (table.size $t))
+              (func (export "fill") (param i32 i32)
+                (table.fill $t (local.get 0) (ref.null extern) (local.get 1))))
+        "#;
+        // the instructions after the 0xfc prefix survive encoding.
+        let bytes = parse(source).unwrap().to_bytes();
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        let invoke = |name, args: &[i32]| {
+            let args: Vec<Value> = args.iter().map(|arg| Value::I32(*arg)).collect();
+            instance.invoke(name, &args)
+        };
+        assert_eq!(invoke("grow", &[1]), Ok(vec![Value::I32(1)]));
+        assert_eq!(invoke("grow", &[2]), Ok(vec![Value::I32(-1)]));
+        assert_eq!(invoke("size", &[]), Ok(vec![Value::I32(2)]));
+        assert_eq!(invoke("fill", &[0, 2]), Ok(vec![]));
+        assert_eq!(invoke("fill", &[1, 2]), Err(Trap::TableOutOfBounds));
+        assert_eq!(invoke("fill", &[2, 0]), Ok(vec![]));
+    }
+
+    #[test]
+    fn bulk_table_instructions_should_work() {
+        use crate::serialize::Encode;
+
+        let source = r#"
+            (module
+              (type $t (func (result i32)))
+              (table $t0 4 funcref)
+              (table $t1 2 funcref)
+              (table $t2 1 externref)
+              (func $one (result i32) (i32.const 1))
+              (func $two (result i32) (i32.const 2))
+              (elem $e0 func $one $two)
+              (elem $e1 (table $t1) (i32.const 0) func $two)
+              (elem $e2 declare func $one)
+              (func (export "init") (param i32 i32 i32)
+                (table.init $t0 $e0 (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "drop") (elem.drop $e0))
+              (func (export "copy") (param i32 i32 i32)
+                (table.copy (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "copy_t1") (param i32 i32 i32)
+                (table.copy $t0 $t1 (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "init_dropped") (param i32)
+                (table.init $t1 $e1 (i32.const 0) (i32.const 0) (local.get 0)))
+              (func (export "call") (param i32) (result i32)
+                (call_indirect (type $t) (local.get 0))))
+        "#;
+        // the operands after the 0xfc prefix survive encoding.
+        let bytes = parse(source).unwrap().to_bytes();
+        let module = WasmModule::from_bytes(&bytes).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        let invoke = |name, args: &[i32]| {
+            let args = args.iter().map(|arg| Value::I32(*arg)).collect::<Vec<_>>();
+            instance.invoke(name, &args)
+        };
+        let call = |index| invoke("call", &[index]);
+
+        assert_eq!(invoke("init", &[1, 0, 2]), Ok(vec![]));
+        assert_eq!(call(1), Ok(vec![Value::I32(1)]));
+        assert_eq!(call(2), Ok(vec![Value::I32(2)]));
+        // nothing is written if any element is out of bounds.
+        assert_eq!(invoke("init", &[3, 0, 2]), Err(Trap::TableOutOfBounds));
+        assert_eq!(invoke("init", &[0, 1, 2]), Err(Trap::TableOutOfBounds));
+        assert_eq!(call(3), Err(Trap::UninitializedElement));
+        assert_eq!(invoke("init", &[4, 2, 0]), Ok(vec![]));
+
+        // overlapping ranges are copied as if through a temporary buffer.
+        assert_eq!(invoke("copy", &[2, 1, 2]), Ok(vec![]));
+        assert_eq!(call(2), Ok(vec![Value::I32(1)]));
+        assert_eq!(call(3), Ok(vec![Value::I32(2)]));
+        assert_eq!(invoke("copy", &[3, 0, 2]), Err(Trap::TableOutOfBounds));
+        assert_eq!(invoke("copy_t1", &[0, 0, 1]), Ok(vec![]));
+        assert_eq!(call(0), Ok(vec![Value::I32(2)]));
+
+        // dropped, active and declarative segments are empty.
+        assert_eq!(invoke("drop", &[]), Ok(vec![]));
+        assert_eq!(invoke("init", &[0, 0, 1]), Err(Trap::TableOutOfBounds));
+        assert_eq!(invoke("init", &[0, 0, 0]), Ok(vec![]));
+        assert_eq!(invoke("init_dropped", &[1]), Err(Trap::TableOutOfBounds));
+        assert_eq!(invoke("init_dropped", &[0]), Ok(vec![]));
+
+        let invalid = [
+            (
+                "(table 1 externref) (elem func 0) (func (table.init 0 0 (i32.const 0) (i32.const 0) (i32.const 0)))",
+                ValidationError::TypeMismatch,
+            ),
+            (
+                "(table 1 funcref) (table 1 externref) (func (table.copy 0 1 (i32.const 0) (i32.const 0) (i32.const 0)))",
+                ValidationError::TypeMismatch,
+            ),
+            ("(func (elem.drop 0))", ValidationError::UnknownElement(0)),
+        ];
+        for (source, expected) in invalid {
+            match validate(&parse(source).unwrap()) {
+                Err(ValidationError::Function { error, .. }) => assert_eq!(*error, expected),
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn call_indirect_should_use_element_segments() {
+        let source = r#"
+            (module
+              (type $unary (func (param i32) (result i32)))
+              (table $t0 (export "t0") 3 funcref)
+              (table $t1 (export "t1") externref (elem (ref.null extern)))
+              (table $t2 2 funcref)
+              (func $double (type $unary) (i32.mul (local.get 0) (i32.const 2)))
+              (func $inc (type $unary) (i32.add (local.get 0) (i32.const 1)))
+              (func $nop)
+              (elem (i32.const 0) func $double $nop)
+              (elem (table $t2) (i32.const 1) funcref (item ref.func $inc))
+              (elem declare func $inc)
+              (elem funcref (ref.null func))
+              (func (export "call") (param i32 i32) (result i32)
+                (call_indirect (type $unary) (local.get 0) (local.get 1)))
+              (func (export "call_t2") (param i32 i32) (result i32)
+                (call_indirect $t2 (type $unary) (local.get 0) (local.get 1))))
+        "#;
+        let module = parse(source).unwrap();
+        let instance = Instance::new(&module, &[]).unwrap();
+        let call = |name, index| instance.invoke(name, &[Value::I32(5), Value::I32(index)]);
+
+        assert_eq!(call("call", 0), Ok(vec![Value::I32(10)]));
+        assert_eq!(call("call", 1), Err(Trap::IndirectCallTypeMismatch));
+        assert_eq!(call("call", 2), Err(Trap::UninitializedElement));
+        assert_eq!(call("call", 3), Err(Trap::UndefinedElement));
+        assert_eq!(call("call", -1), Err(Trap::UndefinedElement));
+        assert_eq!(call("call_t2", 1), Ok(vec![Value::I32(6)]));
+        assert_eq!(call("call_t2", 0), Err(Trap::UninitializedElement));
+
+        let t0 = instance.get_table("t0").unwrap();
+        assert!(matches!(t0.get(0), Ok(Value::FuncRef(Some(_)))));
+        let t1 = instance.get_table("t1").unwrap();
+        assert_eq!(t1.ref_type(), ReferenceType::ExternRef);
+        assert_eq!(t1.get(0), Ok(Value::ExternRef(None)));
+
+        // the segment is out of bounds by one element.
+        let module = parse("(table 1 funcref) (func) (elem (i32.const 1) func 0)").unwrap();
+        assert_eq!(
+            Instance::new(&module, &[]).unwrap_err(),
+            InstantiationError::Trap(Trap::TableOutOfBounds)
+        );
+        // the type of segment must match the table.
+        let module = parse("(table 1 externref) (func) (elem (i32.const 0) func 0)").unwrap();
+        assert!(matches!(
+            Instance::new(&module, &[]).unwrap_err(),
+            InstantiationError::Invalid(crate::validation::ValidationError::TypeMismatch)
+        ));
+    }
+}
@@ -0,0 +1,49 @@
+use thiserror::Error;
+
+/// Errors happened while executing a function, the execution is aborted.
+/// The messages follow the official spec tests.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Trap {
+    #[error("unreachable")]
+    Unreachable,
+    #[error("integer divide by zero")]
+    IntegerDivideByZero,
+    #[error("integer overflow")]
+    IntegerOverflow,
+    #[error("invalid conversion to integer")]
+    InvalidConversionToInteger,
+    #[error("out of bounds memory access")]
+    MemoryOutOfBounds,
+    #[error("out of bounds table access")]
+    TableOutOfBounds,
+    #[error("undefined element")]
+    UndefinedElement,
+    #[error("uninitialized element")]
+    UninitializedElement,
+    #[error("indirect call type mismatch")]
+    IndirectCallTypeMismatch,
+    #[error("call stack exhausted")]
+    CallStackExhausted,
+    #[error("operand stack overflow")]
+    StackOverflow,
+    #[error("out of fuel")]
+    OutOfFuel,
+    #[error("type mismatch")]
+    TypeMismatch,
+    #[error("global is immutable")]
+    ImmutableGlobal,
+    #[error("operand stack underflow")]
+    StackUnderflow,
+    #[error("unknown {kind} {index}")]
+    UnknownIndex { kind: &'static str, index: u32 },
+    #[error("unknown export {0}")]
+    UnknownExport(String),
+    /// Raised by host functions.
+    #[error("{0}")]
+    Host(String),
+    /// Raised by WASI `proc_exit`, the program terminates normally with the code.
+    #[error("exit with code {0}")]
+    Exit(i32),
+    #[error("unsupported {0}")]
+    Unsupported(&'static str),
+}
@@ -0,0 +1,293 @@
+use super::*;
+use core::marker::PhantomData;
+
+/// A Rust type which is the same as a wasm number type.
+pub trait WasmType: Sized {
+    const TYPE: NumberType;
+
+    fn into_value(self) -> Value;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! wasm_type {
+    ($($ty: ty => $variant: ident),*) => {
+        $(impl WasmType for $ty {
+            const TYPE: NumberType = NumberType::$variant;
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(*v),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+
+wasm_type!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
+
+/// The parameters or results of a function, a single type or a tuple of them.
+pub trait WasmTypeList: Sized {
+    fn types() -> Vec<ValueType>;
+
+    fn into_values(self) -> Vec<Value>;
+
+    /// `None` if the number or the types of the values don't match.
+    fn from_values(values: &[Value]) -> Option<Self>;
+}
+
+impl<T: WasmType> WasmTypeList for T {
+    fn types() -> Vec<ValueType> {
+        vec![ValueType::NumType(T::TYPE)]
+    }
+
+    fn into_values(self) -> Vec<Value> {
+        vec![self.into_value()]
+    }
+
+    fn from_values(values: &[Value]) -> Option<Self> {
+        match values {
+            [value] => T::from_value(value),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! wasm_type_list {
+    ($($ty: ident),*) => {
+        impl<$($ty: WasmType),*> WasmTypeList for ($($ty,)*) {
+            fn types() -> Vec<ValueType> {
+                vec![$(ValueType::NumType($ty::TYPE)),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Vec<Value> {
+                let ($($ty,)*) = self;
+                vec![$($ty.into_value()),*]
+            }
+
+            #[allow(unused_mut)]
+            fn from_values(values: &[Value]) -> Option<Self> {
+                let mut values = values.iter();
+                let list = ($($ty::from_value(values.next()?)?,)*);
+                match values.next() {
+                    Some(_) => None,
+                    None => Some(list),
+                }
+            }
+        }
+
+        impl<F, R, $($ty),*> IntoFunc<($($ty,)*), R> for F
+        where
+            F: Fn($($ty),*) -> R + 'static,
+            R: HostResult,
+            $($ty: WasmType),*
+        {
+            #[allow(non_snake_case)]
+            fn into_func(self) -> Func {
+                let func_type = FunctionType {
+                    param_types: <($($ty,)*)>::types(),
+                    return_types: R::Results::types(),
+                };
+                Func::new(func_type, move |args| {
+                    let ($($ty,)*) = <($($ty,)*)>::from_values(args).ok_or(Trap::TypeMismatch)?;
+                    self($($ty),*).into_result().map(WasmTypeList::into_values)
+                })
+            }
+        }
+    };
+}
+
+wasm_type_list!();
+wasm_type_list!(A1);
+wasm_type_list!(A1, A2);
+wasm_type_list!(A1, A2, A3);
+wasm_type_list!(A1, A2, A3, A4);
+wasm_type_list!(A1, A2, A3, A4, A5);
+wasm_type_list!(A1, A2, A3, A4, A5, A6);
+wasm_type_list!(A1, A2, A3, A4, A5, A6, A7);
+wasm_type_list!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+/// What host closures can return, the results or a trap.
+pub trait HostResult {
+    type Results: WasmTypeList;
+
+    fn into_result(self) -> Result<Self::Results, Trap>;
+}
+
+impl<T: WasmTypeList> HostResult for T {
+    type Results = T;
+
+    fn into_result(self) -> Result<T, Trap> {
+        Ok(self)
+    }
+}
+
+impl<T: WasmTypeList> HostResult for Result<T, Trap> {
+    type Results = T;
+
+    fn into_result(self) -> Result<T, Trap> {
+        self
+    }
+}
+
+/// A Rust closure which can be a host function, the signature is inferred from its types.
+pub trait IntoFunc<Params, Results> {
+    fn into_func(self) -> Func;
+}
+
+impl Func {
+    /// Define a host function from a closure like `|a: i32, b: i32| a + b`.
+    pub fn wrap<Params, Results>(callback: impl IntoFunc<Params, Results>) -> Self {
+        callback.into_func()
+    }
+}
+
+/// An exported function whose signature has been checked against `Params` and `Results`.
+pub struct TypedFunc<Params, Results> {
+    instance: Instance,
+    func: Func,
+    _signature: PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params: WasmTypeList, Results: WasmTypeList> TypedFunc<Params, Results> {
+    pub fn call(&self, params: Params) -> Result<Results, Trap> {
+        let results = self.instance.call(&self.func, &params.into_values())?;
+        Results::from_values(&results).ok_or(Trap::TypeMismatch)
+    }
+
+    pub fn func(&self) -> &Func {
+        &self.func
+    }
+}
+
+impl<Params, Results> Clone for TypedFunc<Params, Results> {
+    fn clone(&self) -> Self {
+        Self {
+            instance: self.instance.clone(),
+            func: self.func.clone(),
+            _signature: PhantomData,
+        }
+    }
+}
+
+impl<Params, Results> core::fmt::Debug for TypedFunc<Params, Results> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("TypedFunc").field(&self.func).finish()
+    }
+}
+
+impl Instance {
+    /// Get the exported function by name, it fails if the signature isn't `Params -> Results`.
+    pub fn get_typed_func<Params, Results>(
+        &self,
+        name: &str,
+    ) -> Result<TypedFunc<Params, Results>, TypedFuncError>
+    where
+        Params: WasmTypeList,
+        Results: WasmTypeList,
+    {
+        let func = self
+            .get_func(name)
+            .ok_or_else(|| TypedFuncError::UnknownExport(name.to_string()))?;
+        let expected = FunctionType {
+            param_types: Params::types(),
+            return_types: Results::types(),
+        };
+        let actual = func.func_type();
+        if *actual != expected {
+            return Err(TypedFuncError::SignatureMismatch {
+                name: name.to_string(),
+                expected,
+                actual: actual.clone(),
+            });
+        }
+        Ok(TypedFunc {
+            instance: self.clone(),
+            func,
+            _signature: PhantomData,
+        })
+    }
+}
+
+impl Linker {
+    /// Register a host function from a closure, the signature is inferred from its types.
+    pub fn func_wrap<Params, Results>(
+        &mut self,
+        module: &str,
+        name: &str,
+        callback: impl IntoFunc<Params, Results>,
+    ) -> &mut Self {
+        self.define(module, name, Extern::Func(Func::wrap(callback)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_funcs_should_work() {
+        let source = r#"
+            (module
+              (import "env" "add" (func $add (param i32 i32) (result i32)))
+              (import "env" "check" (func $check (param i64)))
+              (func (export "mix") (param i32 i64) (result f64)
+                (call $check (local.get 1))
+                (f64.add (f64.convert_i32_s (call $add (local.get 0) (i32.const 1)))
+                  (f64.convert_i64_s (local.get 1))))
+              (func (export "pair") (result i32 f32)
+                (i32.const 1) (f32.const 2.5)))
+        "#;
+        let module = crate::text::parse(source).unwrap();
+        let mut linker = Linker::new();
+        linker
+            .func_wrap("env", "add", |a: i32, b: i32| a.wrapping_add(b))
+            .func_wrap("env", "check", |v: i64| {
+                if v < 0 {
+                    return Err(Trap::Host("negative".to_string()));
+                }
+                Ok(())
+            });
+        let instance = linker.instantiate(&module).unwrap();
+
+        let mix = instance.get_typed_func::<(i32, i64), f64>("mix").unwrap();
+        assert_eq!(mix.call((1, 2)), Ok(4.0));
+        assert_eq!(mix.call((1, -2)), Err(Trap::Host("negative".to_string())));
+        let pair = instance.get_typed_func::<(), (i32, f32)>("pair").unwrap();
+        assert_eq!(pair.call(()), Ok((1, 2.5)));
+
+        assert_eq!(
+            instance
+                .get_typed_func::<(i32, i32), f64>("mix")
+                .unwrap_err(),
+            TypedFuncError::SignatureMismatch {
+                name: "mix".to_string(),
+                expected: FunctionType {
+                    param_types: <(i32, i32)>::types(),
+                    return_types: f64::types(),
+                },
+                actual: FunctionType {
+                    param_types: <(i32, i64)>::types(),
+                    return_types: f64::types(),
+                },
+            }
+        );
+        assert_eq!(
+            instance
+                .get_typed_func::<(), i32>("pair")
+                .unwrap_err()
+                .to_string(),
+            "signature mismatch for pair: expected (func (result i32)), got (func (result i32 f32))"
+        );
+        assert_eq!(
+            instance.get_typed_func::<(), ()>("none").unwrap_err(),
+            TypedFuncError::UnknownExport("none".to_string())
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use super::*;
+use crate::instructions::Primitives;
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#values
+#[derive(Clone, Debug)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    FuncRef(Option<Func>),
+    ExternRef(Option<u32>),
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::I32(_) => ValueType::NumType(NumberType::I32),
+            Value::I64(_) => ValueType::NumType(NumberType::I64),
+            Value::F32(_) => ValueType::NumType(NumberType::F32),
+            Value::F64(_) => ValueType::NumType(NumberType::F64),
+            Value::FuncRef(_) => ValueType::RefType(ReferenceType::FuncRef),
+            Value::ExternRef(_) => ValueType::RefType(ReferenceType::ExternRef),
+        }
+    }
+
+    /// The default value of a type, zero for numbers, null for references.
+    /// Vector type is not supported yet.
+    pub fn default_of(ty: ValueType) -> Option<Self> {
+        match ty {
+            ValueType::NumType(NumberType::I32) => Some(Value::I32(0)),
+            ValueType::NumType(NumberType::I64) => Some(Value::I64(0)),
+            ValueType::NumType(NumberType::F32) => Some(Value::F32(0.0)),
+            ValueType::NumType(NumberType::F64) => Some(Value::F64(0.0)),
+            ValueType::RefType(ReferenceType::FuncRef) => Some(Value::FuncRef(None)),
+            ValueType::RefType(ReferenceType::ExternRef) => Some(Value::ExternRef(None)),
+            ValueType::VectorType => None,
+        }
+    }
+
+    pub fn is_null(&self) -> Option<bool> {
+        match self {
+            Value::FuncRef(func) => Some(func.is_none()),
+            Value::ExternRef(extern_ref) => Some(extern_ref.is_none()),
+            _ => None,
+        }
+    }
+}
+
+/// The values must match the types one by one.
+pub(crate) fn check_types(values: &[Value], types: &[ValueType]) -> Result<(), Trap> {
+    if values.len() != types.len()
+        || values
+            .iter()
+            .zip(types.iter())
+            .any(|(value, ty)| value.value_type() != *ty)
+    {
+        return Err(Trap::TypeMismatch);
+    }
+    Ok(())
+}
+
+/// Floats are compared by bits, so NaN equals to the same NaN, and 0.0 doesn't equal to -0.0.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::I32(a), Value::I32(b)) => a == b,
+            (Value::I64(a), Value::I64(b)) => a == b,
+            (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+            (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+            (Value::FuncRef(a), Value::FuncRef(b)) => a == b,
+            (Value::ExternRef(a), Value::ExternRef(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<Primitives> for Value {
+    fn from(primitive: Primitives) -> Self {
+        match primitive {
+            Primitives::I32(v) => Value::I32(v),
+            Primitives::I64(v) => Value::I64(v),
+            Primitives::F32(v) => Value::F32(v),
+            Primitives::F64(v) => Value::F64(v),
+        }
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::I32(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::I64(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::F32(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::F64(v)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::vm::Trap;
+use thiserror::Error;
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#errno
+/// The error codes returned to the guest, only the ones used by this implementation are listed.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Errno {
+    #[error("permission denied")]
+    Acces = 2,
+    #[error("bad file descriptor")]
+    Badf = 8,
+    #[error("file exists")]
+    Exist = 20,
+    #[error("bad address")]
+    Fault = 21,
+    #[error("file too large")]
+    Fbig = 22,
+    #[error("invalid argument")]
+    Inval = 28,
+    #[error("i/o error")]
+    Io = 29,
+    #[error("is a directory")]
+    Isdir = 31,
+    #[error("filename too long")]
+    Nametoolong = 37,
+    #[error("no such file or directory")]
+    Noent = 44,
+    #[error("no space left on device")]
+    Nospc = 51,
+    #[error("function not supported")]
+    Nosys = 52,
+    #[error("not a directory")]
+    Notdir = 54,
+    #[error("invalid seek")]
+    Spipe = 70,
+    #[error("capabilities insufficient")]
+    Notcapable = 76,
+}
+
+impl From<std::io::Error> for Errno {
+    fn from(error: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match error.kind() {
+            ErrorKind::NotFound => Errno::Noent,
+            ErrorKind::PermissionDenied => Errno::Acces,
+            ErrorKind::AlreadyExists => Errno::Exist,
+            ErrorKind::InvalidInput => Errno::Inval,
+            _ => Errno::Io,
+        }
+    }
+}
+
+/// The guest passed a pointer out of its memory.
+impl From<Trap> for Errno {
+    fn from(_: Trap) -> Self {
+        Errno::Fault
+    }
+}
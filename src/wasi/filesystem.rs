@@ -0,0 +1,208 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use super::Errno;
+
+/// The flags of `path_open`, converted from `oflags`, `fdflags` and rights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenFlags {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    /// Fail if the file exists, only with `create`.
+    pub exclusive: bool,
+    pub truncate: bool,
+    /// Fail if it's not a directory.
+    pub directory: bool,
+}
+
+impl OpenFlags {
+    pub fn writable(&self) -> bool {
+        self.write || self.append || self.truncate
+    }
+
+    /// A file opened without any right to write is readable.
+    pub fn readable(&self) -> bool {
+        self.read || !self.writable()
+    }
+}
+
+/// An open file of the guest.
+pub trait WasiFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno>;
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Errno>;
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno>;
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+/// An open directory of the guest, it's the only way to reach files.
+/// The implementation must refuse any path escaping the preopened directory which it comes from.
+pub trait WasiDir {
+    /// Open a file or directory by the path relative to this directory.
+    fn open(&self, path: &str, flags: &OpenFlags) -> Result<Opened, Errno>;
+}
+
+pub enum Opened {
+    File(Box<dyn WasiFile>),
+    Dir(Box<dyn WasiDir>),
+}
+
+impl WasiFile for fs::File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(Read::read(self, buf)?)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Errno> {
+        Ok(Write::write(self, bytes)?)
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        Ok(Seek::seek(self, pos)?)
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(self.sync_all()?)
+    }
+}
+
+/// A directory of the host filesystem.
+pub struct HostDir {
+    path: PathBuf,
+    /// The canonical path of preopened directory which contains this one.
+    root: PathBuf,
+}
+
+impl HostDir {
+    /// The directory becomes the root, nothing outside of it can be opened.
+    pub fn new(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let root = fs::canonicalize(path)?;
+        if !root.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "not a directory",
+            ));
+        }
+        Ok(Self {
+            path: root.clone(),
+            root,
+        })
+    }
+
+    /// Join the relative `path`, the result must stay inside the root, even through symbolic links.
+    fn resolve(&self, path: &str) -> Result<PathBuf, Errno> {
+        let mut resolved = self.path.clone();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => (),
+                Component::ParentDir if resolved != self.root => {
+                    resolved.pop();
+                }
+                _ => return Err(Errno::Notcapable),
+            }
+        }
+        // a new file is checked by its parent, but a dangling symbolic link isn't a new file,
+        // creating it would create the target which may be anywhere.
+        let existing = match fs::canonicalize(&resolved) {
+            Ok(real) => real,
+            Err(_) if fs::symlink_metadata(&resolved).is_ok() => return Err(Errno::Notcapable),
+            Err(_) => match resolved.parent().map(fs::canonicalize) {
+                Some(Ok(parent)) => parent,
+                _ => return Err(Errno::Noent),
+            },
+        };
+        if !existing.starts_with(&self.root) {
+            return Err(Errno::Notcapable);
+        }
+        Ok(resolved)
+    }
+}
+
+impl WasiDir for HostDir {
+    fn open(&self, path: &str, flags: &OpenFlags) -> Result<Opened, Errno> {
+        let resolved = self.resolve(path)?;
+        if flags.directory || (!flags.create && resolved.is_dir()) {
+            if !resolved.is_dir() {
+                return Err(if resolved.exists() {
+                    Errno::Notdir
+                } else {
+                    Errno::Noent
+                });
+            }
+            return Ok(Opened::Dir(Box::new(HostDir {
+                path: resolved,
+                root: self.root.clone(),
+            })));
+        }
+        let file = OpenOptions::new()
+            .read(flags.readable())
+            .write(flags.writable() && !flags.append)
+            .append(flags.append)
+            .truncate(flags.truncate)
+            .create(flags.create)
+            .create_new(flags.create && flags.exclusive)
+            .open(&resolved)?;
+        Ok(Opened::File(Box::new(file)))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_dir_should_refuse_escapes() {
+        let base = std::env::temp_dir().join(format!("luban-host-dir-{}", std::process::id()));
+        let root = base.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        fs::write(root.join("sub/file.txt"), "file").unwrap();
+        std::os::unix::fs::symlink(&base, root.join("link")).unwrap();
+        std::os::unix::fs::symlink("../outside.txt", root.join("dangling")).unwrap();
+
+        let dir = HostDir::new(&root).unwrap();
+        let flags = OpenFlags::default();
+        assert!(matches!(
+            dir.open("sub/../sub/file.txt", &flags),
+            Ok(Opened::File(_))
+        ));
+        assert!(matches!(dir.open("sub", &flags), Ok(Opened::Dir(_))));
+        for path in [
+            "../secret.txt",
+            "sub/../../secret.txt",
+            "/etc",
+            "link/secret.txt",
+        ] {
+            assert!(
+                matches!(dir.open(path, &flags), Err(Errno::Notcapable)),
+                "{}",
+                path
+            );
+        }
+        let create = OpenFlags {
+            create: true,
+            write: true,
+            ..OpenFlags::default()
+        };
+        assert!(matches!(
+            dir.open("link/new.txt", &create),
+            Err(Errno::Notcapable)
+        ));
+        assert!(!base.join("new.txt").exists());
+        assert!(matches!(
+            dir.open("dangling", &create),
+            Err(Errno::Notcapable)
+        ));
+        assert!(!base.join("outside.txt").exists());
+        assert!(matches!(
+            dir.open("missing/new.txt", &create),
+            Err(Errno::Noent)
+        ));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,809 @@
+//! https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md
+//! The `wasi_snapshot_preview1` host module, enough for programs built for `wasm32-wasi`.
+//! The files are only reachable through the preopened directories, which are backed by
+//! the host filesystem or any other implementation of [`WasiDir`].
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::ops::Range;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::sections::{FunctionType, NumberType, ValueType, WasmModule};
+use crate::vm::{Instance, InstantiationError, Linker, Memory, Trap, Value};
+
+pub mod errno;
+pub mod filesystem;
+pub mod virtual_fs;
+
+pub use self::errno::Errno;
+pub use self::filesystem::{HostDir, OpenFlags, Opened, WasiDir, WasiFile};
+pub use self::virtual_fs::VirtualDir;
+
+/// The name of import module.
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+const I32: ValueType = ValueType::NumType(NumberType::I32);
+const I64: ValueType = ValueType::NumType(NumberType::I64);
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#filetype
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+/// Every right defined by preview1, they are not restricted by this implementation.
+const RIGHTS_ALL: u64 = (1 << 30) - 1;
+
+const OFLAGS_CREAT: u32 = 1 << 0;
+const OFLAGS_DIRECTORY: u32 = 1 << 1;
+const OFLAGS_EXCL: u32 = 1 << 2;
+const OFLAGS_TRUNC: u32 = 1 << 3;
+const FDFLAGS_APPEND: u32 = 1 << 0;
+
+/// An open file descriptor.
+enum Descriptor {
+    Stdin(Box<dyn Read>),
+    /// Both stdout and stderr.
+    Stdout(Box<dyn Write>),
+    File(Box<dyn WasiFile>),
+    Dir {
+        dir: Box<dyn WasiDir>,
+        /// The guest path if it's preopened.
+        preopen: Option<String>,
+    },
+}
+
+/// The state of WASI, like the arguments, environment variables and open files.
+pub struct WasiCtx {
+    args: Vec<String>,
+    envs: Vec<String>,
+    fds: Vec<Option<Descriptor>>,
+    memory: Option<Memory>,
+    start: Instant,
+    random: RandomState,
+    random_counter: u64,
+}
+
+impl Default for WasiCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasiCtx {
+    /// No arguments and environment variables, the stdio are inherited from the host.
+    pub fn new() -> Self {
+        Self {
+            args: vec![],
+            envs: vec![],
+            fds: vec![
+                Some(Descriptor::Stdin(Box::new(std::io::stdin()))),
+                Some(Descriptor::Stdout(Box::new(std::io::stdout()))),
+                Some(Descriptor::Stdout(Box::new(std::io::stderr()))),
+            ],
+            memory: None,
+            start: Instant::now(),
+            random: RandomState::new(),
+            random_counter: 0,
+        }
+    }
+
+    /// Append an argument, the first one is the program name by convention.
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.envs.push(format!("{}={}", key, value));
+        self
+    }
+
+    pub fn stdin(&mut self, reader: impl Read + 'static) -> &mut Self {
+        self.fds[0] = Some(Descriptor::Stdin(Box::new(reader)));
+        self
+    }
+
+    pub fn stdout(&mut self, writer: impl Write + 'static) -> &mut Self {
+        self.fds[1] = Some(Descriptor::Stdout(Box::new(writer)));
+        self
+    }
+
+    pub fn stderr(&mut self, writer: impl Write + 'static) -> &mut Self {
+        self.fds[2] = Some(Descriptor::Stdout(Box::new(writer)));
+        self
+    }
+
+    /// Give the guest access to the directory, it's visible as `guest` path.
+    pub fn preopen(&mut self, dir: impl WasiDir + 'static, guest: &str) -> &mut Self {
+        self.fds.push(Some(Descriptor::Dir {
+            dir: Box::new(dir),
+            preopen: Some(guest.to_string()),
+        }));
+        self
+    }
+
+    /// Preopen the host directory.
+    pub fn preopen_dir(
+        &mut self,
+        host: impl AsRef<Path>,
+        guest: &str,
+    ) -> std::io::Result<&mut Self> {
+        Ok(self.preopen(HostDir::new(host)?, guest))
+    }
+
+    fn memory(&self) -> Result<&Memory, Errno> {
+        self.memory.as_ref().ok_or(Errno::Fault)
+    }
+
+    /// The range of guest memory, it's checked before anything is allocated for the length.
+    fn range(&self, ptr: u32, len: u32) -> Result<Range<usize>, Errno> {
+        let size = self.memory()?.data_size();
+        let start = ptr as usize;
+        start
+            .checked_add(len as usize)
+            .filter(|end| *end <= size)
+            .map(|end| start..end)
+            .ok_or(Errno::Fault)
+    }
+
+    fn read_bytes(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+        let range = self.range(ptr, len)?;
+        Ok(self.memory()?.data()[range].to_vec())
+    }
+
+    fn read_u32(&self, ptr: u32) -> Result<u32, Errno> {
+        let mut buf = [0; 4];
+        self.memory()?.read(ptr as usize, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_bytes(&self, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+        Ok(self.memory()?.write(ptr as usize, bytes)?)
+    }
+
+    fn write_u32(&self, ptr: u32, value: u32) -> Result<(), Errno> {
+        self.write_bytes(ptr, &value.to_le_bytes())
+    }
+
+    fn write_u64(&self, ptr: u32, value: u64) -> Result<(), Errno> {
+        self.write_bytes(ptr, &value.to_le_bytes())
+    }
+
+    fn descriptor(&mut self, fd: u32) -> Result<&mut Descriptor, Errno> {
+        self.fds
+            .get_mut(fd as usize)
+            .and_then(Option::as_mut)
+            .ok_or(Errno::Badf)
+    }
+
+    /// The lowest free descriptor is reused.
+    fn insert(&mut self, descriptor: Descriptor) -> u32 {
+        match self.fds.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.fds[fd] = Some(descriptor);
+                fd as u32
+            }
+            None => {
+                self.fds.push(Some(descriptor));
+                (self.fds.len() - 1) as u32
+            }
+        }
+    }
+
+    /// The strings are written to `buf` with nul terminators, their addresses are written to `ptrs`.
+    fn write_strings(strings: &[String], ctx: &Self, ptrs: u32, buf: u32) -> Result<(), Errno> {
+        let mut offset = buf;
+        for (i, string) in strings.iter().enumerate() {
+            let ptr = (i as u32)
+                .checked_mul(4)
+                .and_then(|n| ptrs.checked_add(n))
+                .ok_or(Errno::Fault)?;
+            ctx.write_u32(ptr, offset)?;
+            let mut bytes = string.as_bytes().to_vec();
+            bytes.push(0);
+            ctx.write_bytes(offset, &bytes)?;
+            offset = offset.checked_add(bytes.len() as u32).ok_or(Errno::Fault)?;
+        }
+        Ok(())
+    }
+
+    fn write_sizes(strings: &[String], ctx: &Self, count: u32, size: u32) -> Result<(), Errno> {
+        ctx.write_u32(count, strings.len() as u32)?;
+        let len: usize = strings.iter().map(|s| s.len() + 1).sum();
+        ctx.write_u32(size, len as u32)
+    }
+
+    fn args_get(&mut self, argv: u32, argv_buf: u32) -> Result<(), Errno> {
+        Self::write_strings(&self.args, self, argv, argv_buf)
+    }
+
+    fn args_sizes_get(&mut self, argc: u32, argv_buf_size: u32) -> Result<(), Errno> {
+        Self::write_sizes(&self.args, self, argc, argv_buf_size)
+    }
+
+    fn environ_get(&mut self, environ: u32, environ_buf: u32) -> Result<(), Errno> {
+        Self::write_strings(&self.envs, self, environ, environ_buf)
+    }
+
+    fn environ_sizes_get(&mut self, count: u32, buf_size: u32) -> Result<(), Errno> {
+        Self::write_sizes(&self.envs, self, count, buf_size)
+    }
+
+    /// The ranges of guest memory in iovec array, the total length must fit in u32.
+    fn iovecs(&self, iovs: u32, iovs_len: u32) -> Result<Vec<Range<usize>>, Errno> {
+        let size = iovs_len.checked_mul(8).ok_or(Errno::Fault)?;
+        // the iovec array itself is in bounds, so the addresses of its items never overflow.
+        self.range(iovs, size)?;
+        let mut total = 0u32;
+        (0..iovs_len)
+            .map(|i| {
+                let iov = iovs + i * 8;
+                let len = self.read_u32(iov + 4)?;
+                total = total.checked_add(len).ok_or(Errno::Inval)?;
+                self.range(self.read_u32(iov)?, len)
+            })
+            .collect()
+    }
+
+    /// The bytes are written from the guest memory in place.
+    fn fd_write(&mut self, fd: u32, iovs: u32, iovs_len: u32, nwritten: u32) -> Result<(), Errno> {
+        let memory = self.memory()?.clone();
+        let mut total = 0;
+        for range in self.iovecs(iovs, iovs_len)? {
+            let data = memory.data();
+            let bytes = &data[range];
+            let n = match self.descriptor(fd)? {
+                Descriptor::Stdout(writer) => {
+                    writer.write_all(bytes)?;
+                    bytes.len()
+                }
+                Descriptor::File(file) => file.write(bytes)?,
+                _ => return Err(Errno::Badf),
+            };
+            total += n as u32;
+            if n < bytes.len() {
+                break;
+            }
+        }
+        self.write_u32(nwritten, total)
+    }
+
+    /// The bytes are read into the guest memory in place.
+    fn fd_read(&mut self, fd: u32, iovs: u32, iovs_len: u32, nread: u32) -> Result<(), Errno> {
+        let memory = self.memory()?.clone();
+        let mut total = 0;
+        for range in self.iovecs(iovs, iovs_len)? {
+            let len = range.len();
+            let mut data = memory.data_mut();
+            let bytes = &mut data[range];
+            let n = match self.descriptor(fd)? {
+                Descriptor::Stdin(reader) => reader.read(bytes)?,
+                Descriptor::File(file) => file.read(bytes)?,
+                _ => return Err(Errno::Badf),
+            };
+            total += n as u32;
+            // stop at the end of file, or when no more bytes are available now.
+            if n < len {
+                break;
+            }
+        }
+        self.write_u32(nread, total)
+    }
+
+    fn fd_seek(&mut self, fd: u32, offset: i64, whence: u32, newoffset: u32) -> Result<(), Errno> {
+        let pos = match whence {
+            0 => SeekFrom::Start(u64::try_from(offset).map_err(|_| Errno::Inval)?),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return Err(Errno::Inval),
+        };
+        let position = match self.descriptor(fd)? {
+            Descriptor::File(file) => file.seek(pos)?,
+            Descriptor::Dir { .. } => return Err(Errno::Badf),
+            _ => return Err(Errno::Spipe),
+        };
+        self.write_u64(newoffset, position)
+    }
+
+    fn fd_close(&mut self, fd: u32) -> Result<(), Errno> {
+        self.descriptor(fd)?;
+        self.fds[fd as usize] = None;
+        Ok(())
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#fdstat
+    fn fd_fdstat_get(&mut self, fd: u32, buf: u32) -> Result<(), Errno> {
+        let filetype = match self.descriptor(fd)? {
+            Descriptor::Stdin(_) | Descriptor::Stdout(_) => FILETYPE_CHARACTER_DEVICE,
+            Descriptor::File(_) => FILETYPE_REGULAR_FILE,
+            Descriptor::Dir { .. } => FILETYPE_DIRECTORY,
+        };
+        let mut stat = [0; 24];
+        stat[0] = filetype;
+        stat[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        stat[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        self.write_bytes(buf, &stat)
+    }
+
+    /// Only preopened directories have prestat, the guest enumerates them from fd 3 until `Badf`.
+    fn fd_prestat_get(&mut self, fd: u32, buf: u32) -> Result<(), Errno> {
+        let len = match self.descriptor(fd)? {
+            Descriptor::Dir {
+                preopen: Some(name),
+                ..
+            } => name.len() as u32,
+            _ => return Err(Errno::Badf),
+        };
+        // the tag of directory is 0.
+        self.write_u32(buf, 0)?;
+        self.write_u32(buf.checked_add(4).ok_or(Errno::Fault)?, len)
+    }
+
+    fn fd_prestat_dir_name(&mut self, fd: u32, path: u32, path_len: u32) -> Result<(), Errno> {
+        let name = match self.descriptor(fd)? {
+            Descriptor::Dir {
+                preopen: Some(name),
+                ..
+            } => name.clone(),
+            _ => return Err(Errno::Badf),
+        };
+        if name.len() > path_len as usize {
+            return Err(Errno::Nametoolong);
+        }
+        self.write_bytes(path, name.as_bytes())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn path_open(
+        &mut self,
+        dirfd: u32,
+        path: u32,
+        path_len: u32,
+        oflags: u32,
+        rights: u64,
+        fdflags: u32,
+        opened_fd: u32,
+    ) -> Result<(), Errno> {
+        let path = self.read_bytes(path, path_len)?;
+        let path = core::str::from_utf8(&path).map_err(|_| Errno::Inval)?;
+        let flags = OpenFlags {
+            read: rights & RIGHTS_FD_READ != 0,
+            write: rights & RIGHTS_FD_WRITE != 0,
+            append: fdflags & FDFLAGS_APPEND != 0,
+            create: oflags & OFLAGS_CREAT != 0,
+            exclusive: oflags & OFLAGS_EXCL != 0,
+            truncate: oflags & OFLAGS_TRUNC != 0,
+            directory: oflags & OFLAGS_DIRECTORY != 0,
+        };
+        let opened = match self.descriptor(dirfd)? {
+            Descriptor::Dir { dir, .. } => dir.open(path, &flags)?,
+            _ => return Err(Errno::Notdir),
+        };
+        let descriptor = match opened {
+            Opened::File(file) => Descriptor::File(file),
+            Opened::Dir(dir) => Descriptor::Dir { dir, preopen: None },
+        };
+        let fd = self.insert(descriptor);
+        self.write_u32(opened_fd, fd)
+    }
+
+    fn clock_time_get(&mut self, id: u32, _precision: u64, time: u32) -> Result<(), Errno> {
+        let nanos = match id {
+            // realtime
+            0 => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| Errno::Io)?
+                .as_nanos(),
+            // monotonic, the cpu time of process and thread are approximated by it.
+            1..=3 => self.start.elapsed().as_nanos(),
+            _ => return Err(Errno::Inval),
+        };
+        self.write_u64(time, nanos as u64)
+    }
+
+    /// The bytes come from the randomly keyed hasher of std, they are unpredictable
+    /// but not suitable for cryptography.
+    fn random_get(&mut self, buf: u32, len: u32) -> Result<(), Errno> {
+        let range = self.range(buf, len)?;
+        let memory = self.memory()?.clone();
+        let mut data = memory.data_mut();
+        for chunk in data[range].chunks_mut(8) {
+            let mut hasher = self.random.build_hasher();
+            hasher.write_u64(self.random_counter);
+            self.random_counter += 1;
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+fn u32_arg(args: &[Value], index: usize) -> u32 {
+    match args.get(index) {
+        Some(Value::I32(v)) => *v as u32,
+        _ => 0,
+    }
+}
+
+fn u64_arg(args: &[Value], index: usize) -> u64 {
+    match args.get(index) {
+        Some(Value::I64(v)) => *v as u64,
+        _ => 0,
+    }
+}
+
+/// The arguments are checked against the signature before calling.
+type Handler = fn(&mut WasiCtx, &[Value]) -> Result<(), Errno>;
+
+/// A reference to the state of WASI, it's shared by the host functions.
+#[derive(Clone)]
+pub struct Wasi(Rc<RefCell<WasiCtx>>);
+
+impl Wasi {
+    pub fn new(ctx: WasiCtx) -> Self {
+        Self(Rc::new(RefCell::new(ctx)))
+    }
+
+    /// The memory of guest, the pointers passed to WASI functions point into it.
+    pub fn set_memory(&self, memory: Memory) {
+        self.0.borrow_mut().memory = Some(memory);
+    }
+
+    /// Register the functions of `wasi_snapshot_preview1` to the linker.
+    pub fn define(&self, linker: &mut Linker) {
+        let handlers: [(&str, &[ValueType], Handler); 16] = [
+            ("args_get", &[I32, I32], |ctx, a| {
+                ctx.args_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("args_sizes_get", &[I32, I32], |ctx, a| {
+                ctx.args_sizes_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("environ_get", &[I32, I32], |ctx, a| {
+                ctx.environ_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("environ_sizes_get", &[I32, I32], |ctx, a| {
+                ctx.environ_sizes_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("fd_write", &[I32, I32, I32, I32], |ctx, a| {
+                ctx.fd_write(u32_arg(a, 0), u32_arg(a, 1), u32_arg(a, 2), u32_arg(a, 3))
+            }),
+            ("fd_read", &[I32, I32, I32, I32], |ctx, a| {
+                ctx.fd_read(u32_arg(a, 0), u32_arg(a, 1), u32_arg(a, 2), u32_arg(a, 3))
+            }),
+            ("fd_seek", &[I32, I64, I32, I32], |ctx, a| {
+                let offset = u64_arg(a, 1) as i64;
+                ctx.fd_seek(u32_arg(a, 0), offset, u32_arg(a, 2) & 0xff, u32_arg(a, 3))
+            }),
+            ("fd_close", &[I32], |ctx, a| ctx.fd_close(u32_arg(a, 0))),
+            ("fd_fdstat_get", &[I32, I32], |ctx, a| {
+                ctx.fd_fdstat_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("fd_prestat_get", &[I32, I32], |ctx, a| {
+                ctx.fd_prestat_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("fd_prestat_dir_name", &[I32, I32, I32], |ctx, a| {
+                ctx.fd_prestat_dir_name(u32_arg(a, 0), u32_arg(a, 1), u32_arg(a, 2))
+            }),
+            // the lookup flags(symbolic links are always followed) and inherited rights are ignored.
+            (
+                "path_open",
+                &[I32, I32, I32, I32, I32, I64, I64, I32, I32],
+                |ctx, a| {
+                    ctx.path_open(
+                        u32_arg(a, 0),
+                        u32_arg(a, 2),
+                        u32_arg(a, 3),
+                        u32_arg(a, 4),
+                        u64_arg(a, 5),
+                        u32_arg(a, 7),
+                        u32_arg(a, 8),
+                    )
+                },
+            ),
+            ("clock_time_get", &[I32, I64, I32], |ctx, a| {
+                ctx.clock_time_get(u32_arg(a, 0), u64_arg(a, 1), u32_arg(a, 2))
+            }),
+            ("random_get", &[I32, I32], |ctx, a| {
+                ctx.random_get(u32_arg(a, 0), u32_arg(a, 1))
+            }),
+            ("sched_yield", &[], |_, _| Ok(())),
+            ("fd_sync", &[I32], |ctx, a| {
+                match ctx.descriptor(u32_arg(a, 0))? {
+                    Descriptor::File(file) => file.sync(),
+                    _ => Ok(()),
+                }
+            }),
+        ];
+        for (name, params, handler) in handlers {
+            let ctx = self.0.clone();
+            let func_type = FunctionType {
+                param_types: params.to_vec(),
+                return_types: vec![I32],
+            };
+            linker.func(MODULE, name, func_type, move |args| {
+                let errno = handler(&mut ctx.borrow_mut(), args)
+                    .err()
+                    .map_or(0, |e| e as i32);
+                Ok(vec![Value::I32(errno)])
+            });
+        }
+
+        let func_type = FunctionType {
+            param_types: vec![I32],
+            return_types: vec![],
+        };
+        linker.func(MODULE, "proc_exit", func_type, |args| {
+            Err(Trap::Exit(u32_arg(args, 0) as i32))
+        });
+    }
+
+    /// Instantiate the module with the linker, its exported `memory` is bound to WASI
+    /// before the start function runs.
+    pub fn instantiate(
+        &self,
+        linker: &Linker,
+        module: &WasmModule,
+    ) -> Result<Instance, InstantiationError> {
+        let instance =
+            linker.instantiate_with(module, |module, name| linker.get(module, name).cloned())?;
+        // the start function may call WASI already.
+        if let Some(memory) = instance.get_memory("memory") {
+            self.set_memory(memory);
+        }
+        instance.start(module)?;
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse;
+    use std::fs;
+
+    /// A writer which can be inspected after moving into WASI.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(ctx: WasiCtx, source: &str) -> (Result<Vec<Value>, Trap>, Wasi) {
+        let wasi = Wasi::new(ctx);
+        let mut linker = Linker::new();
+        wasi.define(&mut linker);
+        let module = parse(source).unwrap();
+        let instance = wasi.instantiate(&linker, &module).unwrap();
+        (instance.invoke("_start", &[]), wasi)
+    }
+
+    #[test]
+    fn hello_world_should_work() {
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 8) "hello, ")
+              (func (export "_start")
+                ;; iovecs at 100: "hello, " and the second argument
+                (drop (call $args_sizes_get (i32.const 200) (i32.const 204)))
+                (drop (call $args_get (i32.const 300) (i32.const 400)))
+                (i32.store (i32.const 100) (i32.const 8))
+                (i32.store (i32.const 104) (i32.const 7))
+                (i32.store (i32.const 108) (i32.load (i32.const 304)))
+                (i32.store (i32.const 112) (i32.sub (i32.load (i32.const 204)) (i32.const 7)))
+                (drop (call $fd_write (i32.const 1) (i32.const 100) (i32.const 2) (i32.const 0)))
+                (call $proc_exit (i32.load (i32.const 200)))
+                unreachable))
+        "#;
+        let stdout = Output::default();
+        let mut ctx = WasiCtx::new();
+        ctx.arg("hello").arg("world").stdout(stdout.clone());
+        let (result, wasi) = run(ctx, source);
+        // the program exits with argc.
+        assert_eq!(result, Err(Trap::Exit(2)));
+        assert_eq!(&*stdout.0.borrow(), b"hello, world");
+        let memory = wasi.0.borrow().memory.clone().unwrap();
+        let mut nwritten = [0; 4];
+        memory.read(0, &mut nwritten).unwrap();
+        assert_eq!(u32::from_le_bytes(nwritten), 12);
+    }
+
+    #[test]
+    fn guest_lengths_should_be_checked_before_allocating() {
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_prestat_get" (func $prestat_get (param i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (func (export "_start") (result i32 i32 i32 i32 i32 i32 i32 i32)
+                ;; an iovec of 4GiB, and an iovec after a valid one.
+                (i32.store (i32.const 16) (i32.const 0))
+                (i32.store (i32.const 20) (i32.const -1))
+                (i32.store (i32.const 24) (i32.const 0xfff0))
+                (i32.store (i32.const 28) (i32.const 0x20))
+                (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 0))
+                (call $fd_read (i32.const 0) (i32.const 16) (i32.const 1) (i32.const 0))
+                (call $fd_write (i32.const 1) (i32.const 8) (i32.const 3) (i32.const 0))
+                ;; the iovec array itself is out of bounds.
+                (call $fd_write (i32.const 1) (i32.const 16) (i32.const 0x20000000) (i32.const 0))
+                (call $random_get (i32.const 0) (i32.const 0x7fffffff))
+                (call $random_get (i32.const -1) (i32.const 2))
+                (call $args_get (i32.const -4) (i32.const 0))
+                (call $prestat_get (i32.const 3) (i32.const -2))))
+        "#;
+        let stdout = Output::default();
+        let mut ctx = WasiCtx::new();
+        ctx.arg("a")
+            .arg("b")
+            .stdout(stdout.clone())
+            .preopen(VirtualDir::new(), "/");
+        let (result, _) = run(ctx, source);
+        let expected = vec![Value::I32(Errno::Fault as i32); 8];
+        assert_eq!(result, Ok(expected));
+        assert!(stdout.0.borrow().is_empty());
+    }
+
+    #[test]
+    fn start_function_should_call_wasi() {
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (global $errno (mut i32) (i32.const -1))
+              (data (i32.const 8) "started")
+              (func $init
+                (i32.store (i32.const 0) (i32.const 8))
+                (i32.store (i32.const 4) (i32.const 7))
+                (global.set $errno (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16))))
+              (func (export "_start") (result i32) (global.get $errno))
+              (start $init))
+        "#;
+        let stdout = Output::default();
+        let mut ctx = WasiCtx::new();
+        ctx.stdout(stdout.clone());
+        let (result, _) = run(ctx, source);
+        assert_eq!(result, Ok(vec![Value::I32(0)]));
+        assert_eq!(&*stdout.0.borrow(), b"started");
+    }
+
+    #[test]
+    fn path_open_should_stay_in_preopened_dirs() {
+        let dir = std::env::temp_dir().join(format!("luban-wasi-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/input.txt"), "from host").unwrap();
+
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_prestat_get" (func $prestat_get (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_prestat_dir_name" (func $prestat_dir_name (param i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "path_open"
+                (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 16) "sub/../sub/input.txt")
+              (data (i32.const 48) "../etc/passwd")
+              (data (i32.const 64) "output.txt")
+              (func $open (param $path i32) (param $len i32) (param $oflags i32) (param $rights i64) (result i32)
+                (call $path_open (i32.const 3) (i32.const 0) (local.get $path) (local.get $len)
+                  (local.get $oflags) (local.get $rights) (i64.const 0) (i32.const 0) (i32.const 0)))
+              (func (export "prestat") (result i32 i32 i32)
+                (call $prestat_get (i32.const 3) (i32.const 0))
+                (drop (call $prestat_dir_name (i32.const 3) (i32.const 8) (i32.load (i32.const 4))))
+                (i32.load (i32.const 8))
+                (call $prestat_get (i32.const 4) (i32.const 0)))
+              (func (export "read") (result i32 i32)
+                (drop (call $open (i32.const 16) (i32.const 20) (i32.const 0) (i64.const 2)))
+                ;; read into 200 through the iovec at 100
+                (i32.store (i32.const 100) (i32.const 200))
+                (i32.store (i32.const 104) (i32.const 64))
+                (drop (call $fd_read (i32.load (i32.const 0)) (i32.const 100) (i32.const 1) (i32.const 108)))
+                (i32.store (i32.const 104) (i32.load (i32.const 108)))
+                (drop (call $fd_write (i32.const 1) (i32.const 100) (i32.const 1) (i32.const 112)))
+                (i32.load (i32.const 108))
+                (call $fd_close (i32.load (i32.const 0))))
+              (func (export "escape") (result i32)
+                (call $open (i32.const 48) (i32.const 13) (i32.const 0) (i64.const 2)))
+              (func (export "create") (result i32)
+                ;; O_CREAT with fd_write right
+                (drop (call $open (i32.const 64) (i32.const 10) (i32.const 1) (i64.const 64)))
+                (i32.store (i32.const 100) (i32.const 16))
+                (i32.store (i32.const 104) (i32.const 3))
+                (call $fd_write (i32.load (i32.const 0)) (i32.const 100) (i32.const 1) (i32.const 108))))
+        "#;
+        let stdout = Output::default();
+        let mut ctx = WasiCtx::new();
+        ctx.stdout(stdout.clone())
+            .preopen_dir(&dir, "/data")
+            .unwrap();
+        let wasi = Wasi::new(ctx);
+        let mut linker = Linker::new();
+        wasi.define(&mut linker);
+        let instance = wasi.instantiate(&linker, &parse(source).unwrap()).unwrap();
+
+        // "/data" is 5 bytes, fd 4 is not opened.
+        let badf = Value::I32(Errno::Badf as i32);
+        let name = i32::from_le_bytes(*b"/dat");
+        assert_eq!(
+            instance.invoke("prestat", &[]),
+            Ok(vec![Value::I32(0), Value::I32(name), badf.clone()])
+        );
+        assert_eq!(
+            instance.invoke("read", &[]),
+            Ok(vec![Value::I32(9), Value::I32(0)])
+        );
+        assert_eq!(&*stdout.0.borrow(), b"from host");
+        assert_eq!(
+            instance.invoke("escape", &[]),
+            Ok(vec![Value::I32(Errno::Notcapable as i32)])
+        );
+        assert_eq!(instance.invoke("create", &[]), Ok(vec![Value::I32(0)]));
+        assert_eq!(fs::read(dir.join("output.txt")).unwrap(), b"sub");
+        // the closed descriptor is reused.
+        assert_eq!(
+            instance.invoke("read", &[]),
+            Ok(vec![Value::I32(9), Value::I32(0)])
+        );
+        assert_eq!(instance.invoke("read", &[]).unwrap()[1], Value::I32(0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn guest_should_copy_files_in_virtual_dir() {
+        let source = r#"
+            (module
+              (import "wasi_snapshot_preview1" "path_open"
+                (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 16) "in.txt")
+              (data (i32.const 32) "out/copy.txt")
+              (data (i32.const 48) "../in.txt")
+              (func $open (param $path i32) (param $len i32) (param $oflags i32) (param $rights i64)
+                (param $fd i32) (result i32)
+                (call $path_open (i32.const 3) (i32.const 0) (local.get $path) (local.get $len)
+                  (local.get $oflags) (local.get $rights) (i64.const 0) (i32.const 0) (local.get $fd)))
+              (func (export "_start") (result i32 i32)
+                (drop (call $open (i32.const 16) (i32.const 6) (i32.const 0) (i64.const 2) (i32.const 0)))
+                ;; O_CREAT | O_TRUNC
+                (drop (call $open (i32.const 32) (i32.const 12) (i32.const 9) (i64.const 64) (i32.const 4)))
+                (i32.store (i32.const 100) (i32.const 200))
+                (i32.store (i32.const 104) (i32.const 64))
+                (drop (call $fd_read (i32.load (i32.const 0)) (i32.const 100) (i32.const 1) (i32.const 104)))
+                (call $fd_write (i32.load (i32.const 4)) (i32.const 100) (i32.const 1) (i32.const 108))
+                (call $open (i32.const 48) (i32.const 9) (i32.const 0) (i64.const 2) (i32.const 8))))
+        "#;
+        let dir = VirtualDir::new();
+        dir.write_file("in.txt", "virtual").unwrap();
+        dir.write_file("out/copy.txt", "to be truncated").unwrap();
+        let mut ctx = WasiCtx::new();
+        ctx.preopen(dir.clone(), ".");
+        let (result, _) = run(ctx, source);
+        let notcapable = Value::I32(Errno::Notcapable as i32);
+        assert_eq!(result, Ok(vec![Value::I32(0), notcapable]));
+        assert_eq!(dir.read_file("out/copy.txt").unwrap(), b"virtual");
+    }
+}
@@ -0,0 +1,374 @@
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use std::io::SeekFrom;
+
+use super::filesystem::{OpenFlags, Opened, WasiDir, WasiFile};
+use super::Errno;
+
+type Entries = Rc<RefCell<BTreeMap<String, Node>>>;
+
+#[derive(Clone)]
+enum Node {
+    File(Rc<RefCell<Vec<u8>>>),
+    Dir(Entries),
+}
+
+/// The size limit of files written by the guest by default, 1GiB.
+pub const MAX_FILE_SIZE: usize = 1 << 30;
+
+/// The size limit of all files in a tree by default, 1GiB.
+pub const MAX_TOTAL_SIZE: usize = 1 << 30;
+
+/// The bytes held by the files of a tree, shared by its directories and opened files.
+struct Usage {
+    used: Cell<usize>,
+    max: usize,
+}
+
+impl Usage {
+    /// The guest can't grow the files beyond the budget.
+    fn reserve(&self, size: usize) -> Result<(), Errno> {
+        let used = self
+            .used
+            .get()
+            .checked_add(size)
+            .filter(|used| *used <= self.max)
+            .ok_or(Errno::Nospc)?;
+        self.used.set(used);
+        Ok(())
+    }
+
+    /// The files written by the host are counted, but never refused.
+    fn add(&self, size: usize) {
+        self.used.set(self.used.get().saturating_add(size));
+    }
+
+    fn release(&self, size: usize) {
+        self.used.set(self.used.get().saturating_sub(size));
+    }
+}
+
+/// An in-memory directory tree, the host can prepare and inspect files through it
+/// while the guest is using it.
+#[derive(Clone)]
+pub struct VirtualDir {
+    root: Entries,
+    /// The names from the root to this directory.
+    path: Vec<String>,
+    max_file_size: usize,
+    usage: Rc<Usage>,
+}
+
+impl Default for VirtualDir {
+    fn default() -> Self {
+        Self::with_limits(MAX_FILE_SIZE, MAX_TOTAL_SIZE)
+    }
+}
+
+impl VirtualDir {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The guest can't write a file beyond `size` bytes, the files written by the host are not limited.
+    pub fn with_max_file_size(size: usize) -> Self {
+        Self::with_limits(size, MAX_TOTAL_SIZE)
+    }
+
+    /// Limit every file and all files of the tree, [`Errno::Nospc`] is returned
+    /// when the guest writes beyond `max_total_size` bytes in total.
+    pub fn with_limits(max_file_size: usize, max_total_size: usize) -> Self {
+        Self {
+            root: Entries::default(),
+            path: vec![],
+            max_file_size,
+            usage: Rc::new(Usage {
+                used: Cell::new(0),
+                max: max_total_size,
+            }),
+        }
+    }
+
+    /// The path is split by `/`, `..` can't go beyond the root.
+    fn names(&self, path: &str) -> Result<Vec<String>, Errno> {
+        if path.starts_with('/') {
+            return Err(Errno::Notcapable);
+        }
+        let mut names = self.path.clone();
+        for name in path.split('/') {
+            match name {
+                "" | "." => (),
+                ".." => {
+                    names.pop().ok_or(Errno::Notcapable)?;
+                }
+                _ => names.push(name.to_string()),
+            }
+        }
+        Ok(names)
+    }
+
+    fn dir(&self, names: &[String], create: bool) -> Result<Entries, Errno> {
+        let mut entries = self.root.clone();
+        for name in names {
+            let next = match entries.borrow().get(name) {
+                Some(Node::Dir(dir)) => dir.clone(),
+                Some(Node::File(_)) => return Err(Errno::Notdir),
+                None if create => Entries::default(),
+                None => return Err(Errno::Noent),
+            };
+            entries
+                .borrow_mut()
+                .entry(name.clone())
+                .or_insert_with(|| Node::Dir(next.clone()));
+            entries = next;
+        }
+        Ok(entries)
+    }
+
+    /// Create a file with the contents, or replace the existing one, the missing parents are created.
+    pub fn write_file(&self, path: &str, contents: impl Into<Vec<u8>>) -> Result<(), Errno> {
+        let mut names = self.names(path)?;
+        let name = names.pop().ok_or(Errno::Isdir)?;
+        let dir = self.dir(&names, true)?;
+        let mut entries = dir.borrow_mut();
+        match entries.get(&name) {
+            Some(Node::Dir(_)) => return Err(Errno::Isdir),
+            Some(Node::File(data)) => self.usage.release(data.borrow().len()),
+            None => (),
+        }
+        let contents = contents.into();
+        self.usage.add(contents.len());
+        entries.insert(name, Node::File(Rc::new(RefCell::new(contents))));
+        Ok(())
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Errno> {
+        let mut names = self.names(path)?;
+        let name = names.pop().ok_or(Errno::Isdir)?;
+        match self.dir(&names, false)?.borrow().get(&name) {
+            Some(Node::File(data)) => Ok(data.borrow().clone()),
+            Some(Node::Dir(_)) => Err(Errno::Isdir),
+            None => Err(Errno::Noent),
+        }
+    }
+
+    /// Create the directory and its missing parents.
+    pub fn create_dir(&self, path: &str) -> Result<(), Errno> {
+        let names = self.names(path)?;
+        self.dir(&names, true).map(|_| ())
+    }
+}
+
+impl WasiDir for VirtualDir {
+    fn open(&self, path: &str, flags: &OpenFlags) -> Result<Opened, Errno> {
+        let mut names = self.names(path)?;
+        let name = match names.pop() {
+            Some(name) => name,
+            None if flags.writable() => return Err(Errno::Isdir),
+            None => return Ok(Opened::Dir(Box::new(self.clone()))),
+        };
+        let dir = self.dir(&names, false)?;
+        let existing = dir.borrow().get(&name).cloned();
+        let data = match existing {
+            Some(Node::Dir(_)) if flags.writable() => return Err(Errno::Isdir),
+            Some(Node::Dir(_)) => {
+                names.push(name);
+                return Ok(Opened::Dir(Box::new(VirtualDir {
+                    root: self.root.clone(),
+                    path: names,
+                    max_file_size: self.max_file_size,
+                    usage: self.usage.clone(),
+                })));
+            }
+            Some(Node::File(_)) if flags.directory => return Err(Errno::Notdir),
+            Some(Node::File(_)) if flags.create && flags.exclusive => return Err(Errno::Exist),
+            Some(Node::File(data)) => data,
+            None if flags.create && !flags.directory => {
+                let data = Rc::new(RefCell::new(vec![]));
+                dir.borrow_mut().insert(name, Node::File(data.clone()));
+                data
+            }
+            None => return Err(Errno::Noent),
+        };
+        if flags.truncate {
+            let mut data = data.borrow_mut();
+            self.usage.release(data.len());
+            data.clear();
+        }
+        Ok(Opened::File(Box::new(VirtualFile {
+            data,
+            position: 0,
+            flags: *flags,
+            max_size: self.max_file_size,
+            usage: self.usage.clone(),
+        })))
+    }
+}
+
+struct VirtualFile {
+    data: Rc<RefCell<Vec<u8>>>,
+    position: usize,
+    flags: OpenFlags,
+    max_size: usize,
+    usage: Rc<Usage>,
+}
+
+impl WasiFile for VirtualFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        if !self.flags.readable() {
+            return Err(Errno::Badf);
+        }
+        let data = self.data.borrow();
+        let start = self.position.min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.position = start + n;
+        Ok(n)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Errno> {
+        if !self.flags.writable() {
+            return Err(Errno::Badf);
+        }
+        let mut data = self.data.borrow_mut();
+        if self.flags.append {
+            self.position = data.len();
+        }
+        // the guest can seek anywhere, but can't make the host allocate for it.
+        let end = self
+            .position
+            .checked_add(bytes.len())
+            .filter(|end| *end <= self.max_size)
+            .ok_or(Errno::Fbig)?;
+        if data.len() < end {
+            self.usage.reserve(end - data.len())?;
+            data.resize(end, 0);
+        }
+        data[self.position..end].copy_from_slice(bytes);
+        self.position = end;
+        Ok(bytes.len())
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => add_offset(self.position as u64, offset),
+            SeekFrom::End(offset) => add_offset(self.data.borrow().len() as u64, offset),
+        };
+        let position = position.ok_or(Errno::Inval)?;
+        self.position = usize::try_from(position).map_err(|_| Errno::Inval)?;
+        Ok(position)
+    }
+}
+
+fn add_offset(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.unsigned_abs())
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_dir_should_work() {
+        let root = VirtualDir::new();
+        root.write_file("a/b.txt", "hello").unwrap();
+        let sub = match root.open("a", &OpenFlags::default()) {
+            Ok(Opened::Dir(dir)) => dir,
+            _ => panic!("expected directory"),
+        };
+        let flags = OpenFlags {
+            read: true,
+            write: true,
+            ..OpenFlags::default()
+        };
+        let mut file = match sub.open("../a/./b.txt", &flags) {
+            Ok(Opened::File(file)) => file,
+            _ => panic!("expected file"),
+        };
+        let mut buf = [0; 3];
+        assert_eq!(file.read(&mut buf), Ok(3));
+        assert_eq!(&buf, b"hel");
+        assert_eq!(file.seek(SeekFrom::End(-1)), Ok(4));
+        assert_eq!(file.write(b"!!"), Ok(2));
+        assert_eq!(root.read_file("a/b.txt").unwrap(), b"hell!!");
+        assert_eq!(file.seek(SeekFrom::Current(-7)), Err(Errno::Inval));
+
+        assert!(matches!(sub.open("../..", &flags), Err(Errno::Notcapable)));
+        assert!(matches!(root.open("/a", &flags), Err(Errno::Notcapable)));
+        assert!(matches!(root.open("c", &flags), Err(Errno::Noent)));
+        assert!(matches!(root.open("a/b.txt/c", &flags), Err(Errno::Notdir)));
+        let create = OpenFlags {
+            create: true,
+            exclusive: true,
+            ..flags
+        };
+        assert!(matches!(root.open("a/b.txt", &create), Err(Errno::Exist)));
+        assert!(matches!(sub.open("c.txt", &create), Ok(Opened::File(_))));
+        assert_eq!(root.read_file("a/c.txt").unwrap(), b"");
+    }
+
+    #[test]
+    fn guest_writes_should_be_limited() {
+        let root = VirtualDir::with_max_file_size(8);
+        let flags = OpenFlags {
+            write: true,
+            create: true,
+            ..OpenFlags::default()
+        };
+        let mut file = match root.open("a.txt", &flags) {
+            Ok(Opened::File(file)) => file,
+            _ => panic!("expected file"),
+        };
+        assert_eq!(file.write(b"12345678"), Ok(8));
+        assert_eq!(file.write(b"9"), Err(Errno::Fbig));
+        assert_eq!(
+            file.seek(SeekFrom::Start(i64::MAX as u64)),
+            Ok(i64::MAX as u64)
+        );
+        assert_eq!(file.write(b"x"), Err(Errno::Fbig));
+        assert_eq!(file.seek(SeekFrom::Start(1 << 40)), Ok(1 << 40));
+        assert_eq!(file.write(b"x"), Err(Errno::Fbig));
+        assert_eq!(root.read_file("a.txt").unwrap(), b"12345678");
+    }
+
+    #[test]
+    fn guest_writes_should_share_the_budget() {
+        let root = VirtualDir::with_limits(8, 12);
+        root.write_file("host.txt", "1234").unwrap();
+        let flags = OpenFlags {
+            write: true,
+            create: true,
+            ..OpenFlags::default()
+        };
+        let open = |path| match root.open(path, &flags) {
+            Ok(Opened::File(file)) => file,
+            _ => panic!("expected file"),
+        };
+        let mut a = open("a.txt");
+        let mut b = open("dir/../b.txt");
+        assert_eq!(a.write(b"12345"), Ok(5));
+        assert_eq!(b.write(b"123"), Ok(3));
+        assert_eq!(b.write(b"4"), Err(Errno::Nospc));
+        // overwriting doesn't take more space.
+        assert_eq!(a.seek(SeekFrom::Start(0)), Ok(0));
+        assert_eq!(a.write(b"abcde"), Ok(5));
+        assert_eq!(root.read_file("b.txt").unwrap(), b"123");
+
+        // truncating and replacing files give the space back.
+        let truncate = OpenFlags {
+            truncate: true,
+            ..flags
+        };
+        assert!(matches!(root.open("a.txt", &truncate), Ok(Opened::File(_))));
+        assert_eq!(b.write(b"4"), Ok(1));
+        root.write_file("host.txt", "").unwrap();
+        assert_eq!(b.write(b"5678"), Ok(4));
+        assert_eq!(b.write(b"9"), Err(Errno::Fbig));
+    }
+}
@@ -0,0 +1,26 @@
+use crate::text::ParseError;
+use crate::vm::Trap;
+use thiserror::Error;
+
+/// Why a directive of script failed.
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum WastError {
+    /// The directive itself is malformed, the modules inside are reported as `Rejected`.
+    #[error("{0}")]
+    Parse(#[from] ParseError),
+    #[error("unknown directive {0}")]
+    UnknownDirective(String),
+    #[error("unknown module {0}")]
+    UnknownModule(String),
+    #[error("no module is instantiated")]
+    NoModule,
+    #[error("unknown export {0}")]
+    UnknownExport(String),
+    /// The module should be accepted, but it failed to decode, validate or instantiate.
+    #[error("module is rejected: {0}")]
+    Rejected(String),
+    #[error("unexpected trap: {0}")]
+    Trapped(Trap),
+    #[error("expected {expected}, got {actual}")]
+    Mismatch { expected: String, actual: String },
+}
@@ -0,0 +1,429 @@
+//! https://github.com/WebAssembly/spec/tree/main/interpreter#scripts
+//! Run the `.wast` scripts of the official spec tests, every directive is reported separately.
+
+use std::collections::HashMap;
+
+use crate::sections::WasmModule;
+use crate::text::lexer::{self, parse_float, parse_int, Sexpr};
+use crate::text::parser::{self, number, Cursor};
+use crate::text::{ParseError, Position};
+use crate::validation::validate;
+use crate::vm::{Instance, InstantiationError, Linker, Store, Trap, Value};
+
+pub mod errors;
+pub mod spectest;
+
+pub use self::errors::WastError;
+pub use self::spectest::define_spectest;
+
+/// The result of one directive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    /// The keyword of directive, like `assert_return`.
+    pub directive: String,
+    pub pos: Position,
+    pub result: Result<(), WastError>,
+}
+
+/// Run the script, only malformed tokens fail the whole script.
+pub fn run(source: &str) -> Result<Vec<Outcome>, ParseError> {
+    let items = lexer::tokenize(source)?;
+    let mut runner = Runner::new();
+    Ok(items.iter().map(|item| runner.directive(item)).collect())
+}
+
+/// A module in script, it's decoded when used.
+enum ScriptModule<'a> {
+    Fields(Cursor<'a>),
+    Quote(Position, Vec<u8>),
+    Binary(Vec<u8>),
+}
+
+impl<'a> ScriptModule<'a> {
+    /// `(module $id? field*)`, `(module $id? quote string*)` or `(module $id? binary string*)`.
+    fn parse(cursor: &mut Cursor<'a>) -> Result<(Option<&'a str>, Self), ParseError> {
+        let pos = cursor.peek().map_or_else(Position::default, Sexpr::pos);
+        let mut list = cursor.list()?;
+        list.expect_keyword("module")?;
+        let id = list.id().map(|(id, _)| id);
+        let module = match list.peek_keyword() {
+            Some(kind @ ("quote" | "binary")) => {
+                list.next()?;
+                let mut bytes = vec![];
+                while !list.is_empty() {
+                    bytes.extend_from_slice(list.string()?);
+                }
+                match kind {
+                    "quote" => ScriptModule::Quote(pos, bytes),
+                    _ => ScriptModule::Binary(bytes),
+                }
+            }
+            _ => ScriptModule::Fields(list),
+        };
+        Ok((id, module))
+    }
+
+    fn decode(&self) -> Result<WasmModule<'_>, String> {
+        match self {
+            ScriptModule::Fields(fields) => {
+                parser::module(fields.clone()).map_err(|e| e.to_string())
+            }
+            ScriptModule::Quote(pos, source) => {
+                let source = core::str::from_utf8(source)
+                    .map_err(|_| ParseError::MalformedUtf8 { pos: *pos }.to_string())?;
+                crate::text::parse(source).map_err(|e| e.to_string())
+            }
+            ScriptModule::Binary(bytes) => WasmModule::from_bytes(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// The expected result of `assert_return`.
+#[derive(Clone, Debug)]
+enum Expected {
+    Value(Value),
+    /// A NaN of either float type, only the highest bit of payload is set.
+    CanonicalNan,
+    /// A NaN whose highest bit of payload is set.
+    ArithmeticNan,
+    /// Any non-null function reference.
+    FuncRef,
+    /// Any non-null external reference.
+    ExternRef,
+}
+
+impl Expected {
+    fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Expected::Value(expected), value) => expected == value,
+            (Expected::CanonicalNan, Value::F32(v)) => v.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+            (Expected::CanonicalNan, Value::F64(v)) => {
+                v.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000
+            }
+            (Expected::ArithmeticNan, Value::F32(v)) => v.to_bits() & 0x7fc0_0000 == 0x7fc0_0000,
+            (Expected::ArithmeticNan, Value::F64(v)) => {
+                v.to_bits() & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000
+            }
+            (Expected::FuncRef, Value::FuncRef(func)) => func.is_some(),
+            (Expected::ExternRef, Value::ExternRef(extern_ref)) => extern_ref.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// `(i32.const 1)`, `(f32.const nan:canonical)`, `(ref.null func)`, `(ref.extern 1)` and so on.
+fn constant(cursor: &mut Cursor<'_>) -> Result<Expected, ParseError> {
+    let mut list = cursor.list()?;
+    let (op, pos) = list.keyword()?;
+    let expected = match op {
+        "i32.const" => {
+            let (text, pos) = list.keyword()?;
+            Expected::Value(Value::I32(number(parse_int(text, 32), pos)? as i32))
+        }
+        "i64.const" => {
+            let (text, pos) = list.keyword()?;
+            Expected::Value(Value::I64(number(parse_int(text, 64), pos)? as i64))
+        }
+        "f32.const" | "f64.const" => {
+            let (text, pos) = list.keyword()?;
+            match (text, op) {
+                ("nan:canonical", _) => Expected::CanonicalNan,
+                ("nan:arithmetic", _) => Expected::ArithmeticNan,
+                (_, "f32.const") => {
+                    let bits = number(parse_float(text, 23, 8), pos)?;
+                    Expected::Value(Value::F32(f32::from_bits(bits as u32)))
+                }
+                _ => {
+                    let bits = number(parse_float(text, 52, 11), pos)?;
+                    Expected::Value(Value::F64(f64::from_bits(bits)))
+                }
+            }
+        }
+        "ref.null" => match list.keyword()? {
+            ("func", _) => Expected::Value(Value::FuncRef(None)),
+            ("extern", _) => Expected::Value(Value::ExternRef(None)),
+            (_, pos) => return Err(ParseError::UnexpectedToken { pos }),
+        },
+        "ref.extern" if list.is_empty() => Expected::ExternRef,
+        "ref.extern" => Expected::Value(Value::ExternRef(Some(list.u32()?))),
+        "ref.func" => Expected::FuncRef,
+        _ => {
+            return Err(ParseError::Unsupported {
+                feature: "constant",
+                pos,
+            })
+        }
+    };
+    list.finish()?;
+    Ok(expected)
+}
+
+/// The arguments of `invoke` must be exact values.
+fn argument(cursor: &mut Cursor<'_>) -> Result<Value, ParseError> {
+    let pos = cursor.peek().map_or_else(Position::default, Sexpr::pos);
+    match constant(cursor)? {
+        Expected::Value(value) => Ok(value),
+        _ => Err(ParseError::UnexpectedToken { pos }),
+    }
+}
+
+/// The error message must start with the expected one, like the reference interpreter.
+fn expect_message(actual: &str, expected: &str) -> Result<(), WastError> {
+    if !actual.starts_with(expected) {
+        return Err(WastError::Mismatch {
+            expected: format!("{:?}", expected),
+            actual: format!("{:?}", actual),
+        });
+    }
+    Ok(())
+}
+
+/// The modules are instantiated with `spectest` and the registered modules.
+struct Runner {
+    store: Store,
+    instances: HashMap<String, Instance>,
+    current: Option<Instance>,
+}
+
+impl Runner {
+    fn new() -> Self {
+        let mut linker = Linker::new();
+        define_spectest(&mut linker).expect("the spectest module is valid");
+        Self {
+            store: Store::with_linker(linker),
+            instances: HashMap::new(),
+            current: None,
+        }
+    }
+
+    fn directive(&mut self, item: &Sexpr) -> Outcome {
+        let directive = match item {
+            Sexpr::List(items, _) => match items.first() {
+                Some(Sexpr::Keyword(keyword, _)) => keyword.clone(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        Outcome {
+            directive,
+            pos: item.pos(),
+            result: self.execute(item),
+        }
+    }
+
+    fn execute(&mut self, item: &Sexpr) -> Result<(), WastError> {
+        let mut cursor = Cursor::new(core::slice::from_ref(item), item.pos());
+        if cursor.peek_list("module") {
+            return self.module(&mut cursor);
+        }
+        let mut cursor = cursor.list()?;
+        let (directive, _) = cursor.keyword()?;
+        match directive {
+            "register" => {
+                let name = cursor.name()?;
+                let id = cursor.id().map(|(id, _)| id);
+                cursor.finish()?;
+                let instance = self.instance(id)?.clone();
+                self.store.register(&name, instance);
+            }
+            "invoke" | "get" => {
+                let mut cursor = Cursor::new(core::slice::from_ref(item), item.pos());
+                self.action(&mut cursor)?.map_err(WastError::Trapped)?;
+                cursor.finish()?;
+            }
+            "assert_return" => {
+                let results = self.action(&mut cursor)?.map_err(WastError::Trapped)?;
+                let mut expected = vec![];
+                while !cursor.is_empty() {
+                    expected.push(constant(&mut cursor)?);
+                }
+                if results.len() != expected.len()
+                    || expected.iter().zip(&results).any(|(e, v)| !e.matches(v))
+                {
+                    return Err(WastError::Mismatch {
+                        expected: format!("{:?}", expected),
+                        actual: format!("{:?}", results),
+                    });
+                }
+            }
+            "assert_trap" | "assert_exhaustion" => {
+                let result = if cursor.peek_list("module") {
+                    self.instantiate(&mut cursor)?.map(|_| vec![])
+                } else {
+                    self.action(&mut cursor)?.map_err(InstantiationError::Trap)
+                };
+                let message = cursor.string()?;
+                cursor.finish()?;
+                let expected = String::from_utf8_lossy(message);
+                match result {
+                    Err(InstantiationError::Trap(trap)) => {
+                        expect_message(&trap.to_string(), &expected)?
+                    }
+                    Err(error) => return Err(WastError::Rejected(error.to_string())),
+                    Ok(results) => {
+                        return Err(WastError::Mismatch {
+                            expected: format!("trap {:?}", expected),
+                            actual: format!("{:?}", results),
+                        })
+                    }
+                }
+            }
+            "assert_invalid" | "assert_malformed" | "assert_unlinkable" => {
+                let (_, module) = ScriptModule::parse(&mut cursor)?;
+                let expected = String::from_utf8_lossy(cursor.string()?).into_owned();
+                cursor.finish()?;
+                let decoded = module.decode();
+                // only the errors of validation and linking follow the messages of the spec tests.
+                let error = match (directive, decoded) {
+                    ("assert_malformed", Err(_)) => return Ok(()),
+                    ("assert_malformed", Ok(_)) => None,
+                    (_, Err(error)) => return Err(WastError::Rejected(error)),
+                    ("assert_invalid", Ok(module)) => {
+                        validate(&module).err().map(|e| e.to_string())
+                    }
+                    (_, Ok(module)) => match self.store.instantiate(&module) {
+                        Err(
+                            error @ (InstantiationError::UnknownImport { .. }
+                            | InstantiationError::IncompatibleImportType { .. }),
+                        ) => Some(error.to_string()),
+                        Err(error) => return Err(WastError::Rejected(error.to_string())),
+                        Ok(_) => None,
+                    },
+                };
+                match error {
+                    Some(error) => expect_message(&error, &expected)?,
+                    None => {
+                        return Err(WastError::Mismatch {
+                            expected: format!("{} {:?}", directive, expected),
+                            actual: "a valid module".to_string(),
+                        })
+                    }
+                }
+            }
+            _ => return Err(WastError::UnknownDirective(directive.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Define a module, it becomes the current one.
+    fn module(&mut self, cursor: &mut Cursor<'_>) -> Result<(), WastError> {
+        // the following actions fail if the module is rejected.
+        self.current = None;
+        let (id, module) = ScriptModule::parse(cursor)?;
+        let module = module.decode().map_err(WastError::Rejected)?;
+        let instance = self
+            .store
+            .instantiate(&module)
+            .map_err(|error| WastError::Rejected(error.to_string()))?;
+        if let Some(id) = id {
+            self.instances.insert(id.to_string(), instance.clone());
+        }
+        self.current = Some(instance);
+        Ok(())
+    }
+
+    fn instantiate(
+        &mut self,
+        cursor: &mut Cursor<'_>,
+    ) -> Result<Result<Instance, InstantiationError>, WastError> {
+        let (_, module) = ScriptModule::parse(cursor)?;
+        let module = module.decode().map_err(WastError::Rejected)?;
+        Ok(self.store.instantiate(&module))
+    }
+
+    /// The current module, or the one named `id`.
+    fn instance(&self, id: Option<&str>) -> Result<&Instance, WastError> {
+        match id {
+            Some(id) => self
+                .instances
+                .get(id)
+                .ok_or_else(|| WastError::UnknownModule(id.to_string())),
+            None => self.current.as_ref().ok_or(WastError::NoModule),
+        }
+    }
+
+    /// `(invoke $id? name const*)` or `(get $id? name)`.
+    fn action(&self, cursor: &mut Cursor<'_>) -> Result<Result<Vec<Value>, Trap>, WastError> {
+        let mut list = cursor.list()?;
+        let (kind, pos) = list.keyword()?;
+        let instance = self.instance(list.id().map(|(id, _)| id))?;
+        let name = list.name()?;
+        match kind {
+            "invoke" => {
+                let mut args = vec![];
+                while !list.is_empty() {
+                    args.push(argument(&mut list)?);
+                }
+                Ok(instance.invoke(&name, &args))
+            }
+            "get" => {
+                list.finish()?;
+                let global = instance
+                    .get_global(&name)
+                    .ok_or(WastError::UnknownExport(name))?;
+                Ok(Ok(vec![global.get()]))
+            }
+            _ => Err(ParseError::UnexpectedToken { pos }.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_directives_should_be_reported() {
+        let script = r#"
+            (invoke "f")
+            (module $m (func (export "f") (result i32) (i32.const 1)))
+            (assert_return (invoke "f") (i32.const 1))
+            (assert_return (invoke "f") (i32.const 2))
+            (assert_trap (invoke "f") "unreachable")
+            (assert_invalid (module (func)) "type mismatch")
+            (assert_return (invoke $n "f"))
+            (module (func (import "spectest" "unknown")))
+            (assert_return (invoke "f") (i32.const 1))
+            (assert_foo)
+        "#;
+        let outcomes = run(script).unwrap();
+        let results: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| outcome.result.clone())
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Err(WastError::NoModule),
+                Ok(()),
+                Ok(()),
+                Err(WastError::Mismatch {
+                    expected: "[Value(I32(2))]".to_string(),
+                    actual: "[I32(1)]".to_string(),
+                }),
+                Err(WastError::Mismatch {
+                    expected: "trap \"unreachable\"".to_string(),
+                    actual: "[I32(1)]".to_string(),
+                }),
+                Err(WastError::Mismatch {
+                    expected: "assert_invalid \"type mismatch\"".to_string(),
+                    actual: "a valid module".to_string(),
+                }),
+                Err(WastError::UnknownModule("n".to_string())),
+                Err(WastError::Rejected(
+                    "unknown import spectest.unknown".to_string()
+                )),
+                Err(WastError::NoModule),
+                Err(WastError::UnknownDirective("assert_foo".to_string())),
+            ]
+        );
+        assert_eq!(outcomes[3].directive, "assert_return");
+        assert_eq!(
+            outcomes[3].pos,
+            Position {
+                line: 5,
+                column: 13
+            }
+        );
+    }
+}
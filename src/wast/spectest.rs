@@ -0,0 +1,53 @@
+use crate::sections::{FunctionType, GlobalType, Mutable, NumberType, ReferenceType, ValueType};
+use crate::vm::{Extern, Global, Linker, Memory, Table, Trap, Value};
+
+const I32: ValueType = ValueType::NumType(NumberType::I32);
+const I64: ValueType = ValueType::NumType(NumberType::I64);
+const F32: ValueType = ValueType::NumType(NumberType::F32);
+const F64: ValueType = ValueType::NumType(NumberType::F64);
+
+/// https://github.com/WebAssembly/spec/tree/main/interpreter#spectest-host-module
+/// Define the `spectest` module imported by the official spec tests,
+/// the print functions write their arguments to stdout.
+pub fn define_spectest(linker: &mut Linker) -> Result<(), Trap> {
+    let prints: [(&str, &[ValueType]); 7] = [
+        ("print", &[]),
+        ("print_i32", &[I32]),
+        ("print_i64", &[I64]),
+        ("print_f32", &[F32]),
+        ("print_f64", &[F64]),
+        ("print_i32_f32", &[I32, F32]),
+        ("print_f64_f64", &[F64, F64]),
+    ];
+    for (name, params) in prints {
+        let func_type = FunctionType {
+            param_types: params.to_vec(),
+            return_types: vec![],
+        };
+        linker.func("spectest", name, func_type, |args| {
+            println!("{:?}", args);
+            Ok(vec![])
+        });
+    }
+
+    let globals = [
+        ("global_i32", Value::I32(666)),
+        ("global_i64", Value::I64(666)),
+        ("global_f32", Value::F32(666.6)),
+        ("global_f64", Value::F64(666.6)),
+    ];
+    for (name, value) in globals {
+        let global_type = GlobalType {
+            val_type: value.value_type(),
+            mutable: Mutable::No,
+        };
+        let global = Global::new(global_type, value)?;
+        linker.define("spectest", name, Extern::Global(global));
+    }
+
+    let table = Table::new(ReferenceType::FuncRef, 10, Some(20))?;
+    linker.define("spectest", "table", Extern::Table(table));
+    let memory = Memory::new(1, Some(2))?;
+    linker.define("spectest", "memory", Extern::Memory(memory));
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Run the `.wast` scripts in the format of the official spec tests.
+//!
+//! `tests/testsuite` holds the scripts vendored from https://github.com/WebAssembly/testsuite
+//! by `tests/testsuite/vendor.sh`, and `tests/spec` holds smaller scripts adapted from them.
+//! Set `LUBAN_SPEC_VERBOSE` to print every directive.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use luban::wast;
+
+/// The scripts of the official testsuite which must be vendored.
+const REQUIRED: &[&str] = &[
+    "binary.wast",
+    "br_table.wast",
+    "call_indirect.wast",
+    "conversions.wast",
+    "exports.wast",
+    "f32.wast",
+    "f64.wast",
+    "i32.wast",
+    "i64.wast",
+    "imports.wast",
+    "linking.wast",
+    "memory.wast",
+];
+
+/// The directives which are known to fail, as `file.wast` or `file.wast:line`, with the reason.
+/// A skipped directive which passes is a failure too, so the list never gets stale.
+const SKIPPED: &[(&str, &str)] = &[];
+
+fn verbose() -> bool {
+    env::var_os("LUBAN_SPEC_VERBOSE").is_some()
+}
+
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "wast"))
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths
+}
+
+/// Run the scripts, return the failures which are not skipped,
+/// and the skipped directives which pass.
+fn run_scripts(paths: &[PathBuf], skipped: &[(&str, &str)]) -> Vec<String> {
+    let mut failures = vec![];
+    let mut used = HashSet::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let source = fs::read_to_string(path).unwrap();
+        let skip_file = skipped.iter().any(|(key, _)| *key == name);
+        if skip_file {
+            used.insert(name.clone());
+        }
+        let outcomes = match wast::run(&source) {
+            Ok(outcomes) => outcomes,
+            Err(error) if skip_file => {
+                if verbose() {
+                    println!("{}: skipped: {}", name, error);
+                }
+                continue;
+            }
+            Err(error) => {
+                failures.push(format!("{}: {}", name, error));
+                continue;
+            }
+        };
+        for outcome in outcomes {
+            let key = format!("{}:{}", name, outcome.pos.line);
+            let skip = skipped.iter().any(|(skipped, _)| *skipped == key);
+            if skip {
+                used.insert(key.clone());
+            }
+            let report = format!("{}:{} {}", name, outcome.pos, outcome.directive);
+            match outcome.result {
+                Ok(()) if skip => failures.push(format!("{} passed but it's skipped", report)),
+                Ok(()) => {
+                    if verbose() {
+                        println!("{} ok", report);
+                    }
+                }
+                Err(error) if skip || skip_file => {
+                    if verbose() {
+                        println!("{} skipped: {}", report, error);
+                    }
+                }
+                Err(error) => failures.push(format!("{} failed: {}", report, error)),
+            }
+        }
+    }
+    // the missing scripts are reported on their own.
+    let names: HashSet<_> = paths
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    for (key, reason) in skipped {
+        let name = key.split(':').next().unwrap();
+        if names.contains(name) && !used.contains(*key) {
+            failures.push(format!("{} is skipped ({}) but never run", key, reason));
+        }
+    }
+    failures
+}
+
+#[test]
+fn spec_scripts_should_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    let paths = scripts(&dir);
+    assert!(!paths.is_empty(), "no scripts in {}", dir.display());
+
+    let failures = run_scripts(&paths, &[]);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn testsuite_should_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testsuite");
+    let paths = scripts(&dir);
+    for (key, reason) in SKIPPED {
+        assert!(!reason.is_empty(), "{} is skipped without a reason", key);
+    }
+
+    let mut failures: Vec<_> = REQUIRED
+        .iter()
+        .filter(|name| !dir.join(name).exists())
+        .map(|name| {
+            format!(
+                "{} is required but not vendored, run tests/testsuite/vendor.sh",
+                name
+            )
+        })
+        .collect();
+    failures.extend(run_scripts(&paths, SKIPPED));
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,40 @@
+;; binary modules, adapted from the official binary.wast
+
+(module binary "\00asm" "\01\00\00\00")
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\05\01\60\00\01\7f"            ;; type section: [] -> [i32]
+  "\03\02\01\00"                     ;; function section
+  "\07\07\01\03\6f\6e\65\00\00"      ;; export "one"
+  "\0a\06\01\04\00\41\01\0b"         ;; code: i32.const 1
+)
+(assert_return (invoke "one") (i32.const 1))
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\00asm") "unexpected end")
+(assert_malformed (module binary "asm\00") "magic header not detected")
+(assert_malformed (module binary "\00asm" "\00\00\00\01") "unknown binary version")
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\0c\01\02" "\0b\01\00")
+  "data count and data section have inconsistent lengths"
+)
+
+(assert_trap
+  (module
+    (memory 1)
+    (data (i32.const 65535) "ab")
+  )
+  "out of bounds memory access"
+)
+(assert_trap
+  (module
+    (table 1 funcref)
+    (func)
+    (elem (i32.const 1) 0)
+  )
+  "out of bounds table access"
+)
+(assert_trap
+  (module (func $main (unreachable)) (start $main))
+  "unreachable"
+)
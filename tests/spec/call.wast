@@ -0,0 +1,54 @@
+;; calls and recursion, adapted from the official fac.wast and call.wast
+
+(module
+  (func $fac-rec (export "fac-rec") (param i64) (result i64)
+    (if (result i64) (i64.eq (local.get 0) (i64.const 0))
+      (then (i64.const 1))
+      (else (i64.mul (local.get 0) (call $fac-rec (i64.sub (local.get 0) (i64.const 1)))))
+    )
+  )
+  (func (export "fac-iter") (param i64) (result i64)
+    (local i64)
+    (local.set 1 (i64.const 1))
+    (block
+      (loop
+        (br_if 1 (i64.eqz (local.get 0)))
+        (local.set 1 (i64.mul (local.get 0) (local.get 1)))
+        (local.set 0 (i64.sub (local.get 0) (i64.const 1)))
+        (br 0)
+      )
+    )
+    (local.get 1)
+  )
+  (func $runaway (export "runaway") (call $runaway))
+  (func $even (export "even") (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 44))
+      (else (call $odd (i64.sub (local.get 0) (i64.const 1))))
+    )
+  )
+  (func $odd (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 99))
+      (else (call $even (i64.sub (local.get 0) (i64.const 1))))
+    )
+  )
+  (func (export "unreachable") (unreachable))
+)
+
+(assert_return (invoke "fac-rec" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "fac-iter" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "even" (i64.const 13)) (i32.const 99))
+(assert_return (invoke "even" (i64.const 100)) (i32.const 44))
+(assert_exhaustion (invoke "runaway") "call stack exhausted")
+(assert_trap (invoke "unreachable") "unreachable")
+(invoke "fac-iter" (i64.const 1))
+
+(assert_invalid
+  (module (func $type-void-vs-num (i32.eqz (call 1))) (func))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $unbound-func (call 1)))
+  "unknown function"
+)
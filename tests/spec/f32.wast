@@ -0,0 +1,24 @@
+;; f32 and f64 operations with NaN results, adapted from the official f32.wast and f64.wast
+
+(module
+  (func (export "add") (param $x f32) (param $y f32) (result f32) (f32.add (local.get $x) (local.get $y)))
+  (func (export "div") (param $x f32) (param $y f32) (result f32) (f32.div (local.get $x) (local.get $y)))
+  (func (export "sqrt") (param $x f32) (result f32) (f32.sqrt (local.get $x)))
+  (func (export "neg") (param $x f32) (result f32) (f32.neg (local.get $x)))
+  (func (export "min") (param $x f32) (param $y f32) (result f32) (f32.min (local.get $x) (local.get $y)))
+  (func (export "f64.mul") (param $x f64) (param $y f64) (result f64) (f64.mul (local.get $x) (local.get $y)))
+)
+
+(assert_return (invoke "add" (f32.const 0x1p-1) (f32.const 0x1p-1)) (f32.const 1.0))
+(assert_return (invoke "add" (f32.const -0x0p+0) (f32.const -0x0p+0)) (f32.const -0x0p+0))
+(assert_return (invoke "add" (f32.const inf) (f32.const -inf)) (f32.const nan:canonical))
+(assert_return (invoke "add" (f32.const nan:0x200000) (f32.const 1.0)) (f32.const nan:arithmetic))
+(assert_return (invoke "div" (f32.const 0.0) (f32.const 0.0)) (f32.const nan:canonical))
+(assert_return (invoke "div" (f32.const 1.0) (f32.const -0.0)) (f32.const -inf))
+(assert_return (invoke "sqrt" (f32.const -1.0)) (f32.const nan:canonical))
+(assert_return (invoke "sqrt" (f32.const 0x1p+2)) (f32.const 2.0))
+(assert_return (invoke "neg" (f32.const nan:0x200000)) (f32.const -nan:0x200000))
+(assert_return (invoke "min" (f32.const -0.0) (f32.const 0.0)) (f32.const -0.0))
+(assert_return (invoke "min" (f32.const nan) (f32.const 0.0)) (f32.const nan:canonical))
+(assert_return (invoke "f64.mul" (f64.const inf) (f64.const 0.0)) (f64.const nan:canonical))
+(assert_return (invoke "f64.mul" (f64.const 0x1.8p+0) (f64.const -2.0)) (f64.const -3.0))
@@ -0,0 +1,48 @@
+;; globals, adapted from the official global.wast
+
+(module
+  (global (import "spectest" "global_i32") i32)
+  (global $a i32 (i32.const -2))
+  (global $x (mut i32) (i32.const -12))
+  (global $y (export "y") (mut i64) (i64.const -15))
+  (global $z i32 (global.get 0))
+  (global $r externref (ref.null extern))
+
+  (func (export "get-a") (result i32) (global.get $a))
+  (func (export "get-x") (result i32) (global.get $x))
+  (func (export "get-z") (result i32) (global.get $z))
+  (func (export "get-r") (result externref) (global.get $r))
+  (func (export "set-x") (param i32) (global.set $x (local.get 0)))
+  (func (export "set-y") (param i64) (global.set $y (local.get 0)))
+)
+
+(assert_return (invoke "get-a") (i32.const -2))
+(assert_return (invoke "get-x") (i32.const -12))
+(assert_return (invoke "get-z") (i32.const 666))
+(assert_return (invoke "get-r") (ref.null extern))
+(assert_return (invoke "set-x" (i32.const 6)))
+(assert_return (invoke "get-x") (i32.const 6))
+(assert_return (get "y") (i64.const -15))
+(assert_return (invoke "set-y" (i64.const 7)))
+(assert_return (get "y") (i64.const 7))
+
+(assert_invalid
+  (module (global f32 (f32.const 0)) (func (global.set 0 (f32.const 1))))
+  "global is immutable"
+)
+(assert_invalid
+  (module (global (import "m" "x") (mut i32)) (global i32 (global.get 0)))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global i32 (i32.const 0)) (global i32 (global.get 0)))
+  "unknown global"
+)
+(assert_invalid
+  (module (global i32 (i64.const 0)))
+  "type mismatch"
+)
+(assert_malformed
+  (module quote "(global i32 (i32.const 0)) (global (import \"spectest\" \"global_i32\") i32)")
+  "import after global"
+)
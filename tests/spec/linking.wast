@@ -0,0 +1,144 @@
+;; registered modules and imports, adapted from the official linking.wast and imports.wast
+
+(module $Mf
+  (func (export "call") (result i32) (call $g))
+  (func $g (result i32) (i32.const 2))
+)
+(register "Mf" $Mf)
+
+(module $Nf
+  (func $f (import "Mf" "call") (result i32))
+  (export "Mf.call" (func $f))
+  (func (export "call Mf.call") (result i32) (call $f))
+)
+
+(assert_return (invoke $Mf "call") (i32.const 2))
+(assert_return (invoke $Nf "Mf.call") (i32.const 2))
+(assert_return (invoke "call Mf.call") (i32.const 2))
+
+(module $Mg
+  (global (export "mut_glob") (mut i32) (i32.const 142))
+  (func (export "set_mut") (param i32) (global.set 0 (local.get 0)))
+)
+(register "Mg" $Mg)
+
+(module $Ng
+  (global $mut_glob (import "Mg" "mut_glob") (mut i32))
+  (func (export "get_mut") (result i32) (global.get $mut_glob))
+)
+
+(assert_return (invoke $Ng "get_mut") (i32.const 142))
+(assert_return (invoke $Mg "set_mut" (i32.const 241)))
+(assert_return (invoke $Ng "get_mut") (i32.const 241))
+
+(module
+  (func (import "spectest" "print_i32") (param i32))
+  (func (export "print") (call 0 (i32.const 13)))
+)
+(assert_return (invoke "print"))
+
+(assert_unlinkable
+  (module (import "Mf" "unknown" (func)))
+  "unknown import"
+)
+(assert_unlinkable
+  (module (import "Mf" "call" (func (param i32))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (global (import "Mg" "mut_glob") i32))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "print_i32" (global i32)))
+  "incompatible import type"
+)
+
+(module $Mm
+  (memory (export "mem") 1 5)
+  (data (i32.const 10) "\00\01\02\03\04\05\06\07\08\09")
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+)
+(register "Mm" $Mm)
+
+(module $Nm
+  (memory (import "Mm" "mem") 1)
+  (data (i32.const 12) "\a0\a1")
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke $Mm "load" (i32.const 12)) (i32.const 0xa0))
+(assert_return (invoke $Nm "load" (i32.const 13)) (i32.const 0xa1))
+(assert_return (invoke $Nm "grow" (i32.const 2)) (i32.const 1))
+(assert_return (invoke $Nm "load" (i32.const 0x2ffff)) (i32.const 0))
+
+(module $Mt
+  (type (func (result i32)))
+  (table (export "tab") 10 funcref)
+  (elem (i32.const 2) $g $g)
+  (func $g (result i32) (i32.const 4))
+  (func (export "call") (param i32) (result i32) (call_indirect (type 0) (local.get 0)))
+)
+(register "Mt" $Mt)
+
+(module $Nt
+  (import "Mt" "tab" (table 5 funcref))
+  (elem (i32.const 9) $h)
+  (func $h (result i32) (i32.const 7))
+)
+
+(assert_return (invoke $Mt "call" (i32.const 3)) (i32.const 4))
+(assert_return (invoke $Mt "call" (i32.const 9)) (i32.const 7))
+(assert_trap (invoke $Mt "call" (i32.const 1)) "uninitialized element")
+
+(module
+  (import "spectest" "table" (table 10 funcref))
+  (import "spectest" "memory" (memory 1))
+)
+(assert_unlinkable
+  (module (import "Mt" "tab" (table 1 externref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "Mm" "mem" (table 1 funcref)))
+  "incompatible import type"
+)
+;; spectest.table is (table 10 20 funcref), spectest.memory is (memory 1 2)
+(module
+  (import "spectest" "table" (table 10 20 funcref))
+  (import "spectest" "memory" (memory 0 3))
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 11 funcref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 10 15 funcref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 2)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 1 1)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "global_i32" (global (mut i32))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "global_i32" (global i64)))
+  "incompatible import type"
+)
+(assert_invalid
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\02\0f\02"                        ;; import section with 2 entries
+    "\01m\01a\02\00\01"                ;; (import "m" "a" (memory 1))
+    "\01m\01b\02\00\01"                ;; (import "m" "b" (memory 1))
+  )
+  "multiple memories"
+)
@@ -0,0 +1,27 @@
+#!/bin/sh
+# Copy the scripts required by tests/spec.rs from https://github.com/WebAssembly/testsuite,
+# pinned to the commit recorded in REVISION. Usage: tests/testsuite/vendor.sh [commit]
+# A given commit replaces the pinned one, the scripts are re-vendored from it.
+set -eu
+
+dir=$(cd "$(dirname "$0")" && pwd)
+if [ $# -gt 0 ]; then
+    revision=$1
+elif [ -f "$dir/REVISION" ]; then
+    revision=$(cat "$dir/REVISION")
+else
+    echo "no commit is pinned in $dir/REVISION, pass one to pin it" >&2
+    exit 1
+fi
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+git clone --quiet https://github.com/WebAssembly/testsuite "$tmp"
+git -C "$tmp" checkout --quiet "$revision"
+
+# keep it in sync with `REQUIRED` of tests/spec.rs.
+for name in binary br_table call_indirect conversions exports f32 f64 i32 i64 imports linking memory; do
+    cp "$tmp/$name.wast" "$dir/$name.wast"
+done
+git -C "$tmp" rev-parse HEAD > "$dir/REVISION"
+echo "vendored $(cat "$dir/REVISION")"